@@ -140,6 +140,10 @@ pub fn path_fixes(name: &str) -> String {
     format!("../data/input/fixes/{}.json", name)
 }
 
+pub fn path_speed_limits(map_name: &str) -> String {
+    format!("../data/input/speed_limits/{}.json", map_name)
+}
+
 pub fn path_neighborhood(map_name: &str, neighborhood: &str) -> String {
     format!(
         "../data/input/neighborhoods/{}/{}.json",
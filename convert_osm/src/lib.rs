@@ -5,15 +5,20 @@ mod split_ways;
 
 use abstutil::Timer;
 use geom::{Distance, FindClosest, Line, PolyLine, Pt2D};
-use kml::ExtraShapes;
+use kml::{AttributeMapping, ExtraShapes};
 use map_model::raw::{OriginalBuilding, OriginalRoad, RawMap};
 use map_model::{osm, LaneID, OffstreetParking, Position, LANE_THICKNESS};
 
 pub struct Flags {
     pub osm: String,
+    // These shapes can be KML, GeoJSON, CSV with WKT geometry, or ExtraShapes in binary. The
+    // optional mappings point to JSON kml::AttributeMappings, for datasets that don't use
+    // Seattle's layout.
     pub parking_shapes: Option<String>,
+    pub parking_shapes_mapping: Option<String>,
     pub offstreet_parking: Option<String>,
     pub sidewalks: Option<String>,
+    pub sidewalks_mapping: Option<String>,
     pub gtfs: Option<String>,
     pub neighborhoods: Option<String>,
     pub clip: Option<String>,
@@ -31,13 +36,13 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     abstutil::retain_btreemap(&mut map.roads, |r, _| r.i1 != r.i2);
 
     if let Some(ref path) = flags.parking_shapes {
-        use_parking_hints(&mut map, path.clone(), timer);
+        use_parking_hints(&mut map, path, &flags.parking_shapes_mapping, timer);
     }
     if let Some(ref path) = flags.offstreet_parking {
         use_offstreet_parking(&mut map, path, timer);
     }
     if let Some(ref path) = flags.sidewalks {
-        use_sidewalk_hints(&mut map, path.clone(), timer);
+        use_sidewalk_hints(&mut map, path, &flags.sidewalks_mapping, timer);
     }
     if let Some(ref path) = flags.gtfs {
         timer.start("load GTFS");
@@ -54,9 +59,11 @@ pub fn convert(flags: &Flags, timer: &mut abstutil::Timer) -> RawMap {
     map
 }
 
-fn use_parking_hints(map: &mut RawMap, path: String, timer: &mut Timer) {
+// The shapes should have a PARKING_CATEGORY attribute. "None" or "No Parking Allowed" means
+// there's no parking along that blockface; any other value means there is.
+fn use_parking_hints(map: &mut RawMap, path: &str, mapping: &Option<String>, timer: &mut Timer) {
     timer.start("apply parking hints");
-    let shapes: ExtraShapes = abstutil::read_binary(path, timer);
+    let shapes = load_shapes(map, path, mapping, timer);
 
     // Match shapes with the nearest road + direction (true for forwards)
     let mut closest: FindClosest<(OriginalRoad, bool)> =
@@ -149,7 +156,8 @@ fn use_parking_hints(map: &mut RawMap, path: String, timer: &mut Timer) {
 
 fn use_offstreet_parking(map: &mut RawMap, path: &str, timer: &mut Timer) {
     timer.start("match offstreet parking points");
-    let shapes = kml::load(path, &map.gps_bounds, timer).expect("loading offstreet_parking failed");
+    let shapes = kml::load_any(path, &map.gps_bounds, &AttributeMapping::default(), timer)
+        .expect("loading offstreet_parking failed");
 
     let mut closest: FindClosest<OriginalBuilding> = FindClosest::new(&map.gps_bounds.to_bounds());
     for (id, b) in &map.buildings {
@@ -195,9 +203,11 @@ fn use_offstreet_parking(map: &mut RawMap, path: &str, timer: &mut Timer) {
     timer.stop("match offstreet parking points");
 }
 
-fn use_sidewalk_hints(map: &mut RawMap, path: String, timer: &mut Timer) {
+// Every shape is assumed to be a sidewalk; use the mapping's keep_if to filter datasets with other
+// features mixed in.
+fn use_sidewalk_hints(map: &mut RawMap, path: &str, mapping: &Option<String>, timer: &mut Timer) {
     timer.start("apply sidewalk hints");
-    let shapes: ExtraShapes = abstutil::read_binary(path, timer);
+    let shapes = load_shapes(map, path, mapping, timer);
 
     // Match shapes with the nearest road + direction (true for forwards)
     let mut closest: FindClosest<(OriginalRoad, bool)> =
@@ -266,3 +276,16 @@ fn use_sidewalk_hints(map: &mut RawMap, path: String, timer: &mut Timer) {
     }
     timer.stop("apply sidewalk hints");
 }
+
+fn load_shapes(
+    map: &RawMap,
+    path: &str,
+    mapping: &Option<String>,
+    timer: &mut Timer,
+) -> ExtraShapes {
+    let mapping = AttributeMapping::load(mapping.clone(), timer);
+    match kml::load_any(path, &map.gps_bounds, &mapping, timer) {
+        Ok(shapes) => shapes,
+        Err(err) => panic!("Couldn't load shapes from {}: {}", path, err),
+    }
+}
@@ -6,8 +6,10 @@ fn main() {
     let flags = Flags {
         osm: args.required("--osm"),
         parking_shapes: args.optional("--parking_shapes"),
+        parking_shapes_mapping: args.optional("--parking_shapes_mapping"),
         offstreet_parking: args.optional("--offstreet_parking"),
         sidewalks: args.optional("--sidewalks"),
+        sidewalks_mapping: args.optional("--sidewalks_mapping"),
         gtfs: args.optional("--gtfs"),
         neighborhoods: args.optional("--neighborhoods"),
         clip: args.optional("--clip"),
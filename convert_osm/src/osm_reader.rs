@@ -17,6 +17,8 @@ pub fn extract_osm(
     Vec<(i64, RawRoad)>,
     // Traffic signals
     HashSet<HashablePt2D>,
+    // Marked pedestrian crossings
+    HashSet<HashablePt2D>,
     // OSM Node IDs
     HashMap<HashablePt2D, i64>,
     // Turn restrictions: (restriction type, from way ID, via node ID, to way ID)
@@ -46,6 +48,7 @@ pub fn extract_osm(
     let mut id_to_way: HashMap<i64, Vec<Pt2D>> = HashMap::new();
     let mut roads: Vec<(i64, RawRoad)> = Vec::new();
     let mut traffic_signals: HashSet<HashablePt2D> = HashSet::new();
+    let mut crossings: HashSet<HashablePt2D> = HashSet::new();
     let mut osm_node_ids = HashMap::new();
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
@@ -59,6 +62,12 @@ pub fn extract_osm(
         if tags.get(osm::HIGHWAY) == Some(&"traffic_signals".to_string()) {
             traffic_signals.insert(pt);
         }
+        if tags.get(osm::HIGHWAY) == Some(&"crossing".to_string()) {
+            crossings.insert(pt);
+            if tags.get("crossing") == Some(&"traffic_signals".to_string()) {
+                traffic_signals.insert(pt);
+            }
+        }
     }
 
     let mut coastline_groups: Vec<Vec<Pt2D>> = Vec::new();
@@ -253,7 +262,14 @@ pub fn extract_osm(
         });
     }
 
-    (map, roads, traffic_signals, osm_node_ids, turn_restrictions)
+    (
+        map,
+        roads,
+        traffic_signals,
+        crossings,
+        osm_node_ids,
+        turn_restrictions,
+    )
 }
 
 fn tags_to_map(raw_tags: &[osm_xml::Tag]) -> BTreeMap<String, String> {
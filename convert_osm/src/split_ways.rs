@@ -1,16 +1,20 @@
 use abstutil::{Counter, Timer};
-use geom::{HashablePt2D, Pt2D};
+use geom::{Distance, HashablePt2D, Pt2D};
 use map_model::raw::{
     OriginalIntersection, OriginalRoad, RawIntersection, RawMap, RawRoad, RestrictionType,
 };
 use map_model::{osm, IntersectionType};
 use std::collections::{HashMap, HashSet};
 
+// Crossings closer than this to an intersection are just the normal crosswalks there.
+const MIN_DIST_FROM_CROSSING_TO_INTERSECTION: Distance = Distance::const_meters(15.0);
+
 pub fn split_up_roads(
-    (mut map, roads, traffic_signals, osm_node_ids, turn_restrictions): (
+    (mut map, roads, traffic_signals, crossings, osm_node_ids, turn_restrictions): (
         RawMap,
         Vec<(i64, RawRoad)>,
         HashSet<HashablePt2D>,
+        HashSet<HashablePt2D>,
         HashMap<HashablePt2D, i64>,
         Vec<(RestrictionType, i64, i64, i64)>,
     ),
@@ -37,6 +41,19 @@ pub fn split_up_roads(
         }
     }
 
+    // Marked crossings in the middle of a block become degenerate intersections, so pedestrians
+    // get a crosswalk there and vehicles have to yield to them.
+    for (_, r) in &roads {
+        for pt in midblock_crossings(&r.center_points, &pt_to_intersection, &crossings) {
+            pt_to_intersection.insert(
+                pt,
+                OriginalIntersection {
+                    osm_node_id: osm_node_ids[&pt],
+                },
+            );
+        }
+    }
+
     for (pt, id) in &pt_to_intersection {
         map.intersections.insert(
             *id,
@@ -137,6 +154,41 @@ pub fn split_up_roads(
     map
 }
 
+fn midblock_crossings(
+    pts: &Vec<Pt2D>,
+    pt_to_intersection: &HashMap<HashablePt2D, OriginalIntersection>,
+    crossings: &HashSet<HashablePt2D>,
+) -> Vec<HashablePt2D> {
+    let mut dist_along = vec![Distance::ZERO];
+    for pair in pts.windows(2) {
+        let dist = *dist_along.last().unwrap() + pair[0].dist_to(pair[1]);
+        dist_along.push(dist);
+    }
+    // Both existing intersections and crossings that were just accepted
+    let mut stops: Vec<Distance> = pts
+        .iter()
+        .zip(dist_along.iter())
+        .filter(|(pt, _)| pt_to_intersection.contains_key(&pt.to_hashable()))
+        .map(|(_, dist)| *dist)
+        .collect();
+
+    let mut result = Vec::new();
+    for (pt, dist) in pts.iter().zip(dist_along.iter()) {
+        let hash_pt = pt.to_hashable();
+        if !crossings.contains(&hash_pt) || pt_to_intersection.contains_key(&hash_pt) {
+            continue;
+        }
+        if stops
+            .iter()
+            .all(|stop| (*stop - *dist).abs() >= MIN_DIST_FROM_CROSSING_TO_INTERSECTION)
+        {
+            stops.push(*dist);
+            result.push(hash_pt);
+        }
+    }
+    result
+}
+
 // TODO Consider doing this in PolyLine::new always. extend() there does this too.
 fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     let mut result = Vec::new();
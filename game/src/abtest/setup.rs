@@ -120,10 +120,7 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
     let secondary = ctx.loading_screen(
         format!("Launching A/B test {}", test.test_name),
         |ctx, mut timer| {
-            let scenario: Scenario = abstutil::read_binary(
-                abstutil::path_scenario(&test.map_name, &test.scenario_name),
-                &mut timer,
-            );
+            let scenario = Scenario::load(&ui.primary.map, &test.scenario_name, &mut timer);
 
             {
                 timer.start("load primary");
@@ -134,12 +131,8 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                     format!("{} with {}", test.test_name, test.edits1_name);
                 ui.primary.current_flags.sim_flags.opts.savestate_every = None;
 
-                apply_map_edits(
-                    &mut ui.primary,
-                    &ui.cs,
-                    ctx,
-                    MapEdits::load(&test.map_name, &test.edits1_name, &mut timer),
-                );
+                let edits = MapEdits::load(&mut ui.primary.map, &test.edits1_name, &mut timer);
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
                 ui.primary.map.mark_edits_fresh();
                 ui.primary
                     .map
@@ -163,6 +156,7 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                             load: abstutil::path_map(&test.map_name),
                             use_map_fixes: current_flags.sim_flags.use_map_fixes,
                             rng_seed: current_flags.sim_flags.rng_seed,
+                            bike_lts_tolerance: current_flags.sim_flags.bike_lts_tolerance,
                             opts: SimOptions {
                                 run_name: format!("{} with {}", test.test_name, test.edits2_name),
                                 savestate_every: None,
@@ -178,6 +172,17 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                                     .sim_flags
                                     .opts
                                     .recalc_lanechanging,
+                                kinematics: current_flags.sim_flags.opts.kinematics,
+                                emission_factors: current_flags
+                                    .sim_flags
+                                    .opts
+                                    .emission_factors
+                                    .clone(),
+                                jaywalking_probability: current_flags
+                                    .sim_flags
+                                    .opts
+                                    .jaywalking_probability,
+                                ped_crowding: current_flags.sim_flags.opts.ped_crowding,
                             },
                         },
                         ..current_flags.clone()
@@ -186,12 +191,8 @@ fn launch_test(test: &ABTest, ui: &mut UI, ctx: &mut EventCtx) -> ABTestMode {
                     ctx,
                     &mut timer,
                 );
-                apply_map_edits(
-                    &mut secondary,
-                    &ui.cs,
-                    ctx,
-                    MapEdits::load(&test.map_name, &test.edits2_name, &mut timer),
-                );
+                let edits = MapEdits::load(&mut secondary.map, &test.edits2_name, &mut timer);
+                apply_map_edits(&mut secondary, &ui.cs, ctx, edits);
                 secondary.map.mark_edits_fresh();
                 secondary
                     .map
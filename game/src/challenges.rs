@@ -228,6 +228,8 @@ impl State for ChallengeSplash {
                 if &abstutil::basename(&map_path) != ui.primary.map.get_name() {
                     ui.switch_map(ctx, map_path.clone());
                 }
+                let new_edits =
+                    new_edits.migrate(&mut ui.primary.map, &mut Timer::new("load map edits"));
                 apply_map_edits(&mut ui.primary, &ui.cs, ctx, new_edits);
                 ui.primary.map.mark_edits_fresh();
                 ui.primary
@@ -72,7 +72,7 @@ impl RoadColorerBuilder {
     pub fn build(self, ctx: &EventCtx, map: &Map) -> RoadColorer {
         let mut batch = GeomBatch::new();
         for (r, color) in self.roads {
-            batch.push(color, map.get_r(r).get_thick_polygon().unwrap());
+            batch.push(color, map.get_r(r).get_thick_polygon(map).unwrap());
         }
         RoadColorer {
             zoomed_override_colors: self.zoomed_override_colors,
@@ -142,7 +142,7 @@ impl ObjectColorerBuilder {
             batch.push(*color, poly);
         }
         for (r, color) in self.roads {
-            batch.push(color, map.get_r(r).get_thick_polygon().unwrap());
+            batch.push(color, map.get_r(r).get_thick_polygon(map).unwrap());
             for l in map.get_r(r).all_lanes() {
                 self.zoomed_override_colors.insert(ID::Lane(l), color);
             }
@@ -381,5 +381,6 @@ fn color_for_mode(m: TripMode, ui: &UI) -> Color {
         TripMode::Bike => ui.cs.get("unzoomed bike"),
         TripMode::Transit => ui.cs.get("unzoomed bus"),
         TripMode::Drive => ui.cs.get("unzoomed car"),
+        TripMode::RideHail => ui.cs.get("unzoomed ride-hail"),
     }
 }
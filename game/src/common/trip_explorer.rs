@@ -88,6 +88,9 @@ impl TripExplorer {
                     end_color,
                 ));
             }
+            TripEnd::ServeRideHail => {
+                rows.push(("serve ride-hail requests forever".to_string(), end_color));
+            }
         };
 
         let legend = ColorLegend::new(
@@ -354,7 +354,7 @@ fn search_osm(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &mut UI) -> Option<Trans
             for l in r.all_lanes() {
                 ids.insert(ID::Lane(l));
             }
-            batch.push(color, r.get_thick_polygon().unwrap());
+            batch.push(color, r.get_thick_polygon(map).unwrap());
         }
     }
     for b in map.all_buildings() {
@@ -3,6 +3,7 @@ use crate::game::{msg, State, Transition, WizardState};
 use crate::helpers::ID;
 use crate::ui::UI;
 use ezgui::{hotkey, Button, Choice, Color, EventCtx, GfxCtx, Key, ScreenPt};
+use geom::Distance;
 use map_model::{
     connectivity, EditCmd, IntersectionType, LaneID, LaneType, Map, PathConstraints, RoadID,
 };
@@ -190,6 +191,29 @@ impl LaneEditor {
                 }
             }
 
+            if ui
+                .per_obj
+                .action(ctx, Key::A, "add a lane next to this one")
+            {
+                return Some(Transition::Push(make_add_lane(l)));
+            }
+            if ui.per_obj.action(ctx, Key::X, "delete this lane") {
+                if let Some(err) = can_delete_lane(l, &ui.primary.map) {
+                    return Some(Transition::Push(msg("Error", vec![err])));
+                }
+                let mut edits = ui.primary.map.get_edits().clone();
+                edits
+                    .commands
+                    .push(EditCmd::delete_lane(l, &ui.primary.map));
+                apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+                // The lane is gone
+                ui.primary.current_selection = None;
+                return None;
+            }
+            if ui.per_obj.action(ctx, Key::W, "change this lane's width") {
+                return Some(Transition::Push(make_change_lane_width(l)));
+            }
+
             if ui
                 .per_obj
                 .action(ctx, Key::U, "bulk edit lanes on this road")
@@ -345,6 +369,76 @@ fn try_change_lane_type(l: LaneID, new_lt: LaneType, map: &Map) -> Result<Option
     }
 }
 
+fn can_delete_lane(l: LaneID, map: &Map) -> Option<String> {
+    let lane = map.get_l(l);
+    let r = map.get_parent(l);
+    if r.all_lanes().len() == 1 {
+        return Some(format!("A road needs at least one lane"));
+    }
+    if lane.is_sidewalk() {
+        return Some(format!("Can't delete sidewalks"));
+    }
+    if !lane.building_paths.is_empty() || !lane.bus_stops.is_empty() {
+        return Some(format!("Buildings or bus stops are connected to this lane"));
+    }
+    if (lane.is_driving() || lane.is_bus()) && !r.all_bus_stops(map).is_empty() {
+        return Some(format!("You need a driving or bus lane for the bus stop!"));
+    }
+    None
+}
+
+// The new lane goes on the outer side of the chosen one, unless that's a sidewalk.
+fn make_add_lane(next_to: LaneID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut wizard = wiz.wrap(ctx);
+        let (_, lt) = wizard.choose("Add what kind of lane?", || {
+            vec![
+                Choice::new("driving", LaneType::Driving),
+                Choice::new("parking", LaneType::Parking),
+                Choice::new("biking", LaneType::Biking),
+                Choice::new("bus", LaneType::Bus),
+            ]
+        })?;
+
+        let map = &ui.primary.map;
+        let lane = map.get_l(next_to);
+        let (forwards, idx) = map.get_parent(next_to).dir_and_offset(next_to);
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::AddLane {
+            id: map.next_lane_id(),
+            r: lane.parent,
+            forwards,
+            idx: if lane.is_sidewalk() { idx } else { idx + 1 },
+            lt,
+            width: lt.default_width(),
+        });
+        apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        Some(Transition::Pop)
+    }))
+}
+
+fn make_change_lane_width(l: LaneID) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let orig_width = ui.primary.map.get_l(l).width;
+        let meters = wiz.wrap(ctx).input_something(
+            "New width in meters?",
+            Some(format!("{}", orig_width.inner_meters())),
+            Box::new(|line| line.parse::<f64>().ok().filter(|m| *m >= 0.5 && *m <= 10.0)),
+        )?;
+        let width = Distance::meters(meters);
+        if width != orig_width {
+            let mut edits = ui.primary.map.get_edits().clone();
+            edits.commands.push(EditCmd::ChangeLaneWidth {
+                id: l,
+                width,
+                orig_width,
+            });
+            apply_map_edits(&mut ui.primary, &ui.cs, ctx, edits);
+        }
+        Some(Transition::Pop)
+    }))
+}
+
 fn make_bulk_edit_lanes(road: RoadID) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, ui| {
         let mut wizard = wiz.wrap(ctx);
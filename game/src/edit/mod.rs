@@ -17,7 +17,9 @@ use ezgui::{
     hotkey, lctrl, Choice, Color, EventCtx, EventLoopMode, GfxCtx, Key, Line, ModalMenu, Text,
     WrappedWizard,
 };
-use map_model::{ControlStopSign, ControlTrafficSignal, EditCmd, LaneID, MapEdits};
+use map_model::{
+    ControlStopSign, ControlTrafficSignal, EditCmd, LaneID, MapEdits, PermanentMapEdits,
+};
 use sim::Sim;
 use std::collections::BTreeSet;
 
@@ -116,6 +118,10 @@ impl State for EditMode {
                             EditCmd::ChangeTrafficSignal(ss) => ID::Intersection(ss.id),
                             EditCmd::CloseIntersection { id, .. } => ID::Intersection(*id),
                             EditCmd::UncloseIntersection(id, _) => ID::Intersection(*id),
+                            EditCmd::ChangeSpeedLimit { id, .. } => ID::Road(id.id),
+                            EditCmd::AddLane { id, .. } => ID::Lane(*id),
+                            EditCmd::DeleteLane { r, .. } => ID::Road(*r),
+                            EditCmd::ChangeLaneWidth { id, .. } => ID::Lane(*id),
                         };
                         return Transition::PushWithMode(
                             Warping::new(
@@ -317,7 +323,10 @@ impl State for EditMode {
         } else {
             let color = ui.cs.get_def("unzoomed map diffs", Color::RED);
             for l in edits.original_lts.keys().chain(&edits.reversed_lanes) {
-                g.draw_polygon(color, &ctx.map.get_parent(*l).get_thick_polygon().unwrap());
+                g.draw_polygon(
+                    color,
+                    &ctx.map.get_parent(*l).get_thick_polygon(&ctx.map).unwrap(),
+                );
             }
 
             for i in &edits.changed_intersections {
@@ -390,9 +399,13 @@ fn make_load_edits(mode: GameplayMode) -> Box<dyn State> {
                     .filter(|(_, edits)| mode.allows(edits))
                     .collect(),
             );
-            list.push(Choice::new("no_edits", MapEdits::new(map_name.clone())));
+            list.push(Choice::new(
+                "no_edits",
+                PermanentMapEdits::empty(map_name.clone()),
+            ));
             list
         })?;
+        let new_edits = new_edits.migrate(&mut ui.primary.map, &mut Timer::new("load map edits"));
         apply_map_edits(&mut ui.primary, &ui.cs, ctx, new_edits);
         ui.primary.map.mark_edits_fresh();
         Some(Transition::Pop)
@@ -412,7 +425,7 @@ pub fn apply_map_edits(
         bundle.map.apply_edits(edits, &mut timer);
 
     for l in lanes_changed {
-        bundle.draw_map.lanes[l.0] = DrawLane::new(
+        let draw = DrawLane::new(
             bundle.map.get_l(l),
             &bundle.map,
             bundle.current_flags.draw_lane_markings,
@@ -420,10 +433,11 @@ pub fn apply_map_edits(
             &mut timer,
         )
         .finish(ctx.prerender);
+        bundle.draw_map.update_lane(l, draw, &bundle.map);
     }
     for r in roads_changed {
-        bundle.draw_map.roads[r.0] =
-            DrawRoad::new(bundle.map.get_r(r), &bundle.map, cs, ctx.prerender);
+        let draw = DrawRoad::new(bundle.map.get_r(r), &bundle.map, cs, ctx.prerender);
+        bundle.draw_map.update_road(draw, &bundle.map);
     }
 
     let mut lanes_of_modified_turns: BTreeSet<LaneID> = BTreeSet::new();
@@ -437,12 +451,13 @@ pub fn apply_map_edits(
     }
 
     for i in modified_intersections {
-        bundle.draw_map.intersections[i.0] = DrawIntersection::new(
+        let draw = DrawIntersection::new(
             bundle.map.get_i(i),
             &bundle.map,
             cs,
             ctx.prerender,
             &mut timer,
         );
+        bundle.draw_map.update_intersection(draw, &bundle.map);
     }
 }
@@ -57,6 +57,8 @@ impl TrafficSignalEditor {
                     "convert to dedicated pedestrian scramble signal",
                 ),
                 (hotkey(Key::O), "change signal offset"),
+                (hotkey(Key::I), "change leading pedestrian interval"),
+                (hotkey(Key::T), "toggle pedestrian push button"),
                 (lctrl(Key::P), "preview changes"),
                 (lctrl(Key::Z), "undo"),
                 (hotkey(Key::Escape), "quit"),
@@ -166,6 +168,25 @@ impl State for TrafficSignalEditor {
             ));
         } else if self.menu.action("change signal offset") {
             return Transition::Push(change_offset(orig_signal.offset));
+        } else if self.menu.action("change leading pedestrian interval") {
+            return Transition::Push(change_leading_ped_interval(
+                self.diagram.i,
+                self.diagram.current_phase(),
+            ));
+        } else if self.menu.action("toggle pedestrian push button") {
+            let idx = self.diagram.current_phase();
+            let mut new_signal = orig_signal.clone();
+            new_signal.phases[idx].push_button = !new_signal.phases[idx].push_button;
+            if let Err(err) = new_signal.clone().validate() {
+                return Transition::Push(msg("Error", vec![err]));
+            }
+            self.command_stack.push((
+                "toggle pedestrian push button".to_string(),
+                orig_signal.clone(),
+            ));
+            change_traffic_signal(new_signal, ui, ctx);
+            self.diagram = TrafficSignalDiagram::new(self.diagram.i, idx, ui, ctx);
+            return Transition::Keep;
         } else if self.menu.action("choose a preset signal") {
             return Transition::Push(change_preset(self.diagram.i));
         } else if self.menu.action("reset to original") {
@@ -403,6 +424,33 @@ fn change_offset(current_duration: Duration) -> Box<dyn State> {
     }))
 }
 
+fn change_leading_ped_interval(i: IntersectionID, idx: usize) -> Box<dyn State> {
+    WizardState::new(Box::new(move |wiz, ctx, ui| {
+        let mut signal = ui.primary.map.get_traffic_signal(i).clone();
+        let seconds = wiz.wrap(ctx).input_usize_prefilled(
+            "How many seconds should pedestrians get before vehicles in this phase?",
+            format!(
+                "{}",
+                signal.phases[idx].leading_ped_interval.inner_seconds() as usize
+            ),
+        )?;
+        let orig_signal = signal.clone();
+        signal.phases[idx].leading_ped_interval = Duration::seconds(seconds as f64);
+        if let Err(err) = signal.clone().validate() {
+            return Some(Transition::Replace(msg("Error", vec![err])));
+        }
+        Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
+            let mut editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+            editor.command_stack.push((
+                "change leading pedestrian interval".to_string(),
+                orig_signal,
+            ));
+            change_traffic_signal(signal, ui, ctx);
+            editor.diagram = TrafficSignalDiagram::new(i, idx, ui, ctx);
+        })))
+    }))
+}
+
 fn change_preset(i: IntersectionID) -> Box<dyn State> {
     WizardState::new(Box::new(move |wiz, ctx, ui| {
         let (name, new_signal) =
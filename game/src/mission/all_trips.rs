@@ -34,7 +34,14 @@ impl TripsVisualizer {
                     if let Some(spawn_trip) = trip.to_spawn_trip(map) {
                         let mut rng = flags.make_rng();
                         let (_, spec) = spawn_trip.to_trip_spec(&mut rng);
-                        let req = sim.trip_spec_to_path_req(&spec, map);
+                        let req = if let Some(req) = sim.trip_spec_to_path_req(&spec, map) {
+                            req
+                        } else {
+                            return MaybeTrip::Failure(format!(
+                                "{:?} trip from {:?} to {:?} has no path request",
+                                trip.mode, trip.from, trip.to
+                            ));
+                        };
                         if let Some(route) = map
                             .pathfind(req.clone())
                             .and_then(|path| path.trace(map, req.start.dist_along(), None))
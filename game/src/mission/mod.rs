@@ -64,10 +64,7 @@ fn load_scenario(wiz: &mut Wizard, ctx: &mut EventCtx, ui: &mut UI) -> Option<Tr
     let s = wiz.wrap(ctx).choose_string("Load which scenario?", || {
         abstutil::list_all_objects(abstutil::path_all_scenarios(&map_name))
     })?;
-    let scenario = abstutil::read_binary(
-        abstutil::path_scenario(&map_name, &s),
-        &mut Timer::throwaway(),
-    );
+    let scenario = Scenario::load(&ui.primary.map, &s, &mut Timer::throwaway());
     Some(Transition::Replace(Box::new(
         scenario::ScenarioManager::new(scenario, ctx, ui),
     )))
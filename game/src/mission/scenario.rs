@@ -46,8 +46,8 @@ impl ScenarioManager {
             // trips_from_bldg and trips_from_border
             match trip {
                 // TODO CarAppearing might be from a border
-                SpawnTrip::CarAppearing { .. } => {}
-                SpawnTrip::MaybeUsingParkedCar(_, b, _) => {
+                SpawnTrip::CarAppearing { .. } | SpawnTrip::DeliveryTour { .. } => {}
+                SpawnTrip::MaybeUsingParkedCar(_, b, _) | SpawnTrip::UsingRideHail(_, b, _) => {
                     trips_from_bldg.insert(*b, idx);
                 }
                 SpawnTrip::UsingBike(_, ref spot, _)
@@ -66,6 +66,7 @@ impl ScenarioManager {
             // trips_to_bldg and trips_to_border
             match trip {
                 SpawnTrip::CarAppearing { ref goal, .. }
+                | SpawnTrip::DeliveryTour { ref goal, .. }
                 | SpawnTrip::MaybeUsingParkedCar(_, _, ref goal)
                 | SpawnTrip::UsingBike(_, _, ref goal) => match goal {
                     DrivingGoal::ParkNear(b) => {
@@ -76,7 +77,8 @@ impl ScenarioManager {
                     }
                 },
                 SpawnTrip::JustWalking(_, _, ref spot)
-                | SpawnTrip::UsingTransit(_, _, ref spot, _, _, _) => match spot.connection {
+                | SpawnTrip::UsingTransit(_, _, ref spot, _, _, _)
+                | SpawnTrip::UsingRideHail(_, _, ref spot) => match spot.connection {
                     SidewalkPOI::Building(b) => {
                         trips_to_bldg.insert(b, idx);
                     }
@@ -166,7 +168,7 @@ impl State for ScenarioManager {
             ui.recalculate_current_selection(ctx);
         }
         if self.menu.action("save") {
-            self.scenario.save();
+            self.scenario.save(&ui.primary.map);
         } else if self.menu.action("edit") {
             return Transition::Push(Box::new(ScenarioEditor {
                 scenario: self.scenario.clone(),
@@ -576,6 +578,28 @@ fn describe(trip: &SpawnTrip, home: OD) -> String {
             sidewalk_spot(goal),
             route
         ),
+        SpawnTrip::DeliveryTour {
+            depart,
+            start,
+            stops,
+            goal,
+        } => format!(
+            "{}: truck appears at {}, makes {} deliveries, goes to {}",
+            depart,
+            start.lane(),
+            stops.len(),
+            driving_goal(goal)
+        ),
+        SpawnTrip::UsingRideHail(depart, start_bldg, goal) => format!(
+            "{}: ride-hail from {} to {}",
+            depart,
+            if OD::Bldg(*start_bldg) == home {
+                "HERE".to_string()
+            } else {
+                start_bldg.to_string()
+            },
+            sidewalk_spot(goal)
+        ),
     }
 }
 
@@ -591,7 +615,8 @@ fn other_endpt(trip: &SpawnTrip, home: OD, map: &Map) -> ID {
     };
 
     let (from, to) = match trip {
-        SpawnTrip::CarAppearing { start, goal, .. } => (
+        SpawnTrip::CarAppearing { start, goal, .. }
+        | SpawnTrip::DeliveryTour { start, goal, .. } => (
             ID::Intersection(map.get_l(start.lane()).src_i),
             driving_goal(goal),
         ),
@@ -603,6 +628,9 @@ fn other_endpt(trip: &SpawnTrip, home: OD, map: &Map) -> ID {
         SpawnTrip::UsingTransit(_, start, goal, _, _, _) => {
             (sidewalk_spot(start), sidewalk_spot(goal))
         }
+        SpawnTrip::UsingRideHail(_, start_bldg, goal) => {
+            (ID::Building(*start_bldg), sidewalk_spot(goal))
+        }
     };
     let home_id = match home {
         OD::Bldg(b) => ID::Building(b),
@@ -66,7 +66,7 @@ impl DrawIntersection {
                 let r = map.get_r(*i.roads.iter().next().unwrap());
                 default_geom.extend(
                     cs.get_def("incoming border node arrow", Color::PURPLE),
-                    calculate_border_arrows(i, r, timer),
+                    calculate_border_arrows(i, r, map, timer),
                 );
             }
             IntersectionType::StopSign => {
@@ -99,7 +99,8 @@ impl DrawIntersection {
     // Returns the (octagon, pole) if there's room to draw it.
     pub fn stop_sign_geom(ss: &RoadWithStopSign, map: &Map) -> Option<(Polygon, Polygon)> {
         let trim_back = Distance::meters(0.1);
-        let rightmost = &map.get_l(ss.rightmost_lane).lane_center_pts;
+        let rightmost_lane = map.get_l(ss.rightmost_lane);
+        let rightmost = &rightmost_lane.lane_center_pts;
         // TODO The dream of trimming f64's was to isolate epsilon checks like this...
         if rightmost.length() - trim_back <= EPSILON_DIST {
             // TODO warn
@@ -108,7 +109,7 @@ impl DrawIntersection {
         let last_line = rightmost
             .exact_slice(Distance::ZERO, rightmost.length() - trim_back)
             .last_line()
-            .shift_right(1.0 * LANE_THICKNESS);
+            .shift_right(rightmost_lane.width);
 
         let octagon = make_octagon(last_line.pt2(), Distance::meters(1.0), last_line.angle());
         let pole = Line::new(
@@ -140,14 +141,13 @@ impl Renderable for DrawIntersection {
             if self.intersection_type == IntersectionType::TrafficSignal
                 && opts.suppress_traffic_signal_details != Some(self.id)
             {
-                let signal = ctx.map.get_traffic_signal(self.id);
                 let mut maybe_redraw = self.draw_traffic_signal.borrow_mut();
                 let recalc = maybe_redraw
                     .as_ref()
                     .map(|(_, t)| *t != ctx.sim.time())
                     .unwrap_or(true);
                 if recalc {
-                    let (_, phase, t) = signal.current_phase_and_remaining_time(ctx.sim.time());
+                    let (_, phase, t) = ctx.sim.current_signal_phase(self.id, ctx.map);
                     let mut batch = GeomBatch::new();
                     draw_signal_phase(phase, self.id, Some(t), &mut batch, ctx);
                     *maybe_redraw = Some((g.prerender.upload(batch), ctx.sim.time()));
@@ -182,22 +182,22 @@ pub fn calculate_corners(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<
                 continue;
             }
 
+            let l1 = map.get_l(turn.id.src);
+            let l2 = map.get_l(turn.id.dst);
+
             // Special case for dead-ends: just thicken the geometry.
             if i.roads.len() == 1 {
-                corners.push(turn.geom.make_polygons(LANE_THICKNESS));
+                corners.push(turn.geom.make_polygons(l1.width));
                 continue;
             }
 
-            let l1 = map.get_l(turn.id.src);
-            let l2 = map.get_l(turn.id.dst);
-
-            let src_line = l1.last_line().shift_left(LANE_THICKNESS / 2.0);
-            let dst_line = l2.first_line().shift_left(LANE_THICKNESS / 2.0);
+            let src_line = l1.last_line().shift_left(l1.width / 2.0);
+            let dst_line = l2.first_line().shift_left(l2.width / 2.0);
 
             let pt_maybe_in_intersection = src_line.infinite().intersection(&dst_line.infinite());
             // Now find all of the points on the intersection polygon between the two sidewalks.
-            let corner1 = l1.last_line().shift_right(LANE_THICKNESS / 2.0).pt2();
-            let corner2 = l2.first_line().shift_right(LANE_THICKNESS / 2.0).pt1();
+            let corner1 = l1.last_line().shift_right(l1.width / 2.0).pt2();
+            let corner2 = l2.first_line().shift_right(l2.width / 2.0).pt1();
             // Intersection polygons are constructed in clockwise order, so do corner2 to corner1.
             // TODO This threshold is higher than the 0.1 intersection polygons use to dedupe
             // because of jagged lane teeth from bad polyline shifting. Seemingly.
@@ -230,20 +230,25 @@ pub fn calculate_corners(i: &Intersection, map: &Map, timer: &mut Timer) -> Vec<
     corners
 }
 
-fn calculate_border_arrows(i: &Intersection, r: &Road, timer: &mut Timer) -> Vec<Polygon> {
+fn calculate_border_arrows(
+    i: &Intersection,
+    r: &Road,
+    map: &Map,
+    timer: &mut Timer,
+) -> Vec<Polygon> {
     let mut result = Vec::new();
 
     // These arrows should point from the void to the road
     if !i.outgoing_lanes.is_empty() {
         // The line starts at the border and points down the road
         let (line, width) = if r.dst_i == i.id {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(true, map);
             (
                 r.center_pts.last_line().shift_left(width / 2.0).reverse(),
                 width,
             )
         } else {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(true, map);
             (r.center_pts.first_line().shift_right(width / 2.0), width)
         };
         result.push(
@@ -261,13 +266,13 @@ fn calculate_border_arrows(i: &Intersection, r: &Road, timer: &mut Timer) -> Vec
     if !i.incoming_lanes.is_empty() {
         // The line starts at the border and points down the road
         let (line, width) = if r.dst_i == i.id {
-            let width = (r.children_forwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(true, map);
             (
                 r.center_pts.last_line().shift_right(width / 2.0).reverse(),
                 width,
             )
         } else {
-            let width = (r.children_backwards.len() as f64) * LANE_THICKNESS;
+            let width = r.width(false, map);
             (r.center_pts.first_line().shift_left(width / 2.0), width)
         };
         result.push(
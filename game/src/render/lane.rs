@@ -3,7 +3,10 @@ use crate::render::{dashed_lines, DrawCtx, DrawOptions, Renderable, OUTLINE_THIC
 use abstutil::Timer;
 use ezgui::{Color, Drawable, GeomBatch, GfxCtx, Prerender};
 use geom::{Distance, Line, PolyLine, Polygon, Pt2D};
-use map_model::{Lane, LaneID, LaneType, Map, Road, TurnType, LANE_THICKNESS, PARKING_SPOT_LENGTH};
+use map_model::{
+    Lane, LaneID, LaneType, Map, Road, TurnType, LANE_THICKNESS, LOADING_SPOT_LENGTH,
+    PARKING_SPOT_LENGTH,
+};
 
 // Split into two phases like this, because AlmostDrawLane can be created in parallel, but GPU
 // upload has to be serial.
@@ -42,7 +45,7 @@ impl DrawLane {
         timer: &mut Timer,
     ) -> AlmostDrawLane {
         let road = map.get_r(lane.parent);
-        let polygon = lane.lane_center_pts.make_polygons(LANE_THICKNESS);
+        let polygon = lane.lane_center_pts.make_polygons(lane.width);
 
         let mut draw = GeomBatch::new();
         draw.push(
@@ -50,6 +53,7 @@ impl DrawLane {
                 LaneType::Driving => cs.get_def("driving lane", Color::BLACK),
                 LaneType::Bus => cs.get_def("bus lane", Color::rgb(190, 74, 76)),
                 LaneType::Parking => cs.get_def("parking lane", Color::grey(0.2)),
+                LaneType::LoadingZone => cs.get_def("loading zone", Color::rgb(120, 100, 30)),
                 LaneType::Sidewalk => cs.get_def("sidewalk", Color::grey(0.8)),
                 LaneType::Biking => cs.get_def("bike lane", Color::rgb(15, 125, 75)),
                 LaneType::SharedLeftTurn => cs.get("driving lane"),
@@ -70,7 +74,21 @@ impl DrawLane {
                 LaneType::Parking => {
                     draw.extend(
                         cs.get_def("general road marking", Color::WHITE),
-                        calculate_parking_lines(lane),
+                        calculate_parking_lines(
+                            lane,
+                            lane.number_parking_spots(),
+                            PARKING_SPOT_LENGTH,
+                        ),
+                    );
+                }
+                LaneType::LoadingZone => {
+                    draw.extend(
+                        cs.get("general road marking"),
+                        calculate_parking_lines(
+                            lane,
+                            lane.number_loading_spots(),
+                            LOADING_SPOT_LENGTH,
+                        ),
                     );
                 }
                 LaneType::Driving | LaneType::Bus => {
@@ -88,14 +106,14 @@ impl DrawLane {
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_right(LANE_THICKNESS / 2.0)
+                            .shift_right(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
                     draw.push(
                         cs.get("road center line"),
                         lane.lane_center_pts
-                            .shift_left(LANE_THICKNESS / 2.0)
+                            .shift_left(lane.width / 2.0)
                             .get(timer)
                             .make_polygons(Distance::meters(0.25)),
                     );
@@ -132,9 +150,9 @@ impl Renderable for DrawLane {
     }
 
     fn get_outline(&self, map: &Map) -> Polygon {
-        map.get_l(self.id)
-            .lane_center_pts
-            .to_thick_boundary(LANE_THICKNESS, OUTLINE_THICKNESS)
+        let lane = map.get_l(self.id);
+        lane.lane_center_pts
+            .to_thick_boundary(lane.width, OUTLINE_THICKNESS)
             .unwrap_or_else(|| self.polygon.clone())
     }
 
@@ -166,28 +184,26 @@ fn calculate_sidewalk_lines(lane: &Lane) -> Vec<Polygon> {
         let (pt, angle) = lane.dist_along(dist_along);
         // Reuse perp_line. Project away an arbitrary amount
         let pt2 = pt.project_away(Distance::meters(1.0), angle);
-        result.push(
-            perp_line(Line::new(pt, pt2), LANE_THICKNESS).make_polygons(Distance::meters(0.25)),
-        );
+        result
+            .push(perp_line(Line::new(pt, pt2), lane.width).make_polygons(Distance::meters(0.25)));
         dist_along += tile_every;
     }
 
     result
 }
 
-fn calculate_parking_lines(lane: &Lane) -> Vec<Polygon> {
+fn calculate_parking_lines(lane: &Lane, num_spots: usize, spot_length: Distance) -> Vec<Polygon> {
     // meters, but the dims get annoying below to remove
     let leg_length = Distance::meters(1.0);
 
     let mut result = Vec::new();
-    let num_spots = lane.number_parking_spots();
     if num_spots > 0 {
         for idx in 0..=num_spots {
-            let (pt, lane_angle) = lane.dist_along(PARKING_SPOT_LENGTH * (1.0 + idx as f64));
+            let (pt, lane_angle) = lane.dist_along(spot_length * (1.0 + idx as f64));
             let perp_angle = lane_angle.rotate_degs(270.0);
             // Find the outside of the lane. Actually, shift inside a little bit, since the line will
             // have thickness, but shouldn't really intersect the adjacent line when drawn.
-            let t_pt = pt.project_away(LANE_THICKNESS * 0.4, perp_angle);
+            let t_pt = pt.project_away(lane.width * 0.4, perp_angle);
             // The perp leg
             let p1 = t_pt.project_away(leg_length, perp_angle.opposite());
             result.push(Line::new(t_pt, p1).make_polygons(Distance::meters(0.25)));
@@ -209,10 +225,7 @@ fn calculate_driving_lines(lane: &Lane, parent: &Road, timer: &mut Timer) -> Vec
     if idx == 0 || (dir && parent.children_forwards[idx - 1].1 == LaneType::SharedLeftTurn) {
         return Vec::new();
     }
-    let lane_edge_pts = lane
-        .lane_center_pts
-        .shift_left(LANE_THICKNESS / 2.0)
-        .get(timer);
+    let lane_edge_pts = lane.lane_center_pts.shift_left(lane.width / 2.0).get(timer);
     dashed_lines(
         &lane_edge_pts,
         Distance::meters(0.25),
@@ -254,7 +267,7 @@ fn calculate_turn_markings(map: &Map, lane: &Lane, timer: &mut Timer) -> Vec<Pol
                 common_base.last_pt(),
                 common_base
                     .last_pt()
-                    .project_away(LANE_THICKNESS / 2.0, turn.angle()),
+                    .project_away(lane.width / 2.0, turn.angle()),
             ])
             .make_arrow(thickness)
             .with_context(timer, format!("turn_markings for {}", turn.id)),
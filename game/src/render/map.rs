@@ -8,7 +8,7 @@ use crate::render::lane::DrawLane;
 use crate::render::road::DrawRoad;
 use crate::render::Renderable;
 use crate::ui::Flags;
-use aabb_quadtree::QuadTree;
+use aabb_quadtree::{ItemId, QuadTree};
 use abstutil::{Cloneable, Timer};
 use ezgui::{Color, Drawable, EventCtx, GeomBatch, GfxCtx, ScreenRectangle};
 use geom::{Bounds, Circle, Distance, Duration, FindClosest, Time};
@@ -40,6 +40,8 @@ pub struct DrawMap {
     pub draw_all_areas: Drawable,
 
     quadtree: QuadTree<ID>,
+    // Map edits can move or remove these, so remember where they are in the quadtree.
+    quadtree_ids: HashMap<ID, ItemId>,
 }
 
 impl DrawMap {
@@ -64,7 +66,7 @@ impl DrawMap {
         for r in road_refs {
             all_roads.push(
                 osm_rank_to_color(cs, r.get_rank()),
-                r.get_thick_polygon().get(timer),
+                r.get_thick_polygon(map).get(timer),
             );
             if false {
                 all_roads.push(
@@ -140,14 +142,14 @@ impl DrawMap {
 
         let mut extra_shapes: Vec<DrawExtraShape> = Vec::new();
         if let Some(ref path) = flags.kml {
-            let raw_shapes = if path.ends_with(".kml") {
-                kml::load(&path, &map.get_gps_bounds(), timer)
-                    .expect("Couldn't load extra KML shapes")
-                    .shapes
-            } else {
-                let shapes: kml::ExtraShapes = abstutil::read_binary(path.clone(), timer);
-                shapes.shapes
-            };
+            let raw_shapes = kml::load_any(
+                &path,
+                &map.get_gps_bounds(),
+                &kml::AttributeMapping::default(),
+                timer,
+            )
+            .expect("Couldn't load extra shapes")
+            .shapes;
 
             let mut closest: FindClosest<DirectedRoadID> = FindClosest::new(&map.get_bounds());
             for r in map.all_roads().iter() {
@@ -201,15 +203,37 @@ impl DrawMap {
 
         timer.start("create quadtree");
         let mut quadtree = QuadTree::default(map.get_bounds().as_bbox());
+        let mut quadtree_ids = HashMap::new();
         // TODO use iter chain if everything was boxed as a renderable...
         for obj in &roads {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+            let id = obj.get_id();
+            if let Some(item) =
+                quadtree.insert_with_box(id.clone(), obj.get_outline(map).get_bounds().as_bbox())
+            {
+                quadtree_ids.insert(id, item);
+            }
         }
         for obj in &lanes {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+            let id = obj.get_id();
+            // Deleted lanes keep their ID, but shouldn't be found.
+            if let ID::Lane(l) = id {
+                if map.is_lane_deleted(l) {
+                    continue;
+                }
+            }
+            if let Some(item) =
+                quadtree.insert_with_box(id.clone(), obj.get_outline(map).get_bounds().as_bbox())
+            {
+                quadtree_ids.insert(id, item);
+            }
         }
         for obj in &intersections {
-            quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
+            let id = obj.get_id();
+            if let Some(item) =
+                quadtree.insert_with_box(id.clone(), obj.get_outline(map).get_bounds().as_bbox())
+            {
+                quadtree_ids.insert(id, item);
+            }
         }
         for obj in &buildings {
             quadtree.insert_with_box(obj.get_id(), obj.get_outline(map).get_bounds().as_bbox());
@@ -249,6 +273,48 @@ impl DrawMap {
             }),
 
             quadtree,
+            quadtree_ids,
+        }
+    }
+
+    // After map edits change a lane. It might be new, moved, or deleted.
+    pub fn update_lane(&mut self, l: LaneID, draw: DrawLane, map: &Map) {
+        let bounds = draw.get_outline(map).get_bounds();
+        if l.0 < self.lanes.len() {
+            self.lanes[l.0] = draw;
+        } else {
+            self.lanes.push(draw);
+        }
+        let bounds = if map.is_lane_deleted(l) {
+            None
+        } else {
+            Some(bounds)
+        };
+        self.reindex(ID::Lane(l), bounds);
+    }
+
+    pub fn update_road(&mut self, draw: DrawRoad, map: &Map) {
+        let id = draw.get_id();
+        let bounds = draw.get_outline(map).get_bounds();
+        self.roads[draw.id.0] = draw;
+        self.reindex(id, Some(bounds));
+    }
+
+    pub fn update_intersection(&mut self, draw: DrawIntersection, map: &Map) {
+        let id = draw.get_id();
+        let bounds = draw.get_outline(map).get_bounds();
+        self.intersections[draw.id.0] = draw;
+        self.reindex(id, Some(bounds));
+    }
+
+    fn reindex(&mut self, id: ID, bounds: Option<Bounds>) {
+        if let Some(item) = self.quadtree_ids.remove(&id) {
+            self.quadtree.remove(item);
+        }
+        if let Some(b) = bounds {
+            if let Some(item) = self.quadtree.insert_with_box(id.clone(), b.as_bbox()) {
+                self.quadtree_ids.insert(id, item);
+            }
         }
     }
 
@@ -399,6 +465,10 @@ impl InnerAgentColorScheme {
                     ("car", cs.get_def("unzoomed car", Color::RED.alpha(0.5))),
                     ("bike", cs.get_def("unzoomed bike", Color::GREEN.alpha(0.5))),
                     ("bus", cs.get_def("unzoomed bus", Color::BLUE.alpha(0.5))),
+                    (
+                        "truck",
+                        cs.get_def("unzoomed truck", Color::PURPLE.alpha(0.5)),
+                    ),
                     (
                         "pedestrian",
                         cs.get_def("unzoomed pedestrian", Color::ORANGE.alpha(0.5)),
@@ -445,6 +515,7 @@ impl InnerAgentColorScheme {
                 Some(VehicleType::Car) => "car".to_string(),
                 Some(VehicleType::Bike) => "bike".to_string(),
                 Some(VehicleType::Bus) => "bus".to_string(),
+                Some(VehicleType::Truck) => "truck".to_string(),
                 None => "pedestrian".to_string(),
             },
             InnerAgentColorScheme::Delay => classify_delay(agent.metadata.time_spent_blocked),
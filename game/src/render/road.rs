@@ -2,7 +2,7 @@ use crate::helpers::{ColorScheme, ID};
 use crate::render::{dashed_lines, DrawCtx, DrawOptions, Renderable, OUTLINE_THICKNESS};
 use ezgui::{Color, Drawable, GeomBatch, GfxCtx, Line, Prerender, Text};
 use geom::{Distance, Polygon, Pt2D};
-use map_model::{LaneType, Map, Road, RoadID, LANE_THICKNESS};
+use map_model::{LaneType, Map, Road, RoadID};
 
 pub struct DrawRoad {
     pub id: RoadID,
@@ -17,14 +17,14 @@ impl DrawRoad {
     pub fn new(r: &Road, map: &Map, cs: &ColorScheme, prerender: &Prerender) -> DrawRoad {
         let mut draw = GeomBatch::new();
         // The road's original center_pts don't account for contraflow lane edits.
-        let center = map
-            .get_l(if !r.children_forwards.is_empty() {
-                r.children_forwards[0].0
-            } else {
-                r.children_backwards[0].0
-            })
+        let first_lane = map.get_l(if !r.children_forwards.is_empty() {
+            r.children_forwards[0].0
+        } else {
+            r.children_backwards[0].0
+        });
+        let center = first_lane
             .lane_center_pts
-            .shift_left(LANE_THICKNESS / 2.0)
+            .shift_left(first_lane.width / 2.0)
             .unwrap();
         let width = Distance::meters(0.25);
         // If the road is a one-way (only parking and sidewalk on the off-side), draw a solid line
@@ -69,14 +69,14 @@ impl Renderable for DrawRoad {
     }
 
     fn get_outline(&self, map: &Map) -> Polygon {
-        let (pl, width) = map.get_r(self.id).get_thick_polyline().unwrap();
+        let (pl, width) = map.get_r(self.id).get_thick_polyline(map).unwrap();
         pl.to_thick_boundary(width, OUTLINE_THICKNESS)
-            .unwrap_or_else(|| map.get_r(self.id).get_thick_polygon().unwrap())
+            .unwrap_or_else(|| map.get_r(self.id).get_thick_polygon(map).unwrap())
     }
 
     fn contains_pt(&self, pt: Pt2D, map: &Map) -> bool {
         map.get_r(self.id)
-            .get_thick_polygon()
+            .get_thick_polygon(map)
             .unwrap()
             .contains_pt(pt)
     }
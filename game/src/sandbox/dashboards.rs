@@ -315,5 +315,6 @@ fn color_for_mode(m: TripMode, ui: &UI) -> Color {
         TripMode::Bike => ui.cs.get("unzoomed bike"),
         TripMode::Transit => ui.cs.get("unzoomed bus"),
         TripMode::Drive => ui.cs.get("unzoomed car"),
+        TripMode::RideHail => ui.cs.get_def("unzoomed ride-hail", Color::PINK.alpha(0.5)),
     }
 }
@@ -15,7 +15,7 @@ use crate::ui::UI;
 use abstutil::{prettyprint_usize, Timer};
 use ezgui::{layout, Color, EventCtx, GfxCtx, Line, ModalMenu, TextSpan, Wizard};
 use geom::Duration;
-use map_model::{EditCmd, Map, MapEdits};
+use map_model::{EditCmd, Map, PermanentMapEdits};
 use sim::{Analytics, Scenario, TripMode};
 
 pub struct GameplayRunner {
@@ -86,7 +86,7 @@ impl GameplayMode {
             s.seed_buses = true;
             s
         } else {
-            abstutil::read_binary(abstutil::path_scenario(map.get_name(), &name), timer)
+            Scenario::load(map, &name, timer)
         })
     }
 
@@ -104,10 +104,15 @@ impl GameplayMode {
         }
     }
 
-    pub fn allows(&self, edits: &MapEdits) -> bool {
+    pub fn allows(&self, edits: &PermanentMapEdits) -> bool {
         for cmd in &edits.commands {
-            match cmd {
-                EditCmd::ChangeLaneType { .. } | EditCmd::ReverseLane { .. } => {
+            match cmd.cmd {
+                EditCmd::ChangeLaneType { .. }
+                | EditCmd::ReverseLane { .. }
+                | EditCmd::ChangeSpeedLimit { .. }
+                | EditCmd::AddLane { .. }
+                | EditCmd::DeleteLane { .. }
+                | EditCmd::ChangeLaneWidth { .. } => {
                     if !self.can_edit_lanes() {
                         return false;
                     }
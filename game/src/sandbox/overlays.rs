@@ -10,18 +10,21 @@ use crate::ui::{ShowEverything, UI};
 use abstutil::{prettyprint_usize, Counter};
 use ezgui::{Choice, Color, Composite, Drawable, EventCtx, GeomBatch, GfxCtx, Key, Line, Text};
 use geom::{Distance, Duration, PolyLine, Time};
-use map_model::IntersectionID;
-use sim::ParkingSpot;
+use map_model::{IntersectionID, LevelOfTrafficStress, RoadID};
+use sim::{Emissions, ParkingSpot};
 use std::collections::HashSet;
 
 pub enum Overlays {
     Inactive,
     ParkingAvailability(Time, RoadColorer),
     IntersectionDelay(Time, ObjectColorer),
+    NearMisses(Time, ObjectColorer),
     CumulativeThroughput(Time, ObjectColorer),
     FinishedTripsHistogram(Time, Histogram),
     BikeNetwork(RoadColorer),
+    BikeStress(RoadColorer),
     BusNetwork(RoadColorer),
+    Emissions(Time, RoadColorer),
     // Only set by certain gameplay modes
     BusRoute(ShowBusRoute),
     BusDelaysOverTime(Composite),
@@ -32,14 +35,17 @@ impl Overlays {
     pub fn event(&mut self, ctx: &mut EventCtx, ui: &UI) -> Option<Transition> {
         let now = ui.primary.sim.time();
         match self {
-            // Don't bother with Inactive, BusRoute, BusDelaysOverTime, BikeNetwork, BusNetwork --
-            // nothing needed or the gameplay mode will update it.
+            // Don't bother with Inactive, BusRoute, BusDelaysOverTime, BikeNetwork, BikeStress,
+            // BusNetwork -- nothing needed or the gameplay mode will update it.
             Overlays::ParkingAvailability(t, _) if now != *t => {
                 *self = Overlays::parking_availability(ctx, ui);
             }
             Overlays::IntersectionDelay(t, _) if now != *t => {
                 *self = Overlays::intersection_delay(ctx, ui);
             }
+            Overlays::NearMisses(t, _) if now != *t => {
+                *self = Overlays::near_misses(ctx, ui);
+            }
             Overlays::CumulativeThroughput(t, _) if now != *t => {
                 *self = Overlays::cumulative_throughput(ctx, ui);
             }
@@ -49,6 +55,9 @@ impl Overlays {
             Overlays::FinishedTripsHistogram(t, _) if now != *t => {
                 *self = Overlays::finished_trips_histogram(ctx, ui);
             }
+            Overlays::Emissions(t, _) if now != *t => {
+                *self = Overlays::emissions(ctx, ui);
+            }
             _ => {}
         };
         None
@@ -60,11 +69,14 @@ impl Overlays {
             Overlays::Inactive => false,
             Overlays::ParkingAvailability(_, ref heatmap)
             | Overlays::BikeNetwork(ref heatmap)
-            | Overlays::BusNetwork(ref heatmap) => {
+            | Overlays::BikeStress(ref heatmap)
+            | Overlays::BusNetwork(ref heatmap)
+            | Overlays::Emissions(_, ref heatmap) => {
                 heatmap.draw(g, ui);
                 true
             }
             Overlays::IntersectionDelay(_, ref heatmap)
+            | Overlays::NearMisses(_, ref heatmap)
             | Overlays::CumulativeThroughput(_, ref heatmap) => {
                 heatmap.draw(g, ui);
                 true
@@ -115,10 +127,13 @@ impl Overlays {
                         Choice::new("none", ()).key(Key::N),
                         Choice::new("parking availability", ()).key(Key::P),
                         Choice::new("intersection delay", ()).key(Key::I),
+                        Choice::new("near misses", ()).key(Key::M),
                         Choice::new("cumulative throughput", ()).key(Key::T),
                         Choice::new("finished trips histogram", ()).key(Key::H),
                         Choice::new("bike network", ()).key(Key::B),
+                        Choice::new("bike traffic stress", ()).key(Key::S),
                         Choice::new("bus network", ()).key(Key::U),
+                        Choice::new("emissions", ()).key(Key::E),
                     ]
                 })?;
                 Some(Transition::PopWithData(Box::new(move |state, ui, ctx| {
@@ -127,10 +142,13 @@ impl Overlays {
                         "none" => Overlays::Inactive,
                         "parking availability" => Overlays::parking_availability(ctx, ui),
                         "intersection delay" => Overlays::intersection_delay(ctx, ui),
+                        "near misses" => Overlays::near_misses(ctx, ui),
                         "cumulative throughput" => Overlays::cumulative_throughput(ctx, ui),
                         "finished trips histogram" => Overlays::finished_trips_histogram(ctx, ui),
                         "bike network" => Overlays::bike_network(ctx, ui),
+                        "bike traffic stress" => Overlays::bike_stress(ctx, ui),
                         "bus network" => Overlays::bus_network(ctx, ui),
+                        "emissions" => Overlays::emissions(ctx, ui),
                         _ => unreachable!(),
                     };
                 })))
@@ -244,6 +262,38 @@ impl Overlays {
         Overlays::IntersectionDelay(ui.primary.sim.time(), colorer.build(ctx, &ui.primary.map))
     }
 
+    fn near_misses(ctx: &EventCtx, ui: &UI) -> Overlays {
+        let counts = ui
+            .primary
+            .sim
+            .get_analytics()
+            .near_misses_per_intersection(ui.primary.sim.time())
+            .consume();
+
+        let few = Color::YELLOW;
+        let some = Color::ORANGE;
+        let many = Color::RED;
+        let mut txt = Text::from(Line("near misses per intersection"));
+        txt.add(Line(format!(
+            "{} total",
+            prettyprint_usize(counts.values().sum())
+        )));
+        let mut colorer =
+            ObjectColorerBuilder::new(txt, vec![("< 5", few), ("< 20", some), (">= 20", many)]);
+        for (i, cnt) in counts {
+            let color = if cnt < 5 {
+                few
+            } else if cnt < 20 {
+                some
+            } else {
+                many
+            };
+            colorer.add(ID::Intersection(i), color);
+        }
+
+        Overlays::NearMisses(ui.primary.sim.time(), colorer.build(ctx, &ui.primary.map))
+    }
+
     fn cumulative_throughput(ctx: &EventCtx, ui: &UI) -> Overlays {
         let light = Color::GREEN;
         let medium = Color::YELLOW;
@@ -311,6 +361,37 @@ impl Overlays {
         Overlays::BikeNetwork(colorer.build(ctx, &ui.primary.map))
     }
 
+    fn bike_stress(ctx: &EventCtx, ui: &UI) -> Overlays {
+        let map = &ui.primary.map;
+        let mut txt = Text::from(Line("bike level of traffic stress"));
+        txt.add(Line(format!(
+            "routes avoid anything above {:?}",
+            map.get_bike_lts_tolerance()
+        )));
+        // When a road has lanes with different stress, show the best option unzoomed.
+        let color = |lts| match lts {
+            LevelOfTrafficStress::LTS1 => Color::GREEN,
+            LevelOfTrafficStress::LTS2 => Color::YELLOW,
+            LevelOfTrafficStress::LTS3 => Color::ORANGE,
+            LevelOfTrafficStress::LTS4 => Color::RED,
+        };
+        let mut colorer = RoadColorerBuilder::new(
+            txt,
+            vec![
+                ("LTS 1", color(LevelOfTrafficStress::LTS1)),
+                ("LTS 2", color(LevelOfTrafficStress::LTS2)),
+                ("LTS 3", color(LevelOfTrafficStress::LTS3)),
+                ("LTS 4", color(LevelOfTrafficStress::LTS4)),
+            ],
+        );
+        for l in map.all_lanes() {
+            if let Some(lts) = l.bike_lts {
+                colorer.add(l.id, color(lts), map);
+            }
+        }
+        Overlays::BikeStress(colorer.build(ctx, map))
+    }
+
     fn bus_network(ctx: &EventCtx, ui: &UI) -> Overlays {
         let mut colorer = RoadColorerBuilder::new(
             Text::from(Line("bus networks")),
@@ -324,6 +405,59 @@ impl Overlays {
         Overlays::BusNetwork(colorer.build(ctx, &ui.primary.map))
     }
 
+    fn emissions(ctx: &EventCtx, ui: &UI) -> Overlays {
+        let map = &ui.primary.map;
+        let per_road = ui
+            .primary
+            .sim
+            .get_analytics()
+            .emissions_per_road(ui.primary.sim.time(), map);
+
+        let mut total = Emissions::ZERO;
+        for amount in per_road.values() {
+            total += *amount;
+        }
+        let mut txt = Text::from(Line("CO2 emitted per road"));
+        txt.add(Line(format!("{:.1} kg CO2", total.co2 / 1000.0)));
+        txt.add(Line(format!("{:.1} g NOx", total.nox)));
+        txt.add(Line(format!("{:.1} kWh used", total.energy)));
+
+        let light = Color::GREEN;
+        let medium = Color::YELLOW;
+        let heavy = Color::RED;
+        let mut colorer = RoadColorerBuilder::new(
+            txt,
+            vec![
+                ("< 50%ile", light),
+                ("< 90%ile", medium),
+                (">= 90%ile", heavy),
+            ],
+        );
+        // Electric vehicles only use energy, so skip roads without any tailpipe emissions.
+        let mut roads: Vec<(RoadID, f64)> = per_road
+            .into_iter()
+            .filter(|(_, amount)| amount.co2 > 0.0)
+            .map(|(r, amount)| (r, amount.co2))
+            .collect();
+        roads.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        let p50_idx = ((roads.len() as f64) * 0.5) as usize;
+        let p90_idx = ((roads.len() as f64) * 0.9) as usize;
+        for (idx, (r, _)) in roads.into_iter().enumerate() {
+            let color = if idx < p50_idx {
+                light
+            } else if idx < p90_idx {
+                medium
+            } else {
+                heavy
+            };
+            for l in map.get_r(r).all_lanes() {
+                colorer.add(l, color, map);
+            }
+        }
+
+        Overlays::Emissions(ui.primary.sim.time(), colorer.build(ctx, map))
+    }
+
     pub fn finished_trips_histogram(ctx: &EventCtx, ui: &UI) -> Overlays {
         let now = ui.primary.sim.time();
         Overlays::FinishedTripsHistogram(
@@ -52,6 +52,9 @@ impl UI {
                 primary
                     .map
                     .all_lanes()
+                    .iter()
+                    .filter(|l| !primary.map.is_lane_deleted(l.id))
+                    .collect::<Vec<_>>()
                     .choose(&mut rng)
                     .and_then(|l| ID::Lane(l.id).canonical_point(&primary))
             })
@@ -6,7 +6,7 @@ use std::{cmp, f64, ops};
 
 // In seconds. Can be negative.
 // TODO Naming is awkward. Can represent a moment in time or a duration.
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Duration(f64);
 
 // By construction, Duration is a finite f64 with trimmed precision.
@@ -132,6 +132,19 @@ impl Polygon {
         Bounds::from(&self.points)
     }
 
+    // In square meters
+    pub fn area(&self) -> f64 {
+        self.triangles()
+            .into_iter()
+            .map(|tri| {
+                ((tri.pt2.x() - tri.pt1.x()) * (tri.pt3.y() - tri.pt1.y())
+                    - (tri.pt3.x() - tri.pt1.x()) * (tri.pt2.y() - tri.pt1.y()))
+                .abs()
+                    / 2.0
+            })
+            .sum()
+    }
+
     pub fn translate(&self, dx: f64, dy: f64) -> Polygon {
         Polygon {
             points: self.points.iter().map(|pt| pt.offset(dx, dy)).collect(),
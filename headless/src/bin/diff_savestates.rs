@@ -0,0 +1,84 @@
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use sim::{Sim, SimFlags, SimOptions};
+use std::path::Path;
+
+// Reports where two savestates differ. Given two directories of savestates from runs that should
+// be identical (see SimOptions::savestate_every), finds the earliest time they diverge. Everything
+// has to live somewhere in ../data/player/saves/.
+fn main() {
+    let mut args = CmdArgs::new();
+    let path1 = args.required_free();
+    let path2 = args.required_free();
+    args.done();
+
+    let mut timer = Timer::new("diff savestates");
+    if Path::new(&path1).is_dir() && Path::new(&path2).is_dir() {
+        diff_series(path1, path2, &mut timer);
+    } else {
+        let (map, sim1) = load_first(path1, &mut timer);
+        let sim2 = Sim::load_savestate(path2, &map, &mut timer).unwrap();
+        for line in sim1.diff(&sim2).describe() {
+            println!("{}", line);
+        }
+    }
+}
+
+fn diff_series(dir1: String, dir2: String, timer: &mut Timer) {
+    // Savestates are named by time, so sorting by name is chronological.
+    let names2 = abstutil::list_all_objects(dir2.clone());
+    let common: Vec<String> = abstutil::list_all_objects(dir1.clone())
+        .into_iter()
+        .filter(|name| names2.contains(name))
+        .collect();
+    if common.is_empty() {
+        println!(
+            "{} and {} have no savestates from the same time",
+            dir1, dir2
+        );
+        return;
+    }
+
+    let mut map: Option<Map> = None;
+    let mut last_match: Option<&String> = None;
+    for name in &common {
+        let path1 = format!("{}/{}.bin", dir1, name);
+        let path2 = format!("{}/{}.bin", dir2, name);
+        let sim1 = if let Some(ref m) = map {
+            Sim::load_savestate(path1, m, timer).unwrap()
+        } else {
+            let (m, sim) = load_first(path1, timer);
+            map = Some(m);
+            sim
+        };
+        let sim2 = Sim::load_savestate(path2, map.as_ref().unwrap(), timer).unwrap();
+
+        if sim1 != sim2 {
+            println!("Earliest divergence at {}", name);
+            if let Some(prev) = last_match {
+                println!("Still identical at {}", prev);
+            } else {
+                println!("The first savestates already differ");
+            }
+            for line in sim1.diff(&sim2).describe() {
+                println!("{}", line);
+            }
+            return;
+        }
+        last_match = Some(name);
+    }
+    println!("All {} savestates from the same times match", common.len());
+}
+
+// Also loads the map and any edits.
+fn load_first(path: String, timer: &mut Timer) -> (Map, Sim) {
+    let flags = SimFlags {
+        load: path,
+        use_map_fixes: false,
+        rng_seed: None,
+        bike_lts_tolerance: None,
+        opts: SimOptions::new("diff_savestates"),
+    };
+    let (map, sim, _) = flags.load(timer);
+    (map, sim)
+}
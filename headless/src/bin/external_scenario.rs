@@ -0,0 +1,42 @@
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use sim::Scenario;
+
+// Converts between saved scenarios and the human-editable JSON or CSV formats, picking the format
+// from the file extension. --export writes an existing scenario out; --import reads one in and
+// saves it under the --scenario name for the map.
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_name = args.required("--map");
+    let scenario_name = args.required("--scenario");
+    let export = args.optional("--export");
+    let import = args.optional("--import");
+    args.done();
+
+    let mut timer = Timer::new(format!("convert scenario {}", scenario_name));
+    let map = Map::new(abstutil::path_map(&map_name), false, &mut timer);
+    match (export, import) {
+        (Some(path), None) => {
+            let scenario = Scenario::load(&map, &scenario_name, &mut timer);
+            if path.ends_with(".csv") {
+                scenario.export_csv(&path, &map, &mut timer).unwrap();
+            } else {
+                scenario.export_json(path, &map, &mut timer);
+            }
+        }
+        (None, Some(path)) => {
+            let mut scenario = if path.ends_with(".csv") {
+                Scenario::import_csv(&path, &scenario_name, &map, &mut timer)
+            } else {
+                Scenario::import_json(path, &map, &mut timer)
+            }
+            .unwrap();
+            scenario.scenario_name = scenario_name;
+            for line in scenario.describe() {
+                timer.note(line);
+            }
+            scenario.save(&map);
+        }
+        _ => panic!("Pass exactly one of --export and --import"),
+    }
+}
@@ -0,0 +1,85 @@
+use abstutil::{CmdArgs, Timer};
+use geom::{Duration, Time};
+use map_model::{Map, MapEdits};
+use sim::{MonteCarloSummary, RunSummary, Scenario, Sim, SimFlags};
+
+// Runs the same scenario and edits under many RNG seeds in parallel, then summarizes trip times
+// and intersection delays with 95% confidence intervals. Results from a single seed can be
+// misleading; compare summaries from two sets of edits instead. The usual simulation flags, like
+// --kinematics or --freeform_policy, apply to every run.
+fn main() {
+    let mut args = CmdArgs::new();
+    let sim_flags = SimFlags::from_args(&mut args);
+    let map_name = args.required("--map");
+    let scenario_name = args.required("--scenario");
+    let edits_name = args
+        .optional("--edits")
+        .unwrap_or_else(|| "no_edits".to_string());
+    let num_runs = args
+        .optional_parse("--runs", |s| s.parse::<usize>())
+        .unwrap_or(10);
+    // Run i uses --rng_seed plus i
+    let rng_seed = sim_flags.rng_seed.unwrap_or(42);
+    // Some trips might never finish, so optionally stop early.
+    let end_time = args.optional_parse("--end_time", Time::parse);
+    let output = args.required("--output");
+    args.done();
+    assert!(
+        num_runs > 0 && num_runs <= 256,
+        "--runs must be from 1 to 256"
+    );
+
+    let mut timer = Timer::new(format!("run {} under {} seeds", scenario_name, num_runs));
+    let mut map = Map::new(abstutil::path_map(&map_name), false, &mut timer);
+    if let Some(tolerance) = sim_flags.bike_lts_tolerance {
+        map.set_bike_lts_tolerance(tolerance);
+    }
+    if edits_name != "no_edits" {
+        let edits = MapEdits::load(&mut map, &edits_name, &mut timer);
+        map.apply_edits(edits, &mut timer);
+        map.mark_edits_fresh();
+    }
+    if sim_flags.bike_lts_tolerance.is_some() || edits_name != "no_edits" {
+        map.recalculate_pathfinding_after_edits(&mut timer);
+    }
+    let scenario = Scenario::load(&map, &scenario_name, &mut timer);
+    let run_name = if sim_flags.opts.run_name == "unnamed" {
+        scenario.scenario_name.clone()
+    } else {
+        sim_flags.opts.run_name.clone()
+    };
+
+    let map = &map;
+    let scenario = &scenario;
+    let sim_flags = &sim_flags;
+    let run_name = &run_name;
+    let seeds: Vec<u8> = (0..num_runs)
+        .map(|i| rng_seed.wrapping_add(i as u8))
+        .collect();
+    let runs = timer.parallelize("run simulations", seeds, |seed| {
+        let mut opts = sim_flags.opts.clone();
+        // Keep savestates from different runs apart
+        opts.run_name = format!("{}_seed{}", run_name, seed);
+        let mut sim = Sim::new(map, opts, &mut Timer::throwaway());
+        let mut flags = sim_flags.clone();
+        flags.rng_seed = Some(seed);
+        sim.set_rng(flags.make_sim_rng());
+        let mut rng = flags.make_rng();
+        scenario.instantiate(&mut sim, map, &mut rng, &mut Timer::throwaway());
+        if let Some(t) = end_time {
+            sim.step(map, t - Time::START_OF_DAY);
+        } else {
+            while !sim.is_done() {
+                sim.step(map, Duration::minutes(5));
+            }
+        }
+        RunSummary::new(seed, sim.get_analytics(), sim.time())
+    });
+
+    let summary = MonteCarloSummary::new(runs);
+    for line in summary.describe().split('\n') {
+        timer.note(line.to_string());
+    }
+    abstutil::write_json(output.clone(), &summary);
+    timer.note(format!("Wrote {}", output));
+}
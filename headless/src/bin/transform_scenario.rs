@@ -0,0 +1,78 @@
+use abstutil::{CmdArgs, Timer};
+use geom::{Duration, Time};
+use map_model::Map;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{Scenario, TripMode};
+use std::collections::BTreeSet;
+
+// Makes a variation of a saved scenario, like a 10% sample for quick runs or 120% demand. Filters
+// apply first, then scaling or sampling, then shifting departures. The result is saved under a new
+// name for the same map.
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_name = args.required("--map");
+    let input = args.required("--scenario");
+    let output = args.required("--output");
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse::<u8>())
+        .unwrap_or(42);
+    // Like walk,transit
+    let modes = args.optional_parse("--modes", parse_modes);
+    let neighborhood = args.optional("--neighborhood");
+    let window_start = args.optional_parse("--start_time", Time::parse);
+    let window_end = args.optional_parse("--end_time", Time::parse);
+    let scale = args.optional_parse("--scale", |s| s.parse::<f64>());
+    // Between 0 and 1
+    let sample = args.optional_parse("--sample", |s| s.parse::<f64>());
+    // Negative to shift earlier
+    let shift_minutes = args.optional_parse("--shift_minutes", |s| s.parse::<f64>());
+    args.done();
+
+    let mut timer = Timer::new(format!("transform scenario {}", input));
+    let mut rng = XorShiftRng::from_seed([rng_seed; 16]);
+    let map = Map::new(abstutil::path_map(&map_name), false, &mut timer);
+    let mut scenario = Scenario::load(&map, &input, &mut timer);
+
+    if let Some(modes) = modes {
+        scenario = scenario.filter_modes(&modes);
+    }
+    if let Some(name) = neighborhood {
+        scenario = scenario.filter_neighborhood(&name, &map);
+    }
+    if window_start.is_some() || window_end.is_some() {
+        scenario = scenario.filter_time_window(
+            window_start.unwrap_or(Time::START_OF_DAY),
+            window_end.unwrap_or(Time::START_OF_DAY + Duration::hours(24)),
+            &mut rng,
+        );
+    }
+    if let Some(factor) = scale {
+        scenario = scenario.scale(factor, &mut rng);
+    }
+    if let Some(pct) = sample {
+        scenario = scenario.sample(pct, &mut rng);
+    }
+    if let Some(mins) = shift_minutes {
+        scenario = scenario.shift_departures(Duration::seconds(mins * 60.0));
+    }
+
+    scenario.scenario_name = output;
+    for line in scenario.describe() {
+        timer.note(line);
+    }
+    scenario.save(&map);
+}
+
+fn parse_modes(list: &str) -> Result<BTreeSet<TripMode>, String> {
+    list.split(',')
+        .map(|mode| match mode {
+            "walk" => Ok(TripMode::Walk),
+            "bike" => Ok(TripMode::Bike),
+            "transit" => Ok(TripMode::Transit),
+            "drive" => Ok(TripMode::Drive),
+            "ride-hail" => Ok(TripMode::RideHail),
+            x => Err(format!("unknown mode {}", x)),
+        })
+        .collect()
+}
@@ -10,6 +10,8 @@ fn main() {
     let enable_profiler = args.enabled("--enable_profiler");
     // Every 0.1s, pretend to draw everything to make sure there are no bugs.
     let paranoia = args.enabled("--paranoia");
+    // Write the map and per-road/intersection results as GeoJSON when the sim is done.
+    let export_geojson = args.optional("--export_geojson");
     args.done();
 
     let mut timer = Timer::new("setup headless");
@@ -61,6 +63,13 @@ fn main() {
     );
     timer.done();
     println!("Done at {}", sim.time());
+    if let Some(path) = export_geojson {
+        map_model::export_geojson(
+            &map,
+            &sim.get_analytics().geojson_properties(sim.time(), &map),
+            path,
+        );
+    }
     if enable_profiler && save_at.is_none() {
         #[cfg(feature = "profiler")]
         {
@@ -0,0 +1,152 @@
+use crate::{ExtraShape, ExtraShapes};
+use abstutil::{prettyprint_usize, FileWithProgress, Timer};
+use geom::{GPSBounds, LonLat};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+// Tried in order if the caller doesn't say which column has the geometry.
+const GEOMETRY_COLUMNS: [&str; 4] = ["wkt", "geometry", "the_geom", "shape"];
+
+// One row per feature. One column holds WKT geometry; all others become attributes.
+pub fn load(
+    path: &str,
+    gps_bounds: &GPSBounds,
+    geometry_column: Option<&String>,
+    timer: &mut Timer,
+) -> Result<ExtraShapes, Error> {
+    println!("Opening {}", path);
+    let (f, done) = FileWithProgress::new(path)?;
+    let mut reader = csv::Reader::from_reader(f);
+    let headers: Vec<String> = reader.headers()?.iter().map(|h| h.to_string()).collect();
+    let geom_idx = if let Some(col) = geometry_column {
+        headers.iter().position(|h| h == col)
+    } else {
+        headers
+            .iter()
+            .position(|h| GEOMETRY_COLUMNS.contains(&h.to_lowercase().as_str()))
+    }
+    .ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} has no geometry column; headers are {:?}", path, headers),
+        )
+    })?;
+
+    let mut shapes = Vec::new();
+    let mut skipped_count = 0;
+    for rec in reader.records() {
+        let rec = rec?;
+        let mut attributes = BTreeMap::new();
+        for (idx, value) in rec.iter().enumerate() {
+            if idx != geom_idx && !value.is_empty() {
+                attributes.insert(headers[idx].clone(), value.to_string());
+            }
+        }
+
+        match parse_wkt(&rec[geom_idx]) {
+            Some(parts) => {
+                for raw_pts in parts {
+                    if raw_pts.iter().all(|pt| gps_bounds.contains(*pt)) {
+                        shapes.push(ExtraShape {
+                            points: raw_pts,
+                            attributes: attributes.clone(),
+                        });
+                    } else {
+                        skipped_count += 1;
+                    }
+                }
+            }
+            None => {
+                skipped_count += 1;
+            }
+        }
+    }
+
+    println!(
+        "Got {} shapes from {} and skipped {} shapes",
+        prettyprint_usize(shapes.len()),
+        path,
+        prettyprint_usize(skipped_count)
+    );
+    done(timer);
+
+    Ok(ExtraShapes { shapes })
+}
+
+// Handles (MULTI)POINT, (MULTI)LINESTRING, and (MULTI)POLYGON. Multi-part geometries are split
+// into one list of points per part, and only the outer ring of polygons is kept. Z and M values
+// are ignored.
+fn parse_wkt(input: &str) -> Option<Vec<Vec<LonLat>>> {
+    let open = input.find('(')?;
+    let close = input.rfind(')')?;
+    if close < open {
+        return None;
+    }
+    let kind = input[0..open].trim().to_uppercase();
+    let body = &input[open + 1..close];
+    // Strip off things like "LINESTRING Z"
+    let kind = kind.split_whitespace().next()?;
+
+    match kind {
+        "POINT" | "LINESTRING" => Some(vec![parse_coords(body)?]),
+        "MULTIPOINT" => {
+            // Both "MULTIPOINT (1 2, 3 4)" and "MULTIPOINT ((1 2), (3 4))" are valid.
+            let pts = if body.contains('(') {
+                let mut pts = Vec::new();
+                for group in top_level_groups(body) {
+                    pts.extend(parse_coords(group)?);
+                }
+                pts
+            } else {
+                parse_coords(body)?
+            };
+            Some(pts.into_iter().map(|pt| vec![pt]).collect())
+        }
+        "MULTILINESTRING" => top_level_groups(body)
+            .into_iter()
+            .map(parse_coords)
+            .collect(),
+        "POLYGON" => Some(vec![parse_coords(top_level_groups(body).get(0)?)?]),
+        "MULTIPOLYGON" => top_level_groups(body)
+            .into_iter()
+            .map(|polygon| parse_coords(top_level_groups(polygon).get(0)?))
+            .collect(),
+        _ => None,
+    }
+}
+
+// "(a), (b (c))" -> ["a", "b (c)"]
+fn top_level_groups(input: &str) -> Vec<&str> {
+    let mut groups = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in input.char_indices() {
+        if c == '(' {
+            if depth == 0 {
+                start = idx + 1;
+            }
+            depth += 1;
+        } else if c == ')' {
+            depth -= 1;
+            if depth == 0 {
+                groups.push(&input[start..idx]);
+            }
+        }
+    }
+    groups
+}
+
+fn parse_coords(input: &str) -> Option<Vec<LonLat>> {
+    let mut pts = Vec::new();
+    for pair in input.split(',') {
+        let mut nums = pair.split_whitespace();
+        let lon = nums.next()?.parse::<f64>().ok()?;
+        let lat = nums.next()?.parse::<f64>().ok()?;
+        pts.push(LonLat::new(lon, lat));
+    }
+    if pts.is_empty() {
+        None
+    } else {
+        Some(pts)
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{ExtraShape, ExtraShapes};
+use abstutil::{prettyprint_usize, Timer};
+use geojson::{GeoJson, Value};
+use geom::{GPSBounds, LonLat};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+// Every feature becomes one or more shapes; multi-part geometries are split up, since ExtraShape
+// only holds one list of points. For polygons, only the outer ring is kept. Property values are
+// all flattened to strings, to match what KML gives us.
+pub fn load(path: &str, gps_bounds: &GPSBounds, timer: &mut Timer) -> Result<ExtraShapes, Error> {
+    let document: GeoJson = abstutil::maybe_read_json(path.to_string(), timer)?;
+    let features = match document {
+        GeoJson::FeatureCollection(c) => c.features,
+        GeoJson::Feature(f) => vec![f],
+        GeoJson::Geometry(_) => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} is a bare geometry; need features", path),
+            ));
+        }
+    };
+
+    let mut shapes = Vec::new();
+    let mut skipped_count = 0;
+    for f in features {
+        let mut attributes = BTreeMap::new();
+        if let Some(props) = f.properties {
+            for (k, v) in props {
+                let value = v.as_str().map(|s| s.to_string()).unwrap_or_else(|| {
+                    // Numbers, bools, and so on. Nulls just become missing attributes.
+                    if v.is_null() {
+                        String::new()
+                    } else {
+                        v.to_string()
+                    }
+                });
+                if !value.is_empty() {
+                    attributes.insert(k, value);
+                }
+            }
+        }
+
+        let geometry = if let Some(g) = f.geometry {
+            g
+        } else {
+            skipped_count += 1;
+            continue;
+        };
+        for raw_pts in flatten(geometry.value) {
+            if let Some(points) = convert_pts(raw_pts, gps_bounds) {
+                shapes.push(ExtraShape {
+                    points,
+                    attributes: attributes.clone(),
+                });
+            } else {
+                skipped_count += 1;
+            }
+        }
+    }
+
+    println!(
+        "Got {} shapes from {} and skipped {} shapes",
+        prettyprint_usize(shapes.len()),
+        path,
+        prettyprint_usize(skipped_count)
+    );
+    Ok(ExtraShapes { shapes })
+}
+
+fn flatten(value: Value) -> Vec<Vec<Vec<f64>>> {
+    match value {
+        Value::Point(pt) => vec![vec![pt]],
+        Value::MultiPoint(pts) => pts.into_iter().map(|pt| vec![pt]).collect(),
+        Value::LineString(pts) => vec![pts],
+        Value::MultiLineString(lines) => lines,
+        Value::Polygon(mut rings) => {
+            if rings.is_empty() {
+                Vec::new()
+            } else {
+                vec![rings.remove(0)]
+            }
+        }
+        Value::MultiPolygon(polygons) => polygons
+            .into_iter()
+            .filter_map(|mut rings| {
+                if rings.is_empty() {
+                    None
+                } else {
+                    Some(rings.remove(0))
+                }
+            })
+            .collect(),
+        Value::GeometryCollection(list) => {
+            list.into_iter().flat_map(|g| flatten(g.value)).collect()
+        }
+    }
+}
+
+// None if any point is malformed or out-of-bounds, matching how KML shapes are treated.
+fn convert_pts(raw_pts: Vec<Vec<f64>>, gps_bounds: &GPSBounds) -> Option<Vec<LonLat>> {
+    if raw_pts.is_empty() {
+        return None;
+    }
+    let mut pts = Vec::new();
+    for raw in raw_pts {
+        if raw.len() < 2 {
+            return None;
+        }
+        let pt = LonLat::new(raw[0], raw[1]);
+        if !gps_bounds.contains(pt) {
+            return None;
+        }
+        pts.push(pt);
+    }
+    Some(pts)
+}
@@ -1,3 +1,8 @@
+mod from_csv;
+mod from_geojson;
+mod mapping;
+
+pub use crate::mapping::AttributeMapping;
 use abstutil::{prettyprint_usize, FileWithProgress, Timer};
 use geom::{GPSBounds, LonLat};
 use quick_xml::events::Event;
@@ -16,6 +21,31 @@ pub struct ExtraShape {
     pub attributes: BTreeMap<String, String>,
 }
 
+// Figures out the format from the extension: KML, GeoJSON, CSV with a WKT geometry column, or
+// ExtraShapes previously extracted and saved as binary. The mapping is applied afterwards.
+pub fn load_any(
+    path: &str,
+    gps_bounds: &GPSBounds,
+    mapping: &AttributeMapping,
+    timer: &mut Timer,
+) -> Result<ExtraShapes, std::io::Error> {
+    let shapes = if path.ends_with(".kml") {
+        load(path, gps_bounds, timer)?
+    } else if path.ends_with(".geojson") || path.ends_with(".json") {
+        from_geojson::load(path, gps_bounds, timer)?
+    } else if path.ends_with(".csv") {
+        from_csv::load(path, gps_bounds, mapping.geometry_column.as_ref(), timer)?
+    } else if path.ends_with(".bin") {
+        abstutil::maybe_read_binary(path.to_string(), timer)?
+    } else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Don't know how to load shapes from {}", path),
+        ));
+    };
+    Ok(mapping.apply(shapes))
+}
+
 pub fn load(
     path: &str,
     gps_bounds: &GPSBounds,
@@ -1,18 +1,17 @@
-use abstutil::CmdArgs;
+use abstutil::{CmdArgs, Timer};
 use geom::GPSBounds;
+use kml::AttributeMapping;
 
 fn main() {
     let mut args = CmdArgs::new();
     let input = args.required("--input");
     let output = args.required("--output");
+    let mapping = args.optional("--mapping");
     args.done();
 
-    let shapes = kml::load(
-        &input,
-        &GPSBounds::seattle_bounds(),
-        &mut abstutil::Timer::new("extracting shapes from KML"),
-    )
-    .unwrap();
+    let mut timer = Timer::new(format!("extracting shapes from {}", input));
+    let mapping = AttributeMapping::load(mapping, &mut timer);
+    let shapes = kml::load_any(&input, &GPSBounds::seattle_bounds(), &mapping, &mut timer).unwrap();
 
     abstutil::write_binary(output, &shapes);
 }
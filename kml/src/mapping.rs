@@ -0,0 +1,62 @@
+use crate::ExtraShapes;
+use abstutil::Timer;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Different cities publish the same kind of data with different attribute names and values. This
+// translates a dataset's attributes into the ones consumers (like convert_osm) expect, so those
+// consumers only have to understand one layout. Any field can be omitted from the JSON.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct AttributeMapping {
+    // Dataset key -> expected key. Keys not listed here pass through unchanged.
+    pub rename_keys: BTreeMap<String, String>,
+    // Expected key -> (dataset value -> expected value). Applied after renaming keys.
+    pub rename_values: BTreeMap<String, BTreeMap<String, String>>,
+    // Expected key -> allowed values. Shapes missing the key or with some other value are dropped.
+    // Useful for datasets that mix many kinds of features into one layer.
+    pub keep_if: BTreeMap<String, Vec<String>>,
+    // For CSV input, the column containing WKT geometry. If unset, some common names are tried.
+    pub geometry_column: Option<String>,
+}
+
+impl AttributeMapping {
+    // No path means the identity mapping.
+    pub fn load(path: Option<String>, timer: &mut Timer) -> AttributeMapping {
+        if let Some(p) = path {
+            abstutil::read_json(p, timer)
+        } else {
+            AttributeMapping::default()
+        }
+    }
+
+    pub fn apply(&self, input: ExtraShapes) -> ExtraShapes {
+        let mut shapes = Vec::new();
+        for mut s in input.shapes {
+            if !self.rename_keys.is_empty() || !self.rename_values.is_empty() {
+                let mut attributes = BTreeMap::new();
+                for (k, v) in s.attributes {
+                    let key = self.rename_keys.get(&k).cloned().unwrap_or(k);
+                    let value = self
+                        .rename_values
+                        .get(&key)
+                        .and_then(|values| values.get(&v))
+                        .cloned()
+                        .unwrap_or(v);
+                    attributes.insert(key, value);
+                }
+                s.attributes = attributes;
+            }
+
+            if self.keep_if.iter().all(|(key, allowed)| {
+                s.attributes
+                    .get(key)
+                    .map(|v| allowed.contains(v))
+                    .unwrap_or(false)
+            }) {
+                shapes.push(s);
+            }
+        }
+        ExtraShapes { shapes }
+    }
+}
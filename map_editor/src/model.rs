@@ -332,10 +332,10 @@ impl Model {
         osm_tags.insert(osm::SYNTHETIC.to_string(), "true".to_string());
         osm_tags.insert(
             osm::SYNTHETIC_LANES.to_string(),
-            RoadSpec {
-                fwd: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
-                back: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
-            }
+            RoadSpec::new(
+                vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
+                vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
+            )
             .to_string(),
         );
         osm_tags.insert(osm::ENDPT_FWD.to_string(), "true".to_string());
@@ -549,14 +549,16 @@ impl Model {
 
         let mut obj = Object::blank(ID::Road(id));
 
-        for (idx, lt) in spec.fwd.iter().enumerate() {
+        let mut dist_from_center = Distance::ZERO;
+        for (idx, (lt, width)) in spec.fwd.iter().enumerate() {
             obj.push(
                 Model::lt_to_color(*lt, unset, lanes_unknown),
                 center_pts
-                    .shift_right(LANE_THICKNESS * (0.5 + (idx as f64)))
+                    .shift_right(dist_from_center + *width / 2.0)
                     .unwrap()
-                    .make_polygons(LANE_THICKNESS),
+                    .make_polygons(*width),
             );
+            dist_from_center += *width;
             if idx == 0 {
                 obj.push(
                     Color::YELLOW,
@@ -564,15 +566,17 @@ impl Model {
                 );
             }
         }
-        for (idx, lt) in spec.back.iter().enumerate() {
+        let mut dist_from_center = Distance::ZERO;
+        for (lt, width) in &spec.back {
             obj.push(
                 Model::lt_to_color(*lt, unset, lanes_unknown),
                 center_pts
                     .reversed()
-                    .shift_right(LANE_THICKNESS * (0.5 + (idx as f64)))
+                    .shift_right(dist_from_center + *width / 2.0)
                     .unwrap()
-                    .make_polygons(LANE_THICKNESS),
+                    .make_polygons(*width),
             );
+            dist_from_center += *width;
         }
 
         let mut result = vec![obj];
@@ -611,6 +615,7 @@ impl Model {
             LaneType::Driving => Color::BLACK,
             LaneType::Bus => Color::rgb(190, 74, 76),
             LaneType::Parking => Color::grey(0.2),
+            LaneType::LoadingZone => Color::rgb(120, 100, 30),
             LaneType::Sidewalk => Color::grey(0.8),
             LaneType::Biking => Color::rgb(15, 125, 75),
             LaneType::SharedLeftTurn => Color::YELLOW,
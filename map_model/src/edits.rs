@@ -1,8 +1,9 @@
 use crate::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, LaneType, Map,
-    RoadID, TurnID,
+    ControlStopSign, ControlTrafficSignal, DirectedRoadID, IDLookup, IDMapper, IntersectionID,
+    IntersectionType, LaneID, LaneType, Map, PermanentIDs, RoadID, TurnGroup, TurnID,
 };
-use abstutil::{retain_btreemap, retain_btreeset, Timer};
+use abstutil::{retain_btreemap, retain_btreeset, Error, Timer};
+use geom::{Distance, Speed};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -16,6 +17,9 @@ pub struct MapEdits {
     pub original_lts: BTreeMap<LaneID, LaneType>,
     pub reversed_lanes: BTreeSet<LaneID>,
     pub changed_intersections: BTreeSet<IntersectionID>,
+    // Newer than the rest. Edits saved before it existed still load, and apply_edits rebuilds it.
+    #[serde(skip_serializing, default)]
+    pub original_speed_limits: BTreeMap<DirectedRoadID, Speed>,
 
     #[serde(skip_serializing, skip_deserializing)]
     pub dirty: bool,
@@ -40,6 +44,51 @@ pub enum EditCmd {
         orig_it: IntersectionType,
     },
     UncloseIntersection(IntersectionID, IntersectionType),
+    // Only changes the normal limit. Time-conditional limits like school zones still apply.
+    ChangeSpeedLimit {
+        id: DirectedRoadID,
+        new: Speed,
+        old: Speed,
+    },
+    // idx counts outwards from the center of the road on the side given by forwards, like
+    // Road::dir_and_offset. New lanes have to use Map::next_lane_id, in order.
+    AddLane {
+        id: LaneID,
+        r: RoadID,
+        forwards: bool,
+        idx: usize,
+        lt: LaneType,
+        width: Distance,
+    },
+    // Same fields as AddLane, so the lane can be restored exactly. Use EditCmd::delete_lane.
+    DeleteLane {
+        id: LaneID,
+        r: RoadID,
+        forwards: bool,
+        idx: usize,
+        lt: LaneType,
+        width: Distance,
+    },
+    ChangeLaneWidth {
+        id: LaneID,
+        width: Distance,
+        orig_width: Distance,
+    },
+}
+
+// MapEdits as saved to disk. Each command keeps the stable IDs of everything it refers to,
+// recorded against the map just before it applied, so edits survive rebuilding the map.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentMapEdits {
+    pub map_name: String,
+    pub edits_name: String,
+    pub commands: Vec<PermanentEditCmd>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PermanentEditCmd {
+    pub cmd: EditCmd,
+    pub ids: PermanentIDs,
 }
 
 pub struct EditEffects {
@@ -61,6 +110,7 @@ impl MapEdits {
             original_lts: BTreeMap::new(),
             reversed_lanes: BTreeSet::new(),
             changed_intersections: BTreeSet::new(),
+            original_speed_limits: BTreeMap::new(),
             dirty: false,
         }
     }
@@ -69,21 +119,53 @@ impl MapEdits {
         self.edits_name == "no_edits" && self.commands.is_empty()
     }
 
-    pub fn load(map_name: &str, edits_name: &str, timer: &mut Timer) -> MapEdits {
+    // Needs to temporarily replay edits on the map, but leaves it as it was.
+    pub fn load(map: &mut Map, edits_name: &str, timer: &mut Timer) -> MapEdits {
         if edits_name == "no_edits" {
-            return MapEdits::new(map_name.to_string());
+            return MapEdits::new(map.get_name().to_string());
+        }
+        let path = abstutil::path_edits(map.get_name(), edits_name);
+        // Read the file once, then try both formats.
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) => panic!("Couldn't read {}: {}", path, err),
+        };
+        match serde_json::from_str::<PermanentMapEdits>(&contents) {
+            Ok(perma) => perma.migrate(map, timer),
+            Err(_) => {
+                // Edits saved before PermanentMapEdits only have dense IDs, which are wrong if
+                // the map has been rebuilt since.
+                timer.warn(format!(
+                    "{} has no stable IDs; it might not match the map",
+                    path
+                ));
+                let legacy: MapEdits = match serde_json::from_str(&contents) {
+                    Ok(legacy) => legacy,
+                    Err(err) => panic!("Couldn't read {} as map edits: {}", path, err),
+                };
+                let mut edits = MapEdits::new(map.get_name().to_string());
+                edits.edits_name = legacy.edits_name;
+                let mut problems = Vec::new();
+                map.replay_edits(
+                    legacy.commands,
+                    |cmd, map| match cmd.check_ids(map) {
+                        Ok(()) => {
+                            edits.commands.push(cmd.clone());
+                            Some(cmd)
+                        }
+                        Err(err) => {
+                            problems.push(format!("Skipping edit \"{}\": {}", cmd.describe(), err));
+                            None
+                        }
+                    },
+                    timer,
+                );
+                for problem in problems {
+                    timer.warn(problem);
+                }
+                edits
+            }
         }
-        abstutil::read_json(abstutil::path_edits(map_name, edits_name), timer)
-    }
-
-    // TODO Version these
-    pub(crate) fn save(&mut self, map: &Map) {
-        self.compress(map);
-
-        assert!(self.dirty);
-        assert_ne!(self.edits_name, "no_edits");
-        abstutil::write_json(abstutil::path_edits(&self.map_name, &self.edits_name), self);
-        self.dirty = false;
     }
 
     pub fn original_it(&self, i: IntersectionID) -> IntersectionType {
@@ -97,9 +179,10 @@ impl MapEdits {
         panic!("{} isn't closed", i);
     }
 
-    // Original lane types, reversed lanes, and all changed intersections
+    // Original lane types, reversed lanes, all changed intersections, and original speed limits
     pub(crate) fn update_derived(&mut self, map: &Map, timer: &mut Timer) {
         let mut orig_lts = BTreeMap::new();
+        let mut orig_speed_limits = BTreeMap::new();
         let mut reversed_lanes = BTreeSet::new();
         let mut changed_stop_signs = BTreeSet::new();
         let mut changed_traffic_signals = BTreeSet::new();
@@ -131,10 +214,23 @@ impl MapEdits {
                 EditCmd::UncloseIntersection(id, _) => {
                     closed_intersections.remove(id);
                 }
+                EditCmd::ChangeSpeedLimit { id, old, .. } => {
+                    if !orig_speed_limits.contains_key(id) {
+                        orig_speed_limits.insert(*id, *old);
+                    }
+                }
+                EditCmd::AddLane { .. }
+                | EditCmd::DeleteLane { .. }
+                | EditCmd::ChangeLaneWidth { .. } => {}
             }
         }
 
-        retain_btreemap(&mut orig_lts, |l, lt| map.get_l(*l).lane_type != *lt);
+        retain_btreemap(&mut orig_lts, |l, lt| {
+            !map.is_lane_deleted(*l) && map.get_l(*l).lane_type != *lt
+        });
+        retain_btreemap(&mut orig_speed_limits, |dr, speed| {
+            map.get_r(dr.id).get_speed_limit(dr.forwards).normal != *speed
+        });
         for i in &closed_intersections {
             changed_stop_signs.remove(i);
             changed_traffic_signals.remove(i);
@@ -147,6 +243,7 @@ impl MapEdits {
         });
 
         self.original_lts = orig_lts;
+        self.original_speed_limits = orig_speed_limits;
         self.reversed_lanes = reversed_lanes;
         self.changed_intersections = closed_intersections;
         self.changed_intersections.extend(changed_stop_signs);
@@ -155,6 +252,25 @@ impl MapEdits {
 
     // Assumes update_derived has been called.
     pub(crate) fn compress(&mut self, map: &Map) {
+        // Commands that change which lanes exist depend on the exact order of everything before
+        // them, so the final state of the map isn't enough to rebuild the list. Instead, keep
+        // those commands in place and merge everything between them.
+        if self.commands.iter().any(|cmd| cmd.restripes()) {
+            let mut commands = Vec::new();
+            let mut run = Vec::new();
+            for cmd in self.commands.drain(..) {
+                if cmd.restripes() {
+                    commands.extend(compress_run(std::mem::replace(&mut run, Vec::new())));
+                    commands.push(cmd);
+                } else {
+                    run.push(cmd);
+                }
+            }
+            commands.extend(compress_run(run));
+            self.commands = commands;
+            return;
+        }
+
         let orig_cmds: Vec<EditCmd> = self.commands.drain(..).collect();
 
         for (l, orig_lt) in &self.original_lts {
@@ -170,6 +286,13 @@ impl MapEdits {
                 dst_i: map.get_l(*l).dst_i,
             });
         }
+        for (dr, old) in &self.original_speed_limits {
+            self.commands.push(EditCmd::ChangeSpeedLimit {
+                id: *dr,
+                new: map.get_r(dr.id).get_speed_limit(dr.forwards).normal,
+                old: *old,
+            });
+        }
         for i in &self.changed_intersections {
             match map.get_i(*i).intersection_type {
                 IntersectionType::StopSign => {
@@ -201,6 +324,197 @@ impl MapEdits {
     }
 }
 
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum CompressKey {
+    LaneType(LaneID),
+    Reverse(LaneID),
+    SpeedLimit(DirectedRoadID),
+    Intersection(IntersectionID),
+}
+
+// Merges commands that touch the same thing, without looking at the map. Each merged command goes
+// where the last command it replaces was, since later commands might depend on it.
+fn compress_run(cmds: Vec<EditCmd>) -> Vec<EditCmd> {
+    let mut groups: BTreeMap<CompressKey, (usize, Vec<EditCmd>)> = BTreeMap::new();
+    for (idx, cmd) in cmds.into_iter().enumerate() {
+        let key = match cmd {
+            EditCmd::ChangeLaneType { id, .. } => CompressKey::LaneType(id),
+            EditCmd::ReverseLane { l, .. } => CompressKey::Reverse(l),
+            EditCmd::ChangeSpeedLimit { id, .. } => CompressKey::SpeedLimit(id),
+            EditCmd::ChangeStopSign(ref ss) => CompressKey::Intersection(ss.id),
+            EditCmd::ChangeTrafficSignal(ref ts) => CompressKey::Intersection(ts.id),
+            EditCmd::CloseIntersection { id, .. } | EditCmd::UncloseIntersection(id, _) => {
+                CompressKey::Intersection(id)
+            }
+            EditCmd::AddLane { .. }
+            | EditCmd::DeleteLane { .. }
+            | EditCmd::ChangeLaneWidth { .. } => unreachable!(),
+        };
+        let group = groups.entry(key).or_insert_with(|| (idx, Vec::new()));
+        group.0 = idx;
+        group.1.push(cmd);
+    }
+
+    let mut merged: Vec<(usize, EditCmd)> = Vec::new();
+    for (key, (idx, group)) in groups {
+        match key {
+            CompressKey::LaneType(_) => {
+                if let (
+                    EditCmd::ChangeLaneType { id, orig_lt, .. },
+                    EditCmd::ChangeLaneType { lt, .. },
+                ) = (&group[0], group.last().unwrap())
+                {
+                    if orig_lt != lt {
+                        merged.push((
+                            idx,
+                            EditCmd::ChangeLaneType {
+                                id: *id,
+                                lt: *lt,
+                                orig_lt: *orig_lt,
+                            },
+                        ));
+                    }
+                }
+            }
+            CompressKey::Reverse(_) => {
+                // Reversing twice does nothing
+                if group.len() % 2 == 1 {
+                    merged.push((idx, group.last().unwrap().clone()));
+                }
+            }
+            CompressKey::SpeedLimit(_) => {
+                if let (
+                    EditCmd::ChangeSpeedLimit { id, old, .. },
+                    EditCmd::ChangeSpeedLimit { new, .. },
+                ) = (&group[0], group.last().unwrap())
+                {
+                    if old != new {
+                        merged.push((
+                            idx,
+                            EditCmd::ChangeSpeedLimit {
+                                id: *id,
+                                new: *new,
+                                old: *old,
+                            },
+                        ));
+                    }
+                }
+            }
+            CompressKey::Intersection(_) => {
+                // Closing or reopening resets the stop sign or traffic signal, so only changes
+                // after the last one matter. Closing and reopening cancel out.
+                let toggles: Vec<usize> = group
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, cmd)| match cmd {
+                        EditCmd::CloseIntersection { .. } | EditCmd::UncloseIntersection(_, _) => {
+                            true
+                        }
+                        _ => false,
+                    })
+                    .map(|(i, _)| i)
+                    .collect();
+                let mut keep = Vec::new();
+                if let Some(last) = toggles.last() {
+                    if toggles.len() % 2 == 1 {
+                        keep.push(group[*last].clone());
+                    }
+                }
+                let after = toggles.last().map(|i| i + 1).unwrap_or(0);
+                if let Some(cmd) = group[after..].last() {
+                    keep.push(cmd.clone());
+                }
+                for cmd in keep {
+                    merged.push((idx, cmd));
+                }
+            }
+        }
+    }
+    // The sort is stable, so close/reopen stays before the last signal change
+    merged.sort_by_key(|(idx, _)| *idx);
+    merged.into_iter().map(|(_, cmd)| cmd).collect()
+}
+
+impl PermanentMapEdits {
+    pub fn empty(map_name: String) -> PermanentMapEdits {
+        PermanentMapEdits {
+            map_name,
+            edits_name: "no_edits".to_string(),
+            commands: Vec::new(),
+        }
+    }
+
+    // Records the map's current edits
+    pub fn new(map: &mut Map, timer: &mut Timer) -> PermanentMapEdits {
+        let edits = map.get_edits();
+        let map_name = edits.map_name.clone();
+        let edits_name = edits.edits_name.clone();
+        let cmds = edits.commands.clone();
+
+        let mut commands = Vec::new();
+        map.replay_edits(
+            cmds,
+            |cmd, map| {
+                let mut mapper = IDMapper::recorder(map);
+                // Recording can't fail
+                cmd.clone().remap_ids(&mut mapper).unwrap();
+                commands.push(PermanentEditCmd {
+                    cmd: cmd.clone(),
+                    ids: mapper.into_ids(),
+                });
+                Some(cmd)
+            },
+            timer,
+        );
+        PermanentMapEdits {
+            map_name,
+            edits_name,
+            commands,
+        }
+    }
+
+    // Commands referring to something that isn't in this map anymore are skipped with a warning.
+    // Later commands might depend on skipped ones and fail too.
+    pub fn migrate(self, map: &mut Map, timer: &mut Timer) -> MapEdits {
+        let lookup = IDLookup::new(map);
+        let mut edits = MapEdits::new(map.get_name().to_string());
+        edits.edits_name = self.edits_name;
+        let total = self.commands.len();
+
+        let mut problems = Vec::new();
+        map.replay_edits(
+            self.commands,
+            |perma, map| {
+                let mut cmd = perma.cmd;
+                let describe = cmd.describe();
+                match cmd.remap_ids(&mut IDMapper::migrator(map, &lookup, perma.ids)) {
+                    Ok(()) => {
+                        edits.commands.push(cmd.clone());
+                        Some(cmd)
+                    }
+                    Err(err) => {
+                        problems.push(format!("Skipping edit \"{}\": {}", describe, err));
+                        None
+                    }
+                }
+            },
+            timer,
+        );
+        if !problems.is_empty() {
+            timer.warn(format!(
+                "{} of {} edits in {} couldn't be matched to this map",
+                problems.len(),
+                total,
+                edits.edits_name
+            ));
+            for problem in problems {
+                timer.warn(problem);
+            }
+        }
+        edits
+    }
+}
+
 impl EditEffects {
     pub fn new() -> EditEffects {
         EditEffects {
@@ -222,6 +536,164 @@ impl EditCmd {
             EditCmd::ChangeTrafficSignal(ts) => format!("Edit traffic signal {}", ts.id),
             EditCmd::CloseIntersection { id, .. } => format!("Close {}", id),
             EditCmd::UncloseIntersection(id, _) => format!("Restore {}", id),
+            EditCmd::ChangeSpeedLimit { id, new, .. } => {
+                format!("Change speed limit of {} to {}", id, new)
+            }
+            EditCmd::AddLane { r, lt, .. } => format!("Add {:?} lane to {}", lt, r),
+            EditCmd::DeleteLane { id, .. } => format!("Delete {}", id),
+            EditCmd::ChangeLaneWidth { id, width, .. } => {
+                format!("Change width of {} to {}", id, width)
+            }
+        }
+    }
+
+    // True if this changes which lanes exist or how wide they are
+    pub fn restripes(&self) -> bool {
+        match self {
+            EditCmd::AddLane { .. }
+            | EditCmd::DeleteLane { .. }
+            | EditCmd::ChangeLaneWidth { .. } => true,
+            EditCmd::ChangeLaneType { .. }
+            | EditCmd::ReverseLane { .. }
+            | EditCmd::ChangeStopSign(_)
+            | EditCmd::ChangeTrafficSignal(_)
+            | EditCmd::CloseIntersection { .. }
+            | EditCmd::UncloseIntersection(_, _)
+            | EditCmd::ChangeSpeedLimit { .. } => false,
+        }
+    }
+
+    // Walks over every ID this command refers to. See IDMapper. A lane added by this command
+    // doesn't exist yet, so when migrating, AddLane just takes the next lane ID.
+    pub fn remap_ids(&mut self, mapper: &mut IDMapper) -> Result<(), Error> {
+        match self {
+            EditCmd::ChangeLaneType { id, .. } | EditCmd::ChangeLaneWidth { id, .. } => {
+                mapper.lane(id)
+            }
+            EditCmd::ReverseLane { l, dst_i } => {
+                mapper.lane(l)?;
+                mapper.intersection(dst_i)
+            }
+            EditCmd::ChangeStopSign(ss) => {
+                mapper.intersection(&mut ss.id)?;
+                // The rightmost lanes are derived, so only keep which roads have to stop.
+                let mut new_ss = ControlStopSign::new(mapper.get_map(), ss.id);
+                for (r, cfg) in &ss.roads {
+                    let mut r = *r;
+                    mapper.road(&mut r)?;
+                    new_ss
+                        .roads
+                        .get_mut(&r)
+                        .ok_or_else(|| Error::new(format!("{} isn't at {}", r, ss.id)))?
+                        .must_stop = cfg.must_stop;
+                }
+                *ss = new_ss;
+                Ok(())
+            }
+            EditCmd::ChangeTrafficSignal(ts) => {
+                mapper.intersection(&mut ts.id)?;
+                ts.turn_groups = TurnGroup::for_i(ts.id, mapper.get_map());
+                for phase in ts.phases.iter_mut() {
+                    for groups in vec![&mut phase.protected_groups, &mut phase.yield_groups] {
+                        let mut remapped = BTreeSet::new();
+                        for g in groups.iter() {
+                            let mut g = g.clone();
+                            mapper.turn_group(&mut g)?;
+                            if !ts.turn_groups.contains_key(&g) {
+                                return Err(Error::new(format!(
+                                    "{:?} isn't at {} anymore",
+                                    g, ts.id
+                                )));
+                            }
+                            remapped.insert(g);
+                        }
+                        *groups = remapped;
+                    }
+                }
+                Ok(())
+            }
+            EditCmd::CloseIntersection { id, .. } | EditCmd::UncloseIntersection(id, _) => {
+                mapper.intersection(id)
+            }
+            EditCmd::ChangeSpeedLimit { id, .. } => mapper.directed_road(id),
+            EditCmd::AddLane { id, r, .. } => {
+                mapper.road(r)?;
+                if mapper.is_migrating() {
+                    *id = mapper.get_map().next_lane_id();
+                }
+                Ok(())
+            }
+            EditCmd::DeleteLane { id, r, .. } => {
+                mapper.lane(id)?;
+                mapper.road(r)
+            }
+        }
+    }
+
+    // Edits without stable IDs can't be remapped, but at least make sure everything they refer to
+    // exists, and that new lanes get the IDs the map would hand out.
+    pub fn check_ids(&self, map: &Map) -> Result<(), Error> {
+        let lane = |l: &LaneID| {
+            if map.maybe_get_l(*l).is_none() || map.is_lane_deleted(*l) {
+                Err(Error::new(format!("{} doesn't exist", l)))
+            } else {
+                Ok(())
+            }
+        };
+        let road = |r: &RoadID| {
+            if map.maybe_get_r(*r).is_none() {
+                Err(Error::new(format!("{} doesn't exist", r)))
+            } else {
+                Ok(())
+            }
+        };
+        let intersection = |i: &IntersectionID| {
+            if map.maybe_get_i(*i).is_none() {
+                Err(Error::new(format!("{} doesn't exist", i)))
+            } else {
+                Ok(())
+            }
+        };
+        match self {
+            EditCmd::ChangeLaneType { id, .. } | EditCmd::ChangeLaneWidth { id, .. } => lane(id),
+            EditCmd::ReverseLane { l, dst_i } => {
+                lane(l)?;
+                intersection(dst_i)
+            }
+            EditCmd::ChangeStopSign(ss) => intersection(&ss.id),
+            EditCmd::ChangeTrafficSignal(ts) => intersection(&ts.id),
+            EditCmd::CloseIntersection { id, .. } | EditCmd::UncloseIntersection(id, _) => {
+                intersection(id)
+            }
+            EditCmd::ChangeSpeedLimit { id, .. } => road(&id.id),
+            EditCmd::AddLane { id, r, .. } => {
+                road(r)?;
+                if *id != map.next_lane_id() && !map.is_lane_deleted(*id) {
+                    return Err(Error::new(format!(
+                        "Can't add {}; the next free ID is {}",
+                        id,
+                        map.next_lane_id()
+                    )));
+                }
+                Ok(())
+            }
+            EditCmd::DeleteLane { id, r, .. } => {
+                lane(id)?;
+                road(r)
+            }
+        }
+    }
+
+    pub fn delete_lane(id: LaneID, map: &Map) -> EditCmd {
+        let lane = map.get_l(id);
+        let (forwards, idx) = map.get_r(lane.parent).dir_and_offset(id);
+        EditCmd::DeleteLane {
+            id,
+            r: lane.parent,
+            forwards,
+            idx,
+            lt: lane.lane_type,
+            width: lane.width,
         }
     }
 }
@@ -0,0 +1,176 @@
+use crate::{IntersectionID, Map, RoadID};
+use geom::{GPSBounds, PolyLine, Polygon, Pt2D};
+use serde_json::{json, Map as JsonObject, Value};
+use std::collections::BTreeMap;
+
+// Extra numeric properties to attach to exported features, like measurements from a simulation.
+// The map doesn't know anything about these; the caller decides the keys.
+#[derive(Default)]
+pub struct ExtraProperties {
+    pub roads: BTreeMap<RoadID, Vec<(String, f64)>>,
+    pub intersections: BTreeMap<IntersectionID, Vec<(String, f64)>>,
+}
+
+impl ExtraProperties {
+    pub fn new() -> ExtraProperties {
+        Default::default()
+    }
+
+    pub fn add_road(&mut self, r: RoadID, key: &str, value: f64) {
+        self.roads
+            .entry(r)
+            .or_insert_with(Vec::new)
+            .push((key.to_string(), value));
+    }
+
+    pub fn add_intersection(&mut self, i: IntersectionID, key: &str, value: f64) {
+        self.intersections
+            .entry(i)
+            .or_insert_with(Vec::new)
+            .push((key.to_string(), value));
+    }
+}
+
+// Writes roads, lanes, intersections, buildings, and areas as one GeoJSON FeatureCollection in
+// WGS84. Every feature has a "layer" property, so GIS tools can split them back apart.
+pub fn export_geojson(map: &Map, extra: &ExtraProperties, path: String) {
+    let gps = map.get_gps_bounds();
+    let mut features = Vec::new();
+
+    for r in map.all_roads() {
+        let mut props = JsonObject::new();
+        props.insert("layer".to_string(), json!("road"));
+        props.insert("id".to_string(), json!(r.id.0));
+        props.insert("osm_way_id".to_string(), json!(r.orig_id.osm_way_id));
+        props.insert("name".to_string(), json!(r.get_name()));
+        if let Some(hwy) = r.osm_tags.get(crate::osm::HIGHWAY) {
+            props.insert("highway".to_string(), json!(hwy));
+        }
+        props.insert(
+            "speed_limit_forwards_mps".to_string(),
+            json!(r.speed_limit_forwards.normal.inner_meters_per_second()),
+        );
+        props.insert(
+            "speed_limit_backwards_mps".to_string(),
+            json!(r.speed_limit_backwards.normal.inner_meters_per_second()),
+        );
+        props.insert(
+            "lanes_forwards".to_string(),
+            json!(r.children_forwards.len()),
+        );
+        props.insert(
+            "lanes_backwards".to_string(),
+            json!(r.children_backwards.len()),
+        );
+        props.insert("src_i".to_string(), json!(r.src_i.0));
+        props.insert("dst_i".to_string(), json!(r.dst_i.0));
+        add_extra(&mut props, extra.roads.get(&r.id));
+        features.push(feature(linestring(&r.center_pts, gps), props));
+    }
+
+    for l in map.all_lanes() {
+        if map.is_lane_deleted(l.id) {
+            continue;
+        }
+        let mut props = JsonObject::new();
+        props.insert("layer".to_string(), json!("lane"));
+        props.insert("id".to_string(), json!(l.id.0));
+        props.insert("road".to_string(), json!(l.parent.0));
+        props.insert("lane_type".to_string(), json!(format!("{:?}", l.lane_type)));
+        props.insert("length_m".to_string(), json!(l.length().inner_meters()));
+        props.insert("width_m".to_string(), json!(l.width.inner_meters()));
+        props.insert(
+            "speed_limit_mps".to_string(),
+            json!(l.get_speed_limit(map).normal.inner_meters_per_second()),
+        );
+        props.insert("src_i".to_string(), json!(l.src_i.0));
+        props.insert("dst_i".to_string(), json!(l.dst_i.0));
+        features.push(feature(linestring(&l.lane_center_pts, gps), props));
+    }
+
+    for i in map.all_intersections() {
+        let mut props = JsonObject::new();
+        props.insert("layer".to_string(), json!("intersection"));
+        props.insert("id".to_string(), json!(i.id.0));
+        props.insert("osm_node_id".to_string(), json!(i.orig_id.osm_node_id));
+        props.insert(
+            "intersection_type".to_string(),
+            json!(format!("{:?}", i.intersection_type)),
+        );
+        add_extra(&mut props, extra.intersections.get(&i.id));
+        features.push(feature(polygon(&i.polygon, gps), props));
+    }
+
+    for b in map.all_buildings() {
+        let mut props = JsonObject::new();
+        props.insert("layer".to_string(), json!("building"));
+        props.insert("id".to_string(), json!(b.id.0));
+        props.insert("osm_way_id".to_string(), json!(b.osm_way_id));
+        props.insert("address".to_string(), json!(b.get_name(map)));
+        props.insert("sidewalk".to_string(), json!(b.sidewalk().0));
+        if let Some(ref p) = b.parking {
+            props.insert("parking_stalls".to_string(), json!(p.num_stalls));
+        }
+        features.push(feature(polygon(&b.polygon, gps), props));
+    }
+
+    for a in map.all_areas() {
+        let mut props = JsonObject::new();
+        props.insert("layer".to_string(), json!("area"));
+        props.insert("id".to_string(), json!(a.id.0));
+        props.insert("osm_id".to_string(), json!(a.osm_id));
+        props.insert("area_type".to_string(), json!(format!("{:?}", a.area_type)));
+        features.push(feature(polygon(&a.polygon, gps), props));
+    }
+
+    abstutil::write_json(
+        path,
+        &json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }),
+    );
+}
+
+fn add_extra(props: &mut JsonObject<String, Value>, extra: Option<&Vec<(String, f64)>>) {
+    if let Some(list) = extra {
+        for (key, value) in list {
+            props.insert(key.clone(), json!(value));
+        }
+    }
+}
+
+fn feature(geometry: Value, props: JsonObject<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": props,
+    })
+}
+
+fn to_coords(pts: &Vec<Pt2D>, gps: &GPSBounds) -> Vec<Vec<f64>> {
+    gps.must_convert_back(pts)
+        .into_iter()
+        .map(|pt| vec![pt.longitude, pt.latitude])
+        .collect()
+}
+
+fn linestring(pl: &PolyLine, gps: &GPSBounds) -> Value {
+    json!({
+        "type": "LineString",
+        "coordinates": to_coords(pl.points(), gps),
+    })
+}
+
+// Polygons made with Polygon::new preserve the order of the original points, so they form the
+// outer ring. GeoJSON wants the ring closed.
+fn polygon(poly: &Polygon, gps: &GPSBounds) -> Value {
+    let mut ring = to_coords(poly.points(), gps);
+    if ring.first() != ring.last() {
+        ring.push(ring[0].clone());
+    }
+    json!({
+        "type": "Polygon",
+        "coordinates": vec![ring],
+    })
+}
@@ -1,7 +1,7 @@
 use crate::pathfind;
 use crate::{
-    osm, BuildingID, BusStopID, DirectedRoadID, IntersectionID, Map, PathConstraints, Road, RoadID,
-    TurnType,
+    osm, BuildingID, BusStopID, DirectedRoadID, IntersectionID, LevelOfTrafficStress, Map,
+    PathConstraints, Road, RoadID, SpeedLimit, TurnType,
 };
 use geom::{Angle, Distance, Line, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
@@ -10,6 +10,8 @@ use std::fmt;
 
 // Bit longer than the longest car.
 pub const PARKING_SPOT_LENGTH: Distance = Distance::const_meters(8.0);
+// Bit longer than the longest delivery truck.
+pub const LOADING_SPOT_LENGTH: Distance = Distance::const_meters(12.0);
 
 // TODO reconsider pub usize. maybe outside world shouldnt know.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -30,6 +32,8 @@ pub enum LaneType {
     Bus,
     SharedLeftTurn,
     Construction,
+    // Curbside space reserved for trucks making deliveries
+    LoadingZone,
 }
 
 impl LaneType {
@@ -42,6 +46,7 @@ impl LaneType {
             LaneType::Sidewalk => false,
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
+            LaneType::LoadingZone => false,
         }
     }
 
@@ -54,6 +59,21 @@ impl LaneType {
             LaneType::Sidewalk => true,
             LaneType::SharedLeftTurn => false,
             LaneType::Construction => false,
+            LaneType::LoadingZone => false,
+        }
+    }
+
+    // When OSM doesn't say otherwise
+    pub fn default_width(self) -> Distance {
+        match self {
+            LaneType::Driving => Distance::meters(3.0),
+            LaneType::Biking => Distance::meters(1.8),
+            LaneType::Bus => Distance::meters(3.5),
+            LaneType::Parking => Distance::meters(2.5),
+            LaneType::Sidewalk => Distance::meters(1.5),
+            LaneType::SharedLeftTurn => Distance::meters(3.0),
+            LaneType::Construction => Distance::meters(3.0),
+            LaneType::LoadingZone => Distance::meters(2.5),
         }
     }
 
@@ -66,6 +86,7 @@ impl LaneType {
             LaneType::Sidewalk => "a sidewalk",
             LaneType::SharedLeftTurn => "a shared left-turn lane",
             LaneType::Construction => "a lane that's closed for construction",
+            LaneType::LoadingZone => "a curbside loading zone",
         }
     }
 }
@@ -76,6 +97,7 @@ pub struct Lane {
     pub parent: RoadID,
     pub lane_type: LaneType,
     pub lane_center_pts: PolyLine,
+    pub width: Distance,
 
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
@@ -87,6 +109,8 @@ pub struct Lane {
     // If set, cars trying to park near here should actually start their search at this other lane.
     // Only populated for driving lanes inevitably leading to borders.
     pub parking_blackhole: Option<LaneID>,
+    // How stressful it is to bike here. None for lanes bikes can't use.
+    pub bike_lts: Option<LevelOfTrafficStress>,
 }
 
 impl Lane {
@@ -161,6 +185,17 @@ impl Lane {
         }
     }
 
+    pub fn number_loading_spots(&self) -> usize {
+        assert_eq!(self.lane_type, LaneType::LoadingZone);
+        // Same as parking, no spots next to intersections
+        let spots = (self.length() / LOADING_SPOT_LENGTH).floor() - 2.0;
+        if spots >= 1.0 {
+            spots as usize
+        } else {
+            0
+        }
+    }
+
     pub fn is_driving(&self) -> bool {
         self.lane_type == LaneType::Driving
     }
@@ -181,6 +216,10 @@ impl Lane {
         self.lane_type == LaneType::Parking
     }
 
+    pub fn is_loading_zone(&self) -> bool {
+        self.lane_type == LaneType::LoadingZone
+    }
+
     // TODO Store this natively if this winds up being useful.
     pub fn get_directed_parent(&self, map: &Map) -> DirectedRoadID {
         let r = map.get_r(self.parent);
@@ -191,6 +230,13 @@ impl Lane {
         }
     }
 
+    // Lanes going the same direction share a limit, and reversing a lane picks up the limit of
+    // its new direction.
+    pub fn get_speed_limit<'a>(&self, map: &'a Map) -> &'a SpeedLimit {
+        let r = map.get_r(self.parent);
+        r.get_speed_limit(r.is_forwards(self.id))
+    }
+
     pub fn get_turn_restrictions(&self, road: &Road) -> Option<BTreeSet<TurnType>> {
         if !self.is_driving() {
             return None;
@@ -3,15 +3,19 @@ mod building;
 mod bus_stop;
 pub mod connectivity;
 mod edits;
+mod geojson;
 mod intersection;
 mod lane;
+mod lts;
 mod make;
 mod map;
 mod neighborhood;
 pub mod osm;
 mod pathfind;
+mod permanent;
 pub mod raw;
 mod road;
+mod speed_limit;
 mod stop_signs;
 mod traffic_signals;
 mod traversable;
@@ -20,14 +24,22 @@ mod turn;
 pub use crate::area::{Area, AreaID, AreaType};
 pub use crate::building::{Building, BuildingID, FrontPath, OffstreetParking};
 pub use crate::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
-pub use crate::edits::{EditCmd, EditEffects, MapEdits};
+pub use crate::edits::{EditCmd, EditEffects, MapEdits, PermanentEditCmd, PermanentMapEdits};
+pub use crate::geojson::{export_geojson, ExtraProperties};
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
-pub use crate::lane::{Lane, LaneID, LaneType, PARKING_SPOT_LENGTH};
+pub use crate::lane::{Lane, LaneID, LaneType, LOADING_SPOT_LENGTH, PARKING_SPOT_LENGTH};
+pub use crate::lts::LevelOfTrafficStress;
 pub use crate::make::RoadSpec;
 pub use crate::map::Map;
 pub use crate::neighborhood::{FullNeighborhoodInfo, Neighborhood, NeighborhoodBuilder};
-pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
+pub use crate::pathfind::{
+    AlternativePath, Isochrone, Path, PathConstraints, PathRequest, PathStep, TravelMode,
+};
+pub use crate::permanent::{IDLookup, IDMapper, OriginalLane, PermanentIDs};
 pub use crate::road::{DirectedRoadID, Road, RoadID};
+pub use crate::speed_limit::{
+    parse_conditional_maxspeed, parse_maxspeed, SpeedLimit, SpeedLimitDefaults,
+};
 pub use crate::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::traffic_signals::{ControlTrafficSignal, Phase};
 pub use crate::traversable::{Position, Traversable};
@@ -43,5 +55,6 @@ impl Cloneable for IntersectionID {}
 impl Cloneable for LaneType {}
 impl Cloneable for MapEdits {}
 impl Cloneable for Neighborhood {}
+impl Cloneable for PermanentMapEdits {}
 impl Cloneable for NeighborhoodBuilder {}
 impl Cloneable for raw::RestrictionType {}
@@ -0,0 +1,122 @@
+use crate::{osm, Lane, LaneType, Map};
+use geom::Speed;
+use serde_derive::{Deserialize, Serialize};
+
+// How stressful it is to bike somewhere, loosely following Mekuria, Furth, and Nixon's "Low-Stress
+// Bicycling and Network Connectivity". LTS1 is suitable for children, LTS4 only for the most
+// confident riders.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LevelOfTrafficStress {
+    LTS1,
+    LTS2,
+    LTS3,
+    LTS4,
+}
+
+impl LevelOfTrafficStress {
+    pub fn parse(x: &str) -> Result<LevelOfTrafficStress, String> {
+        match x {
+            "1" => Ok(LevelOfTrafficStress::LTS1),
+            "2" => Ok(LevelOfTrafficStress::LTS2),
+            "3" => Ok(LevelOfTrafficStress::LTS3),
+            "4" => Ok(LevelOfTrafficStress::LTS4),
+            _ => Err(format!("{} isn't a level of traffic stress from 1 to 4", x)),
+        }
+    }
+
+    // 1 through 4
+    pub fn level(self) -> usize {
+        match self {
+            LevelOfTrafficStress::LTS1 => 1,
+            LevelOfTrafficStress::LTS2 => 2,
+            LevelOfTrafficStress::LTS3 => 3,
+            LevelOfTrafficStress::LTS4 => 4,
+        }
+    }
+
+    fn from_level(level: usize) -> LevelOfTrafficStress {
+        match level {
+            0 | 1 => LevelOfTrafficStress::LTS1,
+            2 => LevelOfTrafficStress::LTS2,
+            3 => LevelOfTrafficStress::LTS3,
+            _ => LevelOfTrafficStress::LTS4,
+        }
+    }
+
+    // None for lanes that bikes never ride in
+    pub(crate) fn classify(lane: &Lane, map: &Map) -> Option<LevelOfTrafficStress> {
+        if lane.lane_type != LaneType::Biking
+            && lane.lane_type != LaneType::Driving
+            && lane.lane_type != LaneType::Bus
+        {
+            return None;
+        }
+
+        let road = map.get_r(lane.parent);
+        let (fwds, idx) = road.dir_and_offset(lane.id);
+        let speed = road.get_speed_limit(fwds).normal;
+        let side = if fwds {
+            &road.children_forwards
+        } else {
+            &road.children_backwards
+        };
+        let is_motor_lane = |lt: LaneType| lt == LaneType::Driving || lt == LaneType::Bus;
+        let lanes_this_side = side.iter().filter(|(_, lt)| is_motor_lane(*lt)).count();
+        let lanes_total = lanes_this_side
+            + if fwds {
+                &road.children_backwards
+            } else {
+                &road.children_forwards
+            }
+            .iter()
+            .filter(|(_, lt)| is_motor_lane(*lt))
+            .count();
+
+        let level = if lane.lane_type == LaneType::Biking {
+            let mut level = if speed <= Speed::miles_per_hour(25.0) {
+                1
+            } else if speed <= Speed::miles_per_hour(30.0) {
+                2
+            } else if speed <= Speed::miles_per_hour(40.0) {
+                3
+            } else {
+                4
+            };
+            // Riding between multiple lanes of traffic is stressful no matter the speed.
+            if lanes_this_side >= 2 {
+                level = level.max(3);
+            }
+            // Dooring
+            let next_to_parking = (idx > 0 && side[idx - 1].1 == LaneType::Parking)
+                || side.get(idx + 1).map(|(_, lt)| *lt) == Some(LaneType::Parking);
+            if next_to_parking && speed > Speed::miles_per_hour(25.0) {
+                level += 1;
+            }
+            level
+        } else {
+            // Mixed traffic
+            let residential = match road.osm_tags.get(osm::HIGHWAY).map(|x| x.as_str()) {
+                Some("residential") | Some("living_street") | Some("service") => true,
+                _ => false,
+            };
+            if lanes_total <= 3 {
+                if speed <= Speed::miles_per_hour(25.0) {
+                    if residential {
+                        1
+                    } else {
+                        2
+                    }
+                } else if speed <= Speed::miles_per_hour(30.0) {
+                    3
+                } else {
+                    4
+                }
+            } else if lanes_total <= 5 && speed <= Speed::miles_per_hour(25.0) {
+                3
+            } else {
+                4
+            }
+        };
+        Some(LevelOfTrafficStress::from_level(level))
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{osm, LaneType};
+use geom::Distance;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::{fmt, iter};
@@ -6,11 +7,7 @@ use std::{fmt, iter};
 // (original direction, reversed direction)
 pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Vec<LaneType>) {
     if let Some(s) = osm_tags.get(osm::SYNTHETIC_LANES) {
-        if let Some(spec) = RoadSpec::parse(s.to_string()) {
-            return (spec.fwd, spec.back);
-        } else {
-            panic!("Bad {} RoadSpec: {}", osm::SYNTHETIC_LANES, s);
-        }
+        return synthetic_spec(s).lane_types();
     }
 
     // Easy special cases first.
@@ -120,11 +117,27 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
         || has_parking(osm_tags.get(osm::PARKING_BOTH));
     let parking_lane_back = has_parking(osm_tags.get(osm::PARKING_LEFT))
         || has_parking(osm_tags.get(osm::PARKING_BOTH));
+    // Curbside space restricted to loading becomes a loading zone instead of parking.
+    fn is_loading(value: Option<&String>) -> bool {
+        value == Some(&"loading".to_string()) || value == Some(&"loading_only".to_string())
+    }
+    let loading_fwd = is_loading(osm_tags.get("parking:condition:right"))
+        || is_loading(osm_tags.get("parking:condition:both"));
+    let loading_back = is_loading(osm_tags.get("parking:condition:left"))
+        || is_loading(osm_tags.get("parking:condition:both"));
     if parking_lane_fwd {
-        fwd_side.push(LaneType::Parking);
+        fwd_side.push(if loading_fwd {
+            LaneType::LoadingZone
+        } else {
+            LaneType::Parking
+        });
     }
     if parking_lane_back {
-        back_side.push(LaneType::Parking);
+        back_side.push(if loading_back {
+            LaneType::LoadingZone
+        } else {
+            LaneType::Parking
+        });
     }
 
     // TODO Need to snap separate sidewalks to ways. Until then, just do this.
@@ -142,39 +155,179 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
     (fwd_side, back_side)
 }
 
-// This is a convenient way for map_editor to plumb instructions here.
+// Lane types and widths, in the same order as get_lane_types.
+pub fn get_road_spec(osm_tags: &BTreeMap<String, String>) -> RoadSpec {
+    if let Some(s) = osm_tags.get(osm::SYNTHETIC_LANES) {
+        return synthetic_spec(s);
+    }
+
+    let (fwd_types, back_types) = get_lane_types(osm_tags);
+    // Lanes that OSM explicitly gives a width for won't be stretched to fit the road's width.
+    let mut fwd: Vec<(LaneType, Distance, bool)> = fwd_types
+        .into_iter()
+        .map(|lt| lane_width(osm_tags, lt, "right"))
+        .collect();
+    let mut back: Vec<(LaneType, Distance, bool)> = back_types
+        .into_iter()
+        .map(|lt| lane_width(osm_tags, lt, "left"))
+        .collect();
+
+    // OSM's width is the whole carriageway, which doesn't include sidewalks.
+    if let Some(total) = osm_tags.get("width").and_then(|w| parse_width(w)) {
+        let mut fixed = Distance::ZERO;
+        let mut flexible = Distance::ZERO;
+        for (lt, width, explicit) in fwd.iter().chain(back.iter()) {
+            if *lt == LaneType::Sidewalk {
+                continue;
+            }
+            if *explicit {
+                fixed += *width;
+            } else {
+                flexible += *width;
+            }
+        }
+        if total > fixed && flexible > Distance::ZERO {
+            let factor = (total - fixed) / flexible;
+            for (lt, width, explicit) in fwd.iter_mut().chain(back.iter_mut()) {
+                if *lt != LaneType::Sidewalk && !*explicit {
+                    *width = *width * factor;
+                }
+            }
+        }
+    }
+
+    RoadSpec {
+        fwd: fwd.into_iter().map(|(lt, width, _)| (lt, width)).collect(),
+        back: back.into_iter().map(|(lt, width, _)| (lt, width)).collect(),
+    }
+}
+
+fn synthetic_spec(s: &str) -> RoadSpec {
+    if let Some(spec) = RoadSpec::parse(s.to_string()) {
+        spec
+    } else {
+        panic!("Bad {} RoadSpec: {}", osm::SYNTHETIC_LANES, s);
+    }
+}
+
+// side is "right" for the forwards direction and "left" for backwards. True if the width came from
+// a tag.
+fn lane_width(
+    osm_tags: &BTreeMap<String, String>,
+    lt: LaneType,
+    side: &str,
+) -> (LaneType, Distance, bool) {
+    let key = match lt {
+        LaneType::Sidewalk => "sidewalk",
+        LaneType::Biking => "cycleway",
+        _ => {
+            return (lt, lt.default_width(), false);
+        }
+    };
+    for tag in vec![
+        format!("{}:{}:width", key, side),
+        format!("{}:both:width", key),
+        format!("{}:width", key),
+    ] {
+        if let Some(width) = osm_tags.get(&tag).and_then(|w| parse_width(w)) {
+            return (lt, width, true);
+        }
+    }
+    (lt, lt.default_width(), false)
+}
+
+// Meters by default, like OSM. Also handles "3.5 m", "12 ft", and feet and inches like 12'6".
+fn parse_width(value: &str) -> Option<Distance> {
+    let value = value.trim();
+    let width = if let Some(idx) = value.find('\'') {
+        let feet = value[0..idx].trim().parse::<f64>().ok()?;
+        let inches = value[idx + 1..].trim().trim_end_matches('"');
+        let inches = if inches.is_empty() {
+            0.0
+        } else {
+            inches.parse::<f64>().ok()?
+        };
+        Distance::inches(12.0 * feet + inches)
+    } else if value.ends_with("ft") {
+        Distance::inches(12.0 * value.trim_end_matches("ft").trim().parse::<f64>().ok()?)
+    } else {
+        Distance::meters(value.trim_end_matches('m').trim().parse::<f64>().ok()?)
+    };
+    // Some tags are nonsense, like 0 or a typo'd 350.
+    if width > Distance::ZERO && width < Distance::meters(50.0) {
+        Some(width)
+    } else {
+        None
+    }
+}
+
+// This is a convenient way for map_editor to plumb instructions here. Each lane is a character,
+// optionally followed by its width in meters.
 #[derive(Serialize, Deserialize)]
 pub struct RoadSpec {
-    pub fwd: Vec<LaneType>,
-    pub back: Vec<LaneType>,
+    pub fwd: Vec<(LaneType, Distance)>,
+    pub back: Vec<(LaneType, Distance)>,
 }
 
 impl fmt::Display for RoadSpec {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for lt in &self.fwd {
-            write!(f, "{}", RoadSpec::lt_to_char(*lt))?;
+        for (lt, width) in &self.fwd {
+            RoadSpec::write_lane(f, *lt, *width)?;
         }
         write!(f, "/")?;
-        for lt in &self.back {
-            write!(f, "{}", RoadSpec::lt_to_char(*lt))?;
+        for (lt, width) in &self.back {
+            RoadSpec::write_lane(f, *lt, *width)?;
         }
         Ok(())
     }
 }
 
 impl RoadSpec {
+    // Every lane gets the default width for its type.
+    pub fn new(fwd: Vec<LaneType>, back: Vec<LaneType>) -> RoadSpec {
+        RoadSpec {
+            fwd: fwd.into_iter().map(|lt| (lt, lt.default_width())).collect(),
+            back: back
+                .into_iter()
+                .map(|lt| (lt, lt.default_width()))
+                .collect(),
+        }
+    }
+
+    pub fn lane_types(&self) -> (Vec<LaneType>, Vec<LaneType>) {
+        (
+            self.fwd.iter().map(|(lt, _)| *lt).collect(),
+            self.back.iter().map(|(lt, _)| *lt).collect(),
+        )
+    }
+
     pub fn parse(s: String) -> Option<RoadSpec> {
-        let mut fwd: Vec<LaneType> = Vec::new();
-        let mut back: Vec<LaneType> = Vec::new();
+        let mut fwd: Vec<(LaneType, Distance)> = Vec::new();
+        let mut back: Vec<(LaneType, Distance)> = Vec::new();
         let mut seen_slash = false;
-        for c in s.chars() {
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
             if !seen_slash && c == '/' {
                 seen_slash = true;
             } else if let Some(lt) = RoadSpec::char_to_lt(c) {
+                let mut number = String::new();
+                while let Some(d) = chars.peek() {
+                    if d.is_ascii_digit() || *d == '.' {
+                        number.push(*d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let width = if number.is_empty() {
+                    lt.default_width()
+                } else {
+                    Distance::meters(number.parse::<f64>().ok()?)
+                };
                 if seen_slash {
-                    back.push(lt);
+                    back.push((lt, width));
                 } else {
-                    fwd.push(lt);
+                    fwd.push((lt, width));
                 }
             } else {
                 return None;
@@ -187,6 +340,14 @@ impl RoadSpec {
         }
     }
 
+    fn write_lane(f: &mut fmt::Formatter, lt: LaneType, width: Distance) -> fmt::Result {
+        write!(f, "{}", RoadSpec::lt_to_char(lt))?;
+        if width != lt.default_width() {
+            write!(f, "{}", width.inner_meters())?;
+        }
+        Ok(())
+    }
+
     fn lt_to_char(lt: LaneType) -> char {
         match lt {
             LaneType::Driving => 'd',
@@ -196,6 +357,7 @@ impl RoadSpec {
             LaneType::Bus => 'u',
             LaneType::SharedLeftTurn => 'l',
             LaneType::Construction => 'c',
+            LaneType::LoadingZone => 'z',
         }
     }
 
@@ -208,6 +370,7 @@ impl RoadSpec {
             'u' => Some(LaneType::Bus),
             'l' => Some(LaneType::SharedLeftTurn),
             'c' => Some(LaneType::Construction),
+            'z' => Some(LaneType::LoadingZone),
             _ => None,
         }
     }
@@ -3,7 +3,7 @@ pub mod lane_specs;
 
 pub use self::geometry::intersection_polygon;
 use crate::raw::{OriginalIntersection, OriginalRoad, RawMap, RawRoad};
-use crate::{IntersectionType, LaneType, LANE_THICKNESS};
+use crate::{IntersectionType, LaneType};
 use abstutil::Timer;
 use geom::{Bounds, Distance, PolyLine, Pt2D};
 use std::collections::{BTreeMap, BTreeSet};
@@ -35,9 +35,9 @@ impl Road {
         let mut back_width = Distance::ZERO;
         for l in &lane_specs {
             if l.reverse_pts {
-                back_width += LANE_THICKNESS;
+                back_width += l.width;
             } else {
-                fwd_width += LANE_THICKNESS;
+                fwd_width += l.width;
             }
         }
 
@@ -109,22 +109,25 @@ impl InitialMap {
 pub struct LaneSpec {
     pub lane_type: LaneType,
     pub reverse_pts: bool,
+    pub width: Distance,
 }
 
 pub fn get_lane_specs(osm_tags: &BTreeMap<String, String>) -> Vec<LaneSpec> {
-    let (side1_types, side2_types) = lane_specs::get_lane_types(osm_tags);
+    let road_spec = lane_specs::get_road_spec(osm_tags);
 
     let mut specs: Vec<LaneSpec> = Vec::new();
-    for lane_type in side1_types {
+    for (lane_type, width) in road_spec.fwd {
         specs.push(LaneSpec {
             lane_type,
             reverse_pts: false,
+            width,
         });
     }
-    for lane_type in side2_types {
+    for (lane_type, width) in road_spec.back {
         specs.push(LaneSpec {
             lane_type,
             reverse_pts: true,
+            width,
         });
     }
     if specs.is_empty() {
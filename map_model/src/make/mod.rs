@@ -7,6 +7,6 @@ mod turns;
 
 pub use self::buildings::make_all_buildings;
 pub use self::bus_stops::{fix_bus_route, make_bus_stops};
-pub use self::initial::lane_specs::{get_lane_types, RoadSpec};
+pub use self::initial::lane_specs::{get_road_spec, RoadSpec};
 pub use self::remove_disconnected::remove_disconnected_roads;
 pub use self::turns::make_all_turns;
@@ -1,13 +1,14 @@
 use crate::pathfind::Pathfinder;
 use crate::raw::{OriginalIntersection, OriginalRoad, RawMap};
 use crate::{
-    connectivity, make, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
-    IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits, Path, PathConstraints,
-    PathRequest, Position, Road, RoadID, Turn, TurnGroupID, TurnID, TurnType, LANE_THICKNESS,
+    connectivity, make, AlternativePath, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID,
+    BusStop, BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, Intersection,
+    IntersectionID, IntersectionType, Isochrone, Lane, LaneID, LaneType, LevelOfTrafficStress,
+    MapEdits, Path, PathConstraints, PathRequest, PermanentMapEdits, Position, Road, RoadID,
+    SpeedLimitDefaults, TravelMode, Turn, TurnGroupID, TurnID, TurnType,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer};
-use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D};
+use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
@@ -42,6 +43,11 @@ pub struct Map {
     // TODO Argh, hack, initialization order is hard!
     pathfinder: Option<Pathfinder>,
     pathfinder_dirty: bool,
+    // Bike routes avoid lanes more stressful than this
+    bike_lts_tolerance: LevelOfTrafficStress,
+    // Lanes removed by edits. They keep their ID so the edit can be undone, but they don't belong
+    // to a road or intersection anymore.
+    deleted_lanes: BTreeSet<LaneID>,
 
     name: String,
     edits: MapEdits,
@@ -91,6 +97,8 @@ impl Map {
             turn_lookup: Vec::new(),
             pathfinder: None,
             pathfinder_dirty: false,
+            bike_lts_tolerance: LevelOfTrafficStress::LTS3,
+            deleted_lanes: BTreeSet::new(),
             name: "blank".to_string(),
             edits: MapEdits::new("blank".to_string()),
         }
@@ -132,6 +140,8 @@ impl Map {
         // Here's a fun one: we can't set up walking_using_transit yet, because we haven't
         // finalized bus stops and routes. We need the bus graph in place for that. So setup
         // pathfinding in two stages.
+        m.classify_bike_lts();
+
         timer.start("setup (most of) Pathfinder");
         m.pathfinder = Some(Pathfinder::new_without_transit(&m, timer));
         timer.stop("setup (most of) Pathfinder");
@@ -235,6 +245,15 @@ impl Map {
         self.lanes.get(id.0)
     }
 
+    pub fn is_lane_deleted(&self, id: LaneID) -> bool {
+        self.deleted_lanes.contains(&id)
+    }
+
+    // For edits that add a lane
+    pub fn next_lane_id(&self) -> LaneID {
+        LaneID(self.lanes.len())
+    }
+
     pub fn maybe_get_i(&self, id: IntersectionID) -> Option<&Intersection> {
         self.intersections.get(id.0)
     }
@@ -595,6 +614,37 @@ impl Map {
         self.pathfinder.as_ref().unwrap().pathfind(req, self)
     }
 
+    // Up to k different routes for a vehicle, best first. Much slower than pathfind.
+    pub fn pathfind_alternatives(&self, req: PathRequest, k: usize) -> Vec<AlternativePath> {
+        assert!(!self.pathfinder_dirty);
+        crate::pathfind::pathfind_alternatives(req, k, self)
+    }
+
+    // Like pathfind, but never using a turn that avoid matches. Much slower than pathfind.
+    pub fn pathfind_avoiding<F: Fn(TurnID) -> bool>(
+        &self,
+        req: PathRequest,
+        avoid: F,
+    ) -> Option<Path> {
+        assert!(!self.pathfinder_dirty);
+        crate::pathfind::pathfind_avoiding(req, avoid, self)
+    }
+
+    // Free-flow travel time from one building to every other reachable one
+    pub fn all_travel_times(
+        &self,
+        start: BuildingID,
+        mode: TravelMode,
+    ) -> BTreeMap<BuildingID, Duration> {
+        assert!(!self.pathfinder_dirty);
+        crate::pathfind::all_travel_times(start, mode, self)
+    }
+
+    pub fn isochrone(&self, start: BuildingID, mode: TravelMode, limit: Duration) -> Isochrone {
+        assert!(!self.pathfinder_dirty);
+        Isochrone::new(start, mode, limit, self)
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -635,8 +685,46 @@ impl Map {
 
     pub fn save_edits(&mut self) {
         let mut edits = std::mem::replace(&mut self.edits, MapEdits::new(self.name.clone()));
-        edits.save(self);
+        edits.compress(self);
+        assert!(edits.dirty);
+        assert_ne!(edits.edits_name, "no_edits");
         self.edits = edits;
+
+        let perma = PermanentMapEdits::new(self, &mut Timer::throwaway());
+        abstutil::write_json(abstutil::path_edits(&self.name, &perma.edits_name), &perma);
+        self.edits.dirty = false;
+    }
+
+    // Temporarily rewinds to the unedited map, then calls f for each item with the map as it is at
+    // that point, applying whichever command f returns before moving on. Restores the current
+    // edits afterwards. This is how edits get translated to and from stable IDs, since lanes are
+    // only identified by their position on a road, which earlier commands can change.
+    pub(crate) fn replay_edits<T, F: FnMut(T, &Map) -> Option<EditCmd>>(
+        &mut self,
+        items: Vec<T>,
+        mut f: F,
+        timer: &mut Timer,
+    ) {
+        let mut effects = EditEffects::new();
+        let current = self.edits.commands.clone();
+        for cmd in current.iter().rev() {
+            cmd.undo(&mut effects, self, timer);
+        }
+
+        let mut replayed = Vec::new();
+        for item in items {
+            if let Some(cmd) = f(item, self) {
+                cmd.apply(&mut effects, self, timer);
+                replayed.push(cmd);
+            }
+        }
+
+        for cmd in replayed.iter().rev() {
+            cmd.undo(&mut effects, self, timer);
+        }
+        for cmd in &current {
+            cmd.apply(&mut effects, self, timer);
+        }
     }
 
     // new_edits assumed to be valid. Returns actual lanes that changed, roads changed, turns
@@ -697,6 +785,8 @@ impl Map {
 
         new_edits.update_derived(self, timer);
         self.edits = new_edits;
+        // Lane types and speed limits change, so this is easiest to redo from scratch.
+        self.classify_bike_lts();
         self.pathfinder_dirty = true;
         (
             effects.changed_lanes,
@@ -734,6 +824,35 @@ impl Map {
 
         self.pathfinder_dirty = false;
     }
+
+    pub fn get_bike_lts_tolerance(&self) -> LevelOfTrafficStress {
+        self.bike_lts_tolerance
+    }
+
+    // Like apply_edits, the caller has to recalculate_pathfinding_after_edits.
+    pub fn set_bike_lts_tolerance(&mut self, tolerance: LevelOfTrafficStress) {
+        if tolerance != self.bike_lts_tolerance {
+            self.bike_lts_tolerance = tolerance;
+            self.pathfinder_dirty = true;
+        }
+    }
+
+    fn classify_bike_lts(&mut self) {
+        let lts: Vec<Option<LevelOfTrafficStress>> = self
+            .lanes
+            .iter()
+            .map(|l| {
+                if self.deleted_lanes.contains(&l.id) {
+                    None
+                } else {
+                    LevelOfTrafficStress::classify(l, self)
+                }
+            })
+            .collect();
+        for (l, lts) in self.lanes.iter_mut().zip(lts) {
+            l.bike_lts = lts;
+        }
+    }
 }
 
 fn make_half_map(
@@ -760,6 +879,8 @@ fn make_half_map(
         turn_lookup: Vec::new(),
         pathfinder: None,
         pathfinder_dirty: false,
+        bike_lts_tolerance: LevelOfTrafficStress::LTS3,
+        deleted_lanes: BTreeSet::new(),
         name: raw.name.clone(),
         edits: MapEdits::new(raw.name.clone()),
     };
@@ -790,6 +911,8 @@ fn make_half_map(
         intersection_id_mapping.insert(i.id, id);
     }
 
+    let speed_limits = SpeedLimitDefaults::load(&raw.name, timer);
+
     timer.start_iter("expand roads to lanes", initial_map.roads.len());
     for r in initial_map.roads.values() {
         timer.next();
@@ -798,9 +921,10 @@ fn make_half_map(
         let i1 = intersection_id_mapping[&r.src_i];
         let i2 = intersection_id_mapping[&r.dst_i];
 
+        let osm_tags = &raw.roads[&r.id].osm_tags;
         let mut road = Road {
             id: road_id,
-            osm_tags: raw.roads[&r.id].osm_tags.clone(),
+            osm_tags: osm_tags.clone(),
             turn_restrictions: raw.roads[&r.id]
                 .turn_restrictions
                 .iter()
@@ -810,10 +934,16 @@ fn make_half_map(
             children_forwards: Vec::new(),
             children_backwards: Vec::new(),
             center_pts: r.trimmed_center_pts.clone(),
+            untrimmed_center_pts: r.original_center_pts.clone(),
             src_i: i1,
             dst_i: i2,
+            speed_limit_forwards: speed_limits.infer(osm_tags, true),
+            speed_limit_backwards: speed_limits.infer(osm_tags, false),
         };
 
+        // How far the next lane on each side is from the center line
+        let mut fwd_dist = Distance::ZERO;
+        let mut back_dist = Distance::ZERO;
         for lane in &r.lane_specs {
             let id = LaneID(map.lanes.len());
 
@@ -821,27 +951,26 @@ fn make_half_map(
             map.intersections[src_i.0].outgoing_lanes.push(id);
             map.intersections[dst_i.0].incoming_lanes.push(id);
 
-            let (unshifted_pts, offset) = if lane.reverse_pts {
+            let (unshifted_pts, dist_from_center) = if lane.reverse_pts {
                 road.children_backwards.push((id, lane.lane_type));
-                (
-                    road.center_pts.reversed(),
-                    road.children_backwards.len() - 1,
-                )
+                back_dist += lane.width;
+                (road.center_pts.reversed(), back_dist - lane.width / 2.0)
             } else {
                 road.children_forwards.push((id, lane.lane_type));
-                (road.center_pts.clone(), road.children_forwards.len() - 1)
+                fwd_dist += lane.width;
+                (road.center_pts.clone(), fwd_dist - lane.width / 2.0)
             };
             // TODO probably different behavior for oneways
             // TODO need to factor in yellow center lines (but what's the right thing to even do?
             // Reverse points for British-style driving on the left
-            let width = LANE_THICKNESS * (0.5 + (offset as f64));
             let lane_center_pts = unshifted_pts
-                .shift_right(width)
+                .shift_right(dist_from_center)
                 .with_context(timer, format!("shift for {}", id));
 
             map.lanes.push(Lane {
                 id,
                 lane_center_pts,
+                width: lane.width,
                 src_i,
                 dst_i,
                 lane_type: lane.lane_type,
@@ -849,6 +978,7 @@ fn make_half_map(
                 building_paths: Vec::new(),
                 bus_stops: Vec::new(),
                 parking_blackhole: None,
+                bike_lts: None,
             });
         }
         if road.get_name() == "???" {
@@ -1087,6 +1217,138 @@ impl EditCmd {
                 effects.changed_intersections.insert(id);
                 true
             }
+            EditCmd::ChangeSpeedLimit { id, new, .. } => {
+                let r = &mut map.roads[id.id.0];
+                let limit = if id.forwards {
+                    &mut r.speed_limit_forwards
+                } else {
+                    &mut r.speed_limit_backwards
+                };
+                if limit.normal == *new {
+                    return false;
+                }
+
+                limit.normal = *new;
+                let lanes = if id.forwards {
+                    &r.children_forwards
+                } else {
+                    &r.children_backwards
+                };
+                for (l, _) in lanes {
+                    effects.changed_lanes.insert(*l);
+                }
+                effects.changed_roads.insert(id.id);
+                true
+            }
+            EditCmd::AddLane {
+                id,
+                r,
+                forwards,
+                idx,
+                lt,
+                width,
+            } => {
+                let id = *id;
+                let road = &map.roads[r.0];
+                if road.all_lanes().contains(&id) {
+                    return false;
+                }
+
+                let (src_i, dst_i) = if *forwards {
+                    (road.src_i, road.dst_i)
+                } else {
+                    (road.dst_i, road.src_i)
+                };
+                let mut lane = Lane {
+                    id,
+                    parent: *r,
+                    lane_type: *lt,
+                    // restripe_road fixes this
+                    lane_center_pts: road.center_pts.clone(),
+                    width: *width,
+                    src_i,
+                    dst_i,
+                    building_paths: Vec::new(),
+                    bus_stops: Vec::new(),
+                    parking_blackhole: None,
+                    bike_lts: None,
+                };
+                if id.0 == map.lanes.len() {
+                    map.lanes.push(lane);
+                } else if map.deleted_lanes.remove(&id) {
+                    lane.parking_blackhole = map.lanes[id.0].parking_blackhole;
+                    map.lanes[id.0] = lane;
+                } else {
+                    // EditCmd::check_ids should've caught this
+                    timer.warn(format!(
+                        "Can't add {}; the next free ID is {}",
+                        id,
+                        map.next_lane_id()
+                    ));
+                    return false;
+                }
+
+                map.intersections[src_i.0].outgoing_lanes.push(id);
+                map.intersections[dst_i.0].incoming_lanes.push(id);
+                let road = &mut map.roads[r.0];
+                let children = if *forwards {
+                    &mut road.children_forwards
+                } else {
+                    &mut road.children_backwards
+                };
+                let idx = (*idx).min(children.len());
+                children.insert(idx, (id, *lt));
+
+                restripe_road(*r, map, effects, timer);
+                true
+            }
+            EditCmd::DeleteLane { id, r, .. } => {
+                let id = *id;
+                let road = &map.roads[r.0];
+                if !road.all_lanes().contains(&id) {
+                    return false;
+                }
+
+                let lane = &map.lanes[id.0];
+                // Moving buildings and bus stops to another lane is too complicated for now.
+                if !lane.building_paths.is_empty()
+                    || !lane.bus_stops.is_empty()
+                    || road.all_lanes().len() == 1
+                    || ((lane.is_driving() || lane.is_bus()) && !road.all_bus_stops(map).is_empty())
+                {
+                    timer.warn(format!("Can't delete {}", id));
+                    return false;
+                }
+
+                let (src_i, dst_i) = (lane.src_i, lane.dst_i);
+                map.intersections[src_i.0]
+                    .outgoing_lanes
+                    .retain(|x| *x != id);
+                map.intersections[dst_i.0]
+                    .incoming_lanes
+                    .retain(|x| *x != id);
+                let road = &mut map.roads[r.0];
+                road.children_forwards.retain(|(l, _)| *l != id);
+                road.children_backwards.retain(|(l, _)| *l != id);
+                // Nothing should try to use the leftover lane.
+                map.lanes[id.0].lane_type = LaneType::Construction;
+                map.deleted_lanes.insert(id);
+
+                effects.changed_lanes.insert(id);
+                restripe_road(*r, map, effects, timer);
+                true
+            }
+            EditCmd::ChangeLaneWidth { id, width, .. } => {
+                let lane = &mut map.lanes[id.0];
+                if lane.width == *width {
+                    return false;
+                }
+
+                lane.width = *width;
+                let r = lane.parent;
+                restripe_road(r, map, effects, timer);
+                true
+            }
         }
     }
 
@@ -1127,8 +1389,196 @@ impl EditCmd {
                 orig_it: *orig_it,
             }
             .apply(effects, map, timer),
+            EditCmd::ChangeSpeedLimit { id, new, old } => EditCmd::ChangeSpeedLimit {
+                id: *id,
+                new: *old,
+                old: *new,
+            }
+            .apply(effects, map, timer),
+            EditCmd::AddLane {
+                id,
+                r,
+                forwards,
+                idx,
+                lt,
+                width,
+            } => EditCmd::DeleteLane {
+                id: *id,
+                r: *r,
+                forwards: *forwards,
+                idx: *idx,
+                lt: *lt,
+                width: *width,
+            }
+            .apply(effects, map, timer),
+            EditCmd::DeleteLane {
+                id,
+                r,
+                forwards,
+                idx,
+                lt,
+                width,
+            } => EditCmd::AddLane {
+                id: *id,
+                r: *r,
+                forwards: *forwards,
+                idx: *idx,
+                lt: *lt,
+                width: *width,
+            }
+            .apply(effects, map, timer),
+            EditCmd::ChangeLaneWidth {
+                id,
+                width,
+                orig_width,
+            } => EditCmd::ChangeLaneWidth {
+                id: *id,
+                width: *orig_width,
+                orig_width: *width,
+            }
+            .apply(effects, map, timer),
+        }
+    }
+}
+
+// After lanes on a road are added, deleted, or resized, the road has a different width. Trim back
+// every road meeting it again, rebuild both intersection polygons, shift all of the lanes into
+// place, and regenerate turns at both ends.
+fn restripe_road(r: RoadID, map: &mut Map, effects: &mut EditEffects, timer: &mut Timer) {
+    let (i1, i2) = (map.roads[r.0].src_i, map.roads[r.0].dst_i);
+    let mut affected: BTreeSet<RoadID> = BTreeSet::new();
+    affected.insert(r);
+    affected.extend(map.intersections[i1.0].roads.iter().cloned());
+    affected.extend(map.intersections[i2.0].roads.iter().cloned());
+    let old_trims: BTreeMap<RoadID, (Distance, Distance)> = affected
+        .iter()
+        .map(|id| (*id, trimmed_dists(&map.roads[id.0])))
+        .collect();
+
+    retrim_intersection(i1, map, timer);
+    retrim_intersection(i2, map, timer);
+
+    for id in affected {
+        let (old_start, old_end) = old_trims[&id];
+        let (new_start, new_end) = trimmed_dists(&map.roads[id.0]);
+        let road = &map.roads[id.0];
+        let mut moved_starts: BTreeMap<LaneID, Distance> = BTreeMap::new();
+        for (children, unshifted_pts, start_moved_by) in vec![
+            (
+                &road.children_forwards,
+                road.center_pts.clone(),
+                new_start - old_start,
+            ),
+            (
+                &road.children_backwards,
+                road.center_pts.reversed(),
+                old_end - new_end,
+            ),
+        ] {
+            let mut dist_from_center = Distance::ZERO;
+            for (l, _) in children {
+                let lane = &mut map.lanes[l.0];
+                lane.lane_center_pts = unshifted_pts
+                    .shift_right(dist_from_center + lane.width / 2.0)
+                    .with_context(timer, format!("shift for {}", l));
+                dist_from_center += lane.width;
+                moved_starts.insert(*l, start_moved_by);
+                effects.changed_lanes.insert(*l);
+            }
+        }
+        fix_positions(moved_starts, map);
+        effects.changed_roads.insert(id);
+    }
+
+    effects.changed_intersections.insert(i1);
+    effects.changed_intersections.insert(i2);
+    recalculate_turns(i1, map, effects, timer);
+    recalculate_turns(i2, map, effects, timer);
+}
+
+// Where a road's trimmed center line starts and ends, along the untrimmed one
+fn trimmed_dists(road: &Road) -> (Distance, Distance) {
+    let untrimmed = &road.untrimmed_center_pts;
+    let start = untrimmed
+        .dist_along_of_point(road.center_pts.first_pt())
+        .map(|(dist, _)| dist)
+        .unwrap_or(Distance::ZERO);
+    let end = (start + road.center_pts.length()).min(untrimmed.length());
+    (start, end)
+}
+
+// Like InitialMap does, but using the current lane widths. The other end of each road stays put.
+fn retrim_intersection(i: IntersectionID, map: &mut Map, timer: &mut Timer) {
+    let mut roads: BTreeMap<OriginalRoad, make::initial::Road> = BTreeMap::new();
+    for r in &map.intersections[i.0].roads {
+        let road = &map.roads[r.0];
+        let untrimmed = &road.untrimmed_center_pts;
+        let (start, end) = trimmed_dists(road);
+        let trimmed_center_pts = if road.dst_i == i {
+            untrimmed.exact_slice(start, untrimmed.length())
+        } else {
+            untrimmed.exact_slice(Distance::ZERO, end)
+        };
+        let width = |children: &Vec<(LaneID, LaneType)>| {
+            children
+                .iter()
+                .fold(Distance::ZERO, |sum, (l, _)| sum + map.lanes[l.0].width)
+        };
+        roads.insert(
+            road.orig_id,
+            make::initial::Road {
+                id: road.orig_id,
+                src_i: road.orig_id.i1,
+                dst_i: road.orig_id.i2,
+                original_center_pts: untrimmed.clone(),
+                trimmed_center_pts,
+                fwd_width: width(&road.children_forwards),
+                back_width: width(&road.children_backwards),
+                lane_specs: Vec::new(),
+            },
+        );
+    }
+    let intersection = make::initial::Intersection {
+        id: map.intersections[i.0].orig_id,
+        polygon: Vec::new(),
+        roads: roads.keys().cloned().collect(),
+        intersection_type: map.intersections[i.0].intersection_type,
+    };
+    let polygon = make::initial::intersection_polygon(&intersection, &mut roads, timer).0;
+    map.intersections[i.0].polygon = Polygon::new(&polygon);
+
+    for r in map.intersections[i.0].roads.clone() {
+        let road = &mut map.roads[r.0];
+        let (start, end) = trimmed_dists(road);
+        let len = roads[&road.orig_id].trimmed_center_pts.length();
+        let (start, end) = if road.dst_i == i {
+            (start, (start + len).min(road.untrimmed_center_pts.length()))
+        } else {
+            ((end - len).max(Distance::ZERO), end)
+        };
+        road.center_pts = road.untrimmed_center_pts.exact_slice(start, end);
+    }
+}
+
+// When a lane's start moves, everything positioned along it has to move with it.
+fn fix_positions(moved_starts: BTreeMap<LaneID, Distance>, map: &mut Map) {
+    let fix = |pos: &mut Position, lanes: &Vec<Lane>| {
+        if let Some(moved) = moved_starts.get(&pos.lane()) {
+            let len = lanes[pos.lane().0].length();
+            let dist = (pos.dist_along() - *moved).max(Distance::ZERO).min(len);
+            *pos = Position::new(pos.lane(), dist);
+        }
+    };
+    for b in map.buildings.iter_mut() {
+        fix(&mut b.front_path.sidewalk, &map.lanes);
+        if let Some(ref mut p) = b.parking {
+            fix(&mut p.driving_pos, &map.lanes);
         }
     }
+    for bs in map.bus_stops.values_mut() {
+        fix(&mut bs.sidewalk_pos, &map.lanes);
+        fix(&mut bs.driving_pos, &map.lanes);
+    }
 }
 
 // This clobbers previously set traffic signal overrides.
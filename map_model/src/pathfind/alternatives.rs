@@ -0,0 +1,254 @@
+use crate::pathfind::driving::cost;
+use crate::{LaneID, Map, Path, PathConstraints, PathRequest, PathStep, TurnID, TurnType};
+use geom::{Distance, Duration};
+use petgraph::graphmap::DiGraphMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+// Each time a lane is used by an earlier route, it gets this much more expensive.
+const PENALTY_FACTOR: f64 = 1.5;
+// Don't bother suggesting anything that's this many times worse than the best route.
+const MAX_DETOUR: f64 = 1.5;
+// Penalizing doesn't always produce a new route, so give up eventually.
+const MAX_ATTEMPTS_PER_ROUTE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct AlternativePath {
+    pub path: Path,
+    // In the units of the pathfinding cost function, without any penalties for overlap
+    pub cost: usize,
+    pub distance: Distance,
+    // Going exactly the speed limit everywhere, with no delays at intersections
+    pub free_flow_time: Duration,
+    // Left and right turns; going straight and changing lanes don't count.
+    pub num_turns: usize,
+    // Fraction of the distance spent on bike lanes, from 0 to 1
+    pub pct_bike_lanes: f64,
+}
+
+// Uses the penalty method: after finding a route, make all of its lanes more expensive and search
+// again. The contraction hierarchy can't change weights per query, so the alternatives come from
+// a plain A* over a graph built just for this request. The first result always matches
+// Map::pathfind.
+pub fn pathfind_alternatives(req: PathRequest, k: usize, map: &Map) -> Vec<AlternativePath> {
+    assert!(req.constraints != PathConstraints::Pedestrian);
+    let mut results = Vec::new();
+    if k == 0 {
+        return results;
+    }
+    let best = match map.pathfind(req.clone()) {
+        Some(path) => path,
+        None => {
+            return results;
+        }
+    };
+    let best_lanes = lanes_of(&best);
+    if req.start.lane() == req.end.lane() {
+        results.push(summarize(best, 0, &req, map));
+        return results;
+    }
+
+    let mut graph: DiGraphMap<LaneID, usize> = DiGraphMap::new();
+    for l in map.all_lanes() {
+        if !req.constraints.can_use(l, map) {
+            continue;
+        }
+        for turn in map.get_turns_for(l.id, req.constraints) {
+            graph.add_edge(l.id, turn.id.dst, cost(l, turn, req.constraints, map));
+        }
+    }
+    let unpenalized_cost = |lanes: &Vec<LaneID>| -> usize {
+        lanes
+            .windows(2)
+            .map(|pair| *graph.edge_weight(pair[0], pair[1]).unwrap())
+            .sum()
+    };
+    let best_cost = unpenalized_cost(&best_lanes);
+    results.push(summarize(best, best_cost, &req, map));
+    let max_cost = MAX_DETOUR * (best_cost as f64);
+
+    let mut uses: BTreeMap<LaneID, i32> = BTreeMap::new();
+    let mut seen: HashSet<Vec<LaneID>> = HashSet::new();
+    for l in &best_lanes {
+        *uses.entry(*l).or_insert(0) += 1;
+    }
+    seen.insert(best_lanes);
+
+    for _ in 0..(k - 1) * MAX_ATTEMPTS_PER_ROUTE {
+        if results.len() == k {
+            break;
+        }
+        let (_, lanes) = match petgraph::algo::astar(
+            &graph,
+            req.start.lane(),
+            |l| l == req.end.lane(),
+            |(_, dst, weight)| {
+                let n = uses.get(&dst).cloned().unwrap_or(0);
+                ((*weight as f64) * PENALTY_FACTOR.powi(n)).round() as usize
+            },
+            |_| 0,
+        ) {
+            Some(x) => x,
+            None => {
+                break;
+            }
+        };
+        for l in &lanes {
+            *uses.entry(*l).or_insert(0) += 1;
+        }
+        let cost = unpenalized_cost(&lanes);
+        if seen.contains(&lanes) || (cost as f64) > max_cost {
+            continue;
+        }
+        let path = lanes_to_path(&lanes, &req, map);
+        results.push(summarize(path, cost, &req, map));
+        seen.insert(lanes);
+    }
+    // Heavy penalties sometimes turn up a cheaper detour after a more expensive one.
+    results.sort_by_key(|alt| alt.cost);
+    results
+}
+
+// Like Map::pathfind, but never using a turn that avoid matches. Meant for routing around
+// temporary closures, so it's a plain Dijkstra instead of a contraction hierarchy that'd have to
+// be rebuilt.
+pub fn pathfind_avoiding<F: Fn(TurnID) -> bool>(
+    req: PathRequest,
+    avoid: F,
+    map: &Map,
+) -> Option<Path> {
+    assert!(req.constraints != PathConstraints::Pedestrian);
+    let (start, end) = (req.start.lane(), req.end.lane());
+    if start == end && req.start.dist_along() <= req.end.dist_along() {
+        return Some(lanes_to_path(&vec![start], &req, map));
+    }
+
+    // Expand the start lane before anything else, so that a goal behind the start on the same
+    // lane means going around the block.
+    let mut queue: BinaryHeap<(Reverse<usize>, LaneID, LaneID)> = BinaryHeap::new();
+    let mut prev: HashMap<LaneID, LaneID> = HashMap::new();
+    push_successors(start, 0, &avoid, req.constraints, &mut queue, map);
+    while let Some((Reverse(cost_so_far), lane, from)) = queue.pop() {
+        if prev.contains_key(&lane) {
+            continue;
+        }
+        prev.insert(lane, from);
+        if lane == end {
+            let mut lanes = vec![end];
+            let mut current = end;
+            loop {
+                current = prev[&current];
+                lanes.push(current);
+                if current == start {
+                    break;
+                }
+            }
+            lanes.reverse();
+            return Some(lanes_to_path(&lanes, &req, map));
+        }
+        push_successors(lane, cost_so_far, &avoid, req.constraints, &mut queue, map);
+    }
+    None
+}
+
+fn push_successors<F: Fn(TurnID) -> bool>(
+    from: LaneID,
+    cost_so_far: usize,
+    avoid: &F,
+    constraints: PathConstraints,
+    queue: &mut BinaryHeap<(Reverse<usize>, LaneID, LaneID)>,
+    map: &Map,
+) {
+    let lane = map.get_l(from);
+    for turn in map.get_turns_for(from, constraints) {
+        if avoid(turn.id) || !constraints.can_use(map.get_l(turn.id.dst), map) {
+            continue;
+        }
+        queue.push((
+            Reverse(cost_so_far + cost(lane, turn, constraints, map)),
+            turn.id.dst,
+            from,
+        ));
+    }
+}
+
+fn lanes_of(path: &Path) -> Vec<LaneID> {
+    path.get_steps()
+        .iter()
+        .filter_map(|step| match step {
+            PathStep::Lane(l) => Some(*l),
+            _ => None,
+        })
+        .collect()
+}
+
+fn lanes_to_path(lanes: &Vec<LaneID>, req: &PathRequest, map: &Map) -> Path {
+    let mut steps = Vec::new();
+    let mut total_length = Distance::ZERO;
+    for pair in lanes.windows(2) {
+        let turn = TurnID {
+            parent: map.get_l(pair[0]).dst_i,
+            src: pair[0],
+            dst: pair[1],
+        };
+        steps.push(PathStep::Lane(pair[0]));
+        steps.push(PathStep::Turn(turn));
+        total_length += map.get_l(pair[0]).length() + map.get_t(turn).geom.length();
+    }
+    steps.push(PathStep::Lane(req.end.lane()));
+    total_length += map.get_l(req.end.lane()).length();
+    Path::new(map, steps, req.end.dist_along(), total_length)
+}
+
+fn summarize(path: Path, cost: usize, req: &PathRequest, map: &Map) -> AlternativePath {
+    let mut distance = Distance::ZERO;
+    let mut free_flow_time = Duration::ZERO;
+    let mut num_turns = 0;
+    let mut bike_lane_dist = Distance::ZERO;
+
+    let steps = path.get_steps();
+    for (idx, step) in steps.iter().enumerate() {
+        let (dist, lane) = match step {
+            PathStep::Lane(l) => {
+                let lane = map.get_l(*l);
+                let start = if idx == 0 {
+                    req.start.dist_along()
+                } else {
+                    Distance::ZERO
+                };
+                let end = if idx == steps.len() - 1 {
+                    req.end.dist_along()
+                } else {
+                    lane.length()
+                };
+                (end - start, lane)
+            }
+            PathStep::ContraflowLane(_) => unreachable!(),
+            PathStep::Turn(t) => {
+                let turn = map.get_t(*t);
+                if turn.turn_type == TurnType::Left || turn.turn_type == TurnType::Right {
+                    num_turns += 1;
+                }
+                (turn.geom.length(), map.get_l(t.dst))
+            }
+        };
+        distance += dist;
+        free_flow_time += dist / lane.get_speed_limit(map).normal;
+        if lane.is_biking() {
+            bike_lane_dist += dist;
+        }
+    }
+
+    AlternativePath {
+        path,
+        cost,
+        distance,
+        free_flow_time,
+        num_turns,
+        pct_bike_lanes: if distance == Distance::ZERO {
+            0.0
+        } else {
+            bike_lane_dist / distance
+        },
+    }
+}
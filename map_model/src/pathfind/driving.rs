@@ -1,5 +1,8 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
-use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
+use crate::{
+    Lane, LaneID, LevelOfTrafficStress, Map, Path, PathConstraints, PathRequest, PathStep, Turn,
+    TurnID,
+};
 use fast_paths::{FastGraph, InputGraph, PathCalculator};
 use geom::Distance;
 use serde_derive::{Deserialize, Serialize};
@@ -83,13 +86,21 @@ impl VehiclePathfinder {
     }
 
     pub fn apply_edits(&mut self, map: &Map) {
-        // The NodeMap is just all lanes -- it won't change. So we can also reuse the node
-        // ordering.
+        // The NodeMap is just all lanes. Unless edits added lanes, it won't change, so we can
+        // also reuse the node ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
+        let num_nodes = self.nodes.len();
+        for l in map.all_lanes() {
+            self.nodes.get_or_insert(l.id);
+        }
         let input_graph = make_input_graph(map, &self.nodes, self.constraints);
-        let node_ordering = self.graph.get_node_ordering();
-        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        if self.nodes.len() == num_nodes {
+            let node_ordering = self.graph.get_node_ordering();
+            self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        } else {
+            self.graph = fast_paths::prepare(&input_graph);
+        }
     }
 }
 
@@ -130,37 +141,34 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
     // TODO Could cost turns differently.
 
     match constraints {
-        PathConstraints::Car => {
+        PathConstraints::Car | PathConstraints::Truck => {
             // Prefer slightly longer route on faster roads
-            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            let t1 = lane.length() / lane.get_speed_limit(map).normal;
+            let t2 = turn.geom.length() / map.get_l(turn.id.dst).get_speed_limit(map).normal;
             (t1 + t2).inner_seconds().round() as usize
         }
         PathConstraints::Bike => {
             // Speed limits don't matter, bikes are usually constrained by their own speed limit.
             let dist = lane.length() + turn.geom.length();
             // TODO Elevation gain is bad, loss is good.
-            // TODO If we're on a driving lane, higher speed limit is worse.
-            // TODO Bike lanes next to parking is dangerous.
 
-            // TODO Prefer bike lanes, then bus lanes, then driving lanes. For now, express that as
-            // an extra cost.
-            let lt_penalty = if lane.is_biking() {
-                1.0
-            } else if lane.is_bus() {
-                1.1
-            } else {
-                assert!(lane.is_driving());
-                1.5
-            };
+            // Stressful lanes are a bit worse, and anything beyond the rider's tolerance is much
+            // worse. Still allow those lanes, or some trips would become impossible.
+            // Anything unclassified is assumed to be the worst.
+            let lts = lane.bike_lts.unwrap_or(LevelOfTrafficStress::LTS4).level() as i32;
+            let tolerance = map.get_bike_lts_tolerance().level() as i32;
+            let mut lt_penalty = 1.0 + 0.1 * f64::from(lts - 1);
+            if lts > tolerance {
+                lt_penalty *= 3.0_f64.powi(lts - tolerance);
+            }
 
             // 1m resolution is fine
             (lt_penalty * dist).inner_meters().round() as usize
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
-            let t1 = lane.length() / map.get_r(lane.parent).get_speed_limit();
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).get_speed_limit();
+            let t1 = lane.length() / lane.get_speed_limit(map).normal;
+            let t2 = turn.geom.length() / map.get_l(turn.id.dst).get_speed_limit(map).normal;
             let lt_penalty = if lane.is_bus() {
                 1.0
             } else {
@@ -0,0 +1,293 @@
+use crate::{
+    BuildingID, BusStopID, Lane, LaneID, Map, PathConstraints, PathRequest, PathStep, Position,
+};
+use geom::{Distance, Duration, Speed};
+use petgraph::graphmap::DiGraphMap;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Same as the walking pathfinder
+const WALKING_SPEED: Speed = Speed::const_meters_per_second(1.34);
+// About 9mph, the middle of what Scenario::rand_bike picks
+const BIKING_SPEED: Speed = Speed::const_meters_per_second(4.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TravelMode {
+    Walk,
+    // Walking and riding buses
+    Transit,
+    Bike,
+    Drive,
+}
+
+// Everywhere reachable from one building within some time.
+#[derive(Clone, Debug)]
+pub struct Isochrone {
+    pub start: BuildingID,
+    pub mode: TravelMode,
+    pub limit: Duration,
+    pub times: BTreeMap<BuildingID, Duration>,
+}
+
+impl Isochrone {
+    pub fn new(start: BuildingID, mode: TravelMode, limit: Duration, map: &Map) -> Isochrone {
+        Isochrone {
+            start,
+            mode,
+            limit,
+            times: all_travel_times(start, mode, map)
+                .into_iter()
+                .filter(|(_, t)| *t <= limit)
+                .collect(),
+        }
+    }
+
+    // Returns (buildings only reachable in self, buildings only reachable in other). Meant for
+    // comparing the same isochrone before and after map edits.
+    pub fn compare(&self, other: &Isochrone) -> (BTreeSet<BuildingID>, BTreeSet<BuildingID>) {
+        let mine: BTreeSet<BuildingID> = self.times.keys().cloned().collect();
+        let theirs: BTreeSet<BuildingID> = other.times.keys().cloned().collect();
+        (
+            mine.difference(&theirs).cloned().collect(),
+            theirs.difference(&mine).cloned().collect(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum Node {
+    Start,
+    // Sidewalks are bidirectional, so for them, these are just the src_i and dst_i ends.
+    LaneStart(LaneID),
+    LaneEnd(LaneID),
+    RideBus(BusStopID),
+}
+
+// Free-flow travel time to every reachable building, ignoring delays at intersections, waiting
+// for buses, and finding parking. The contraction hierarchies in Pathfinder only answer one-to-one
+// queries, so this runs Dijkstra over the same network instead.
+pub fn all_travel_times(
+    start: BuildingID,
+    mode: TravelMode,
+    map: &Map,
+) -> BTreeMap<BuildingID, Duration> {
+    let mut results = BTreeMap::new();
+    let start_pos = match building_pos(start, mode, map) {
+        Some(pos) => pos,
+        None => {
+            return results;
+        }
+    };
+    let graph = make_graph(start_pos, mode, map);
+    let costs = petgraph::algo::dijkstra(&graph, Node::Start, None, |(_, _, cost)| *cost);
+    let reached = |n: Node| costs.get(&n).map(|cost| from_ms(*cost));
+    let start_walk = front_path_time(start, mode, map);
+
+    for b in map.all_buildings() {
+        let pos = match building_pos(b.id, mode, map) {
+            Some(pos) => pos,
+            None => {
+                continue;
+            }
+        };
+        let lane = map.get_l(pos.lane());
+        let speed = lane_speed(lane, mode, map);
+        let mut best: Option<Duration> = None;
+        let mut consider = |time: Duration| {
+            if best.map(|t| time < t).unwrap_or(true) {
+                best = Some(time);
+            }
+        };
+
+        if pos.lane() == start_pos.lane() {
+            if pos.dist_along() >= start_pos.dist_along() {
+                consider((pos.dist_along() - start_pos.dist_along()) / speed);
+            } else if lane.is_sidewalk() {
+                consider((start_pos.dist_along() - pos.dist_along()) / speed);
+            }
+        }
+        if let Some(t) = reached(Node::LaneStart(lane.id)) {
+            consider(t + pos.dist_along() / speed);
+        }
+        if lane.is_sidewalk() {
+            if let Some(t) = reached(Node::LaneEnd(lane.id)) {
+                consider(t + (lane.length() - pos.dist_along()) / speed);
+            }
+        }
+
+        if let Some(t) = best {
+            results.insert(b.id, start_walk + t + front_path_time(b.id, mode, map));
+        }
+    }
+    results
+}
+
+fn make_graph(start: Position, mode: TravelMode, map: &Map) -> DiGraphMap<Node, usize> {
+    let constraints = match mode {
+        TravelMode::Walk | TravelMode::Transit => PathConstraints::Pedestrian,
+        TravelMode::Bike => PathConstraints::Bike,
+        TravelMode::Drive => PathConstraints::Car,
+    };
+    let mut graph = DiGraphMap::new();
+
+    for l in map.all_lanes() {
+        if !constraints.can_use(l, map) {
+            continue;
+        }
+        let cost = to_ms(l.length() / lane_speed(l, mode, map));
+        graph.add_edge(Node::LaneStart(l.id), Node::LaneEnd(l.id), cost);
+        if l.is_sidewalk() {
+            graph.add_edge(Node::LaneEnd(l.id), Node::LaneStart(l.id), cost);
+        }
+    }
+
+    for t in map.all_turns().values() {
+        let (src, dst) = (map.get_l(t.id.src), map.get_l(t.id.dst));
+        if !constraints.can_use(src, map) || !constraints.can_use(dst, map) {
+            continue;
+        }
+        let cost = to_ms(t.geom.length() / lane_speed(dst, mode, map));
+        if t.between_sidewalks() {
+            graph.add_edge(
+                endpoint(src, src.dst_i == t.id.parent),
+                endpoint(dst, dst.dst_i == t.id.parent),
+                cost,
+            );
+        } else if !src.is_sidewalk() && !dst.is_sidewalk() {
+            graph.add_edge(Node::LaneEnd(src.id), Node::LaneStart(dst.id), cost);
+        }
+    }
+
+    let start_lane = map.get_l(start.lane());
+    let speed = lane_speed(start_lane, mode, map);
+    graph.add_edge(
+        Node::Start,
+        Node::LaneEnd(start_lane.id),
+        to_ms((start_lane.length() - start.dist_along()) / speed),
+    );
+    if start_lane.is_sidewalk() {
+        graph.add_edge(
+            Node::Start,
+            Node::LaneStart(start_lane.id),
+            to_ms(start.dist_along() / speed),
+        );
+    }
+
+    if mode == TravelMode::Transit {
+        // Like the walking-with-transit pathfinder, optimistically assume no waiting at stops.
+        for stop in map.all_bus_stops().values() {
+            let lane = map.get_l(stop.sidewalk_pos.lane());
+            let ride_bus = Node::RideBus(stop.id);
+            for (node, dist) in vec![
+                (Node::LaneStart(lane.id), stop.sidewalk_pos.dist_along()),
+                (
+                    Node::LaneEnd(lane.id),
+                    lane.length() - stop.sidewalk_pos.dist_along(),
+                ),
+            ] {
+                let cost = to_ms(dist / WALKING_SPEED);
+                graph.add_edge(node, ride_bus, cost);
+                graph.add_edge(ride_bus, node, cost);
+            }
+        }
+        for route in map.get_all_bus_routes() {
+            // Nowhere to ride
+            if route.stops.len() < 2 {
+                continue;
+            }
+            let mut hops: Vec<(BusStopID, BusStopID)> = route
+                .stops
+                .iter()
+                .cloned()
+                .zip(route.stops.iter().skip(1).cloned())
+                .collect();
+            // Buses loop back to the first stop, unless the route already lists it again at the
+            // end.
+            let (first, last) = (route.stops[0], *route.stops.last().unwrap());
+            if first != last {
+                hops.push((last, first));
+            }
+            for (stop1, stop2) in hops {
+                if let Some(time) = bus_ride_time(stop1, stop2, map) {
+                    graph.add_edge(Node::RideBus(stop1), Node::RideBus(stop2), to_ms(time));
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+// Where trips to and from a building using some mode start and end
+fn building_pos(b: BuildingID, mode: TravelMode, map: &Map) -> Option<Position> {
+    match mode {
+        TravelMode::Walk | TravelMode::Transit => Some(Position::bldg_via_walking(b, map)),
+        TravelMode::Bike => Position::bldg_via_biking(b, map),
+        TravelMode::Drive => Position::bldg_via_driving(b, map),
+    }
+}
+
+fn front_path_time(b: BuildingID, mode: TravelMode, map: &Map) -> Duration {
+    match mode {
+        TravelMode::Walk | TravelMode::Transit => {
+            map.get_b(b).front_path.line.length() / WALKING_SPEED
+        }
+        TravelMode::Bike | TravelMode::Drive => Duration::ZERO,
+    }
+}
+
+fn endpoint(sidewalk: &Lane, dst_i: bool) -> Node {
+    if dst_i {
+        Node::LaneEnd(sidewalk.id)
+    } else {
+        Node::LaneStart(sidewalk.id)
+    }
+}
+
+fn lane_speed(lane: &Lane, mode: TravelMode, map: &Map) -> Speed {
+    match mode {
+        TravelMode::Walk | TravelMode::Transit => WALKING_SPEED,
+        TravelMode::Bike => BIKING_SPEED.min(lane.get_speed_limit(map).normal),
+        TravelMode::Drive => lane.get_speed_limit(map).normal,
+    }
+}
+
+fn bus_ride_time(stop1: BusStopID, stop2: BusStopID, map: &Map) -> Option<Duration> {
+    let req = PathRequest {
+        start: map.get_bs(stop1).driving_pos,
+        end: map.get_bs(stop2).driving_pos,
+        constraints: PathConstraints::Bus,
+    };
+    let path = map.pathfind(req.clone())?;
+    let steps = path.get_steps();
+    let mut time = Duration::ZERO;
+    for (idx, step) in steps.iter().enumerate() {
+        let (dist, lane) = match step {
+            PathStep::Lane(l) => {
+                let lane = map.get_l(*l);
+                let start = if idx == 0 {
+                    req.start.dist_along()
+                } else {
+                    Distance::ZERO
+                };
+                let end = if idx == steps.len() - 1 {
+                    req.end.dist_along()
+                } else {
+                    lane.length()
+                };
+                (end - start, lane)
+            }
+            PathStep::ContraflowLane(_) => unreachable!(),
+            PathStep::Turn(t) => (map.get_t(*t).geom.length(), map.get_l(t.dst)),
+        };
+        time += dist / lane.get_speed_limit(map).normal;
+    }
+    Some(time)
+}
+
+fn to_ms(time: Duration) -> usize {
+    (time.inner_seconds() * 1000.0).round() as usize
+}
+
+fn from_ms(ms: usize) -> Duration {
+    Duration::seconds((ms as f64) / 1000.0)
+}
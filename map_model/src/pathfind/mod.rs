@@ -1,9 +1,13 @@
+mod alternatives;
 mod driving;
+mod isochrone;
 mod node_map;
 mod walking;
 
+pub use self::alternatives::{pathfind_alternatives, pathfind_avoiding, AlternativePath};
 pub use self::driving::cost;
 use self::driving::VehiclePathfinder;
+pub use self::isochrone::{all_travel_times, Isochrone, TravelMode};
 use self::walking::SidewalkPathfinder;
 use crate::{
     osm, BusRouteID, BusStopID, Lane, LaneID, LaneType, Map, Position, Traversable, TurnID,
@@ -180,6 +184,14 @@ impl Path {
         self.total_length += self.steps[idx].as_traversable().length(map);
     }
 
+    // Only for vehicles moving to an adjacent lane in the middle of a road. Trusting the caller
+    // to fix up the next step too.
+    pub fn modify_current_step(&mut self, step: PathStep, map: &Map) {
+        self.total_length -= self.steps[0].as_traversable().length(map);
+        self.steps[0] = step;
+        self.total_length += self.steps[0].as_traversable().length(map);
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -299,6 +311,7 @@ pub enum PathConstraints {
     Car,
     Bike,
     Bus,
+    Truck,
 }
 
 impl PathConstraints {
@@ -331,6 +344,9 @@ impl PathConstraints {
                 }
             }
             PathConstraints::Bus => l.is_driving() || l.is_bus(),
+            PathConstraints::Truck => {
+                l.is_driving() && map.get_r(l.parent).osm_tags.get("hgv") != Some(&"no".to_string())
+            }
         }
     }
 }
@@ -404,6 +420,7 @@ pub struct Pathfinder {
     car_graph: VehiclePathfinder,
     bike_graph: VehiclePathfinder,
     bus_graph: VehiclePathfinder,
+    truck_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     // TODO Option just during initialization! Ewww.
     walking_with_transit_graph: Option<SidewalkPathfinder>,
@@ -425,6 +442,10 @@ impl Pathfinder {
         let bus_graph = VehiclePathfinder::new(map, PathConstraints::Bus, Some(&car_graph));
         timer.stop("prepare pathfinding for buses");
 
+        timer.start("prepare pathfinding for trucks");
+        let truck_graph = VehiclePathfinder::new(map, PathConstraints::Truck, Some(&car_graph));
+        timer.stop("prepare pathfinding for trucks");
+
         timer.start("prepare pathfinding for pedestrians");
         let walking_graph = SidewalkPathfinder::new(map, false, &bus_graph);
         timer.stop("prepare pathfinding for pedestrians");
@@ -433,6 +454,7 @@ impl Pathfinder {
             car_graph,
             bike_graph,
             bus_graph,
+            truck_graph,
             walking_graph,
             walking_with_transit_graph: None,
         }
@@ -448,6 +470,7 @@ impl Pathfinder {
             PathConstraints::Car => self.car_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bike => self.bike_graph.pathfind(&req, map).map(|(p, _)| p),
             PathConstraints::Bus => self.bus_graph.pathfind(&req, map).map(|(p, _)| p),
+            PathConstraints::Truck => self.truck_graph.pathfind(&req, map).map(|(p, _)| p),
         }
     }
 
@@ -476,6 +499,10 @@ impl Pathfinder {
         self.bus_graph.apply_edits(map);
         timer.stop("apply edits to bus pathfinding");
 
+        timer.start("apply edits to truck pathfinding");
+        self.truck_graph.apply_edits(map);
+        timer.stop("apply edits to truck pathfinding");
+
         timer.start("apply edits to pedestrian pathfinding");
         self.walking_graph.apply_edits(map, &self.bus_graph);
         timer.stop("apply edits to pedestrian pathfinding");
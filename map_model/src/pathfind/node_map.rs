@@ -37,6 +37,10 @@ impl<T: Copy + Ord + Debug + Serialize> NodeMap<T> {
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.id_to_node.len()
+    }
+
     pub fn translate(&self, path: &ShortestPath) -> Vec<T> {
         path.get_nodes()
             .iter()
@@ -54,11 +54,25 @@ impl SidewalkPathfinder {
     }
 
     pub fn apply_edits(&mut self, map: &Map, bus_graph: &VehiclePathfinder) {
-        // The NodeMap is all sidewalks and bus stops -- it won't change. So we can also reuse the
-        // node ordering.
+        // The NodeMap is all sidewalks and bus stops. Unless edits added sidewalks, it won't
+        // change, so we can also reuse the node ordering.
+        let num_nodes = self.nodes.len();
+        for l in map.all_lanes() {
+            if l.is_sidewalk() {
+                self.nodes.get_or_insert(Node::SidewalkEndpoint(l.id, true));
+                self.nodes
+                    .get_or_insert(Node::SidewalkEndpoint(l.id, false));
+            }
+        }
         let input_graph = make_input_graph(map, &self.nodes, self.use_transit, bus_graph);
-        let node_ordering = self.graph.get_node_ordering();
-        self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
+        // Deleted sidewalks leave nodes without any edges behind, which can also make the old
+        // ordering unusable.
+        let reused = if self.nodes.len() == num_nodes {
+            fast_paths::prepare_with_order(&input_graph, &self.graph.get_node_ordering()).ok()
+        } else {
+            None
+        };
+        self.graph = reused.unwrap_or_else(|| fast_paths::prepare(&input_graph));
     }
 
     pub fn pathfind(&self, req: &PathRequest, map: &Map) -> Option<Path> {
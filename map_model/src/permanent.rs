@@ -0,0 +1,305 @@
+use crate::raw::{OriginalBuilding, OriginalIntersection, OriginalRoad};
+use crate::{
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, Position,
+    RoadID, TurnGroupID, TurnID,
+};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Error};
+use geom::Distance;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// A way to refer to lanes across many maps: the road, then the side and the offset from the
+// center, like Road::dir_and_offset. Adding or deleting lanes shifts these, so they only make
+// sense against the map with the same lanes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OriginalLane {
+    pub parent: OriginalRoad,
+    pub forwards: bool,
+    pub idx: usize,
+}
+
+impl OriginalLane {
+    pub fn new(l: LaneID, map: &Map) -> OriginalLane {
+        let road = map.get_parent(l);
+        let (forwards, idx) = road.dir_and_offset(l);
+        OriginalLane {
+            parent: road.orig_id,
+            forwards,
+            idx,
+        }
+    }
+}
+
+// The stable version of every ID that something saved to disk refers to, recorded against the
+// map it was saved with. The dense IDs themselves change whenever the map is rebuilt from OSM, so
+// loading the file against a different map uses these to find the equivalent objects.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PermanentIDs {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    roads: BTreeMap<RoadID, OriginalRoad>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    lanes: BTreeMap<LaneID, OriginalLane>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    intersections: BTreeMap<IntersectionID, OriginalIntersection>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    buildings: BTreeMap<BuildingID, OriginalBuilding>,
+    // The sidewalk and how far along it the stop is
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    bus_stops: BTreeMap<BusStopID, (OriginalLane, Distance)>,
+    // By name
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    bus_routes: BTreeMap<BusRouteID, String>,
+}
+
+// Finds objects in one map by their stable IDs. Edits don't change any of these, so build once
+// per map.
+pub struct IDLookup {
+    roads: HashMap<OriginalRoad, RoadID>,
+    intersections: HashMap<OriginalIntersection, IntersectionID>,
+    buildings: HashMap<OriginalBuilding, BuildingID>,
+}
+
+impl IDLookup {
+    pub fn new(map: &Map) -> IDLookup {
+        IDLookup {
+            roads: map.all_roads().iter().map(|r| (r.orig_id, r.id)).collect(),
+            intersections: map
+                .all_intersections()
+                .iter()
+                .map(|i| (i.orig_id, i.id))
+                .collect(),
+            buildings: map
+                .all_buildings()
+                .iter()
+                .map(|b| {
+                    (
+                        OriginalBuilding {
+                            osm_way_id: b.osm_way_id,
+                        },
+                        b.id,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+// Walks over every ID in something, either recording the stable version of each (when saving) or
+// replacing each with the equivalent in a possibly rebuilt map (when loading). Anything that
+// walks its IDs through one of these can be saved and loaded both ways.
+pub struct IDMapper<'a> {
+    map: &'a Map,
+    ids: PermanentIDs,
+    // Only when migrating
+    lookup: Option<&'a IDLookup>,
+}
+
+impl<'a> IDMapper<'a> {
+    pub fn recorder(map: &'a Map) -> IDMapper<'a> {
+        IDMapper {
+            map,
+            ids: PermanentIDs::default(),
+            lookup: None,
+        }
+    }
+
+    pub fn migrator(map: &'a Map, lookup: &'a IDLookup, ids: PermanentIDs) -> IDMapper<'a> {
+        IDMapper {
+            map,
+            ids,
+            lookup: Some(lookup),
+        }
+    }
+
+    pub fn is_migrating(&self) -> bool {
+        self.lookup.is_some()
+    }
+
+    pub fn get_map(&self) -> &'a Map {
+        self.map
+    }
+
+    // Everything recorded so far
+    pub fn into_ids(self) -> PermanentIDs {
+        self.ids
+    }
+
+    pub fn road(&mut self, r: &mut RoadID) -> Result<(), Error> {
+        if let Some(lookup) = self.lookup {
+            let orig = self.ids.roads.get(r).ok_or_else(|| not_recorded(r))?;
+            *r = *lookup.roads.get(orig).ok_or_else(|| missing(orig))?;
+        } else {
+            self.ids.roads.insert(*r, self.map.get_r(*r).orig_id);
+        }
+        Ok(())
+    }
+
+    pub fn directed_road(&mut self, dr: &mut DirectedRoadID) -> Result<(), Error> {
+        self.road(&mut dr.id)
+    }
+
+    pub fn lane(&mut self, l: &mut LaneID) -> Result<(), Error> {
+        if self.lookup.is_some() {
+            let orig = *self.ids.lanes.get(l).ok_or_else(|| not_recorded(l))?;
+            *l = self.resolve_lane(orig)?;
+        } else {
+            self.ids.lanes.insert(*l, OriginalLane::new(*l, self.map));
+        }
+        Ok(())
+    }
+
+    // The distance along may not fit on a rebuilt lane, so it's clamped.
+    pub fn position(&mut self, pos: &mut Position) -> Result<(), Error> {
+        let mut l = pos.lane();
+        self.lane(&mut l)?;
+        *pos = Position::new(l, pos.dist_along().min(self.map.get_l(l).length()));
+        Ok(())
+    }
+
+    pub fn intersection(&mut self, i: &mut IntersectionID) -> Result<(), Error> {
+        if let Some(lookup) = self.lookup {
+            let orig = self
+                .ids
+                .intersections
+                .get(i)
+                .ok_or_else(|| not_recorded(i))?;
+            *i = *lookup
+                .intersections
+                .get(orig)
+                .ok_or_else(|| missing(orig))?;
+        } else {
+            self.ids
+                .intersections
+                .insert(*i, self.map.get_i(*i).orig_id);
+        }
+        Ok(())
+    }
+
+    pub fn turn(&mut self, t: &mut TurnID) -> Result<(), Error> {
+        self.intersection(&mut t.parent)?;
+        self.lane(&mut t.src)?;
+        self.lane(&mut t.dst)?;
+        if self.lookup.is_some() && self.map.maybe_get_t(*t).is_none() {
+            return Err(Error::new(format!("{} doesn't exist anymore", t)));
+        }
+        Ok(())
+    }
+
+    pub fn turn_group(&mut self, id: &mut TurnGroupID) -> Result<(), Error> {
+        self.road(&mut id.from)?;
+        self.road(&mut id.to)?;
+        if let Some(ref mut t) = id.crosswalk {
+            self.turn(t)?;
+        }
+        Ok(())
+    }
+
+    pub fn building(&mut self, b: &mut BuildingID) -> Result<(), Error> {
+        if let Some(lookup) = self.lookup {
+            let orig = self.ids.buildings.get(b).ok_or_else(|| not_recorded(b))?;
+            *b = *lookup.buildings.get(orig).ok_or_else(|| missing(orig))?;
+        } else {
+            self.ids.buildings.insert(
+                *b,
+                OriginalBuilding {
+                    osm_way_id: self.map.get_b(*b).osm_way_id,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    // Bus stops get regenerated along with the map, so this finds the closest stop on the same
+    // sidewalk.
+    pub fn bus_stop(&mut self, bs: &mut BusStopID) -> Result<(), Error> {
+        if self.lookup.is_some() {
+            let (orig, dist) = *self.ids.bus_stops.get(bs).ok_or_else(|| not_recorded(bs))?;
+            let l = self.resolve_lane(orig)?;
+            let map = self.map;
+            *bs = *map
+                .get_l(l)
+                .bus_stops
+                .iter()
+                .min_by_key(|id| {
+                    let other = map.get_bs(**id).sidewalk_pos.dist_along();
+                    if other > dist {
+                        other - dist
+                    } else {
+                        dist - other
+                    }
+                })
+                .ok_or_else(|| Error::new(format!("no bus stop on {} anymore", l)))?;
+        } else {
+            let pos = self.map.get_bs(*bs).sidewalk_pos;
+            self.ids.bus_stops.insert(
+                *bs,
+                (OriginalLane::new(pos.lane(), self.map), pos.dist_along()),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn bus_route(&mut self, br: &mut BusRouteID) -> Result<(), Error> {
+        if self.lookup.is_some() {
+            let name = self
+                .ids
+                .bus_routes
+                .get(br)
+                .ok_or_else(|| not_recorded(br))?;
+            *br = self
+                .map
+                .get_bus_route(name)
+                .ok_or_else(|| Error::new(format!("bus route {} doesn't exist anymore", name)))?
+                .id;
+        } else {
+            self.ids
+                .bus_routes
+                .insert(*br, self.map.get_br(*br).name.clone());
+        }
+        Ok(())
+    }
+
+    fn resolve_lane(&self, orig: OriginalLane) -> Result<LaneID, Error> {
+        let r = *self
+            .lookup
+            .unwrap()
+            .roads
+            .get(&orig.parent)
+            .ok_or_else(|| missing(orig.parent))?;
+        let road = self.map.get_r(r);
+        let side = if orig.forwards {
+            &road.children_forwards
+        } else {
+            &road.children_backwards
+        };
+        side.get(orig.idx)
+            .map(|(l, _)| *l)
+            .ok_or_else(|| Error::new(format!("{:?} doesn't exist anymore", orig)))
+    }
+}
+
+fn not_recorded<T: std::fmt::Display>(id: T) -> Error {
+    Error::new(format!("no stable ID was saved for {}", id))
+}
+
+fn missing<T: std::fmt::Display>(orig: T) -> Error {
+    Error::new(format!("{} doesn't exist anymore", orig))
+}
@@ -1,4 +1,4 @@
-use crate::make::get_lane_types;
+use crate::make::get_road_spec;
 use crate::{osm, AreaType, IntersectionType, OffstreetParking, RoadSpec};
 use abstutil::{deserialize_btreemap, retain_btreemap, serialize_btreemap, Error, Timer};
 use geom::{GPSBounds, Polygon, Pt2D};
@@ -525,8 +525,7 @@ pub struct RawRoad {
 
 impl RawRoad {
     pub fn get_spec(&self) -> RoadSpec {
-        let (fwd, back) = get_lane_types(&self.osm_tags);
-        RoadSpec { fwd, back }
+        get_road_spec(&self.osm_tags)
     }
 
     pub fn synthetic(&self) -> bool {
@@ -1,9 +1,7 @@
 use crate::raw::{OriginalRoad, RestrictionType};
-use crate::{
-    osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints, LANE_THICKNESS,
-};
+use crate::{osm, BusStopID, IntersectionID, LaneID, LaneType, Map, PathConstraints, SpeedLimit};
 use abstutil::{Error, Warn};
-use geom::{Distance, PolyLine, Polygon, Speed};
+use geom::{Distance, PolyLine, Polygon};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 use std::fmt;
@@ -113,8 +111,15 @@ pub struct Road {
     // Unshifted original center points. Order implies road orientation. Reversing lanes doesn't
     // change this.
     pub center_pts: PolyLine,
+    // Before trimming back at either intersection. Needed to recompute the trimming when edits
+    // change the road's width.
+    pub untrimmed_center_pts: PolyLine,
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
+
+    // Inferred from OSM tags when the map is built, and possibly edited later
+    pub speed_limit_forwards: SpeedLimit,
+    pub speed_limit_backwards: SpeedLimit,
 }
 
 impl Road {
@@ -153,6 +158,26 @@ impl Road {
         panic!("{} doesn't contain {}", self.id, lane);
     }
 
+    // The lanes immediately to either side of this one going the same direction, if they're the
+    // same type. Vehicles can move between these in the middle of the road.
+    pub fn adjacent_lanes(&self, lane: LaneID) -> Vec<LaneID> {
+        let (fwds, idx) = self.dir_and_offset(lane);
+        let children = if fwds {
+            &self.children_forwards
+        } else {
+            &self.children_backwards
+        };
+        let lt = children[idx].1;
+        let mut result = Vec::new();
+        if idx > 0 && children[idx - 1].1 == lt {
+            result.push(children[idx - 1].0);
+        }
+        if idx + 1 < children.len() && children[idx + 1].1 == lt {
+            result.push(children[idx + 1].0);
+        }
+        result
+    }
+
     pub fn parking_to_driving(&self, parking: LaneID) -> Option<LaneID> {
         // TODO Crossing bike/bus lanes means higher layers of sim should know to block these off
         // when parking/unparking
@@ -211,23 +236,12 @@ impl Road {
         }
     }
 
-    pub fn get_speed_limit(&self) -> Speed {
-        // TODO Should probably cache this
-        if let Some(limit) = self.osm_tags.get(osm::MAXSPEED) {
-            // TODO handle other units
-            if limit.ends_with(" mph") {
-                if let Ok(mph) = limit[0..limit.len() - 4].parse::<f64>() {
-                    return Speed::miles_per_hour(mph);
-                }
-            }
-        }
-
-        if self.osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
-            || self.osm_tags.get(osm::HIGHWAY) == Some(&"secondary".to_string())
-        {
-            return Speed::miles_per_hour(40.0);
+    pub fn get_speed_limit(&self, forwards: bool) -> &SpeedLimit {
+        if forwards {
+            &self.speed_limit_forwards
+        } else {
+            &self.speed_limit_backwards
         }
-        Speed::miles_per_hour(20.0)
     }
 
     pub fn get_zorder(&self) -> isize {
@@ -316,9 +330,21 @@ impl Road {
         search.iter().find(|(_, t)| lt == *t).map(|(id, _)| *id)
     }
 
-    pub fn get_thick_polyline(&self) -> Warn<(PolyLine, Distance)> {
-        let width_right = (self.children_forwards.len() as f64) * LANE_THICKNESS;
-        let width_left = (self.children_backwards.len() as f64) * LANE_THICKNESS;
+    // The total width of the lanes on one side of the road
+    pub fn width(&self, forwards: bool, map: &Map) -> Distance {
+        let children = if forwards {
+            &self.children_forwards
+        } else {
+            &self.children_backwards
+        };
+        children
+            .iter()
+            .fold(Distance::ZERO, |sum, (l, _)| sum + map.get_l(*l).width)
+    }
+
+    pub fn get_thick_polyline(&self, map: &Map) -> Warn<(PolyLine, Distance)> {
+        let width_right = self.width(true, map);
+        let width_left = self.width(false, map);
         let total_width = width_right + width_left;
         if width_right >= width_left {
             self.center_pts
@@ -331,8 +357,8 @@ impl Road {
         }
     }
 
-    pub fn get_thick_polygon(&self) -> Warn<Polygon> {
-        self.get_thick_polyline()
+    pub fn get_thick_polygon(&self, map: &Map) -> Warn<Polygon> {
+        self.get_thick_polyline(map)
             .map(|(pl, width)| pl.make_polygons(width))
     }
 
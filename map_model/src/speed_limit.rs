@@ -0,0 +1,150 @@
+use crate::osm;
+use abstutil::Timer;
+use geom::{Speed, Time};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// The legal limit for one direction of a road.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpeedLimit {
+    pub normal: Speed,
+    // School zones and such: from the first time until the second, a lower limit applies.
+    pub conditional: Vec<(Time, Time, Speed)>,
+}
+
+impl SpeedLimit {
+    pub fn at(&self, time: Time) -> Speed {
+        let mut speed = self.normal;
+        for (start, end, limit) in &self.conditional {
+            // Conditional limits can only lower things. If the normal limit is edited below a
+            // school zone's limit, the edit wins.
+            if time >= *start && time < *end {
+                speed = speed.min(*limit);
+            }
+        }
+        speed
+    }
+}
+
+// How to guess the limit when OSM doesn't say. Values are written like OSM maxspeed tags, so "25
+// mph" or "40" (km/h).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpeedLimitDefaults {
+    // Keyed by the highway tag
+    pub highway: BTreeMap<String, String>,
+    pub fallback: String,
+}
+
+impl SpeedLimitDefaults {
+    // Each map can have its own table in data/input/speed_limits. Everything else gets Seattle's.
+    pub fn load(map_name: &str, timer: &mut Timer) -> SpeedLimitDefaults {
+        match abstutil::maybe_read_json(abstutil::path_speed_limits(map_name), timer) {
+            Ok(defaults) => defaults,
+            Err(_) => SpeedLimitDefaults::seattle(),
+        }
+    }
+
+    pub fn seattle() -> SpeedLimitDefaults {
+        let mut highway = BTreeMap::new();
+        for (hwy, limit) in vec![
+            ("motorway", "60 mph"),
+            ("motorway_link", "40 mph"),
+            ("trunk", "40 mph"),
+            ("primary", "40 mph"),
+            ("secondary", "40 mph"),
+        ] {
+            highway.insert(hwy.to_string(), limit.to_string());
+        }
+        SpeedLimitDefaults {
+            highway,
+            fallback: "20 mph".to_string(),
+        }
+    }
+
+    fn lookup(&self, highway: Option<&String>) -> Speed {
+        let value = highway
+            .and_then(|hwy| self.highway.get(hwy))
+            .unwrap_or(&self.fallback);
+        parse_maxspeed(value).unwrap_or_else(|| panic!("Bad speed limit default {}", value))
+    }
+
+    pub(crate) fn infer(&self, tags: &BTreeMap<String, String>, forwards: bool) -> SpeedLimit {
+        let dir = if forwards { "forward" } else { "backward" };
+        let directed_key = format!("{}:{}", osm::MAXSPEED, dir);
+        let normal = tags
+            .get(&directed_key)
+            .and_then(|x| parse_maxspeed(x))
+            .or_else(|| tags.get(osm::MAXSPEED).and_then(|x| parse_maxspeed(x)))
+            .unwrap_or_else(|| self.lookup(tags.get(osm::HIGHWAY)));
+        let conditional = tags
+            .get(&format!("{}:conditional", directed_key))
+            .or_else(|| tags.get(&format!("{}:conditional", osm::MAXSPEED)))
+            .map(|x| parse_conditional_maxspeed(x))
+            .unwrap_or_else(Vec::new);
+        SpeedLimit {
+            normal,
+            conditional,
+        }
+    }
+}
+
+// A bare number is km/h, following OSM. Values like "none" or "signals" aren't understood.
+pub fn parse_maxspeed(value: &str) -> Option<Speed> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let number = value[0..split].parse::<f64>().ok()?;
+    let kmh = match value[split..].trim() {
+        "" | "km/h" | "kmh" | "kph" => number,
+        "mph" => {
+            return Some(Speed::miles_per_hour(number));
+        }
+        "knots" => number * 1.852,
+        _ => {
+            return None;
+        }
+    };
+    Some(Speed::meters_per_second(kmh / 3.6))
+}
+
+// Handles the part of maxspeed:conditional that matters for a single simulated day, like "20 mph
+// @ (Mo-Fr 07:00-09:00, 14:00-16:00)". Days of the week are ignored, and conditions that aren't
+// times (like "wet") are skipped.
+pub fn parse_conditional_maxspeed(value: &str) -> Vec<(Time, Time, Speed)> {
+    let mut result = Vec::new();
+    for rule in value.split(';') {
+        let parts: Vec<&str> = rule.splitn(2, '@').collect();
+        if parts.len() != 2 {
+            continue;
+        }
+        let speed = match parse_maxspeed(parts[0]) {
+            Some(s) => s,
+            None => {
+                continue;
+            }
+        };
+        let condition = parts[1]
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')');
+        for range in condition.split(|c: char| c == ',' || c.is_whitespace()) {
+            let times: Vec<&str> = range.split('-').collect();
+            if times.len() != 2 {
+                continue;
+            }
+            if let (Some(start), Some(end)) = (parse_hhmm(times[0]), parse_hhmm(times[1])) {
+                result.push((start, end, speed));
+            }
+        }
+    }
+    result
+}
+
+fn parse_hhmm(value: &str) -> Option<Time> {
+    if value.matches(':').count() != 1 {
+        return None;
+    }
+    // Time::parse reads two parts as minutes and seconds
+    Time::parse(&format!("{}:00", value)).ok()
+}
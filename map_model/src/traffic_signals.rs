@@ -22,6 +22,13 @@ pub struct Phase {
     pub protected_groups: BTreeSet<TurnGroupID>,
     pub yield_groups: BTreeSet<TurnGroupID>,
     pub duration: Duration,
+    // Protected crosswalks get a head start this long before conflicting vehicle turns can go.
+    #[serde(default)]
+    pub leading_ped_interval: Duration,
+    // Only for phases with nothing but crosswalks. If nobody's waiting to cross when the phase
+    // would start, skip it.
+    #[serde(default)]
+    pub push_button: bool,
 }
 
 impl ControlTrafficSignal {
@@ -127,6 +134,45 @@ impl ControlTrafficSignal {
             for g in phase.yield_groups.iter().map(|g| &self.turn_groups[g]) {
                 assert!(g.turn_type != TurnType::Crosswalk);
             }
+
+            let has_crosswalks = phase
+                .protected_groups
+                .iter()
+                .any(|g| self.turn_groups[g].turn_type == TurnType::Crosswalk);
+            let only_crosswalks = phase
+                .protected_groups
+                .iter()
+                .all(|g| self.turn_groups[g].turn_type == TurnType::Crosswalk)
+                && phase.yield_groups.is_empty();
+            if phase.leading_ped_interval > Duration::ZERO {
+                if !has_crosswalks || only_crosswalks {
+                    return Err(format!(
+                        "Traffic signal {} has a leading pedestrian interval in a phase without \
+                         both crosswalks and vehicles",
+                        self.id
+                    ));
+                }
+                if phase.leading_ped_interval >= phase.duration {
+                    return Err(format!(
+                        "Traffic signal {} has a leading pedestrian interval of {}, but the \
+                         phase only lasts {}",
+                        self.id, phase.leading_ped_interval, phase.duration
+                    ));
+                }
+            }
+            if phase.push_button && !(has_crosswalks && only_crosswalks) {
+                return Err(format!(
+                    "Traffic signal {} has a push-button phase that isn't only crosswalks",
+                    self.id
+                ));
+            }
+        }
+        // Otherwise every phase could be skipped.
+        if !self.phases.is_empty() && self.phases.iter().all(|p| p.push_button) {
+            return Err(format!(
+                "Traffic signal {} only has push-button phases",
+                self.id
+            ));
         }
 
         Ok(self)
@@ -487,6 +533,8 @@ impl Phase {
             protected_groups: BTreeSet::new(),
             yield_groups: BTreeSet::new(),
             duration: Duration::seconds(30.0),
+            leading_ped_interval: Duration::ZERO,
+            push_button: false,
         }
     }
 
@@ -505,14 +553,32 @@ impl Phase {
     }
 
     pub fn get_priority_of_turn(&self, t: TurnID, parent: &ControlTrafficSignal) -> TurnPriority {
-        // TODO Cache this?
-        let g = parent
-            .turn_groups
-            .values()
-            .find(|g| g.members.contains(&t))
-            .map(|g| g.id)
-            .unwrap();
-        self.get_priority_of_group(g)
+        self.get_priority_of_group(find_group(t, parent))
+    }
+
+    // Like get_priority_of_turn, but also holds back vehicles during a leading pedestrian
+    // interval.
+    pub fn get_priority_of_turn_at(
+        &self,
+        t: TurnID,
+        parent: &ControlTrafficSignal,
+        time_into_phase: Duration,
+    ) -> TurnPriority {
+        let g = find_group(t, parent);
+        let pri = self.get_priority_of_group(g);
+        if pri == TurnPriority::Banned || time_into_phase >= self.leading_ped_interval {
+            return pri;
+        }
+        let group = &parent.turn_groups[&g];
+        if group.turn_type != TurnType::Crosswalk
+            && self.protected_groups.iter().any(|c| {
+                let crosswalk = &parent.turn_groups[c];
+                crosswalk.turn_type == TurnType::Crosswalk && crosswalk.conflicts_with(group)
+            })
+        {
+            return TurnPriority::Banned;
+        }
+        pri
     }
 
     pub fn get_priority_of_group(&self, g: TurnGroupID) -> TurnPriority {
@@ -555,6 +621,16 @@ impl Phase {
     }
 }
 
+// TODO Cache this?
+fn find_group(t: TurnID, parent: &ControlTrafficSignal) -> TurnGroupID {
+    parent
+        .turn_groups
+        .values()
+        .find(|g| g.members.contains(&t))
+        .map(|g| g.id)
+        .unwrap()
+}
+
 // Add all possible protected groups to existing phases.
 fn expand_all_phases(phases: &mut Vec<Phase>, turn_groups: &BTreeMap<TurnGroupID, TurnGroup>) {
     for phase in phases.iter_mut() {
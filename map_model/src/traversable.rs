@@ -1,5 +1,5 @@
 use crate::{BuildingID, LaneID, LaneType, Map, TurnID};
-use geom::{Angle, Distance, PolyLine, Pt2D, Speed};
+use geom::{Angle, Distance, PolyLine, Pt2D, Speed, Time};
 use serde_derive::{Deserialize, Serialize};
 use std::fmt;
 
@@ -165,10 +165,18 @@ impl Traversable {
         }
     }
 
+    // Ignores time-conditional limits; use speed_limit_at when the time is known.
     pub fn speed_limit(&self, map: &Map) -> Speed {
         match *self {
-            Traversable::Lane(id) => map.get_parent(id).get_speed_limit(),
-            Traversable::Turn(id) => map.get_parent(id.dst).get_speed_limit(),
+            Traversable::Lane(id) => map.get_l(id).get_speed_limit(map).normal,
+            Traversable::Turn(id) => map.get_l(id.dst).get_speed_limit(map).normal,
+        }
+    }
+
+    pub fn speed_limit_at(&self, map: &Map, time: Time) -> Speed {
+        match *self {
+            Traversable::Lane(id) => map.get_l(id).get_speed_limit(map).at(time),
+            Traversable::Turn(id) => map.get_l(id.dst).get_speed_limit(map).at(time),
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::{IntersectionID, LaneID, Map, RoadID, LANE_THICKNESS};
+use crate::{IntersectionID, LaneID, Map, RoadID};
 use abstutil::MultiMap;
 use geom::{Angle, Distance, PolyLine, Pt2D};
 use serde_derive::{Deserialize, Serialize};
@@ -231,13 +231,23 @@ impl TurnGroup {
         offsets.dedup();
         // TODO This breaks if the group is non-contiguous. Add a rightmost bike lane that gets a
         // crazy left turn.
-        let offset = if offsets.len() % 2 == 0 {
-            // Middle of two lanes
-            (offsets[offsets.len() / 2] as f64) - 0.5
+        let children = if dir {
+            &r.children_forwards
         } else {
-            offsets[offsets.len() / 2] as f64
+            &r.children_backwards
         };
-        let pl = pl.shift_right(LANE_THICKNESS * (0.5 + offset)).unwrap();
+        // Distance from the road's center line to the near and far edge of the group
+        let mut near = Distance::ZERO;
+        let mut far = Distance::ZERO;
+        for (idx, (l, _)) in children.iter().enumerate() {
+            if idx < offsets[0] {
+                near += map.get_l(*l).width;
+            }
+            if idx <= *offsets.last().unwrap() {
+                far += map.get_l(*l).width;
+            }
+        }
+        let pl = pl.shift_right((near + far) / 2.0).unwrap();
         let pl = if self
             .id
             .crosswalk
@@ -248,8 +258,7 @@ impl TurnGroup {
         } else {
             pl.reversed()
         };
-        let width = LANE_THICKNESS * ((*offsets.last().unwrap() - offsets[0] + 1) as f64);
-        (pl, width)
+        (pl, far - near)
     }
 
     pub fn conflicts_with(&self, other: &TurnGroup) -> bool {
@@ -0,0 +1,24 @@
+use crate::psrc::Parcel;
+use map_model::{BuildingID, Isochrone};
+use std::collections::HashMap;
+
+// A cumulative opportunities measure: how many households and jobs are within an isochrone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Accessibility {
+    pub households: usize,
+    pub jobs: usize,
+}
+
+impl Accessibility {
+    // The parcels come from clip_trips.
+    pub fn new(iso: &Isochrone, parcels: &HashMap<BuildingID, Parcel>) -> Accessibility {
+        let mut result = Accessibility::default();
+        for b in iso.times.keys() {
+            if let Some(parcel) = parcels.get(b) {
+                result.households += parcel.num_households;
+                result.jobs += parcel.num_employees;
+            }
+        }
+        result
+    }
+}
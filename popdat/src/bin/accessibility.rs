@@ -0,0 +1,70 @@
+use abstutil::{CmdArgs, Timer};
+use geom::Duration;
+use map_model::{BuildingID, Map, MapEdits, TravelMode};
+use popdat::{clip_trips, Accessibility};
+
+// How many households and jobs can one building reach within some time? With --edits, also
+// compares against the same map after applying those edits.
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_name = args.required("--map");
+    let start = BuildingID(
+        args.required("--building")
+            .parse::<usize>()
+            .expect("--building should be a building ID"),
+    );
+    let mode = args
+        .optional_parse("--mode", parse_mode)
+        .unwrap_or(TravelMode::Walk);
+    let limit = Duration::minutes(
+        args.optional_parse("--minutes", |s| s.parse::<usize>())
+            .unwrap_or(15),
+    );
+    let edits_name = args.optional("--edits");
+    args.done();
+
+    let mut timer = Timer::new(format!("accessibility from {}", start));
+    let mut map = Map::new(abstutil::path_map(&map_name), false, &mut timer);
+    let (_, parcels) = clip_trips(&map, &mut timer);
+
+    let before = map.isochrone(start, mode, limit);
+    let score = Accessibility::new(&before, &parcels);
+    println!(
+        "Within {} by {:?} from {}: {} buildings, {} households, {} jobs",
+        limit,
+        mode,
+        start,
+        before.times.len(),
+        score.households,
+        score.jobs
+    );
+
+    if let Some(name) = edits_name {
+        let edits = MapEdits::load(&mut map, &name, &mut timer);
+        map.apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+
+        let after = map.isochrone(start, mode, limit);
+        let new_score = Accessibility::new(&after, &parcels);
+        let (lost, gained) = before.compare(&after);
+        println!(
+            "With {}: {} buildings, {} households, {} jobs ({} buildings lost, {} gained)",
+            name,
+            after.times.len(),
+            new_score.households,
+            new_score.jobs,
+            lost.len(),
+            gained.len()
+        );
+    }
+}
+
+fn parse_mode(x: &str) -> Result<TravelMode, String> {
+    match x {
+        "walk" => Ok(TravelMode::Walk),
+        "transit" => Ok(TravelMode::Transit),
+        "bike" => Ok(TravelMode::Bike),
+        "drive" => Ok(TravelMode::Drive),
+        _ => Err(format!("unknown mode {}", x)),
+    }
+}
@@ -0,0 +1,57 @@
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use popdat::{import_od_matrix, BuildingWeight};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{Scenario, SpawnTrip};
+
+// Turns a travel demand model's zone-to-zone OD matrix into a scenario for one map. See
+// import_od_matrix for the input formats.
+fn main() {
+    let mut args = CmdArgs::new();
+    let map_name = args.required("--map");
+    let zones = args.required("--zones");
+    let zone_id = args
+        .optional("--zone_id")
+        .unwrap_or_else(|| "id".to_string());
+    let matrix = args.required("--matrix");
+    let weight = args
+        .optional_parse("--building_weight", parse_weight)
+        .unwrap_or(BuildingWeight::Uniform);
+    let output = args.required("--output");
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse::<u8>())
+        .unwrap_or(42);
+    args.done();
+
+    let mut timer = Timer::new(format!("import OD matrix {}", matrix));
+    let mut rng = XorShiftRng::from_seed([rng_seed; 16]);
+    let map = Map::new(abstutil::path_map(&map_name), false, &mut timer);
+    let trips = import_od_matrix(
+        &zones, &zone_id, &matrix, weight, &map, &mut rng, &mut timer,
+    )
+    .unwrap();
+
+    let mut scenario = Scenario::empty(&map, &output);
+    scenario.seed_buses = true;
+    // Everybody driving from a building needs a car waiting there.
+    for trip in &trips {
+        if let SpawnTrip::MaybeUsingParkedCar(_, b, _) = trip {
+            *scenario.individ_parked_cars.entry(*b).or_insert(0) += 1;
+        }
+    }
+    scenario.individ_trips = trips;
+    for line in scenario.describe() {
+        timer.note(line);
+    }
+    scenario.save(&map);
+}
+
+fn parse_weight(x: &str) -> Result<BuildingWeight, String> {
+    match x {
+        "uniform" => Ok(BuildingWeight::Uniform),
+        "area" => Ok(BuildingWeight::Area),
+        "floor_area" => Ok(BuildingWeight::FloorArea),
+        _ => Err(format!("unknown building weight {}", x)),
+    }
+}
@@ -1,6 +1,10 @@
+mod accessibility;
+mod od;
 pub mod psrc;
 mod trips;
 
+pub use accessibility::Accessibility;
+pub use od::{import_od_matrix, BuildingWeight};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 pub use trips::{clip_trips, trips_to_scenario, Trip, TripEndpt};
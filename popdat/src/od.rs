@@ -0,0 +1,353 @@
+use crate::psrc::Mode;
+use crate::trips::spawn_trip;
+use crate::TripEndpt;
+use abstutil::{prettyprint_usize, FileWithProgress, Timer};
+use geom::{Duration, LonLat, Polygon, Pt2D, Time};
+use map_model::{BuildingID, IntersectionID, Map, PathConstraints};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde_derive::Deserialize;
+use serde_json::Value;
+use sim::SpawnTrip;
+use std::collections::BTreeMap;
+
+// How to pick the building in a zone where a trip starts or ends
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuildingWeight {
+    Uniform,
+    // Bigger footprints attract more trips
+    Area,
+    // Footprint times building:levels, assuming one level when that's missing
+    FloorArea,
+}
+
+// One row of the matrix: this many trips between two zones, departing uniformly within the time
+// period. Fractional counts are rounded randomly.
+#[derive(Deserialize)]
+struct Record {
+    origin: String,
+    destination: String,
+    mode: String,
+    start_time: String,
+    end_time: String,
+    trips: f64,
+}
+
+struct Zone {
+    // Empty for zones outside the map
+    buildings: Vec<BuildingID>,
+    weights: Option<WeightedIndex<f64>>,
+    // Used to find the closest border for zones outside the map
+    center: LonLat,
+}
+
+impl Zone {
+    fn pick(&self, rng: &mut XorShiftRng) -> Option<BuildingID> {
+        let weights = self.weights.as_ref()?;
+        Some(self.buildings[weights.sample(rng)])
+    }
+}
+
+// Turns a travel demand model's zone-to-zone OD matrix into trips. Zones are polygons in a GeoJSON
+// FeatureCollection, named by the zone_id_property of each feature. The CSV matrix has columns
+// origin, destination, mode (walk, bike, drive, or transit), start_time, end_time, and trips.
+// Trips to or from zones without any buildings in the map use the closest border instead;
+// pass-through trips are skipped.
+pub fn import_od_matrix(
+    zones_path: &str,
+    zone_id_property: &str,
+    matrix_path: &str,
+    weight: BuildingWeight,
+    map: &Map,
+    rng: &mut XorShiftRng,
+    timer: &mut Timer,
+) -> Result<Vec<SpawnTrip>, failure::Error> {
+    let zones = load_zones(zones_path, zone_id_property, weight, map, timer)?;
+
+    // (constraints, incoming borders, outgoing borders)
+    let all_borders: Vec<(
+        PathConstraints,
+        Vec<(IntersectionID, LonLat)>,
+        Vec<(IntersectionID, LonLat)>,
+    )> = vec![
+        PathConstraints::Pedestrian,
+        PathConstraints::Car,
+        PathConstraints::Bike,
+    ]
+    .into_iter()
+    .map(|constraints| {
+        let incoming = map
+            .all_incoming_borders()
+            .into_iter()
+            .filter(|i| !i.get_outgoing_lanes(map, constraints).is_empty())
+            .map(|i| i.id)
+            .collect();
+        let outgoing = map
+            .all_outgoing_borders()
+            .into_iter()
+            .filter(|i| !i.get_incoming_lanes(map, constraints).is_empty())
+            .map(|i| i.id)
+            .collect();
+        (constraints, borders(incoming, map), borders(outgoing, map))
+    })
+    .collect();
+
+    let mut trips = Vec::new();
+    let mut skipped = 0;
+    let (reader, done) = FileWithProgress::new(matrix_path)?;
+    for rec in csv::Reader::from_reader(reader).deserialize() {
+        let rec: Record = rec?;
+        if !rec.trips.is_finite() || rec.trips < 0.0 {
+            return Err(failure::err_msg(format!(
+                "{} trips from {} to {} doesn't make sense",
+                rec.trips, rec.origin, rec.destination
+            )));
+        }
+        let from_zone = zones
+            .get(&rec.origin)
+            .ok_or_else(|| failure::err_msg(format!("Unknown origin zone {}", rec.origin)))?;
+        let to_zone = zones.get(&rec.destination).ok_or_else(|| {
+            failure::err_msg(format!("Unknown destination zone {}", rec.destination))
+        })?;
+        let mode = parse_mode(&rec.mode)?;
+        let constraints = match mode {
+            Mode::Walk | Mode::Transit => PathConstraints::Pedestrian,
+            Mode::Drive => PathConstraints::Car,
+            Mode::Bike => PathConstraints::Bike,
+        };
+        let (_, incoming_borders, outgoing_borders) = all_borders
+            .iter()
+            .find(|(c, _, _)| *c == constraints)
+            .unwrap();
+        let start_time = Time::parse(&rec.start_time)?;
+        let end_time = Time::parse(&rec.end_time)?;
+        if end_time <= start_time {
+            return Err(failure::err_msg(format!(
+                "Period {} - {} is empty",
+                rec.start_time, rec.end_time
+            )));
+        }
+
+        let mut count = rec.trips.floor() as usize;
+        if rng.gen_bool(rec.trips - rec.trips.floor()) {
+            count += 1;
+        }
+        for _ in 0..count {
+            let from = match from_zone.pick(rng) {
+                Some(b) => TripEndpt::Building(b),
+                None => match closest_border(from_zone, incoming_borders, map) {
+                    Some(endpt) => endpt,
+                    None => {
+                        skipped += 1;
+                        continue;
+                    }
+                },
+            };
+            let to = match to_zone.pick(rng) {
+                Some(b) => TripEndpt::Building(b),
+                None => match closest_border(to_zone, outgoing_borders, map) {
+                    Some(endpt) => endpt,
+                    None => {
+                        skipped += 1;
+                        continue;
+                    }
+                },
+            };
+            match (&from, &to) {
+                (TripEndpt::Border(_, _), TripEndpt::Border(_, _)) => {
+                    skipped += 1;
+                    continue;
+                }
+                (TripEndpt::Building(b1), TripEndpt::Building(b2)) if b1 == b2 => {
+                    skipped += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let depart = start_time
+                + Duration::seconds(rng.gen_range(0.0, (end_time - start_time).inner_seconds()));
+            if let Some(trip) = spawn_trip(&from, &to, mode, depart, map) {
+                trips.push(trip);
+            } else {
+                skipped += 1;
+            }
+        }
+    }
+    done(timer);
+
+    timer.note(format!(
+        "{} trips from the OD matrix, {} skipped",
+        prettyprint_usize(trips.len()),
+        prettyprint_usize(skipped)
+    ));
+    Ok(trips)
+}
+
+fn load_zones(
+    path: &str,
+    id_property: &str,
+    weight: BuildingWeight,
+    map: &Map,
+    timer: &mut Timer,
+) -> Result<BTreeMap<String, Zone>, failure::Error> {
+    let geojson: Value = serde_json::from_reader(std::fs::File::open(path)?)?;
+    let features = geojson["features"]
+        .as_array()
+        .ok_or_else(|| failure::err_msg(format!("{} isn't a FeatureCollection", path)))?;
+    let gps_bounds = map.get_gps_bounds();
+
+    let mut polygons: Vec<(String, Vec<Polygon>, LonLat)> = Vec::new();
+    for feature in features {
+        let id = match &feature["properties"][id_property] {
+            Value::String(s) => s.clone(),
+            Value::Null => {
+                return Err(failure::err_msg(format!(
+                    "A zone is missing the {} property",
+                    id_property
+                )));
+            }
+            x => x.to_string(),
+        };
+        let rings: Vec<&Value> = match feature["geometry"]["type"].as_str() {
+            Some("Polygon") => vec![&feature["geometry"]["coordinates"][0]],
+            Some("MultiPolygon") => feature["geometry"]["coordinates"]
+                .as_array()
+                .map(|list| list.iter().map(|poly| &poly[0]).collect())
+                .unwrap_or_else(Vec::new),
+            x => {
+                return Err(failure::err_msg(format!(
+                    "Zone {} has unsupported geometry {:?}",
+                    id, x
+                )));
+            }
+        };
+
+        let mut zone_polygons = Vec::new();
+        let mut all_pts = Vec::new();
+        for ring in rings {
+            let mut pts: Vec<LonLat> = ring
+                .as_array()
+                .map(|list| {
+                    list.iter()
+                        .filter_map(|pair| Some(LonLat::new(pair[0].as_f64()?, pair[1].as_f64()?)))
+                        .collect()
+                })
+                .unwrap_or_else(Vec::new);
+            // GeoJSON rings are closed
+            if pts.len() > 1 && pts[0] == *pts.last().unwrap() {
+                pts.pop();
+            }
+            if pts.len() < 3 {
+                timer.warn(format!("Skipping a degenerate polygon in zone {}", id));
+                continue;
+            }
+            zone_polygons.push(Polygon::new(
+                &pts.iter()
+                    .map(|pt| Pt2D::forcibly_from_gps(*pt, gps_bounds))
+                    .collect(),
+            ));
+            all_pts.extend(pts);
+        }
+        if all_pts.is_empty() {
+            timer.warn(format!("Zone {} has no geometry", id));
+            continue;
+        }
+        let center = LonLat::new(
+            all_pts.iter().map(|pt| pt.longitude).sum::<f64>() / (all_pts.len() as f64),
+            all_pts.iter().map(|pt| pt.latitude).sum::<f64>() / (all_pts.len() as f64),
+        );
+        polygons.push((id, zone_polygons, center));
+    }
+
+    // A building in overlapping zones belongs to the first one.
+    let mut buildings_per_zone: Vec<Vec<BuildingID>> = vec![Vec::new(); polygons.len()];
+    timer.start_iter("match buildings to zones", map.all_buildings().len());
+    for b in map.all_buildings() {
+        timer.next();
+        let pt = b.polygon.center();
+        if let Some(idx) = polygons.iter().position(|(_, polys, _)| {
+            polys
+                .iter()
+                .any(|poly| poly.get_bounds().contains(pt) && poly.contains_pt(pt))
+        }) {
+            buildings_per_zone[idx].push(b.id);
+        }
+    }
+
+    let mut zones = BTreeMap::new();
+    for ((id, _, center), buildings) in polygons.into_iter().zip(buildings_per_zone) {
+        let weights = if buildings.is_empty() {
+            None
+        } else {
+            let raw: Vec<f64> = buildings
+                .iter()
+                .map(|b| {
+                    let bldg = map.get_b(*b);
+                    match weight {
+                        BuildingWeight::Uniform => 1.0,
+                        BuildingWeight::Area => bldg.polygon.area(),
+                        BuildingWeight::FloorArea => {
+                            let levels = bldg
+                                .osm_tags
+                                .get("building:levels")
+                                .and_then(|x| x.parse::<f64>().ok())
+                                .unwrap_or(1.0);
+                            bldg.polygon.area() * levels
+                        }
+                    }
+                })
+                .collect();
+            // Fall back to uniform for weird geometry
+            Some(WeightedIndex::new(&raw).or_else(|_| WeightedIndex::new(vec![1.0; raw.len()]))?)
+        };
+        zones.insert(
+            id,
+            Zone {
+                buildings,
+                weights,
+                center,
+            },
+        );
+    }
+    Ok(zones)
+}
+
+fn borders(ids: Vec<IntersectionID>, map: &Map) -> Vec<(IntersectionID, LonLat)> {
+    ids.into_iter()
+        .filter_map(|i| {
+            map.get_i(i)
+                .polygon
+                .center()
+                .to_gps(map.get_gps_bounds())
+                .map(|pt| (i, pt))
+        })
+        .collect()
+}
+
+fn closest_border(
+    zone: &Zone,
+    borders: &Vec<(IntersectionID, LonLat)>,
+    map: &Map,
+) -> Option<TripEndpt> {
+    borders
+        .iter()
+        .min_by_key(|(_, pt)| pt.fast_dist(zone.center))
+        .map(|(i, _)| {
+            TripEndpt::Border(
+                *i,
+                Pt2D::forcibly_from_gps(zone.center, map.get_gps_bounds()),
+            )
+        })
+}
+
+fn parse_mode(mode: &str) -> Result<Mode, failure::Error> {
+    match mode.to_lowercase().as_str() {
+        "walk" => Ok(Mode::Walk),
+        "bike" => Ok(Mode::Bike),
+        "drive" => Ok(Mode::Drive),
+        "transit" => Ok(Mode::Transit),
+        x => Err(failure::err_msg(format!("Unknown mode {}", x))),
+    }
+}
@@ -32,84 +32,89 @@ impl Trip {
     }
 
     pub fn to_spawn_trip(&self, map: &Map) -> Option<SpawnTrip> {
-        match self.mode {
-            Mode::Drive => match self.from {
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Car)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            depart: self.depart_at,
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Car, map),
-                            is_bike: false,
-                        })
-                    } else {
-                        // TODO need to be able to emit warnings from parallelize
-                        //timer.warn(format!("No room for car to appear at {:?}", self.from));
-                        None
-                    }
-                }
-                TripEndpt::Building(b) => Some(SpawnTrip::MaybeUsingParkedCar(
-                    self.depart_at,
-                    b,
-                    self.to.driving_goal(PathConstraints::Car, map),
-                )),
-            },
-            Mode::Bike => match self.from {
-                TripEndpt::Building(b) => Some(SpawnTrip::UsingBike(
-                    self.depart_at,
-                    SidewalkSpot::building(b, map),
-                    self.to.driving_goal(PathConstraints::Bike, map),
-                )),
-                TripEndpt::Border(i, _) => {
-                    if let Some(start) = TripSpec::spawn_car_at(
-                        Position::new(
-                            map.get_i(i).get_outgoing_lanes(map, PathConstraints::Bike)[0],
-                            Distance::ZERO,
-                        ),
-                        map,
-                    ) {
-                        Some(SpawnTrip::CarAppearing {
-                            depart: self.depart_at,
-                            start,
-                            goal: self.to.driving_goal(PathConstraints::Bike, map),
-                            is_bike: true,
-                        })
-                    } else {
-                        //timer.warn(format!("No room for bike to appear at {:?}", self.from));
-                        None
-                    }
+        spawn_trip(&self.from, &self.to, self.mode, self.depart_at, map)
+    }
+}
+
+pub(crate) fn spawn_trip(
+    from: &TripEndpt,
+    to: &TripEndpt,
+    mode: Mode,
+    depart_at: Time,
+    map: &Map,
+) -> Option<SpawnTrip> {
+    match mode {
+        Mode::Drive => match *from {
+            TripEndpt::Border(i, _) => {
+                if let Some(start) = TripSpec::spawn_car_at(
+                    Position::new(
+                        map.get_i(i).get_outgoing_lanes(map, PathConstraints::Car)[0],
+                        Distance::ZERO,
+                    ),
+                    map,
+                ) {
+                    Some(SpawnTrip::CarAppearing {
+                        depart: depart_at,
+                        start,
+                        goal: to.driving_goal(PathConstraints::Car, map),
+                        is_bike: false,
+                    })
+                } else {
+                    // TODO need to be able to emit warnings from parallelize
+                    //timer.warn(format!("No room for car to appear at {:?}", from));
+                    None
                 }
-            },
-            Mode::Walk => Some(SpawnTrip::JustWalking(
-                self.depart_at,
-                self.from.start_sidewalk_spot(map),
-                self.to.end_sidewalk_spot(map),
+            }
+            TripEndpt::Building(b) => Some(SpawnTrip::MaybeUsingParkedCar(
+                depart_at,
+                b,
+                to.driving_goal(PathConstraints::Car, map),
+            )),
+        },
+        Mode::Bike => match *from {
+            TripEndpt::Building(b) => Some(SpawnTrip::UsingBike(
+                depart_at,
+                SidewalkSpot::building(b, map),
+                to.driving_goal(PathConstraints::Bike, map),
             )),
-            Mode::Transit => {
-                let start = self.from.start_sidewalk_spot(map);
-                let goal = self.to.end_sidewalk_spot(map);
-                if let Some((stop1, stop2, route)) =
-                    map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
-                {
-                    Some(SpawnTrip::UsingTransit(
-                        self.depart_at,
+            TripEndpt::Border(i, _) => {
+                if let Some(start) = TripSpec::spawn_car_at(
+                    Position::new(
+                        map.get_i(i).get_outgoing_lanes(map, PathConstraints::Bike)[0],
+                        Distance::ZERO,
+                    ),
+                    map,
+                ) {
+                    Some(SpawnTrip::CarAppearing {
+                        depart: depart_at,
                         start,
-                        goal,
-                        route,
-                        stop1,
-                        stop2,
-                    ))
+                        goal: to.driving_goal(PathConstraints::Bike, map),
+                        is_bike: true,
+                    })
                 } else {
-                    //timer.warn(format!("{:?} not actually using transit, because pathfinding didn't find any useful route", trip));
-                    Some(SpawnTrip::JustWalking(self.depart_at, start, goal))
+                    //timer.warn(format!("No room for bike to appear at {:?}", from));
+                    None
                 }
             }
+        },
+        Mode::Walk => Some(SpawnTrip::JustWalking(
+            depart_at,
+            from.start_sidewalk_spot(map),
+            to.end_sidewalk_spot(map),
+        )),
+        Mode::Transit => {
+            let start = from.start_sidewalk_spot(map);
+            let goal = to.end_sidewalk_spot(map);
+            if let Some((stop1, stop2, route)) =
+                map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
+            {
+                Some(SpawnTrip::UsingTransit(
+                    depart_at, start, goal, route, stop1, stop2,
+                ))
+            } else {
+                //timer.warn(format!("{:?} not actually using transit, because pathfinding didn't find any useful route", trip));
+                Some(SpawnTrip::JustWalking(depart_at, start, goal))
+            }
         }
     }
 }
@@ -322,5 +327,7 @@ pub fn trips_to_scenario(map: &Map, timer: &mut Timer) -> Scenario {
         border_spawn_over_time: Vec::new(),
         individ_trips,
         individ_parked_cars,
+        ride_hail_fleet: 0,
+        incidents: Vec::new(),
     }
 }
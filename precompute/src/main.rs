@@ -17,6 +17,6 @@ fn main() {
     timer.stop("save map");
 
     if !disable_psrc_scenarios {
-        trips_to_scenario(&map, &mut timer).save();
+        trips_to_scenario(&map, &mut timer).save(&map);
     }
 }
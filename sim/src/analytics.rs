@@ -1,12 +1,15 @@
-use crate::{AgentID, CarID, Event, TripID, TripMode, VehicleType};
-use abstutil::Counter;
+use crate::{AgentID, CarID, Emissions, Event, NearMiss, TripID, TripMode, VehicleType};
+use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use derivative::Derivative;
-use geom::{Distance, Duration, DurationHistogram, PercentageHistogram, Time};
+use geom::{Distance, Duration, DurationHistogram, PercentageHistogram, Statistic, Time};
 use map_model::{
-    BusRouteID, BusStopID, IntersectionID, Map, Path, PathRequest, RoadID, Traversable, TurnGroupID,
+    BusRouteID, BusStopID, ExtraProperties, IntersectionID, Map, Path, PathRequest, RoadID,
+    Traversable, TurnGroupID,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+const PED_DENSITY_SAMPLE: Duration = Duration::const_seconds(60.0);
 
 #[derive(Clone, Serialize, Deserialize, Derivative)]
 pub struct Analytics {
@@ -22,6 +25,25 @@ pub struct Analytics {
     // TODO This subsumes finished_trips
     pub trip_log: Vec<(Time, TripID, Option<PathRequest>, String)>,
     pub intersection_delays: BTreeMap<IntersectionID, Vec<(Time, Duration)>>,
+    pub near_misses: Vec<(Time, IntersectionID, AgentID, NearMiss)>,
+    // Peak pedestrians per square meter on sidewalks and crosswalks. Each entry covers
+    // PED_DENSITY_SAMPLE starting from its time, so this doesn't grow with the number of people.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub ped_density: BTreeMap<Traversable, Vec<(Time, f64)>>,
+    // Vehicles moving to an adjacent lane in the middle of a road
+    pub lane_changes: Vec<(Time, RoadID)>,
+    // Pedestrians crossing mid-block, and the vehicles that stopped for them
+    pub mid_block_crossings: Vec<(Time, RoadID)>,
+    pub yields_to_peds: Vec<(Time, CarID)>,
+    // Arrival time, vehicle, distance driven since the last stop, and whether a passenger was
+    // aboard
+    pub ride_hail_moves: Vec<(Time, CarID, Distance, bool)>,
+    // Pickup time, how long the passenger waited since requesting
+    pub ride_hail_waits: Vec<(Time, Duration)>,
+    pub emissions: Vec<(Time, TripID, Traversable, Emissions)>,
 
     // After we restore from a savestate, don't record anything. This is only going to make sense
     // if savestates are only used for quickly previewing against prebaked results, where we have
@@ -59,6 +81,14 @@ impl Analytics {
             finished_trips: Vec::new(),
             trip_log: Vec::new(),
             intersection_delays: BTreeMap::new(),
+            near_misses: Vec::new(),
+            ped_density: BTreeMap::new(),
+            lane_changes: Vec::new(),
+            mid_block_crossings: Vec::new(),
+            yields_to_peds: Vec::new(),
+            ride_hail_moves: Vec::new(),
+            ride_hail_waits: Vec::new(),
+            emissions: Vec::new(),
             record_anything: true,
         }
     }
@@ -76,7 +106,7 @@ impl Analytics {
             let mode = match a {
                 AgentID::Pedestrian(_) => TripMode::Walk,
                 AgentID::Car(c) => match c.1 {
-                    VehicleType::Car => TripMode::Drive,
+                    VehicleType::Car | VehicleType::Truck => TripMode::Drive,
                     VehicleType::Bike => TripMode::Bike,
                     VehicleType::Bus => TripMode::Transit,
                 },
@@ -136,6 +166,50 @@ impl Analytics {
                 .push((time, delay));
         }
 
+        // Surrogate safety measures
+        if let Event::NearMiss(i, agent, near_miss) = ev {
+            self.near_misses.push((time, i, agent, near_miss));
+        }
+
+        // Sidewalk crowding
+        if let Event::PedDensityMeasured(on, density) = ev {
+            let list = self.ped_density.entry(on).or_insert_with(Vec::new);
+            match list.last_mut() {
+                Some((start, peak)) if time < *start + PED_DENSITY_SAMPLE => {
+                    *peak = peak.max(density);
+                }
+                _ => {
+                    list.push((time, density));
+                }
+            }
+        }
+
+        // Lane changes
+        if let Event::CarChangedLanes(_, from, _) = ev {
+            self.lane_changes.push((time, map.get_l(from).parent));
+        }
+
+        // Jaywalking
+        if let Event::PedCrossedMidBlock(_, r) = ev {
+            self.mid_block_crossings.push((time, r));
+        }
+        if let Event::CarYieldedToPed(car, _) = ev {
+            self.yields_to_peds.push((time, car));
+        }
+
+        // Ride-hail service
+        if let Event::RideHailVehicleMoved(car, dist, with_passenger) = ev {
+            self.ride_hail_moves.push((time, car, dist, with_passenger));
+        }
+        if let Event::RideHailPickup(_, _, wait) = ev {
+            self.ride_hail_waits.push((time, wait));
+        }
+
+        // Emissions
+        if let Event::EmissionsProduced(_, trip, on, amount) = ev {
+            self.emissions.push((time, trip, on, amount));
+        }
+
         // TODO Kinda hacky, but these all consume the event, so kinda bundle em.
         match ev {
             Event::TripPhaseStarting(id, maybe_req, metadata) => {
@@ -211,6 +285,85 @@ impl Analytics {
         (all, num_aborted, per_mode)
     }
 
+    pub fn lane_changes_per_road(&self, now: Time) -> Counter<RoadID> {
+        let mut cnt = Counter::new();
+        for (t, r) in &self.lane_changes {
+            if *t > now {
+                break;
+            }
+            cnt.inc(*r);
+        }
+        cnt
+    }
+
+    // Returns (distance driven with passengers, distance deadheading, passenger wait times)
+    pub fn ride_hail_summary(&self, now: Time) -> (Distance, Distance, DurationHistogram) {
+        let mut with_passengers = Distance::ZERO;
+        let mut deadheading = Distance::ZERO;
+        for (t, _, dist, with_passenger) in &self.ride_hail_moves {
+            if *t > now {
+                break;
+            }
+            if *with_passenger {
+                with_passengers += *dist;
+            } else {
+                deadheading += *dist;
+            }
+        }
+        let mut waits = DurationHistogram::new();
+        for (t, dt) in &self.ride_hail_waits {
+            if *t > now {
+                break;
+            }
+            waits.add(*dt);
+        }
+        (with_passengers, deadheading, waits)
+    }
+
+    // Only counts what's produced on lanes, not while turning.
+    pub fn emissions_per_road(&self, now: Time, map: &Map) -> BTreeMap<RoadID, Emissions> {
+        let mut per_road = BTreeMap::new();
+        for (t, _, on, amount) in &self.emissions {
+            if *t > now {
+                break;
+            }
+            if let Traversable::Lane(l) = on {
+                *per_road
+                    .entry(map.get_l(*l).parent)
+                    .or_insert(Emissions::ZERO) += *amount;
+            }
+        }
+        per_road
+    }
+
+    // Everything produced in each hour, labeled by the start of the hour
+    pub fn emissions_per_hour(&self, now: Time) -> Vec<(Time, Emissions)> {
+        let mut results: Vec<(Time, Emissions)> = Vec::new();
+        for (t, _, _, amount) in &self.emissions {
+            if *t > now {
+                break;
+            }
+            let hour =
+                Time::START_OF_DAY + Duration::hours((t.inner_seconds() / 3600.0).floor() as usize);
+            if results.last().map(|(h, _)| *h != hour).unwrap_or(true) {
+                results.push((hour, Emissions::ZERO));
+            }
+            results.last_mut().unwrap().1 += *amount;
+        }
+        results
+    }
+
+    pub fn emissions_per_trip(&self, now: Time) -> BTreeMap<TripID, Emissions> {
+        let mut per_trip = BTreeMap::new();
+        for (t, trip, _, amount) in &self.emissions {
+            if *t > now {
+                break;
+            }
+            *per_trip.entry(*trip).or_insert(Emissions::ZERO) += *amount;
+        }
+        per_trip
+    }
+
     // Returns unsorted list of deltas, one for each trip finished in both worlds. Positive dt
     // means faster.
     pub fn finished_trip_deltas(&self, now: Time, baseline: &Analytics) -> Vec<Duration> {
@@ -486,6 +639,135 @@ impl Analytics {
         }
         results
     }
+
+    pub fn near_misses_per_intersection(&self, now: Time) -> Counter<IntersectionID> {
+        let mut cnt = Counter::new();
+        for (t, i, _, _) in &self.near_misses {
+            if *t > now {
+                break;
+            }
+            cnt.inc(*i);
+        }
+        cnt
+    }
+
+    // The highest density measured in each bucket of time
+    pub fn ped_density_bucketized(
+        &self,
+        now: Time,
+        on: Traversable,
+        bucket: Duration,
+    ) -> Vec<(Time, f64)> {
+        let mut max_this_bucket = now.min(Time::START_OF_DAY + bucket);
+        let mut results = vec![(Time::START_OF_DAY, 0.0), (max_this_bucket, 0.0)];
+        if let Some(list) = self.ped_density.get(&on) {
+            for (t, density) in list {
+                if *t > now {
+                    break;
+                }
+                if *t > max_this_bucket {
+                    max_this_bucket = now.min(max_this_bucket + bucket);
+                    results.push((max_this_bucket, 0.0));
+                }
+                let last = &mut results.last_mut().unwrap().1;
+                *last = last.max(*density);
+            }
+        }
+        results
+    }
+
+    // Every sidewalk and crosswalk that got more crowded than the threshold at some point up to
+    // now, with the peak density. Most crowded first.
+    pub fn overcrowded_sidewalks(&self, now: Time, threshold: f64) -> Vec<(Traversable, f64)> {
+        let mut results = Vec::new();
+        for (on, list) in &self.ped_density {
+            let peak = list
+                .iter()
+                .take_while(|(t, _)| *t <= now)
+                .fold(0.0, |peak, (_, density)| density.max(peak));
+            if peak > threshold {
+                results.push((*on, peak));
+            }
+        }
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        results
+    }
+
+    // Per-road throughput and emissions, per-intersection delay and near misses, and peak
+    // pedestrian density up to some time, for attaching to exported map features.
+    pub fn geojson_properties(&self, now: Time, map: &Map) -> ExtraProperties {
+        let mut per_road: BTreeMap<RoadID, BTreeMap<TripMode, usize>> = BTreeMap::new();
+        for (t, m, r) in &self.thruput_stats.raw_per_road {
+            if *t > now {
+                break;
+            }
+            *per_road
+                .entry(*r)
+                .or_insert_with(BTreeMap::new)
+                .entry(*m)
+                .or_insert(0) += 1;
+        }
+        let mut per_intersection: Counter<IntersectionID> = Counter::new();
+        for (t, _, i) in &self.thruput_stats.raw_per_intersection {
+            if *t > now {
+                break;
+            }
+            per_intersection.inc(*i);
+        }
+
+        let mut props = ExtraProperties::new();
+        for (r, counts) in per_road {
+            props.add_road(r, "throughput", counts.values().sum::<usize>() as f64);
+            for (m, cnt) in counts {
+                props.add_road(r, &format!("throughput_{}", m), cnt as f64);
+            }
+        }
+        for (i, cnt) in per_intersection.consume() {
+            props.add_intersection(i, "throughput", cnt as f64);
+        }
+        for (i, cnt) in self.near_misses_per_intersection(now).consume() {
+            props.add_intersection(i, "near_misses", cnt as f64);
+        }
+        for i in self.intersection_delays.keys() {
+            let delays = self.intersection_delays(*i, Time::START_OF_DAY, now);
+            if delays.count() == 0 {
+                continue;
+            }
+            props.add_intersection(*i, "delay_count", delays.count() as f64);
+            for (stat, key) in vec![
+                (Statistic::Mean, "delay_mean_s"),
+                (Statistic::P90, "delay_p90_s"),
+                (Statistic::Max, "delay_max_s"),
+            ] {
+                props.add_intersection(*i, key, delays.select(stat).inner_seconds());
+            }
+        }
+        for (r, amount) in self.emissions_per_road(now, map) {
+            props.add_road(r, "co2_g", amount.co2);
+            props.add_road(r, "nox_g", amount.nox);
+            props.add_road(r, "energy_kwh", amount.energy);
+        }
+        // Sorted by peak density, so only the first sidewalk or crosswalk per road or
+        // intersection counts.
+        let mut seen_roads = BTreeSet::new();
+        let mut seen_intersections = BTreeSet::new();
+        for (on, peak) in self.overcrowded_sidewalks(now, 0.0) {
+            match on {
+                Traversable::Lane(l) => {
+                    let r = map.get_l(l).parent;
+                    if seen_roads.insert(r) {
+                        props.add_road(r, "ped_density_max", peak);
+                    }
+                }
+                Traversable::Turn(t) => {
+                    if seen_intersections.insert(t.parent) {
+                        props.add_intersection(t.parent, "ped_density_max", peak);
+                    }
+                }
+            }
+        }
+        props
+    }
 }
 
 impl Default for Analytics {
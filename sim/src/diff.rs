@@ -0,0 +1,84 @@
+use crate::{CarID, PedestrianID};
+use geom::Time;
+use map_model::Traversable;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Where two simulations disagree, for tracking down nondeterminism. Each part is empty if that
+// part of the state matches.
+pub struct SimDiff {
+    pub times: Option<(Time, Time)>,
+    pub cars: Vec<(CarID, String)>,
+    pub peds: Vec<(PedestrianID, String)>,
+    pub queues: Vec<(Traversable, String)>,
+    // Queued commands
+    pub scheduler: Vec<String>,
+    // Names of anything else that differs, without details
+    pub other: Vec<String>,
+}
+
+impl SimDiff {
+    pub fn is_empty(&self) -> bool {
+        self.times.is_none()
+            && self.cars.is_empty()
+            && self.peds.is_empty()
+            && self.queues.is_empty()
+            && self.scheduler.is_empty()
+            && self.other.is_empty()
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some((t1, t2)) = self.times {
+            lines.push(format!("Time: {} vs {}", t1, t2));
+        }
+        for (id, msg) in &self.cars {
+            lines.push(format!("{}: {}", id, msg));
+        }
+        for (id, msg) in &self.peds {
+            lines.push(format!("{}: {}", id, msg));
+        }
+        for (on, msg) in &self.queues {
+            lines.push(format!("Queue on {:?}: {}", on, msg));
+        }
+        for msg in &self.scheduler {
+            lines.push(format!("Scheduler: {}", msg));
+        }
+        for name in &self.other {
+            lines.push(format!("{} differs", name));
+        }
+        if lines.is_empty() {
+            lines.push("No differences".to_string());
+        }
+        lines
+    }
+}
+
+// Every key whose value differs or only exists on one side, described by both sides.
+pub(crate) fn diff_maps<K: Ord + Clone, V: PartialEq, F: Fn(&V) -> String>(
+    map1: &BTreeMap<K, V>,
+    map2: &BTreeMap<K, V>,
+    describe: F,
+) -> Vec<(K, String)> {
+    let keys: BTreeSet<&K> = map1.keys().chain(map2.keys()).collect();
+    let mut results = Vec::new();
+    for key in keys {
+        let msg = match (map1.get(key), map2.get(key)) {
+            (Some(v1), Some(v2)) => {
+                if v1 == v2 {
+                    continue;
+                }
+                let (d1, d2) = (describe(v1), describe(v2));
+                if d1 == d2 {
+                    format!("{}, but other details differ", d1)
+                } else {
+                    format!("{} vs {}", d1, d2)
+                }
+            }
+            (Some(v1), None) => format!("{} vs missing", describe(v1)),
+            (None, Some(v2)) => format!("missing vs {}", describe(v2)),
+            (None, None) => unreachable!(),
+        };
+        results.push((key.clone(), msg));
+    }
+    results
+}
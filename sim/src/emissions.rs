@@ -0,0 +1,116 @@
+use crate::{Vehicle, VehicleType};
+use geom::{Distance, Duration};
+use serde_derive::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+// Tailpipe emissions and energy used. For electric vehicles, only energy is non-zero.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub struct Emissions {
+    // Grams
+    pub co2: f64,
+    pub nox: f64,
+    // Kilowatt-hours. For combustion engines, the energy content of the fuel burned.
+    pub energy: f64,
+}
+
+impl Emissions {
+    pub const ZERO: Emissions = Emissions {
+        co2: 0.0,
+        nox: 0.0,
+        energy: 0.0,
+    };
+
+    pub fn new(co2: f64, nox: f64, energy: f64) -> Emissions {
+        Emissions { co2, nox, energy }
+    }
+
+    pub fn scale(self, factor: f64) -> Emissions {
+        Emissions {
+            co2: self.co2 * factor,
+            nox: self.nox * factor,
+            energy: self.energy * factor,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        self == Emissions::ZERO
+    }
+}
+
+impl AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        self.co2 += other.co2;
+        self.nox += other.nox;
+        self.energy += other.energy;
+    }
+}
+
+// A vehicle running for some time produces per_hour, plus per_km for the distance it covers.
+// Crawling along in traffic takes longer, so it costs more per km than cruising.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EmissionRates {
+    pub per_hour: Emissions,
+    pub per_km: Emissions,
+}
+
+// Per vehicle class. Bikes don't produce anything. Load different numbers with
+// --emission_factors=path.json.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EmissionFactors {
+    pub car: EmissionRates,
+    pub electric_car: EmissionRates,
+    pub truck: EmissionRates,
+    pub electric_truck: EmissionRates,
+    pub bus: EmissionRates,
+    pub electric_bus: EmissionRates,
+}
+
+impl EmissionFactors {
+    // Rough averages for gasoline cars and diesel trucks and buses.
+    pub fn default_factors() -> EmissionFactors {
+        let electric = |per_hour, per_km| EmissionRates {
+            per_hour: Emissions::new(0.0, 0.0, per_hour),
+            per_km: Emissions::new(0.0, 0.0, per_km),
+        };
+        EmissionFactors {
+            car: EmissionRates {
+                per_hour: Emissions::new(1400.0, 1.0, 5.3),
+                per_km: Emissions::new(130.0, 0.05, 0.55),
+            },
+            electric_car: electric(0.5, 0.17),
+            truck: EmissionRates {
+                per_hour: Emissions::new(3000.0, 30.0, 11.0),
+                per_km: Emissions::new(600.0, 3.0, 2.3),
+            },
+            electric_truck: electric(1.5, 0.9),
+            bus: EmissionRates {
+                per_hour: Emissions::new(4000.0, 40.0, 15.0),
+                per_km: Emissions::new(1100.0, 6.0, 4.2),
+            },
+            electric_bus: electric(3.0, 1.3),
+        }
+    }
+
+    fn rates(&self, vehicle: &Vehicle) -> Option<&EmissionRates> {
+        match (vehicle.vehicle_type, vehicle.electric) {
+            (VehicleType::Car, false) => Some(&self.car),
+            (VehicleType::Car, true) => Some(&self.electric_car),
+            (VehicleType::Truck, false) => Some(&self.truck),
+            (VehicleType::Truck, true) => Some(&self.electric_truck),
+            (VehicleType::Bus, false) => Some(&self.bus),
+            (VehicleType::Bus, true) => Some(&self.electric_bus),
+            (VehicleType::Bike, _) => None,
+        }
+    }
+
+    // For a vehicle running for dt and covering dist in that time. Idling is just a zero dist.
+    pub fn calculate(&self, vehicle: &Vehicle, dist: Distance, dt: Duration) -> Emissions {
+        if let Some(rates) = self.rates(vehicle) {
+            let mut total = rates.per_hour.scale(dt.inner_seconds() / 3600.0);
+            total += rates.per_km.scale(dist.inner_meters() / 1000.0);
+            total
+        } else {
+            Emissions::ZERO
+        }
+    }
+}
@@ -1,7 +1,8 @@
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, TripID, TripMode};
-use geom::Duration;
+use crate::{AgentID, CarID, Emissions, ParkingSpot, PedestrianID, TripID, TripMode};
+use geom::{Distance, Duration};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Path, PathRequest, Traversable,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Path, PathRequest, RoadID,
+    Traversable,
 };
 use serde_derive::{Deserialize, Serialize};
 
@@ -19,11 +20,34 @@ pub enum Event {
     PedReachedBusStop(PedestrianID, BusStopID),
     PedEntersBus(PedestrianID, CarID, BusRouteID),
     PedLeavesBus(PedestrianID, CarID, BusRouteID),
+    // Jaywalked across this road instead of walking to the corner
+    PedCrossedMidBlock(PedestrianID, RoadID),
 
     BikeStoppedAtSidewalk(CarID, LaneID),
+    // Since the vehicle's last state change
+    EmissionsProduced(CarID, TripID, Traversable, Emissions),
+    // From one lane to an adjacent one, in the middle of the road
+    CarChangedLanes(CarID, LaneID, LaneID),
+    // Stopped for somebody crossing mid-block
+    CarYieldedToPed(CarID, PedestrianID),
+
+    DeliveryMade(CarID, BuildingID),
+    // No loading zone was free, so the truck blocked this lane
+    TruckDoubleParked(CarID, LaneID),
+
+    // How long the passenger waited since requesting the ride
+    RideHailPickup(CarID, PedestrianID, Duration),
+    RideHailDropoff(CarID, PedestrianID),
+    // Distance a ride-hail vehicle just drove, and whether it had a passenger. If not, it was
+    // deadheading.
+    RideHailVehicleMoved(CarID, Distance, bool),
 
     AgentEntersTraversable(AgentID, Traversable),
     IntersectionDelayMeasured(IntersectionID, Duration),
+    // Somebody did something risky while starting a turn
+    NearMiss(IntersectionID, AgentID, NearMiss),
+    // Pedestrians per square meter, right after somebody enters
+    PedDensityMeasured(Traversable, f64),
 
     TripFinished(TripID, TripMode, Duration),
     TripAborted(TripID),
@@ -33,3 +57,15 @@ pub enum Event {
     // to plumb info into Analytics is Event.
     PathAmended(Path),
 }
+
+// Surrogate safety measures. Nobody actually crashes in the simulation, but these are the
+// situations where real collisions tend to happen.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum NearMiss {
+    // A vehicle started a yield turn only this long after a conflicting pedestrian cleared it
+    YieldedLateToPedestrian(Duration),
+    // A vehicle started a yield turn only this long after a conflicting vehicle cleared it
+    ShortGapAccepted(Duration),
+    // A bike turned into a general-purpose lane with a high speed limit
+    BikeInFastTraffic,
+}
@@ -1,8 +1,12 @@
 mod analytics;
+mod diff;
+mod emissions;
 mod events;
 mod make;
 mod mechanics;
+mod monte_carlo;
 mod render;
+mod ridehail;
 mod router;
 mod scheduler;
 mod sim;
@@ -10,14 +14,20 @@ mod transit;
 mod trips;
 
 pub use self::analytics::{Analytics, TripPhase};
-pub use self::events::Event;
+pub(crate) use self::diff::diff_maps;
+pub use self::diff::SimDiff;
+pub use self::emissions::{EmissionFactors, EmissionRates, Emissions};
+pub use self::events::{Event, NearMiss};
 pub use self::make::{
-    ABTest, BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SimFlags,
+    ABTest, Blockage, BorderSpawnOverTime, ExternalScenario, ExternalTrip, Incident,
+    OriginDestination, PermanentScenario, SavedScenario, Scenario, SeedParkedCars, SimFlags,
     SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
 };
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
+pub use self::monte_carlo::{Estimate, MonteCarloSummary, RunSummary};
+pub(crate) use self::ridehail::{RideHailRequest, RideHailSimState};
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
 pub use self::sim::{Sim, SimOptions};
@@ -46,6 +56,11 @@ pub const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
+// Box trucks and delivery vans. These two must be < LOADING_SPOT_LENGTH
+pub const MIN_TRUCK_LENGTH: Distance = Distance::const_meters(7.0);
+pub const MAX_TRUCK_LENGTH: Distance = Distance::const_meters(10.0);
+// What fraction of cars are electric
+pub const EV_SHARE: f64 = 0.05;
 
 // At all speeds (including at rest), cars must be at least this far apart, measured from front of
 // one car to the back of the other.
@@ -67,6 +82,7 @@ impl fmt::Display for CarID {
                 VehicleType::Car => "car",
                 VehicleType::Bus => "bus",
                 VehicleType::Bike => "bike",
+                VehicleType::Truck => "truck",
             }
         )
     }
@@ -119,6 +135,7 @@ pub enum VehicleType {
     Car,
     Bus,
     Bike,
+    Truck,
 }
 
 impl VehicleType {
@@ -127,6 +144,18 @@ impl VehicleType {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Truck => PathConstraints::Truck,
+        }
+    }
+
+    // Typical (max acceleration, max braking) in m/s^2. Only used when SimOptions::kinematics is
+    // enabled.
+    pub fn accel_limits(self) -> (f64, f64) {
+        match self {
+            VehicleType::Car => (3.0, 4.5),
+            VehicleType::Bus => (1.2, 3.0),
+            VehicleType::Bike => (1.0, 2.5),
+            VehicleType::Truck => (1.5, 3.0),
         }
     }
 }
@@ -138,6 +167,11 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // In m/s^2
+    pub max_accel: f64,
+    pub max_decel: f64,
+    // Battery-electric, instead of a combustion engine
+    pub electric: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -145,6 +179,11 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // In m/s^2
+    pub max_accel: f64,
+    pub max_decel: f64,
+    // Battery-electric, instead of a combustion engine
+    pub electric: bool,
 }
 
 impl VehicleSpec {
@@ -155,6 +194,9 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            max_accel: self.max_accel,
+            max_decel: self.max_decel,
+            electric: self.electric,
         }
     }
 }
@@ -209,7 +251,7 @@ impl DrivingGoal {
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Position {
         match self {
             DrivingGoal::ParkNear(b) => match constraints {
-                PathConstraints::Car => {
+                PathConstraints::Car | PathConstraints::Truck => {
                     Position::new(map.find_driving_lane_near_building(*b), Distance::ZERO)
                 }
                 PathConstraints::Bike => {
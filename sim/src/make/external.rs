@@ -0,0 +1,547 @@
+use crate::{
+    DrivingGoal, Scenario, SidewalkPOI, SidewalkSpot, SpawnTrip, TripSpec, MAX_CAR_LENGTH,
+    MAX_TRUCK_LENGTH,
+};
+use abstutil::{Error, Timer};
+use geom::{Distance, Duration, FindClosest, LonLat, Pt2D, Time};
+use map_model::{BuildingID, IntersectionID, Map, PathConstraints, Position};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+// A Scenario that people can read and edit. Buildings and borders are referred to by OSM IDs
+// instead of BuildingID and IntersectionID, so this survives rebuilding the map. The higher-level
+// ways of specifying a scenario (SpawnOverTime and friends) and incidents aren't included.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExternalScenario {
+    pub scenario_name: String,
+    pub map_name: String,
+    pub seed_buses: bool,
+    pub trips: Vec<ExternalTrip>,
+    // OSM way ID of the building, number of cars to park there
+    pub parked_cars: BTreeMap<i64, usize>,
+    #[serde(default)]
+    pub ride_hail_fleet: usize,
+}
+
+// One row per SpawnTrip. Each endpoint is exactly one of a building's OSM way ID, a border's OSM
+// node ID, or a lon/lat point (which snaps to the closest building).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ExternalTrip {
+    // Like 07:30:00.0
+    pub departure: String,
+    // walk, bike, drive, transit, delivery, or ride-hail
+    pub mode: String,
+    pub origin_osm_building: Option<i64>,
+    pub origin_osm_node: Option<i64>,
+    pub origin_lon: Option<f64>,
+    pub origin_lat: Option<f64>,
+    pub destination_osm_building: Option<i64>,
+    pub destination_osm_node: Option<i64>,
+    pub destination_lon: Option<f64>,
+    pub destination_lat: Option<f64>,
+    // Only for deliveries. Each stop is a building's OSM way ID and the dwell time in seconds,
+    // like 123:300;456:60
+    pub delivery_stops: Option<String>,
+}
+
+enum Endpoint {
+    Building(BuildingID),
+    Border(IntersectionID),
+}
+
+// Snapping a lon/lat point to a building only looks this far.
+const MAX_SNAP_DIST: Distance = Distance::const_meters(100.0);
+
+impl Scenario {
+    pub fn to_external(&self, map: &Map, timer: &mut Timer) -> ExternalScenario {
+        if !self.seed_parked_cars.is_empty()
+            || !self.spawn_over_time.is_empty()
+            || !self.border_spawn_over_time.is_empty()
+        {
+            timer.warn(format!(
+                "{} has SeedParkedCars, SpawnOverTime, or BorderSpawnOverTime; only individual \
+                 trips and parked cars are exported",
+                self.scenario_name
+            ));
+        }
+        ExternalScenario {
+            scenario_name: self.scenario_name.clone(),
+            map_name: self.map_name.clone(),
+            seed_buses: self.seed_buses,
+            trips: self
+                .individ_trips
+                .iter()
+                .map(|trip| ExternalTrip::new(trip, map))
+                .collect(),
+            parked_cars: self
+                .individ_parked_cars
+                .iter()
+                .filter(|(_, cnt)| **cnt > 0)
+                .map(|(b, cnt)| (map.get_b(*b).osm_way_id, *cnt))
+                .collect(),
+            ride_hail_fleet: self.ride_hail_fleet,
+        }
+    }
+
+    pub fn export_json(&self, path: String, map: &Map, timer: &mut Timer) {
+        abstutil::write_json(path, &self.to_external(map, timer));
+    }
+
+    // Only the individual trips; the CSV has no place for parked cars.
+    pub fn export_csv(&self, path: &str, map: &Map, timer: &mut Timer) -> Result<(), Error> {
+        let mut writer = csv::Writer::from_path(path).map_err(csv_error)?;
+        for trip in self.to_external(map, timer).trips {
+            writer.serialize(trip).map_err(csv_error)?;
+        }
+        writer.flush().map_err(|err| Error::new(err.to_string()))?;
+        Ok(())
+    }
+
+    pub fn import_json(path: String, map: &Map, timer: &mut Timer) -> Result<Scenario, Error> {
+        let external: ExternalScenario = abstutil::maybe_read_json(path, timer)?;
+        Ok(external.import(map, timer))
+    }
+
+    pub fn import_csv(
+        path: &str,
+        scenario_name: &str,
+        map: &Map,
+        timer: &mut Timer,
+    ) -> Result<Scenario, Error> {
+        let mut trips = Vec::new();
+        for rec in csv::Reader::from_path(path)
+            .map_err(csv_error)?
+            .deserialize()
+        {
+            trips.push(rec.map_err(csv_error)?);
+        }
+        let external = ExternalScenario {
+            scenario_name: scenario_name.to_string(),
+            map_name: map.get_name().to_string(),
+            seed_buses: true,
+            trips,
+            parked_cars: BTreeMap::new(),
+            ride_hail_fleet: 0,
+        };
+        Ok(external.import(map, timer))
+    }
+}
+
+impl ExternalScenario {
+    // Trips that can't be matched to this map are skipped with a warning.
+    pub fn import(self, map: &Map, timer: &mut Timer) -> Scenario {
+        let lookup = Lookup::new(map);
+
+        let mut individ_trips = Vec::new();
+        timer.start_iter("import trips", self.trips.len());
+        for (idx, trip) in self.trips.into_iter().enumerate() {
+            timer.next();
+            match trip.import(&lookup, map) {
+                Ok(spawn) => {
+                    individ_trips.push(spawn);
+                }
+                Err(err) => {
+                    timer.warn(format!("Skipping trip {}: {}", idx + 1, err));
+                }
+            }
+        }
+
+        let mut individ_parked_cars = BTreeMap::new();
+        for (osm_id, cnt) in self.parked_cars {
+            if let Some(b) = lookup.buildings.get(&osm_id) {
+                individ_parked_cars.insert(*b, cnt);
+            } else {
+                timer.warn(format!(
+                    "No building with OSM ID {} for parked cars",
+                    osm_id
+                ));
+            }
+        }
+
+        Scenario {
+            scenario_name: self.scenario_name,
+            map_name: self.map_name,
+            seed_buses: self.seed_buses,
+            seed_parked_cars: Vec::new(),
+            spawn_over_time: Vec::new(),
+            border_spawn_over_time: Vec::new(),
+            individ_trips,
+            individ_parked_cars,
+            ride_hail_fleet: self.ride_hail_fleet,
+            incidents: Vec::new(),
+        }
+    }
+}
+
+impl ExternalTrip {
+    fn new(trip: &SpawnTrip, map: &Map) -> ExternalTrip {
+        let mut delivery_stops = None;
+        let (depart, mode, origin, destination) = match trip {
+            SpawnTrip::CarAppearing {
+                depart,
+                start,
+                goal,
+                is_bike,
+            } => {
+                let mode = if *is_bike { "bike" } else { "drive" };
+                (
+                    *depart,
+                    mode,
+                    ExternalEndpoint::vehicle_start(start, MAX_CAR_LENGTH, map),
+                    ExternalEndpoint::driving_goal(goal, map),
+                )
+            }
+            SpawnTrip::MaybeUsingParkedCar(depart, b, goal) => (
+                *depart,
+                "drive",
+                ExternalEndpoint::Building(map.get_b(*b).osm_way_id),
+                ExternalEndpoint::driving_goal(goal, map),
+            ),
+            SpawnTrip::UsingBike(depart, start, goal) => (
+                *depart,
+                "bike",
+                ExternalEndpoint::sidewalk_spot(start, map),
+                ExternalEndpoint::driving_goal(goal, map),
+            ),
+            SpawnTrip::JustWalking(depart, start, goal) => (
+                *depart,
+                "walk",
+                ExternalEndpoint::sidewalk_spot(start, map),
+                ExternalEndpoint::sidewalk_spot(goal, map),
+            ),
+            SpawnTrip::UsingTransit(depart, start, goal, _, _, _) => (
+                *depart,
+                "transit",
+                ExternalEndpoint::sidewalk_spot(start, map),
+                ExternalEndpoint::sidewalk_spot(goal, map),
+            ),
+            SpawnTrip::UsingRideHail(depart, b, goal) => (
+                *depart,
+                "ride-hail",
+                ExternalEndpoint::Building(map.get_b(*b).osm_way_id),
+                ExternalEndpoint::sidewalk_spot(goal, map),
+            ),
+            SpawnTrip::DeliveryTour {
+                depart,
+                start,
+                stops,
+                goal,
+            } => {
+                delivery_stops = Some(
+                    stops
+                        .iter()
+                        .map(|(b, dwell)| {
+                            format!("{}:{}", map.get_b(*b).osm_way_id, dwell.inner_seconds())
+                        })
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                );
+                (
+                    *depart,
+                    "delivery",
+                    ExternalEndpoint::vehicle_start(start, MAX_TRUCK_LENGTH, map),
+                    ExternalEndpoint::driving_goal(goal, map),
+                )
+            }
+        };
+
+        let mut trip = ExternalTrip {
+            departure: depart.to_string(),
+            mode: mode.to_string(),
+            delivery_stops,
+            ..Default::default()
+        };
+        match origin {
+            ExternalEndpoint::Building(id) => {
+                trip.origin_osm_building = Some(id);
+            }
+            ExternalEndpoint::Border(id) => {
+                trip.origin_osm_node = Some(id);
+            }
+            ExternalEndpoint::LonLat(pt) => {
+                trip.origin_lon = Some(pt.longitude);
+                trip.origin_lat = Some(pt.latitude);
+            }
+        }
+        match destination {
+            ExternalEndpoint::Building(id) => {
+                trip.destination_osm_building = Some(id);
+            }
+            ExternalEndpoint::Border(id) => {
+                trip.destination_osm_node = Some(id);
+            }
+            ExternalEndpoint::LonLat(pt) => {
+                trip.destination_lon = Some(pt.longitude);
+                trip.destination_lat = Some(pt.latitude);
+            }
+        }
+        trip
+    }
+
+    fn import(self, lookup: &Lookup, map: &Map) -> Result<SpawnTrip, Error> {
+        let depart = Time::parse(&self.departure)?;
+        let from = lookup.endpoint(
+            self.origin_osm_building,
+            self.origin_osm_node,
+            self.origin_lon
+                .and_then(|lon| Some(LonLat::new(lon, self.origin_lat?))),
+            map,
+        )?;
+        let to = lookup.endpoint(
+            self.destination_osm_building,
+            self.destination_osm_node,
+            self.destination_lon
+                .and_then(|lon| Some(LonLat::new(lon, self.destination_lat?))),
+            map,
+        )?;
+        if let (Endpoint::Border(_), Endpoint::Border(_)) = (&from, &to) {
+            return Err(Error::new(
+                "trips between two borders aren't supported".to_string(),
+            ));
+        }
+
+        match self.mode.as_str() {
+            "drive" | "bike" => {
+                let constraints = if self.mode == "drive" {
+                    PathConstraints::Car
+                } else {
+                    PathConstraints::Bike
+                };
+                let goal = match to {
+                    Endpoint::Building(b) => DrivingGoal::ParkNear(b),
+                    Endpoint::Border(i) => DrivingGoal::end_at_border(
+                        map.get_i(i).some_incoming_road(map),
+                        constraints,
+                        map,
+                    )
+                    .ok_or_else(|| {
+                        Error::new(format!("can't end a {} trip at {}", self.mode, i))
+                    })?,
+                };
+                match from {
+                    Endpoint::Building(b) => Ok(if constraints == PathConstraints::Car {
+                        SpawnTrip::MaybeUsingParkedCar(depart, b, goal)
+                    } else {
+                        SpawnTrip::UsingBike(depart, SidewalkSpot::building(b, map), goal)
+                    }),
+                    Endpoint::Border(i) => {
+                        let lanes = map.get_i(i).get_outgoing_lanes(map, constraints);
+                        let start = lanes
+                            .get(0)
+                            .and_then(|l| {
+                                TripSpec::spawn_car_at(Position::new(*l, Distance::ZERO), map)
+                            })
+                            .ok_or_else(|| {
+                                Error::new(format!("can't start a {} trip at {}", self.mode, i))
+                            })?;
+                        Ok(SpawnTrip::CarAppearing {
+                            depart,
+                            start,
+                            goal,
+                            is_bike: constraints == PathConstraints::Bike,
+                        })
+                    }
+                }
+            }
+            "walk" | "transit" => {
+                let start = match from {
+                    Endpoint::Building(b) => SidewalkSpot::building(b, map),
+                    Endpoint::Border(i) => SidewalkSpot::start_at_border(i, map)
+                        .ok_or_else(|| Error::new(format!("can't start walking at {}", i)))?,
+                };
+                let goal = match to {
+                    Endpoint::Building(b) => SidewalkSpot::building(b, map),
+                    Endpoint::Border(i) => SidewalkSpot::end_at_border(i, map)
+                        .ok_or_else(|| Error::new(format!("can't end walking at {}", i)))?,
+                };
+                if self.mode == "transit" {
+                    // Pick the route again; bus routes and stops aren't stable either.
+                    if let Some((stop1, stop2, route)) =
+                        map.should_use_transit(start.sidewalk_pos, goal.sidewalk_pos)
+                    {
+                        return Ok(SpawnTrip::UsingTransit(
+                            depart, start, goal, route, stop1, stop2,
+                        ));
+                    }
+                }
+                Ok(SpawnTrip::JustWalking(depart, start, goal))
+            }
+            "ride-hail" => {
+                let start = match from {
+                    Endpoint::Building(b) => b,
+                    Endpoint::Border(i) => {
+                        return Err(Error::new(format!(
+                            "can't request a ride-hail vehicle at {}",
+                            i
+                        )));
+                    }
+                };
+                let goal = match to {
+                    Endpoint::Building(b) => SidewalkSpot::building(b, map),
+                    Endpoint::Border(i) => SidewalkSpot::end_at_border(i, map)
+                        .ok_or_else(|| Error::new(format!("can't end walking at {}", i)))?,
+                };
+                Ok(SpawnTrip::UsingRideHail(depart, start, goal))
+            }
+            "delivery" => {
+                let stops = parse_delivery_stops(
+                    self.delivery_stops
+                        .as_ref()
+                        .map(|s| s.as_str())
+                        .unwrap_or(""),
+                    lookup,
+                )?;
+                let goal = match to {
+                    Endpoint::Building(b) => DrivingGoal::ParkNear(b),
+                    Endpoint::Border(i) => DrivingGoal::end_at_border(
+                        map.get_i(i).some_incoming_road(map),
+                        PathConstraints::Truck,
+                        map,
+                    )
+                    .ok_or_else(|| Error::new(format!("can't end a delivery at {}", i)))?,
+                };
+                let start = match from {
+                    Endpoint::Building(b) => Position::bldg_via_driving(b, map)
+                        .and_then(|pos| TripSpec::spawn_truck_at(pos, map)),
+                    Endpoint::Border(i) => map
+                        .get_i(i)
+                        .get_outgoing_lanes(map, PathConstraints::Truck)
+                        .get(0)
+                        .and_then(|l| {
+                            TripSpec::spawn_truck_at(Position::new(*l, Distance::ZERO), map)
+                        }),
+                }
+                .ok_or_else(|| Error::new("can't find a place to start the truck".to_string()))?;
+                Ok(SpawnTrip::DeliveryTour {
+                    depart,
+                    start,
+                    stops,
+                    goal,
+                })
+            }
+            x => Err(Error::new(format!("unknown mode {}", x))),
+        }
+    }
+}
+
+enum ExternalEndpoint {
+    Building(i64),
+    Border(i64),
+    LonLat(LonLat),
+}
+
+impl ExternalEndpoint {
+    fn pt(pt: Pt2D, map: &Map) -> ExternalEndpoint {
+        ExternalEndpoint::LonLat(pt.forcibly_to_gps(map.get_gps_bounds()))
+    }
+
+    fn vehicle_start(start: &Position, max_length: Distance, map: &Map) -> ExternalEndpoint {
+        // Vehicles starting at a border get nudged forward a bit when they're spawned
+        let i = map.get_l(start.lane()).src_i;
+        if start.dist_along() <= max_length && map.get_i(i).is_border() {
+            ExternalEndpoint::Border(map.get_i(i).orig_id.osm_node_id)
+        } else {
+            ExternalEndpoint::pt(start.pt(map), map)
+        }
+    }
+
+    fn driving_goal(goal: &DrivingGoal, map: &Map) -> ExternalEndpoint {
+        match goal {
+            DrivingGoal::ParkNear(b) => ExternalEndpoint::Building(map.get_b(*b).osm_way_id),
+            DrivingGoal::Border(i, _) => {
+                ExternalEndpoint::Border(map.get_i(*i).orig_id.osm_node_id)
+            }
+        }
+    }
+
+    fn sidewalk_spot(spot: &SidewalkSpot, map: &Map) -> ExternalEndpoint {
+        match spot.connection {
+            SidewalkPOI::Building(b) => ExternalEndpoint::Building(map.get_b(b).osm_way_id),
+            SidewalkPOI::Border(i) => ExternalEndpoint::Border(map.get_i(i).orig_id.osm_node_id),
+            _ => ExternalEndpoint::pt(spot.sidewalk_pos.pt(map), map),
+        }
+    }
+}
+
+struct Lookup {
+    buildings: HashMap<i64, BuildingID>,
+    borders: HashMap<i64, IntersectionID>,
+    closest_bldg: FindClosest<BuildingID>,
+}
+
+impl Lookup {
+    fn new(map: &Map) -> Lookup {
+        let mut closest_bldg = FindClosest::new(map.get_bounds());
+        for b in map.all_buildings() {
+            closest_bldg.add(b.id, b.polygon.points());
+        }
+        Lookup {
+            buildings: map
+                .all_buildings()
+                .iter()
+                .map(|b| (b.osm_way_id, b.id))
+                .collect(),
+            borders: map
+                .all_intersections()
+                .iter()
+                .filter(|i| i.is_border())
+                .map(|i| (i.orig_id.osm_node_id, i.id))
+                .collect(),
+            closest_bldg,
+        }
+    }
+
+    fn endpoint(
+        &self,
+        osm_building: Option<i64>,
+        osm_node: Option<i64>,
+        pt: Option<LonLat>,
+        map: &Map,
+    ) -> Result<Endpoint, Error> {
+        match (osm_building, osm_node, pt) {
+            (Some(id), None, None) => self
+                .buildings
+                .get(&id)
+                .map(|b| Endpoint::Building(*b))
+                .ok_or_else(|| Error::new(format!("no building with OSM ID {}", id))),
+            (None, Some(id), None) => self
+                .borders
+                .get(&id)
+                .map(|i| Endpoint::Border(*i))
+                .ok_or_else(|| Error::new(format!("no border with OSM node ID {}", id))),
+            (None, None, Some(pt)) => Pt2D::from_gps(pt, map.get_gps_bounds())
+                .and_then(|pt| self.closest_bldg.closest_pt(pt, MAX_SNAP_DIST))
+                .map(|(b, _)| Endpoint::Building(b))
+                .ok_or_else(|| Error::new(format!("no building near {}", pt))),
+            _ => Err(Error::new(
+                "each endpoint needs exactly one of a building, border, or lon/lat".to_string(),
+            )),
+        }
+    }
+}
+
+fn parse_delivery_stops(raw: &str, lookup: &Lookup) -> Result<Vec<(BuildingID, Duration)>, Error> {
+    let mut stops = Vec::new();
+    for stop in raw.split(';').filter(|s| !s.is_empty()) {
+        let parts: Vec<&str> = stop.split(':').collect();
+        let bad_stop = || Error::new(format!("bad delivery stop {}", stop));
+        if parts.len() != 2 {
+            return Err(bad_stop());
+        }
+        let osm_id = parts[0].parse::<i64>().map_err(|_| bad_stop())?;
+        let secs = parts[1].parse::<f64>().map_err(|_| bad_stop())?;
+        let b = lookup
+            .buildings
+            .get(&osm_id)
+            .ok_or_else(|| Error::new(format!("no building with OSM ID {}", osm_id)))?;
+        stops.push((*b, Duration::seconds(secs)));
+    }
+    if stops.is_empty() {
+        return Err(Error::new("a delivery needs at least one stop".to_string()));
+    }
+    Ok(stops)
+}
+
+fn csv_error(err: csv::Error) -> Error {
+    Error::new(err.to_string())
+}
@@ -1,7 +1,7 @@
-use crate::{Scenario, Sim, SimOptions};
-use abstutil::CmdArgs;
+use crate::{EmissionFactors, ExternalScenario, PermanentScenario, Sim, SimOptions};
+use abstutil::{CmdArgs, Timer};
 use geom::Duration;
-use map_model::{Map, MapEdits};
+use map_model::{LevelOfTrafficStress, Map, MapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
 
@@ -10,6 +10,8 @@ pub struct SimFlags {
     pub load: String,
     pub use_map_fixes: bool,
     pub rng_seed: Option<u8>,
+    // Bike routes avoid lanes more stressful than this. If unset, the map's default is used.
+    pub bike_lts_tolerance: Option<LevelOfTrafficStress>,
     pub opts: SimOptions,
 }
 
@@ -21,6 +23,8 @@ impl SimFlags {
                 .unwrap_or_else(|| "../data/system/maps/montlake.bin".to_string()),
             use_map_fixes: !args.enabled("--nofixes"),
             rng_seed: args.optional_parse("--rng_seed", |s| s.parse()),
+            bike_lts_tolerance: args
+                .optional_parse("--bike_lts_tolerance", LevelOfTrafficStress::parse),
             opts: SimOptions {
                 run_name: args
                     .optional("--run_name")
@@ -29,6 +33,15 @@ impl SimFlags {
                 use_freeform_policy_everywhere: args.enabled("--freeform_policy"),
                 disable_block_the_box: args.enabled("--disable_block_the_box"),
                 recalc_lanechanging: !args.enabled("--dont_recalc_lc"),
+                kinematics: args.enabled("--kinematics"),
+                emission_factors: args
+                    .optional("--emission_factors")
+                    .map(|path| abstutil::read_json(path, &mut Timer::throwaway()))
+                    .unwrap_or_else(EmissionFactors::default_factors),
+                jaywalking_probability: args
+                    .optional_parse("--jaywalking", |s| s.parse())
+                    .unwrap_or(0.0),
+                ped_crowding: args.enabled("--ped_crowding"),
             },
         }
     }
@@ -43,6 +56,7 @@ impl SimFlags {
             load: abstutil::path_map(map),
             use_map_fixes: true,
             rng_seed: Some(42),
+            bike_lts_tolerance: None,
             opts: SimOptions::new(run_name),
         }
     }
@@ -55,6 +69,16 @@ impl SimFlags {
         }
     }
 
+    // For choices the sim makes while running. This is seeded separately, so the rng returned by
+    // load() and used to instantiate scenarios stays the same as before.
+    pub fn make_sim_rng(&self) -> XorShiftRng {
+        if let Some(seed) = self.rng_seed {
+            XorShiftRng::from_seed([seed ^ 0xA5; 16])
+        } else {
+            XorShiftRng::from_entropy()
+        }
+    }
+
     // Convenience method to setup everything.
     pub fn load(&self, timer: &mut abstutil::Timer) -> (Map, Sim, XorShiftRng) {
         let mut rng = self.make_rng();
@@ -66,12 +90,10 @@ impl SimFlags {
 
             let mut sim: Sim = abstutil::read_binary(self.load.clone(), timer);
 
-            let mut map = Map::new(abstutil::path_map(&sim.map_name), false, timer);
+            let mut map = self.load_map(abstutil::path_map(&sim.map_name), false, timer);
             if sim.edits_name != "no_edits" {
-                map.apply_edits(
-                    MapEdits::load(map.get_name(), &sim.edits_name, timer),
-                    timer,
-                );
+                let edits = MapEdits::load(&mut map, &sim.edits_name, timer);
+                map.apply_edits(edits, timer);
                 map.mark_edits_fresh();
                 map.recalculate_pathfinding_after_edits(timer);
             }
@@ -84,14 +106,22 @@ impl SimFlags {
                 self.load
             ));
 
-            let scenario: Scenario = abstutil::read_binary(self.load.clone(), timer);
-
-            let map = Map::new(abstutil::path_map(&scenario.map_name), false, timer);
+            // Human-edited scenarios refer to the map by name, so load that first.
+            let (scenario, map) = if self.load.ends_with(".json") {
+                let external: ExternalScenario = abstutil::read_json(self.load.clone(), timer);
+                let map = self.load_map(abstutil::path_map(&external.map_name), false, timer);
+                (external.import(&map, timer), map)
+            } else {
+                let saved = PermanentScenario::read(self.load.clone(), timer);
+                let map = self.load_map(abstutil::path_map(saved.map_name()), false, timer);
+                (saved.into_scenario(&map, timer), map)
+            };
 
             if opts.run_name == "unnamed" {
                 opts.run_name = scenario.scenario_name.clone();
             }
             let mut sim = Sim::new(&map, opts, timer);
+            sim.set_rng(self.make_sim_rng());
             scenario.instantiate(&mut sim, &map, &mut rng, timer);
 
             (map, sim, rng)
@@ -100,20 +130,22 @@ impl SimFlags {
         {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(self.load.clone(), self.use_map_fixes, timer);
+            let map = self.load_map(self.load.clone(), self.use_map_fixes, timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, opts, timer);
+            let mut sim = Sim::new(&map, opts, timer);
+            sim.set_rng(self.make_sim_rng());
             timer.stop("create sim");
 
             (map, sim, rng)
         } else if self.load.starts_with(&abstutil::path_all_maps()) {
             timer.note(format!("Loading map {}", self.load));
 
-            let map = Map::new(self.load.clone(), false, timer);
+            let map = self.load_map(self.load.clone(), false, timer);
 
             timer.start("create sim");
-            let sim = Sim::new(&map, opts, timer);
+            let mut sim = Sim::new(&map, opts, timer);
+            sim.set_rng(self.make_sim_rng());
             timer.stop("create sim");
 
             (map, sim, rng)
@@ -121,4 +153,13 @@ impl SimFlags {
             panic!("Don't know how to load {}", self.load);
         }
     }
+
+    fn load_map(&self, path: String, use_map_fixes: bool, timer: &mut abstutil::Timer) -> Map {
+        let mut map = Map::new(path, use_map_fixes, timer);
+        if let Some(tolerance) = self.bike_lts_tolerance {
+            map.set_bike_lts_tolerance(tolerance);
+            map.recalculate_pathfinding_after_edits(timer);
+        }
+        map
+    }
 }
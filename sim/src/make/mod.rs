@@ -1,11 +1,17 @@
 mod a_b_test;
+mod external;
 mod load;
+mod permanent;
 mod scenario;
 mod spawner;
+mod transform;
 
 pub use self::a_b_test::ABTest;
+pub use self::external::{ExternalScenario, ExternalTrip};
 pub use self::load::SimFlags;
+pub use self::permanent::{PermanentScenario, SavedScenario};
 pub use self::scenario::{
-    BorderSpawnOverTime, OriginDestination, Scenario, SeedParkedCars, SpawnOverTime, SpawnTrip,
+    Blockage, BorderSpawnOverTime, Incident, OriginDestination, Scenario, SeedParkedCars,
+    SpawnOverTime, SpawnTrip,
 };
 pub use self::spawner::{TripSpawner, TripSpec};
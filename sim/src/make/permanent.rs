@@ -0,0 +1,282 @@
+use crate::{
+    Blockage, DrivingGoal, OriginDestination, ParkingSpot, Scenario, SidewalkPOI, SidewalkSpot,
+    SpawnTrip,
+};
+use abstutil::{prettyprint_usize, Error, Timer};
+use map_model::{IDLookup, IDMapper, Map, PermanentIDs};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A Scenario as saved to disk, along with the stable versions of every ID it refers to, so it
+// survives rebuilding the map.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PermanentScenario {
+    pub scenario: Scenario,
+    pub ids: PermanentIDs,
+}
+
+impl PermanentScenario {
+    pub fn new(scenario: &Scenario, map: &Map) -> PermanentScenario {
+        let mut scenario = scenario.clone();
+        let mut mapper = IDMapper::recorder(map);
+        // Recording can't fail
+        scenario.remap_ids(&mut mapper, &mut Vec::new());
+        PermanentScenario {
+            scenario,
+            ids: mapper.into_ids(),
+        }
+    }
+
+    // Scenarios saved before stable IDs existed are a plain Scenario. Those can't be migrated, so
+    // their IDs are trusted as-is.
+    pub fn read(path: String, timer: &mut Timer) -> SavedScenario {
+        // Read the file once, then try both formats.
+        timer.start(format!("read {}", path));
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => panic!("Couldn't read {}: {}", path, err),
+        };
+        timer.stop(format!("read {}", path));
+
+        match bincode::deserialize::<PermanentScenario>(&bytes) {
+            Ok(perma) => SavedScenario::Permanent(perma),
+            Err(err) => {
+                timer.warn(format!(
+                    "{} has no stable IDs ({}), so loading it as an old scenario. Its IDs won't \
+                     be checked against the map.",
+                    path, err
+                ));
+                match bincode::deserialize::<Scenario>(&bytes) {
+                    Ok(scenario) => SavedScenario::Old(scenario),
+                    Err(err) => panic!("Couldn't read {} as a scenario: {}", path, err),
+                }
+            }
+        }
+    }
+
+    // Anything referring to something that isn't in this map anymore is dropped with a warning.
+    pub fn migrate(self, map: &Map, timer: &mut Timer) -> Scenario {
+        let (scenario, problems) = self.try_migrate(map);
+        if !problems.is_empty() {
+            timer.warn(format!(
+                "{} parts of {} couldn't be matched to this map",
+                prettyprint_usize(problems.len()),
+                scenario.scenario_name
+            ));
+            for problem in problems {
+                timer.warn(problem);
+            }
+        }
+        scenario
+    }
+
+    // Also returns a description of everything that was dropped.
+    pub fn try_migrate(self, map: &Map) -> (Scenario, Vec<String>) {
+        let lookup = IDLookup::new(map);
+        let mut mapper = IDMapper::migrator(map, &lookup, self.ids);
+        let mut scenario = self.scenario;
+        let mut problems = Vec::new();
+        scenario.remap_ids(&mut mapper, &mut problems);
+        (scenario, problems)
+    }
+}
+
+pub enum SavedScenario {
+    Permanent(PermanentScenario),
+    Old(Scenario),
+}
+
+impl SavedScenario {
+    pub fn map_name(&self) -> &str {
+        match self {
+            SavedScenario::Permanent(perma) => &perma.scenario.map_name,
+            SavedScenario::Old(scenario) => &scenario.map_name,
+        }
+    }
+
+    pub fn into_scenario(self, map: &Map, timer: &mut Timer) -> Scenario {
+        let mut scenario = match self {
+            SavedScenario::Permanent(perma) => perma.migrate(map, timer),
+            SavedScenario::Old(scenario) => scenario,
+        };
+        scenario.remove_invalid_incidents(timer);
+        scenario
+    }
+}
+
+impl Scenario {
+    fn remap_ids(&mut self, mapper: &mut IDMapper, problems: &mut Vec<String>) {
+        self.spawn_over_time = retain_remapped(
+            std::mem::replace(&mut self.spawn_over_time, Vec::new()),
+            "SpawnOverTime",
+            problems,
+            |s| s.goal.remap_ids(mapper),
+        );
+        self.border_spawn_over_time = retain_remapped(
+            std::mem::replace(&mut self.border_spawn_over_time, Vec::new()),
+            "BorderSpawnOverTime",
+            problems,
+            |s| {
+                mapper.directed_road(&mut s.start_from_border)?;
+                s.goal.remap_ids(mapper)
+            },
+        );
+        self.individ_trips = retain_remapped(
+            std::mem::replace(&mut self.individ_trips, Vec::new()),
+            "trip",
+            problems,
+            |trip| trip.remap_ids(mapper),
+        );
+
+        let mut parked_cars = BTreeMap::new();
+        for (mut b, cnt) in std::mem::replace(&mut self.individ_parked_cars, BTreeMap::new()) {
+            match mapper.building(&mut b) {
+                Ok(()) => {
+                    parked_cars.insert(b, cnt);
+                }
+                Err(err) => {
+                    problems.push(format!("Skipping parked cars: {}", err));
+                }
+            }
+        }
+        self.individ_parked_cars = parked_cars;
+
+        self.incidents = retain_remapped(
+            std::mem::replace(&mut self.incidents, Vec::new()),
+            "incident",
+            problems,
+            |incident| match incident.blockage {
+                Blockage::Lanes(ref mut lanes) => {
+                    for l in lanes {
+                        mapper.lane(l)?;
+                    }
+                    Ok(())
+                }
+                Blockage::Intersection(ref mut i) => mapper.intersection(i),
+            },
+        );
+    }
+}
+
+impl SpawnTrip {
+    fn remap_ids(&mut self, mapper: &mut IDMapper) -> Result<(), Error> {
+        match self {
+            SpawnTrip::CarAppearing { start, goal, .. } => {
+                mapper.position(start)?;
+                goal.remap_ids(mapper)
+            }
+            SpawnTrip::MaybeUsingParkedCar(_, b, goal) => {
+                mapper.building(b)?;
+                goal.remap_ids(mapper)
+            }
+            SpawnTrip::UsingBike(_, start, goal) => {
+                start.remap_ids(mapper, true)?;
+                goal.remap_ids(mapper)
+            }
+            SpawnTrip::JustWalking(_, start, goal) => {
+                start.remap_ids(mapper, true)?;
+                goal.remap_ids(mapper, false)
+            }
+            SpawnTrip::DeliveryTour {
+                start, stops, goal, ..
+            } => {
+                mapper.position(start)?;
+                for (b, _) in stops {
+                    mapper.building(b)?;
+                }
+                goal.remap_ids(mapper)
+            }
+            SpawnTrip::UsingTransit(_, start, goal, route, stop1, stop2) => {
+                start.remap_ids(mapper, true)?;
+                goal.remap_ids(mapper, false)?;
+                mapper.bus_route(route)?;
+                mapper.bus_stop(stop1)?;
+                mapper.bus_stop(stop2)
+            }
+            SpawnTrip::UsingRideHail(_, start, goal) => {
+                mapper.building(start)?;
+                goal.remap_ids(mapper, false)
+            }
+        }
+    }
+}
+
+impl DrivingGoal {
+    fn remap_ids(&mut self, mapper: &mut IDMapper) -> Result<(), Error> {
+        match self {
+            DrivingGoal::ParkNear(b) => mapper.building(b),
+            DrivingGoal::Border(i, l) => {
+                mapper.intersection(i)?;
+                mapper.lane(l)
+            }
+        }
+    }
+}
+
+impl SidewalkSpot {
+    // Spots at buildings and borders are regenerated from the new map, since the exact position
+    // on the sidewalk depends on the geometry.
+    fn remap_ids(&mut self, mapper: &mut IDMapper, start: bool) -> Result<(), Error> {
+        match self.connection {
+            SidewalkPOI::Building(mut b) => {
+                mapper.building(&mut b)?;
+                if mapper.is_migrating() {
+                    *self = SidewalkSpot::building(b, mapper.get_map());
+                }
+                return Ok(());
+            }
+            SidewalkPOI::Border(mut i) => {
+                mapper.intersection(&mut i)?;
+                if mapper.is_migrating() {
+                    let spot = if start {
+                        SidewalkSpot::start_at_border(i, mapper.get_map())
+                    } else {
+                        SidewalkSpot::end_at_border(i, mapper.get_map())
+                    };
+                    *self =
+                        spot.ok_or_else(|| Error::new(format!("no sidewalk at {} anymore", i)))?;
+                }
+                return Ok(());
+            }
+            SidewalkPOI::ParkingSpot(ParkingSpot::Onstreet(ref mut l, _)) => mapper.lane(l)?,
+            SidewalkPOI::ParkingSpot(ParkingSpot::Offstreet(ref mut b, _)) => mapper.building(b)?,
+            SidewalkPOI::DeferredParkingSpot(ref mut b, ref mut goal) => {
+                mapper.building(b)?;
+                goal.remap_ids(mapper)?;
+            }
+            SidewalkPOI::BusStop(ref mut bs) => mapper.bus_stop(bs)?,
+            SidewalkPOI::BikeRack(ref mut pos) => mapper.position(pos)?,
+            SidewalkPOI::SuddenlyAppear => {}
+        }
+        mapper.position(&mut self.sidewalk_pos)
+    }
+}
+
+impl OriginDestination {
+    fn remap_ids(&mut self, mapper: &mut IDMapper) -> Result<(), Error> {
+        match self {
+            OriginDestination::Neighborhood(_) => Ok(()),
+            OriginDestination::EndOfRoad(dr) => mapper.directed_road(dr),
+        }
+    }
+}
+
+fn retain_remapped<T, F: FnMut(&mut T) -> Result<(), Error>>(
+    list: Vec<T>,
+    what: &str,
+    problems: &mut Vec<String>,
+    mut remap: F,
+) -> Vec<T> {
+    let mut results = Vec::new();
+    for (idx, mut x) in list.into_iter().enumerate() {
+        match remap(&mut x) {
+            Ok(()) => {
+                results.push(x);
+            }
+            Err(err) => {
+                problems.push(format!("Skipping {} {}: {}", what, idx + 1, err));
+            }
+        }
+    }
+    results
+}
@@ -1,12 +1,13 @@
 use crate::{
-    CarID, DrivingGoal, ParkingSpot, SidewalkSpot, Sim, TripSpec, VehicleSpec, VehicleType,
-    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    CarID, DrivingGoal, ParkingSpot, PermanentScenario, SidewalkSpot, Sim, TripSpec, VehicleSpec,
+    VehicleType, BIKE_LENGTH, EV_SHARE, MAX_CAR_LENGTH, MAX_TRUCK_LENGTH, MIN_CAR_LENGTH,
+    MIN_TRUCK_LENGTH,
 };
 use abstutil::{fork_rng, prettyprint_usize, Timer, WeightedUsizeChoice};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, Map, PathConstraints,
-    Position, RoadID,
+    BuildingID, BusRouteID, BusStopID, DirectedRoadID, FullNeighborhoodInfo, IntersectionID,
+    LaneID, Map, PathConstraints, Position, RoadID,
 };
 use rand::seq::SliceRandom;
 use rand::Rng;
@@ -28,6 +29,13 @@ pub struct Scenario {
     // Much more detailed
     pub individ_trips: Vec<SpawnTrip>,
     pub individ_parked_cars: BTreeMap<BuildingID, usize>,
+
+    // How many ownerless vehicles circulate, serving UsingRideHail trips
+    #[serde(default)]
+    pub ride_hail_fleet: usize,
+
+    // Things going wrong during the day
+    pub incidents: Vec<Incident>,
 }
 
 // SpawnOverTime and BorderSpawnOverTime should be kept separate. Agents in SpawnOverTime pick
@@ -58,6 +66,21 @@ pub struct BorderSpawnOverTime {
     pub goal: OriginDestination,
 }
 
+// Temporarily close part of the map, like for a crash or a street fair. Agents already past the
+// closure carry on, vehicles find a way around if they can, and everybody else waits.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Incident {
+    pub start: Time,
+    pub end: Time,
+    pub blockage: Blockage,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Blockage {
+    Lanes(Vec<LaneID>),
+    Intersection(IntersectionID),
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SeedParkedCars {
     pub neighborhood: String,
@@ -81,9 +104,30 @@ impl Scenario {
                 prettyprint_usize(self.border_spawn_over_time.len())
             ),
             format!("{} SpawnTrip", prettyprint_usize(self.individ_trips.len())),
+            format!(
+                "{} ride-hail vehicles",
+                prettyprint_usize(self.ride_hail_fleet)
+            ),
+            format!("{} Incident", prettyprint_usize(self.incidents.len())),
         ]
     }
 
+    // Sim::schedule_incident can't handle incidents that don't end after they start, so drop those
+    // with a warning.
+    pub(crate) fn remove_invalid_incidents(&mut self, timer: &mut Timer) {
+        let name = &self.scenario_name;
+        self.incidents.retain(|incident| {
+            if incident.start < incident.end {
+                return true;
+            }
+            timer.warn(format!(
+                "Skipping incident in {} from {} to {}; it has to end after it starts",
+                name, incident.start, incident.end
+            ));
+            false
+        });
+    }
+
     // TODO may need to fork the RNG a bit more
     pub fn instantiate(&self, sim: &mut Sim, map: &Map, rng: &mut XorShiftRng, timer: &mut Timer) {
         sim.set_name(self.scenario_name.clone());
@@ -95,6 +139,9 @@ impl Scenario {
                 sim.seed_bus_route(route, map, timer);
             }
         }
+        if self.ride_hail_fleet > 0 {
+            sim.seed_ride_hail_fleet(self.ride_hail_fleet, map, rng, timer);
+        }
 
         timer.start("load full neighborhood info");
         let neighborhoods = FullNeighborhoodInfo::load_all(map);
@@ -156,16 +203,29 @@ impl Scenario {
         }
 
         sim.spawn_all_trips(map, timer, true);
+
+        for incident in &self.incidents {
+            sim.schedule_incident(incident.clone());
+        }
         timer.stop(format!("Instantiating {}", self.scenario_name));
     }
 
-    pub fn save(&self) {
+    // Saves stable IDs too, so this can be loaded after the map is rebuilt.
+    pub fn save(&self, map: &Map) {
         abstutil::write_binary(
             abstutil::path_scenario(&self.map_name, &self.scenario_name),
-            self,
+            &PermanentScenario::new(self, map),
         );
     }
 
+    pub fn load(map: &Map, scenario_name: &str, timer: &mut Timer) -> Scenario {
+        PermanentScenario::read(
+            abstutil::path_scenario(map.get_name(), scenario_name),
+            timer,
+        )
+        .into_scenario(map, timer)
+    }
+
     pub fn small_run(map: &Map) -> Scenario {
         let mut s = Scenario {
             scenario_name: "small_run".to_string(),
@@ -204,6 +264,8 @@ impl Scenario {
                 .collect(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            ride_hail_fleet: 0,
+            incidents: Vec::new(),
         };
         for i in map.all_outgoing_borders() {
             s.spawn_over_time.push(SpawnOverTime {
@@ -229,6 +291,8 @@ impl Scenario {
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            ride_hail_fleet: 0,
+            incidents: Vec::new(),
         }
     }
 
@@ -256,15 +320,21 @@ impl Scenario {
             border_spawn_over_time: Vec::new(),
             individ_trips: Vec::new(),
             individ_parked_cars: BTreeMap::new(),
+            ride_hail_fleet: 0,
+            incidents: Vec::new(),
         }
     }
 
     pub fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
         let length = Scenario::rand_dist(rng, MIN_CAR_LENGTH, MAX_CAR_LENGTH);
+        let (max_accel, max_decel) = VehicleType::Car.accel_limits();
         VehicleSpec {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            max_accel,
+            max_decel,
+            electric: rng.gen_bool(EV_SHARE),
         }
     }
 
@@ -274,10 +344,34 @@ impl Scenario {
             Speed::miles_per_hour(8.0),
             Speed::miles_per_hour(10.0),
         ));
+        let (max_accel, max_decel) = VehicleType::Bike.accel_limits();
         VehicleSpec {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            max_accel,
+            max_decel,
+            electric: false,
+        }
+    }
+
+    // Without SimOptions::kinematics, heavier vehicles can't be slower to get going, so also cap
+    // their speed lower.
+    pub fn rand_truck(rng: &mut XorShiftRng) -> VehicleSpec {
+        let length = Scenario::rand_dist(rng, MIN_TRUCK_LENGTH, MAX_TRUCK_LENGTH);
+        let max_speed = Some(Scenario::rand_speed(
+            rng,
+            Speed::miles_per_hour(25.0),
+            Speed::miles_per_hour(35.0),
+        ));
+        let (max_accel, max_decel) = VehicleType::Truck.accel_limits();
+        VehicleSpec {
+            vehicle_type: VehicleType::Truck,
+            length,
+            max_speed,
+            max_accel,
+            max_decel,
+            electric: false,
         }
     }
 
@@ -826,6 +920,14 @@ pub enum SpawnTrip {
         BusStopID,
         BusStopID,
     ),
+    // Stop at each building for the given time, in order
+    DeliveryTour {
+        depart: Time,
+        start: Position,
+        stops: Vec<(BuildingID, Duration)>,
+        goal: DrivingGoal,
+    },
+    UsingRideHail(Time, BuildingID, SidewalkSpot),
 }
 
 impl SpawnTrip {
@@ -887,6 +989,29 @@ impl SpawnTrip {
                     ped_speed: Scenario::rand_ped_speed(rng),
                 },
             ),
+            SpawnTrip::DeliveryTour {
+                depart,
+                start,
+                stops,
+                goal,
+            } => (
+                depart,
+                TripSpec::DeliveryTour {
+                    start_pos: start,
+                    vehicle_spec: Scenario::rand_truck(rng),
+                    stops,
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(rng),
+                },
+            ),
+            SpawnTrip::UsingRideHail(depart, start, goal) => (
+                depart,
+                TripSpec::UsingRideHail {
+                    start,
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(rng),
+                },
+            ),
         }
     }
 }
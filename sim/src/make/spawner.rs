@@ -1,10 +1,11 @@
+use crate::ridehail::curb_near;
 use crate::{
     CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, ParkingSimState, ParkingSpot,
-    PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg, TripManager, TripStart,
-    VehicleSpec, MAX_CAR_LENGTH,
+    PedestrianID, RideHailRequest, Router, Scheduler, SidewalkPOI, SidewalkSpot, TripLeg,
+    TripManager, TripStart, VehicleSpec, MAX_CAR_LENGTH, MAX_TRUCK_LENGTH,
 };
 use abstutil::Timer;
-use geom::{Speed, Time, EPSILON_DIST};
+use geom::{Distance, Duration, Speed, Time, EPSILON_DIST};
 use map_model::{BuildingID, BusRouteID, BusStopID, Map, PathConstraints, PathRequest, Position};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeSet;
@@ -48,6 +49,22 @@ pub enum TripSpec {
         stop2: BusStopID,
         ped_speed: Speed,
     },
+    // A truck appears, stops at each building for some time, then drives to the goal. If the goal
+    // is a building, the driver walks in after parking.
+    DeliveryTour {
+        start_pos: Position,
+        vehicle_spec: VehicleSpec,
+        stops: Vec<(BuildingID, Duration)>,
+        goal: DrivingGoal,
+        ped_speed: Speed,
+    },
+    // Wait at the building for a ride-hail vehicle, ride to the curb closest to the goal, and walk
+    // the rest of the way.
+    UsingRideHail {
+        start: BuildingID,
+        goal: SidewalkSpot,
+        ped_speed: Speed,
+    },
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
@@ -73,6 +90,18 @@ impl TripSpawner {
         map: &Map,
         parking: &ParkingSimState,
     ) {
+        let mut spec = spec;
+        if let TripSpec::DeliveryTour { ref mut stops, .. } = spec {
+            stops.retain(|(b, _)| {
+                if Position::bldg_via_driving(*b, map).is_some() {
+                    true
+                } else {
+                    println!("Skipping delivery to {}; no driving lane nearby", b);
+                    false
+                }
+            });
+        }
+
         // TODO We'll want to repeat this validation when we spawn stuff later for a second leg...
         match &spec {
             TripSpec::CarAppearing {
@@ -80,6 +109,12 @@ impl TripSpawner {
                 vehicle_spec,
                 goal,
                 ..
+            }
+            | TripSpec::DeliveryTour {
+                start_pos,
+                vehicle_spec,
+                goal,
+                ..
             } => {
                 if start_pos.dist_along() < vehicle_spec.length {
                     panic!(
@@ -174,6 +209,8 @@ impl TripSpawner {
                 }
             }
             TripSpec::UsingTransit { .. } => {}
+            // spawn_all skips these if there's no curb nearby.
+            TripSpec::UsingRideHail { .. } => {}
         };
 
         self.trips.push((start_time, ped_id, car_id, spec));
@@ -193,13 +230,24 @@ impl TripSpawner {
             std::mem::replace(&mut self.trips, Vec::new()),
             |tuple| {
                 let req = tuple.3.get_pathfinding_request(map, parking);
-                (tuple, req.clone(), map.pathfind(req))
+                let maybe_path = req.clone().and_then(|req| map.pathfind(req));
+                (tuple, req, maybe_path)
             },
         );
 
         timer.start_iter("spawn trips", paths.len());
-        for ((start_time, ped_id, car_id, spec), req, maybe_path) in paths {
+        for ((start_time, ped_id, car_id, spec), maybe_req, maybe_path) in paths {
             timer.next();
+            // TODO Like some bike trips, these are silently erased.
+            let req = if let Some(req) = maybe_req {
+                req
+            } else {
+                timer.warn(format!(
+                    "Skipping a trip starting at {}; no driving lane or bike rack nearby",
+                    start_time
+                ));
+                continue;
+            };
             match spec {
                 TripSpec::CarAppearing {
                     start_pos,
@@ -462,6 +510,90 @@ impl TripSpawner {
                         trips.abort_trip_failed_start(trip);
                     }
                 }
+                TripSpec::DeliveryTour {
+                    start_pos,
+                    vehicle_spec,
+                    stops,
+                    goal,
+                    ped_speed,
+                } => {
+                    let vehicle = vehicle_spec.make(car_id.unwrap(), None);
+                    let mut legs: Vec<TripLeg> = stops
+                        .iter()
+                        .map(|(b, dwell)| TripLeg::Deliver(vehicle.clone(), *b, *dwell))
+                        .collect();
+                    legs.push(TripLeg::Drive(vehicle.clone(), goal.clone()));
+                    if let DrivingGoal::ParkNear(b) = goal {
+                        legs.push(TripLeg::Walk(
+                            ped_id.unwrap(),
+                            ped_speed,
+                            SidewalkSpot::building(b, map),
+                        ));
+                    }
+                    let trip_start = TripStart::Border(map.get_l(start_pos.lane()).src_i);
+                    let trip = trips.new_trip(start_time, trip_start, legs);
+                    if let Some(path) = maybe_path {
+                        let router = match stops.get(0) {
+                            Some((b, _)) => Router::make_delivery(path, *b, req.end.dist_along()),
+                            None => goal.make_router(path, map, vehicle.vehicle_type),
+                        };
+                        scheduler.quick_push(
+                            start_time,
+                            Command::SpawnCar(
+                                CreateCar::for_appearing(vehicle, start_pos, router, req, trip),
+                                retry_if_no_room,
+                            ),
+                        );
+                    } else {
+                        timer.warn(format!(
+                            "DeliveryTour trip couldn't find the first path {}",
+                            req
+                        ));
+                        trips.abort_trip_failed_start(trip);
+                    }
+                }
+                TripSpec::UsingRideHail {
+                    start,
+                    goal,
+                    ped_speed,
+                } => {
+                    let ped = ped_id.unwrap();
+                    // Get out on the sidewalk next to the dropoff
+                    let sidewalk = goal.sidewalk_pos.lane();
+                    let get_out = SidewalkSpot::suddenly_appear(
+                        sidewalk,
+                        req.end
+                            .equiv_pos(sidewalk, Distance::ZERO, map)
+                            .dist_along(),
+                        map,
+                    );
+                    let trip = trips.new_trip(
+                        start_time,
+                        TripStart::Bldg(start),
+                        vec![
+                            TripLeg::RideHail(ped, get_out),
+                            TripLeg::Walk(ped, ped_speed, goal),
+                        ],
+                    );
+                    if maybe_path.is_some() {
+                        scheduler.quick_push(
+                            start_time,
+                            Command::RequestRideHail(RideHailRequest {
+                                ped,
+                                trip,
+                                requested_at: start_time,
+                                pickup: req.start,
+                                dropoff: req.end,
+                            }),
+                        );
+                    } else {
+                        timer.warn(format!(
+                            "UsingRideHail trip couldn't find the first path {}",
+                            req
+                        ));
+                        trips.abort_trip_failed_start(trip);
+                    }
+                }
             }
         }
 
@@ -478,14 +610,22 @@ impl TripSpawner {
 impl TripSpec {
     // If possible, fixes problems that schedule_trip would hit.
     pub fn spawn_car_at(pos: Position, map: &Map) -> Option<Position> {
+        TripSpec::spawn_vehicle_at(pos, MAX_CAR_LENGTH, map)
+    }
+
+    pub fn spawn_truck_at(pos: Position, map: &Map) -> Option<Position> {
+        TripSpec::spawn_vehicle_at(pos, MAX_TRUCK_LENGTH, map)
+    }
+
+    fn spawn_vehicle_at(pos: Position, max_length: Distance, map: &Map) -> Option<Position> {
         let len = map.get_l(pos.lane()).length();
         // There's no hope.
-        if len <= MAX_CAR_LENGTH {
+        if len <= max_length {
             return None;
         }
 
-        if pos.dist_along() < MAX_CAR_LENGTH {
-            Some(Position::new(pos.lane(), MAX_CAR_LENGTH))
+        if pos.dist_along() < max_length {
+            Some(Position::new(pos.lane(), max_length))
         } else if pos.dist_along() == len {
             Some(Position::new(pos.lane(), pos.dist_along() - EPSILON_DIST))
         } else {
@@ -497,8 +637,8 @@ impl TripSpec {
         &self,
         map: &Map,
         parking: &ParkingSimState,
-    ) -> PathRequest {
-        match self {
+    ) -> Option<PathRequest> {
+        Some(match self {
             TripSpec::CarAppearing {
                 start_pos,
                 vehicle_spec,
@@ -534,8 +674,7 @@ impl TripSpec {
             },
             TripSpec::UsingBike { start, .. } => PathRequest {
                 start: start.sidewalk_pos,
-                end: SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map)
-                    .unwrap()
+                end: SidewalkSpot::bike_from_bike_rack(start.sidewalk_pos.lane(), map)?
                     .sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             },
@@ -544,6 +683,28 @@ impl TripSpec {
                 end: SidewalkSpot::bus_stop(*stop1, map).sidewalk_pos,
                 constraints: PathConstraints::Pedestrian,
             },
-        }
+            TripSpec::DeliveryTour {
+                start_pos,
+                vehicle_spec,
+                stops,
+                goal,
+                ..
+            } => {
+                let constraints = vehicle_spec.vehicle_type.to_constraints();
+                PathRequest {
+                    start: *start_pos,
+                    end: match stops.get(0) {
+                        Some((b, _)) => Position::bldg_via_driving(*b, map)?,
+                        None => goal.goal_pos(constraints, map),
+                    },
+                    constraints,
+                }
+            }
+            TripSpec::UsingRideHail { start, goal, .. } => PathRequest {
+                start: curb_near(map.get_b(*start).front_path.sidewalk, map)?,
+                end: curb_near(goal.sidewalk_pos, map)?,
+                constraints: PathConstraints::Car,
+            },
+        })
     }
 }
@@ -0,0 +1,340 @@
+use crate::{
+    BorderSpawnOverTime, DrivingGoal, Incident, OriginDestination, Scenario, SidewalkPOI,
+    SidewalkSpot, SpawnOverTime, SpawnTrip, TripMode,
+};
+use abstutil::fork_rng;
+use geom::{Duration, Time};
+use map_model::{BuildingID, FullNeighborhoodInfo, Map};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use std::collections::{BTreeSet, HashSet};
+
+// Ways to change the demand in a scenario, for sensitivity testing. These all act on
+// individ_trips, spawn_over_time, and border_spawn_over_time, and leave parked cars alone.
+// Incidents are only moved by the ones that change times. The random ones fork the RNG for each
+// list, so the result only depends on the seed.
+impl Scenario {
+    // Scales the number of trips. Individual trips are resampled: below 1, a random subset is
+    // kept; above 1, every trip is kept and extra copies of random trips are added. The counts in
+    // SpawnOverTime and BorderSpawnOverTime are multiplied, rounding randomly.
+    pub fn scale(&self, factor: f64, rng: &mut XorShiftRng) -> Scenario {
+        assert!(factor >= 0.0);
+        let mut s = self.clone();
+
+        let mut trips_rng = fork_rng(rng);
+        let num_trips = round_randomly(self.individ_trips.len() as f64 * factor, &mut trips_rng);
+        s.individ_trips = if num_trips <= self.individ_trips.len() {
+            // Keep the original order of what's left
+            let mut keep: Vec<usize> = (0..self.individ_trips.len()).collect();
+            keep.shuffle(&mut trips_rng);
+            keep.truncate(num_trips);
+            keep.sort();
+            keep.into_iter()
+                .map(|idx| self.individ_trips[idx].clone())
+                .collect()
+        } else {
+            let mut trips = self.individ_trips.clone();
+            for _ in self.individ_trips.len()..num_trips {
+                trips.push(self.individ_trips.choose(&mut trips_rng).unwrap().clone());
+            }
+            trips
+        };
+
+        let mut spawn_rng = fork_rng(rng);
+        for spawn in &mut s.spawn_over_time {
+            spawn.num_agents = scale_count(spawn.num_agents, factor, &mut spawn_rng);
+        }
+        let mut border_rng = fork_rng(rng);
+        for spawn in &mut s.border_spawn_over_time {
+            spawn.num_peds = scale_count(spawn.num_peds, factor, &mut border_rng);
+            spawn.num_cars = scale_count(spawn.num_cars, factor, &mut border_rng);
+            spawn.num_bikes = scale_count(spawn.num_bikes, factor, &mut border_rng);
+        }
+        s
+    }
+
+    // Keeps each individual trip with some probability, and thins out the aggregate counts the
+    // same way. Unlike scale, the number of trips kept isn't exact.
+    pub fn sample(&self, pct: f64, rng: &mut XorShiftRng) -> Scenario {
+        assert!(pct >= 0.0 && pct <= 1.0);
+        let mut s = self.clone();
+
+        let mut trips_rng = fork_rng(rng);
+        s.individ_trips.retain(|_| trips_rng.gen_bool(pct));
+
+        let mut spawn_rng = fork_rng(rng);
+        for spawn in &mut s.spawn_over_time {
+            spawn.num_agents = sample_count(spawn.num_agents, pct, &mut spawn_rng);
+        }
+        let mut border_rng = fork_rng(rng);
+        for spawn in &mut s.border_spawn_over_time {
+            spawn.num_peds = sample_count(spawn.num_peds, pct, &mut border_rng);
+            spawn.num_cars = sample_count(spawn.num_cars, pct, &mut border_rng);
+            spawn.num_bikes = sample_count(spawn.num_bikes, pct, &mut border_rng);
+        }
+        s
+    }
+
+    // Agents in SpawnOverTime only pick their mode while spawning, so those are kept only if
+    // every mode is. Pedestrians from BorderSpawnOverTime might walk or use transit, so they're
+    // kept if either is.
+    pub fn filter_modes(&self, modes: &BTreeSet<TripMode>) -> Scenario {
+        let mut s = self.clone();
+        s.individ_trips.retain(|t| modes.contains(&t.mode()));
+        if TripMode::all().into_iter().any(|m| !modes.contains(&m)) {
+            s.spawn_over_time.clear();
+        }
+        for spawn in &mut s.border_spawn_over_time {
+            if !modes.contains(&TripMode::Walk) && !modes.contains(&TripMode::Transit) {
+                spawn.num_peds = 0;
+            }
+            if !modes.contains(&TripMode::Drive) {
+                spawn.num_cars = 0;
+            }
+            if !modes.contains(&TripMode::Bike) {
+                spawn.num_bikes = 0;
+            }
+        }
+        s.border_spawn_over_time
+            .retain(|spawn| spawn.num_peds + spawn.num_cars + spawn.num_bikes > 0);
+        s
+    }
+
+    // Only trips starting or ending at a building in the neighborhood. SpawnOverTime is kept if
+    // it starts from the neighborhood, and BorderSpawnOverTime if it goes there.
+    pub fn filter_neighborhood(&self, name: &str, map: &Map) -> Scenario {
+        let neighborhoods = FullNeighborhoodInfo::load_all(map);
+        let bldgs: HashSet<BuildingID> = neighborhoods
+            .get(name)
+            .unwrap_or_else(|| panic!("Neighborhood {} isn't defined", name))
+            .buildings
+            .iter()
+            .cloned()
+            .collect();
+
+        let mut s = self.clone();
+        s.individ_trips
+            .retain(|t| t.buildings().into_iter().any(|b| bldgs.contains(&b)));
+        s.spawn_over_time
+            .retain(|spawn| spawn.start_from_neighborhood == name);
+        s.border_spawn_over_time.retain(|spawn| match spawn.goal {
+            OriginDestination::Neighborhood(ref n) => n == name,
+            OriginDestination::EndOfRoad(_) => false,
+        });
+        s
+    }
+
+    // Only trips departing in [start, end). SpawnOverTime and BorderSpawnOverTime get clipped to
+    // the window, keeping the same rate of agents. Incidents get clipped too.
+    pub fn filter_time_window(&self, start: Time, end: Time, rng: &mut XorShiftRng) -> Scenario {
+        assert!(start < end);
+        let mut s = self.clone();
+        s.individ_trips.retain(|t| {
+            let depart = t.departure();
+            depart >= start && depart < end
+        });
+
+        let mut spawn_rng = fork_rng(rng);
+        s.spawn_over_time = self
+            .spawn_over_time
+            .iter()
+            .filter_map(|spawn| {
+                let (pct, start_time, stop_time) =
+                    clip_window(spawn.start_time, spawn.stop_time, start, end)?;
+                Some(SpawnOverTime {
+                    num_agents: scale_count(spawn.num_agents, pct, &mut spawn_rng),
+                    start_time,
+                    stop_time,
+                    ..spawn.clone()
+                })
+            })
+            .collect();
+        let mut border_rng = fork_rng(rng);
+        s.border_spawn_over_time = self
+            .border_spawn_over_time
+            .iter()
+            .filter_map(|spawn| {
+                let (pct, start_time, stop_time) =
+                    clip_window(spawn.start_time, spawn.stop_time, start, end)?;
+                Some(BorderSpawnOverTime {
+                    num_peds: scale_count(spawn.num_peds, pct, &mut border_rng),
+                    num_cars: scale_count(spawn.num_cars, pct, &mut border_rng),
+                    num_bikes: scale_count(spawn.num_bikes, pct, &mut border_rng),
+                    start_time,
+                    stop_time,
+                    ..spawn.clone()
+                })
+            })
+            .collect();
+        s.incidents = self
+            .incidents
+            .iter()
+            .filter_map(|incident| {
+                let (_, clipped_start, clipped_end) =
+                    clip_window(incident.start, incident.end, start, end)?;
+                Some(Incident {
+                    start: clipped_start,
+                    end: clipped_end,
+                    ..incident.clone()
+                })
+            })
+            .collect();
+        s
+    }
+
+    // Negative shifts can't go before midnight.
+    pub fn shift_departures(&self, dt: Duration) -> Scenario {
+        let mut s = self.clone();
+        for trip in &mut s.individ_trips {
+            let depart = shift_time(trip.departure(), dt);
+            trip.set_departure(depart);
+        }
+        for spawn in &mut s.spawn_over_time {
+            spawn.start_time = shift_time(spawn.start_time, dt);
+            spawn.stop_time = shift_time(spawn.stop_time, dt);
+        }
+        for spawn in &mut s.border_spawn_over_time {
+            spawn.start_time = shift_time(spawn.start_time, dt);
+            spawn.stop_time = shift_time(spawn.stop_time, dt);
+        }
+        // An incident pushed entirely before midnight disappears.
+        s.incidents = s
+            .incidents
+            .into_iter()
+            .filter_map(|mut incident| {
+                incident.start = shift_time(incident.start, dt);
+                incident.end = shift_time(incident.end, dt);
+                if incident.start < incident.end {
+                    Some(incident)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        s
+    }
+}
+
+impl SpawnTrip {
+    pub fn departure(&self) -> Time {
+        match self {
+            SpawnTrip::CarAppearing { depart, .. } | SpawnTrip::DeliveryTour { depart, .. } => {
+                *depart
+            }
+            SpawnTrip::MaybeUsingParkedCar(depart, _, _)
+            | SpawnTrip::UsingBike(depart, _, _)
+            | SpawnTrip::JustWalking(depart, _, _)
+            | SpawnTrip::UsingTransit(depart, _, _, _, _, _)
+            | SpawnTrip::UsingRideHail(depart, _, _) => *depart,
+        }
+    }
+
+    pub fn set_departure(&mut self, time: Time) {
+        match self {
+            SpawnTrip::CarAppearing { depart, .. } | SpawnTrip::DeliveryTour { depart, .. } => {
+                *depart = time;
+            }
+            SpawnTrip::MaybeUsingParkedCar(depart, _, _)
+            | SpawnTrip::UsingBike(depart, _, _)
+            | SpawnTrip::JustWalking(depart, _, _)
+            | SpawnTrip::UsingTransit(depart, _, _, _, _, _)
+            | SpawnTrip::UsingRideHail(depart, _, _) => {
+                *depart = time;
+            }
+        }
+    }
+
+    pub fn mode(&self) -> TripMode {
+        match self {
+            SpawnTrip::CarAppearing { is_bike, .. } => {
+                if *is_bike {
+                    TripMode::Bike
+                } else {
+                    TripMode::Drive
+                }
+            }
+            SpawnTrip::MaybeUsingParkedCar(_, _, _) | SpawnTrip::DeliveryTour { .. } => {
+                TripMode::Drive
+            }
+            SpawnTrip::UsingBike(_, _, _) => TripMode::Bike,
+            SpawnTrip::JustWalking(_, _, _) => TripMode::Walk,
+            SpawnTrip::UsingTransit(_, _, _, _, _, _) => TripMode::Transit,
+            SpawnTrip::UsingRideHail(_, _, _) => TripMode::RideHail,
+        }
+    }
+
+    // The buildings this trip starts or ends at, if any
+    fn buildings(&self) -> Vec<BuildingID> {
+        let goal_bldg = |goal: &DrivingGoal| match goal {
+            DrivingGoal::ParkNear(b) => Some(*b),
+            DrivingGoal::Border(_, _) => None,
+        };
+        let spot_bldg = |spot: &SidewalkSpot| match spot.connection {
+            SidewalkPOI::Building(b) => Some(b),
+            _ => None,
+        };
+        match self {
+            SpawnTrip::CarAppearing { goal, .. } => vec![goal_bldg(goal)],
+            SpawnTrip::DeliveryTour { stops, goal, .. } => stops
+                .iter()
+                .map(|(b, _)| Some(*b))
+                .chain(vec![goal_bldg(goal)])
+                .collect(),
+            SpawnTrip::MaybeUsingParkedCar(_, b, goal) => vec![Some(*b), goal_bldg(goal)],
+            SpawnTrip::UsingBike(_, start, goal) => vec![spot_bldg(start), goal_bldg(goal)],
+            SpawnTrip::UsingRideHail(_, start, goal) => vec![Some(*start), spot_bldg(goal)],
+            SpawnTrip::JustWalking(_, start, goal)
+            | SpawnTrip::UsingTransit(_, start, goal, _, _, _) => {
+                vec![spot_bldg(start), spot_bldg(goal)]
+            }
+        }
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+fn round_randomly(x: f64, rng: &mut XorShiftRng) -> usize {
+    let mut n = x.floor() as usize;
+    if rng.gen_bool(x - x.floor()) {
+        n += 1;
+    }
+    n
+}
+
+fn scale_count(count: usize, factor: f64, rng: &mut XorShiftRng) -> usize {
+    round_randomly(count as f64 * factor, rng)
+}
+
+fn sample_count(count: usize, pct: f64, rng: &mut XorShiftRng) -> usize {
+    (0..count).filter(|_| rng.gen_bool(pct)).count()
+}
+
+// Returns the fraction of [start_time, stop_time] inside the window, and the clipped interval.
+fn clip_window(
+    start_time: Time,
+    stop_time: Time,
+    window_start: Time,
+    window_end: Time,
+) -> Option<(f64, Time, Time)> {
+    let clipped_start = start_time.max(window_start);
+    let clipped_stop = stop_time.min(window_end);
+    if clipped_start >= clipped_stop {
+        return None;
+    }
+    let pct = if stop_time > start_time {
+        (clipped_stop - clipped_start) / (stop_time - start_time)
+    } else {
+        1.0
+    };
+    Some((pct, clipped_start, clipped_stop))
+}
+
+fn shift_time(t: Time, dt: Duration) -> Time {
+    if dt >= Duration::ZERO {
+        t + dt
+    } else {
+        t.clamped_sub(Duration::ZERO - dt)
+    }
+}
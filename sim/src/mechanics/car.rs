@@ -2,8 +2,8 @@ use crate::{
     AgentMetadata, CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
 };
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{Map, Traversable, LANE_THICKNESS};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{Map, Traversable, TurnPriority, LANE_THICKNESS};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
@@ -15,6 +15,12 @@ pub struct Car {
     pub trip: TripID,
     pub blocked_since: Option<Time>,
     pub started_at: Time,
+    // If true, use the vehicle's acceleration limits instead of instantly reaching cruising speed.
+    pub kinematics: bool,
+    // With kinematics, when the latest Crossing interval ends and how fast the car is going then.
+    pub end_of_crossing: Option<(Time, Speed)>,
+    // With kinematics, a car braking for a pedestrian stops here, then waits until this time.
+    pub yield_at: Option<(Distance, Time)>,
 
     // In reverse order -- most recently left is first. The sum length of these must be >=
     // vehicle.length.
@@ -23,31 +29,98 @@ pub struct Car {
 
 impl Car {
     // Assumes the current head of the path is the thing to cross.
-    pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
-        let dist_int = DistanceInterval::new_driving(
-            start_dist,
-            if self.router.last_step() {
-                self.router.get_end_dist()
-            } else {
-                self.router.head().length(map)
-            },
-        );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+    pub fn crossing_state(
+        &mut self,
+        start_dist: Distance,
+        start_time: Time,
+        map: &Map,
+    ) -> CarState {
+        let mut end_dist = if self.router.last_step() {
+            self.router.get_end_dist()
+        } else {
+            self.router.head().length(map)
+        };
+        // Still braking for a pedestrian
+        if let Some((stop, _)) = self.yield_at {
+            end_dist = end_dist.min(stop).max(start_dist);
+        }
+        let dist_int = DistanceInterval::new_driving(start_dist, end_dist);
+        let (state, end_speed) = self.plan_crossing(dist_int, start_time, map);
+        if self.kinematics {
+            self.end_of_crossing = Some((state.get_end_time(), end_speed));
+        }
+        state
     }
 
+    // Doesn't remember the speed at the end; just for predicting.
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
     ) -> CarState {
+        self.plan_crossing(dist_int, start_time, map).0
+    }
+
+    fn plan_crossing(
+        &self,
+        dist_int: DistanceInterval,
+        start_time: Time,
+        map: &Map,
+    ) -> (CarState, Speed) {
         let on = self.router.head();
-        let mut speed = on.speed_limit(map);
+        let mut speed = on.speed_limit_at(map, start_time);
         if let Some(s) = self.vehicle.max_speed {
             speed = speed.min(s);
         }
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        let dist = dist_int.end - dist_int.start;
+        let (dt, end_speed) = if self.kinematics {
+            // Only keep going if the previous interval ended right now. Otherwise the car was
+            // stuck somewhere in between, so it starts from rest.
+            let initial_speed = match self.end_of_crossing {
+                Some((t, v)) if t == start_time => v,
+                _ => Speed::ZERO,
+            };
+            let to_rest = self.router.last_step()
+                || self.yield_at.is_some()
+                || (dist_int.end == on.length(map)
+                    && self.must_stop_after(start_time + dist / speed, map));
+            ramp(
+                dist,
+                speed,
+                initial_speed,
+                to_rest,
+                self.vehicle.max_accel,
+                self.vehicle.max_decel,
+            )
+        } else {
+            (dist / speed, speed)
+        };
+        // TODO Positions are still linearly interpolated over the longer interval.
+        (
+            CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int),
+            end_speed,
+        )
+    }
+
+    // Will the car have to stop before the upcoming turn, if it shows up around the given time?
+    // Traffic signal overrides and other agents aren't considered.
+    fn must_stop_after(&self, arrival: Time, map: &Map) -> bool {
+        let turn = match (self.router.head(), self.router.maybe_next()) {
+            (Traversable::Lane(_), Some(Traversable::Turn(t))) => t,
+            _ => {
+                return false;
+            }
+        };
+        if let Some(signal) = map.maybe_get_traffic_signal(turn.parent) {
+            let (_, phase, remaining) = signal.current_phase_and_remaining_time(arrival);
+            phase.get_priority_of_turn_at(turn, signal, phase.duration - remaining)
+                == TurnPriority::Banned
+        } else if let Some(sign) = map.maybe_get_stop_sign(turn.parent) {
+            sign.get_priority(turn, map) == TurnPriority::Yield
+        } else {
+            false
+        }
     }
 
     pub fn get_draw_car(
@@ -157,6 +230,7 @@ impl Car {
                 CarState::Parking(_, _, _) => CarStatus::Parked,
                 // Changing color for idling buses is helpful
                 CarState::Idling(_, _) => CarStatus::Parked,
+                CarState::Yielding(_, _) => CarStatus::Moving,
             },
             on: self.router.head(),
             label: if self.vehicle.vehicle_type == VehicleType::Bus {
@@ -185,6 +259,58 @@ impl Car {
     }
 }
 
+// How long to cover some distance starting at some speed, and how fast the car is going at the
+// end. Accelerations are in m/s^2. If the distance is too short to reach cruising speed, the car
+// speeds up the whole way, or speeds up and immediately brakes when it has to stop.
+fn ramp(
+    dist: Distance,
+    cruise: Speed,
+    initial: Speed,
+    to_rest: bool,
+    accel: f64,
+    decel: f64,
+) -> (Duration, Speed) {
+    let d = dist.inner_meters();
+    let v = cruise.inner_meters_per_second();
+    // If the speed limit dropped, just slow down instantly.
+    let v0 = initial.inner_meters_per_second().min(v);
+    if d <= 0.0 {
+        return (Duration::ZERO, Speed::meters_per_second(v0));
+    }
+    let accel_dist = (v * v - v0 * v0) / (2.0 * accel);
+
+    if !to_rest {
+        if accel_dist <= d {
+            return (
+                Duration::seconds((v - v0) / accel + (d - accel_dist) / v),
+                cruise,
+            );
+        }
+        let v1 = (v0 * v0 + 2.0 * accel * d).sqrt();
+        return (
+            Duration::seconds((v1 - v0) / accel),
+            Speed::meters_per_second(v1),
+        );
+    }
+
+    let decel_dist = v * v / (2.0 * decel);
+    if accel_dist + decel_dist <= d {
+        return (
+            Duration::seconds((v - v0) / accel + v / decel + (d - accel_dist - decel_dist) / v),
+            Speed::ZERO,
+        );
+    }
+    let peak = ((2.0 * accel * decel * d + decel * v0 * v0) / (accel + decel)).sqrt();
+    if peak < v0 {
+        // Already moving too fast to stop in time. Brake harder than the limit.
+        return (Duration::seconds(2.0 * d / v0), Speed::ZERO);
+    }
+    (
+        Duration::seconds((peak - v0) / accel + peak / decel),
+        Speed::ZERO,
+    )
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum CarState {
     Crossing(TimeInterval, DistanceInterval),
@@ -194,6 +320,8 @@ pub enum CarState {
     Unparking(Distance, ParkingSpot, TimeInterval),
     Parking(Distance, ParkingSpot, TimeInterval),
     Idling(Distance, TimeInterval),
+    // Stopped in the middle of a lane to let a pedestrian cross
+    Yielding(Distance, TimeInterval),
 }
 
 impl CarState {
@@ -205,6 +333,7 @@ impl CarState {
             CarState::Unparking(_, _, ref time_int) => time_int.end,
             CarState::Parking(_, _, ref time_int) => time_int.end,
             CarState::Idling(_, ref time_int) => time_int.end,
+            CarState::Yielding(_, ref time_int) => time_int.end,
         }
     }
 }
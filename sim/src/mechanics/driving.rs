@@ -1,20 +1,24 @@
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
-    ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval, DrawCarInput,
-    Event, IntersectionSimState, ParkedCar, ParkingSimState, Scheduler, TimeInterval,
-    TransitSimState, TripManager, TripPositions, UnzoomedAgent, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    diff_maps, ActionAtEnd, AgentID, AgentMetadata, CarID, Command, CreateCar, DistanceInterval,
+    DrawCarInput, EmissionFactors, Event, IntersectionSimState, ParkedCar, ParkingSimState,
+    PedestrianID, RideHailSimState, Scheduler, TimeInterval, TransitSimState, TripManager,
+    TripPositions, UnzoomedAgent, VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{BuildingID, LaneID, Map, Path, PathStep, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{
+    BuildingID, LaneID, Map, Path, PathRequest, PathStep, Position, Traversable, TurnID,
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet, VecDeque};
 
 const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
 const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
+// Closer than this, a driver can't stop for somebody stepping into the road.
+const MIN_DIST_TO_YIELD: Distance = Distance::const_meters(10.0);
 
 // TODO Do something else.
 pub(crate) const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -35,15 +39,24 @@ pub struct DrivingSimState {
     events: Vec<Event>,
 
     recalc_lanechanging: bool,
+    kinematics: bool,
+    emission_factors: EmissionFactors,
 }
 
 impl DrivingSimState {
-    pub fn new(map: &Map, recalc_lanechanging: bool) -> DrivingSimState {
+    pub fn new(
+        map: &Map,
+        recalc_lanechanging: bool,
+        kinematics: bool,
+        emission_factors: EmissionFactors,
+    ) -> DrivingSimState {
         let mut sim = DrivingSimState {
             cars: BTreeMap::new(),
             queues: BTreeMap::new(),
             events: Vec::new(),
             recalc_lanechanging,
+            kinematics,
+            emission_factors,
         };
 
         for l in map.all_lanes() {
@@ -93,6 +106,9 @@ impl DrivingSimState {
                 blocked_since: None,
                 started_at: now,
                 trip: params.trip,
+                kinematics: self.kinematics,
+                end_of_crossing: None,
+                yield_at: None,
             };
             if let Some(p) = params.maybe_parked_car {
                 car.state = CarState::Unparking(
@@ -135,7 +151,9 @@ impl DrivingSimState {
                 // get_idx_to_insert_car does a more detailed check of the current space usage.
                 queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
             }
-            self.cars.insert(car.vehicle.id, car);
+            let id = car.vehicle.id;
+            self.cars.insert(id, car);
+            self.wake_follower_to_change_lanes(id, now, scheduler);
             return true;
         }
         false
@@ -151,6 +169,7 @@ impl DrivingSimState {
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
         transit: &mut TransitSimState,
+        ride_hail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
     ) {
         // State transitions for this car:
@@ -158,6 +177,7 @@ impl DrivingSimState {
         // Crossing -> Queued or WaitingToAdvance
         // Unparking -> Crossing
         // Idling -> Crossing
+        // Yielding -> Crossing
         // Queued -> last step handling (Parking or done)
         // WaitingToAdvance -> try to advance to the next step of the path
         // Parking -> done
@@ -167,9 +187,19 @@ impl DrivingSimState {
         // Crossing -> Crossing (recalculate dist/time)
         // Queued -> Crossing
         //
+        // Pedestrians stepping into the road can also change Crossing -> Yielding.
+        //
         // Why is it safe to process cars in any order, rather than making sure to follow the order
         // of queues? Because of the invariant that distances should never suddenly jump when a car
         // has entered/exiting a queue.
+        // Queued cars usually wait for their leader to wake them up. The exception is being stuck
+        // behind a stopped vehicle; they're woken up to try changing lanes when something nearby
+        // changes.
+        if self.blocked_by_stopped_leader(id) {
+            self.try_to_change_lanes(id, now, map, intersections, scheduler);
+            return;
+        }
+
         // This car might have reached the router's end distance, but maybe not -- might
         // actually be stuck behind other cars. We have to calculate the distances right now to
         // be sure.
@@ -186,6 +216,10 @@ impl DrivingSimState {
             // We need to mutate two different cars in one case. To avoid fighting the borrow
             // checker, temporarily move one of them out of the BTreeMap.
             let mut car = self.cars.remove(&id).unwrap();
+            let was_crossing = match car.state {
+                CarState::Crossing(_, _) => true,
+                _ => false,
+            };
             // Responsibility of update_car to manage scheduling stuff!
             need_distances = self.update_car_without_distances(
                 &mut car,
@@ -196,7 +230,18 @@ impl DrivingSimState {
                 transit,
                 scheduler,
             );
+            let stopped_on = match (was_crossing, car.router.head()) {
+                (true, Traversable::Lane(l)) => Some(l),
+                _ => None,
+            };
             self.cars.insert(id, car);
+            if let Some(l) = stopped_on {
+                // Somebody in the next lane over might be able to slip in behind us now.
+                self.wake_lane_changers(l, now, map, scheduler);
+            }
+            if self.blocked_by_stopped_leader(id) {
+                self.try_to_change_lanes(id, now, map, intersections, scheduler);
+            }
         }
 
         if need_distances {
@@ -220,10 +265,13 @@ impl DrivingSimState {
                 trips,
                 scheduler,
                 transit,
+                ride_hail,
                 walking,
                 intersections,
             ) {
                 self.cars.insert(id, car);
+                // If we just stopped mid-lane, the follower can try to pull around.
+                self.wake_follower_to_change_lanes(id, now, scheduler);
             }
         }
     }
@@ -243,6 +291,14 @@ impl DrivingSimState {
     ) -> bool {
         match car.state {
             CarState::Crossing(_, _) => {
+                record_emissions(car, now, &self.emission_factors, &mut self.events);
+                // Done braking for a pedestrian
+                if let Some((stop, until)) = car.yield_at.take() {
+                    car.state = CarState::Yielding(stop, TimeInterval::new(now, until.max(now)));
+                    car.blocked_since = Some(now);
+                    scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                    return false;
+                }
                 car.state = CarState::Queued;
                 car.blocked_since = Some(now);
                 if car.router.last_step() {
@@ -260,6 +316,7 @@ impl DrivingSimState {
                 }
             }
             CarState::Unparking(front, _, _) => {
+                record_emissions(car, now, &self.emission_factors, &mut self.events);
                 if car.router.last_step() {
                     // Actually, we need to do this first. Ignore the answer -- if we're
                     // doing something weird like vanishing or re-parking immediately
@@ -276,60 +333,51 @@ impl DrivingSimState {
                 }
                 car.state = car.crossing_state(front, now, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                self.wake_up_follower(car, front, now, map, scheduler);
             }
             CarState::Idling(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id);
-                self.events
-                    .push(Event::PathAmended(car.router.get_path().clone()));
+                record_emissions(car, now, &self.emission_factors, &mut self.events);
+                if car.vehicle.vehicle_type == VehicleType::Bus {
+                    car.router = transit.bus_departed_from_stop(car.vehicle.id);
+                    self.events
+                        .push(Event::PathAmended(car.router.get_path().clone()));
+                } else if car.router.last_step() {
+                    // A truck done with a delivery already has the next route. Like Unparking,
+                    // trigger the side effect of choosing an end_dist.
+                    car.router.maybe_handle_end(
+                        dist,
+                        &car.vehicle,
+                        parking,
+                        map,
+                        car.trip,
+                        &mut self.events,
+                    );
+                }
                 car.state = car.crossing_state(dist, now, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-
-                // Update our follower, so they know we stopped idling.
-                let queue = &self.queues[&car.router.head()];
-                let idx = queue
-                    .cars
-                    .iter()
-                    .position(|c| *c == car.vehicle.id)
-                    .unwrap();
-                if idx != queue.cars.len() - 1 {
-                    let mut follower = self.cars.get_mut(&queue.cars[idx + 1]).unwrap();
-                    match follower.state {
-                        CarState::Queued => {
-                            // If they're on their last step, they might be ending early and not
-                            // right behind us.
-                            if !follower.router.last_step() {
-                                follower.state = follower.crossing_state(
-                                    // Since the follower was Queued, this must be where they are.
-                                    dist - car.vehicle.length - FOLLOWING_DISTANCE,
-                                    now,
-                                    map,
-                                );
-                                follower.blocked_since = None;
-                                scheduler.update(
-                                    follower.state.get_end_time(),
-                                    Command::UpdateCar(follower.vehicle.id),
-                                );
-                            }
-                        }
-                        CarState::WaitingToAdvance => unreachable!(),
-                        // They weren't blocked. Note that there's no way the Crossing state could jump
-                        // forwards here; the leader is still in front of them.
-                        CarState::Crossing(_, _)
-                        | CarState::Unparking(_, _, _)
-                        | CarState::Parking(_, _, _)
-                        | CarState::Idling(_, _) => {}
-                    }
-                }
+                self.wake_up_follower(car, dist, now, map, scheduler);
+            }
+            CarState::Yielding(dist, _) => {
+                record_emissions(car, now, &self.emission_factors, &mut self.events);
+                car.state = car.crossing_state(dist, now, map);
+                car.blocked_since = None;
+                scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                self.wake_up_follower(car, dist, now, map, scheduler);
             }
             CarState::Queued => unreachable!(),
             CarState::WaitingToAdvance => {
+                // Paths are calculated before incidents start, so check before going further.
+                if intersections.any_closures() {
+                    detour(car, intersections, map, &mut self.events);
+                }
+
                 // 'car' is the leader.
                 let from = car.router.head();
                 let goto = car.router.next();
                 assert!(from != goto);
 
                 if let Traversable::Turn(t) = goto {
-                    let mut speed = goto.speed_limit(map);
+                    let mut speed = goto.speed_limit_at(map, now);
                     if let Some(s) = car.vehicle.max_speed {
                         speed = speed.min(s);
                     }
@@ -359,6 +407,7 @@ impl DrivingSimState {
                 // We do NOT need to update the follower. If they were Queued, they'll remain that
                 // way, until laggy_head is None.
 
+                record_emissions(car, now, &self.emission_factors, &mut self.events);
                 let last_step =
                     car.router
                         .advance(&car.vehicle, parking, map, car.trip, &mut self.events);
@@ -405,6 +454,285 @@ impl DrivingSimState {
         false
     }
 
+    // An incident just closed something. Everybody with a path through it tries to find a way
+    // around now, instead of when they reach the closure.
+    pub fn detour_around_closures(
+        &mut self,
+        now: Time,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        for car in self.cars.values_mut() {
+            if detour(car, intersections, map, &mut self.events) {
+                // They might be waiting to be woken up for their old turn.
+                if let CarState::WaitingToAdvance = car.state {
+                    scheduler.update(now, Command::UpdateCar(car.vehicle.id));
+                }
+            }
+        }
+    }
+
+    // A cruising ride-hail vehicle was just assigned a request; head to the pickup now.
+    pub fn reroute_ride_hail(
+        &mut self,
+        id: CarID,
+        now: Time,
+        ride_hail: &mut RideHailSimState,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) {
+        // Not spawned yet
+        let car = match self.cars.get_mut(&id) {
+            Some(c) => c,
+            None => {
+                return;
+            }
+        };
+        let new_router = match ride_hail.reroute_assigned(
+            id,
+            car.router.get_path(),
+            car.router.get_end_dist(),
+            map,
+        ) {
+            Some(r) => r,
+            None => {
+                return;
+            }
+        };
+        assert_eq!(car.router.head(), new_router.head());
+        intersections.cancel_request(AgentID::Car(id), car.router.next().as_turn());
+        car.router = new_router;
+        self.events
+            .push(Event::PathAmended(car.router.get_path().clone()));
+        // They might be waiting to be woken up for their old turn.
+        if let CarState::WaitingToAdvance = car.state {
+            scheduler.update(now, Command::UpdateCar(id));
+        }
+    }
+
+    // Queued behind somebody stopped in the middle of the lane, like a bus at a stop or somebody
+    // parking?
+    fn blocked_by_stopped_leader(&self, id: CarID) -> bool {
+        let car = match self.cars.get(&id) {
+            Some(c) => c,
+            None => {
+                return false;
+            }
+        };
+        match car.state {
+            CarState::Queued => {}
+            _ => {
+                return false;
+            }
+        }
+        if car.router.last_step() {
+            return false;
+        }
+        let queue = &self.queues[&car.router.head()];
+        let idx = queue.cars.iter().position(|c| *c == id).unwrap();
+        if idx == 0 {
+            return false;
+        }
+        match self.cars.get(&queue.cars[idx - 1]).map(|c| &c.state) {
+            Some(CarState::Idling(_, _))
+            | Some(CarState::Parking(_, _, _))
+            | Some(CarState::Unparking(_, _, _)) => true,
+            _ => false,
+        }
+    }
+
+    // The car just stopped; if they're blocking somebody, let them try to pull around.
+    fn wake_follower_to_change_lanes(&self, id: CarID, now: Time, scheduler: &mut Scheduler) {
+        let queue = &self.queues[&self.cars[&id].router.head()];
+        let idx = queue.cars.iter().position(|c| *c == id).unwrap();
+        if let Some(follower) = queue.cars.get(idx + 1) {
+            if self.blocked_by_stopped_leader(*follower) {
+                scheduler.update(now, Command::UpdateCar(*follower));
+            }
+        }
+    }
+
+    // Something changed on this lane, so a gap might've opened up. Wake up anybody stuck in an
+    // adjacent lane.
+    fn wake_lane_changers(&self, lane: LaneID, now: Time, map: &Map, scheduler: &mut Scheduler) {
+        for adj in map.get_parent(lane).adjacent_lanes(lane) {
+            if let Some(queue) = self.queues.get(&Traversable::Lane(adj)) {
+                for id in &queue.cars {
+                    if self.blocked_by_stopped_leader(*id) {
+                        scheduler.update(now, Command::UpdateCar(*id));
+                    }
+                }
+            }
+        }
+    }
+
+    // Stuck behind somebody stopped in the middle of the lane? Pull around them into an adjacent
+    // lane, if there's a big enough gap. If not, wait to be woken up when something changes.
+    fn try_to_change_lanes(
+        &mut self,
+        id: CarID,
+        now: Time,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let car = &self.cars[&id];
+        let from = match car.router.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return;
+            }
+        };
+        let next_turn = match car.router.next() {
+            Traversable::Turn(t) => t,
+            Traversable::Lane(_) => {
+                return;
+            }
+        };
+        let queue = &self.queues[&Traversable::Lane(from)];
+        let idx = queue.cars.iter().position(|c| *c == id).unwrap();
+
+        // Only consider lanes that lead to the same place.
+        let candidates: Vec<(LaneID, TurnID)> = map
+            .get_parent(from)
+            .adjacent_lanes(from)
+            .into_iter()
+            .filter_map(|l| {
+                let turn = TurnID {
+                    parent: next_turn.parent,
+                    src: l,
+                    dst: next_turn.dst,
+                };
+                map.maybe_get_t(turn).map(|_| (l, turn))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        // Wait until the back of the car is all the way in this lane. update_laggy_head wakes us up
+        // then.
+        if car.last_steps.is_empty() {
+            let our_dist = queue.get_car_positions(now, &self.cars, &self.queues)[idx].1;
+            for (lane, turn) in candidates {
+                let target = &self.queues[&Traversable::Lane(lane)];
+                if our_dist > target.geom_len || !target.room_for_car(car) {
+                    continue;
+                }
+                // Gap acceptance, leaving FOLLOWING_DISTANCE in front and behind
+                if let Some(new_idx) = target.get_idx_to_insert_car(
+                    our_dist,
+                    car.vehicle.length,
+                    now,
+                    &self.cars,
+                    &self.queues,
+                ) {
+                    self.change_lanes(
+                        id,
+                        our_dist,
+                        lane,
+                        turn,
+                        new_idx,
+                        now,
+                        map,
+                        intersections,
+                        scheduler,
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    fn change_lanes(
+        &mut self,
+        id: CarID,
+        our_dist: Distance,
+        lane: LaneID,
+        turn: TurnID,
+        idx: usize,
+        now: Time,
+        map: &Map,
+        intersections: &mut IntersectionSimState,
+        scheduler: &mut Scheduler,
+    ) {
+        let mut car = self.cars.remove(&id).unwrap();
+        let from = car.router.head().as_lane();
+        // Somebody might've been stuck behind us too.
+        self.wake_up_follower(&car, our_dist, now, map, scheduler);
+        {
+            let old_queue = self.queues.get_mut(&Traversable::Lane(from)).unwrap();
+            old_queue.cars.retain(|c| *c != id);
+            old_queue.free_reserved_space(&car);
+        }
+        intersections.space_freed(now, map.get_l(from).src_i, scheduler, map);
+        self.wake_lane_changers(from, now, map, scheduler);
+        {
+            let new_queue = self.queues.get_mut(&Traversable::Lane(lane)).unwrap();
+            new_queue.cars.insert(idx, id);
+            new_queue.reserved_length += car.vehicle.length + FOLLOWING_DISTANCE;
+        }
+
+        record_emissions(&car, now, &self.emission_factors, &mut self.events);
+        car.router.change_lanes_midblock(lane, turn, map);
+        car.state = car.crossing_state(our_dist, now, map);
+        car.blocked_since = None;
+        scheduler.update(car.state.get_end_time(), Command::UpdateCar(id));
+        self.events.push(Event::CarChangedLanes(id, from, lane));
+        self.cars.insert(id, car);
+    }
+
+    // A car that stopped in the middle of a lane is moving again. Update their follower, so they
+    // know.
+    fn wake_up_follower(
+        &mut self,
+        car: &Car,
+        dist: Distance,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let queue = &self.queues[&car.router.head()];
+        let idx = queue
+            .cars
+            .iter()
+            .position(|c| *c == car.vehicle.id)
+            .unwrap();
+        if idx != queue.cars.len() - 1 {
+            let mut follower = self.cars.get_mut(&queue.cars[idx + 1]).unwrap();
+            match follower.state {
+                CarState::Queued => {
+                    // If they're on their last step, they might be ending early and not
+                    // right behind us.
+                    if !follower.router.last_step() {
+                        record_emissions(follower, now, &self.emission_factors, &mut self.events);
+                        follower.state = follower.crossing_state(
+                            // Since the follower was Queued, this must be where they are.
+                            dist - car.vehicle.length - FOLLOWING_DISTANCE,
+                            now,
+                            map,
+                        );
+                        follower.blocked_since = None;
+                        scheduler.update(
+                            follower.state.get_end_time(),
+                            Command::UpdateCar(follower.vehicle.id),
+                        );
+                    }
+                }
+                CarState::WaitingToAdvance => unreachable!(),
+                // They weren't blocked. Note that there's no way the Crossing state could jump
+                // forwards here; the leader is still in front of them.
+                CarState::Crossing(_, _)
+                | CarState::Unparking(_, _, _)
+                | CarState::Parking(_, _, _)
+                | CarState::Idling(_, _)
+                | CarState::Yielding(_, _) => {}
+            }
+        }
+    }
+
     // Returns true if the car survives.
     fn update_car_with_distances(
         &mut self,
@@ -416,6 +744,7 @@ impl DrivingSimState {
         trips: &mut TripManager,
         scheduler: &mut Scheduler,
         transit: &mut TransitSimState,
+        ride_hail: &mut RideHailSimState,
         walking: &mut WalkingSimState,
         intersections: &mut IntersectionSimState,
     ) -> bool {
@@ -426,11 +755,13 @@ impl DrivingSimState {
         let our_dist = dists[idx].1;
 
         // Just two cases here. In all cases, we leave the Queued state.
+        record_emissions(car, now, &self.emission_factors, &mut self.events);
         car.blocked_since = None;
         match car.state {
             CarState::Crossing(_, _)
             | CarState::Unparking(_, _, _)
             | CarState::Idling(_, _)
+            | CarState::Yielding(_, _)
             | CarState::WaitingToAdvance => unreachable!(),
             CarState::Queued => {
                 match car.router.maybe_handle_end(
@@ -487,6 +818,44 @@ impl DrivingSimState {
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         return true;
                     }
+                    Some(ActionAtEnd::DoubleParkForDelivery) => {
+                        // Block the lane for the delivery, then keep going from here.
+                        if let Some((router, dwell)) = trips.truck_double_parked(
+                            now,
+                            car.vehicle.id,
+                            Position::new(car.router.head().as_lane(), our_dist),
+                            map,
+                        ) {
+                            car.router = router;
+                            self.events
+                                .push(Event::PathAmended(car.router.get_path().clone()));
+                            car.state =
+                                CarState::Idling(our_dist, TimeInterval::new(now, now + dwell));
+                            scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            return true;
+                        }
+                    }
+                    Some(ActionAtEnd::RideHailStop) => {
+                        let (maybe_router, dwell) = ride_hail.vehicle_arrived(
+                            now,
+                            car.vehicle.id,
+                            Position::new(car.router.head().as_lane(), our_dist),
+                            trips,
+                            scheduler,
+                            map,
+                        );
+                        // With nowhere to go, just stay put and check in again later.
+                        if let Some(router) = maybe_router {
+                            car.router = router;
+                            self.events
+                                .push(Event::PathAmended(car.router.get_path().clone()));
+                        }
+                        car.state = CarState::Idling(our_dist, TimeInterval::new(now, now + dwell));
+                        scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        return true;
+                    }
                     None => {
                         scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -581,6 +950,9 @@ impl DrivingSimState {
             };
             intersections.space_freed(now, i, scheduler, map);
         }
+        if let Traversable::Lane(l) = car.router.head() {
+            self.wake_lane_changers(l, now, map, scheduler);
+        }
 
         // We might be vanishing while partly clipping into other stuff.
         self.clear_last_steps(now, car, intersections, scheduler, map);
@@ -601,6 +973,7 @@ impl DrivingSimState {
                     // by leader yet. In that case, recalculating their Crossing state is a
                     // no-op. But if they were blocked, then this will prevent them from
                     // jumping forwards.
+                    record_emissions(follower, now, &self.emission_factors, &mut self.events);
                     follower.state = follower.crossing_state(follower_dist, now, map);
                     follower.blocked_since = None;
                     scheduler.update(
@@ -611,12 +984,98 @@ impl DrivingSimState {
                 // They weren't blocked
                 CarState::Unparking(_, _, _)
                 | CarState::Parking(_, _, _)
-                | CarState::Idling(_, _) => {}
+                | CarState::Idling(_, _)
+                | CarState::Yielding(_, _) => {}
                 CarState::WaitingToAdvance => unreachable!(),
             }
         }
     }
 
+    // A pedestrian wants to cross these lanes mid-block at the given distances, finishing at
+    // 'until'. Cars that would reach them in time stop and wait. If somebody is already in the way
+    // or too close to stop, nothing changes and this returns false; the pedestrian should find
+    // another gap.
+    pub fn yield_to_pedestrian(
+        &mut self,
+        ped: PedestrianID,
+        crossing: Vec<(LaneID, Distance)>,
+        until: Time,
+        now: Time,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let mut must_yield = Vec::new();
+        for (l, crossing_dist) in crossing {
+            let queue = &self.queues[&Traversable::Lane(l)];
+            // Nobody can stop past the back of the car in front of them.
+            let mut leader_back: Option<Distance> = None;
+            for (id, front) in queue.get_car_positions(now, &self.cars, &self.queues) {
+                let car = &self.cars[&id];
+                let limit = leader_back;
+                leader_back = Some(front - car.vehicle.length - FOLLOWING_DISTANCE);
+                if front - car.vehicle.length > crossing_dist {
+                    // Already past
+                    continue;
+                }
+                if front >= crossing_dist {
+                    return false;
+                }
+                // Anybody stopped or creeping out of a parking spot isn't a threat.
+                if let CarState::Crossing(ref time_int, ref dist_int) = car.state {
+                    if time_int.start == time_int.end || dist_int.length() == Distance::ZERO {
+                        continue;
+                    }
+                    let speed =
+                        Speed::from_dist_time(dist_int.length(), time_int.end - time_int.start);
+                    if now + (crossing_dist - front) / speed >= until {
+                        continue;
+                    }
+                    let stop = if self.kinematics {
+                        // Brake as hard as the vehicle can
+                        let v = speed.inner_meters_per_second();
+                        let stop = front + Distance::meters(v * v / (2.0 * car.vehicle.max_decel));
+                        if stop >= crossing_dist {
+                            // Too close to stop
+                            return false;
+                        }
+                        limit.map(|d| d.min(stop)).unwrap_or(stop).max(front)
+                    } else {
+                        if crossing_dist - front < MIN_DIST_TO_YIELD {
+                            return false;
+                        }
+                        // Without kinematics, cars just stop where they are.
+                        front
+                    };
+                    leader_back = Some(stop - car.vehicle.length - FOLLOWING_DISTANCE);
+                    must_yield.push((id, front, stop, speed));
+                }
+            }
+        }
+
+        for (id, front, stop, speed) in must_yield {
+            let car = self.cars.get_mut(&id).unwrap();
+            record_emissions(car, now, &self.emission_factors, &mut self.events);
+            if stop > front {
+                // Slow down at a constant rate, then wait once stopped.
+                let stopped_at = now + (stop - front) * 2.0 / speed;
+                car.state = CarState::Crossing(
+                    TimeInterval::new(now, stopped_at),
+                    DistanceInterval::new_driving(front, stop),
+                );
+                car.end_of_crossing = Some((stopped_at, Speed::ZERO));
+                // They might already be stopping for somebody else
+                let wait_until = car.yield_at.map(|(_, t)| t.max(until)).unwrap_or(until);
+                car.yield_at = Some((stop, wait_until));
+                scheduler.update(stopped_at, Command::UpdateCar(id));
+            } else {
+                car.state = CarState::Yielding(front, TimeInterval::new(now, until));
+                car.blocked_since = Some(now);
+                scheduler.update(until, Command::UpdateCar(id));
+            }
+            self.events.push(Event::CarYieldedToPed(id, ped));
+        }
+        true
+    }
+
     pub fn update_laggy_head(
         &mut self,
         id: CarID,
@@ -675,6 +1134,10 @@ impl DrivingSimState {
         let mut car = self.cars.remove(&id).unwrap();
         self.clear_last_steps(now, &mut car, intersections, scheduler, map);
         self.cars.insert(id, car);
+        // Now that the back of the car is all the way in, maybe it can change lanes.
+        if self.blocked_by_stopped_leader(id) {
+            scheduler.update(now, Command::UpdateCar(id));
+        }
     }
 
     fn clear_last_steps(
@@ -688,6 +1151,7 @@ impl DrivingSimState {
         // If we were blocking a few short lanes, should be better now. Very last one might have
         // somebody to wake up.
         let last_steps: Vec<Traversable> = car.last_steps.drain(..).collect();
+        let mut freed_lanes = Vec::new();
 
         for (idx, on) in last_steps.iter().enumerate() {
             let old_queue = self.queues.get_mut(&on).unwrap();
@@ -706,6 +1170,7 @@ impl DrivingSimState {
                 Traversable::Lane(l) => {
                     old_queue.free_reserved_space(car);
                     intersections.space_freed(now, map.get_l(*l).src_i, scheduler, map);
+                    freed_lanes.push(*l);
                 }
             }
 
@@ -736,13 +1201,17 @@ impl DrivingSimState {
                         CarState::Crossing(_, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
-                        | CarState::Idling(_, _) => {}
+                        | CarState::Idling(_, _)
+                        | CarState::Yielding(_, _) => {}
                     }
                 }
             } else {
                 assert!(self.queues[&on].cars.is_empty());
             }
         }
+        for l in freed_lanes {
+            self.wake_lane_changers(l, now, map, scheduler);
+        }
     }
 
     pub fn get_unzoomed_agents(&self, now: Time, map: &Map) -> Vec<UnzoomedAgent> {
@@ -962,7 +1431,88 @@ impl DrivingSimState {
         }
     }
 
+    pub fn diff_cars(&self, other: &DrivingSimState) -> Vec<(CarID, String)> {
+        diff_maps(&self.cars, &other.cars, |car| format!("{:?}", car.state))
+    }
+
+    pub fn diff_queues(&self, other: &DrivingSimState) -> Vec<(Traversable, String)> {
+        diff_maps(&self.queues, &other.queues, |q| {
+            format!("cars {:?}, laggy head {:?}", q.cars, q.laggy_head)
+        })
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
 }
+
+// If something along the rest of the car's path is closed, try to find a way around. The car's
+// current lane doesn't count; they're already on it. Returns true if the path changed.
+fn detour(
+    car: &mut Car,
+    intersections: &mut IntersectionSimState,
+    map: &Map,
+    events: &mut Vec<Event>,
+) -> bool {
+    let path = car.router.get_path();
+    let start = match path.current_step() {
+        PathStep::Lane(l) => l,
+        _ => {
+            return false;
+        }
+    };
+    // Closed lanes can only be entered by a turn, so just check those.
+    if path.is_last_step()
+        || !path.get_steps().iter().any(|step| match step {
+            PathStep::Turn(t) => intersections.is_turn_closed(*t),
+            _ => false,
+        })
+    {
+        return false;
+    }
+    let old_turn = path.next_step().as_turn();
+    let req = PathRequest {
+        start: Position::new(start, Distance::ZERO),
+        end: Position::new(path.last_step().as_lane(), car.router.get_end_dist()),
+        constraints: car.vehicle.vehicle_type.to_constraints(),
+    };
+    let new_path = match map.pathfind_avoiding(req, |t| intersections.is_turn_closed(t)) {
+        Some(p) => p,
+        None => {
+            return false;
+        }
+    };
+    intersections.cancel_request(AgentID::Car(car.vehicle.id), old_turn);
+    car.router.detour(new_path);
+    events.push(Event::PathAmended(car.router.get_path().clone()));
+    true
+}
+
+// What the car produced in its current state, up to now. Call this right before the car changes
+// state. Cars stopped in a queue or yielding are idling since blocked_since.
+fn record_emissions(car: &Car, now: Time, factors: &EmissionFactors, events: &mut Vec<Event>) {
+    let (dist, dt) = match car.state {
+        CarState::Crossing(ref time_int, ref dist_int) => (
+            dist_int.lerp(time_int.percent_clamp_end(now)) - dist_int.start,
+            now.min(time_int.end) - time_int.start,
+        ),
+        CarState::Unparking(_, _, ref time_int)
+        | CarState::Parking(_, _, ref time_int)
+        | CarState::Idling(_, ref time_int) => {
+            (Distance::ZERO, now.min(time_int.end) - time_int.start)
+        }
+        CarState::Queued | CarState::WaitingToAdvance | CarState::Yielding(_, _) => (
+            Distance::ZERO,
+            car.blocked_since.map(|t| now - t).unwrap_or(Duration::ZERO),
+        ),
+    };
+    let emissions = factors.calculate(&car.vehicle, dist, dt);
+    if !emissions.is_zero() {
+        events.push(Event::EmissionsProduced(
+            car.vehicle.id,
+            car.trip,
+            car.router.head(),
+            emissions,
+        ));
+    }
+}
@@ -1,24 +1,32 @@
 use crate::mechanics::car::Car;
 use crate::mechanics::Queue;
-use crate::{AgentID, Command, Event, Scheduler, Speed};
+use crate::{AgentID, Blockage, Command, Event, NearMiss, Scheduler, Speed, VehicleType};
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use derivative::Derivative;
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, Map, TurnID, TurnPriority,
-    TurnType,
+    ControlStopSign, ControlTrafficSignal, IntersectionID, LaneID, LaneType, Map, Phase,
+    Traversable, TurnID, TurnPriority, TurnType,
 };
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+// Starting a yield turn sooner than this after a conflicting turn finished is a near miss.
+const MIN_SAFE_GAP_TO_PEDESTRIAN: Duration = Duration::const_seconds(3.0);
+const MIN_SAFE_GAP_TO_VEHICLE: Duration = Duration::const_seconds(1.5);
+// Bikes mixing with traffic at least this fast (30mph) is a near miss.
+const FAST_TRAFFIC: Speed = Speed::const_meters_per_second(13.4);
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct IntersectionSimState {
     state: BTreeMap<IntersectionID, State>,
     use_freeform_policy_everywhere: bool,
     force_queue_entry: bool,
+    // Closed by incidents. These count, since incidents can overlap.
+    closed_lanes: BTreeMap<LaneID, usize>,
+    closed_intersections: BTreeMap<IntersectionID, usize>,
     events: Vec<Event>,
 }
 
@@ -33,6 +41,10 @@ struct State {
         deserialize_with = "deserialize_btreemap"
     )]
     waiting: BTreeMap<Request, Time>,
+    // Only for traffic signals. Skipping push-button phases puts the cycle ahead of the real time.
+    skipped: Duration,
+    // Turns finished within the last MIN_SAFE_GAP_TO_PEDESTRIAN, oldest first
+    recently_finished: VecDeque<(Request, Time)>,
 }
 
 impl IntersectionSimState {
@@ -46,19 +58,25 @@ impl IntersectionSimState {
             state: BTreeMap::new(),
             use_freeform_policy_everywhere,
             force_queue_entry: disable_block_the_box,
+            closed_lanes: BTreeMap::new(),
+            closed_intersections: BTreeMap::new(),
             events: Vec::new(),
         };
         for i in map.all_intersections() {
-            sim.state.insert(
-                i.id,
-                State {
-                    id: i.id,
-                    accepted: BTreeSet::new(),
-                    waiting: BTreeMap::new(),
-                },
-            );
+            let mut state = State {
+                id: i.id,
+                accepted: BTreeSet::new(),
+                waiting: BTreeMap::new(),
+                skipped: Duration::ZERO,
+                recently_finished: VecDeque::new(),
+            };
             if i.is_traffic_signal() && !use_freeform_policy_everywhere {
+                // Nobody's waiting yet, so the day can't start with a push-button phase.
+                state.skip_push_button_phases(map.get_traffic_signal(i.id), Time::START_OF_DAY);
+                sim.state.insert(i.id, state);
                 sim.update_intersection(Time::START_OF_DAY, i.id, map, scheduler);
+            } else {
+                sim.state.insert(i.id, state);
             }
         }
         sim
@@ -80,8 +98,10 @@ impl IntersectionSimState {
         map: &Map,
     ) {
         let state = self.state.get_mut(&turn.parent).unwrap();
-        assert!(state.accepted.remove(&Request { agent, turn }));
+        let req = Request { agent, turn };
+        assert!(state.accepted.remove(&req));
         if map.get_t(turn).turn_type != TurnType::SharedSidewalkCorner {
+            state.recently_finished.push_back((req, now));
             self.wakeup_waiting(now, turn.parent, scheduler, map);
         }
     }
@@ -125,9 +145,9 @@ impl IntersectionSimState {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let (_, phase, _) = signal.current_phase_and_remaining_time(now);
+            let (_, phase, remaining) = self.state[&i].current_phase(signal, now);
             for (req, _) in all {
-                match phase.get_priority_of_turn(req.turn, signal) {
+                match phase.get_priority_of_turn_at(req.turn, signal, phase.duration - remaining) {
                     TurnPriority::Protected => {
                         protected.push(req);
                     }
@@ -166,19 +186,90 @@ impl IntersectionSimState {
         }
     }
 
-    // This is only triggered for traffic signals.
+    pub fn close(&mut self, blockage: &Blockage) {
+        match blockage {
+            Blockage::Lanes(ref lanes) => {
+                for l in lanes {
+                    *self.closed_lanes.entry(*l).or_insert(0) += 1;
+                }
+            }
+            Blockage::Intersection(i) => {
+                *self.closed_intersections.entry(*i).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn reopen(&mut self, blockage: &Blockage, now: Time, scheduler: &mut Scheduler, map: &Map) {
+        // Everybody who got turned away is waiting for a wakeup.
+        let mut wakeup = BTreeSet::new();
+        match blockage {
+            Blockage::Lanes(ref lanes) => {
+                for l in lanes {
+                    decrement(&mut self.closed_lanes, *l);
+                    // Pedestrians can enter sidewalks from either end
+                    let lane = map.get_l(*l);
+                    wakeup.insert(lane.src_i);
+                    wakeup.insert(lane.dst_i);
+                }
+            }
+            Blockage::Intersection(i) => {
+                decrement(&mut self.closed_intersections, *i);
+                wakeup.insert(*i);
+            }
+        }
+        for i in wakeup {
+            if !map.get_i(i).is_border() {
+                self.wakeup_waiting(now, i, scheduler, map);
+            }
+        }
+    }
+
+    // Nobody can start a turn through a closed intersection or into a closed lane.
+    pub fn is_turn_closed(&self, t: TurnID) -> bool {
+        self.closed_intersections.contains_key(&t.parent) || self.closed_lanes.contains_key(&t.dst)
+    }
+
+    pub fn any_closures(&self) -> bool {
+        !self.closed_lanes.is_empty() || !self.closed_intersections.is_empty()
+    }
+
+    // This is only triggered for traffic signals, when a phase or a leading pedestrian interval
+    // ends.
     pub fn update_intersection(
-        &self,
+        &mut self,
         now: Time,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
     ) {
+        let signal = map.get_traffic_signal(id);
+        self.state
+            .get_mut(&id)
+            .unwrap()
+            .skip_push_button_phases(signal, now);
+
         self.wakeup_waiting(now, id, scheduler, map);
-        let (_, _, remaining) = map
-            .get_traffic_signal(id)
-            .current_phase_and_remaining_time(now);
-        scheduler.push(now + remaining, Command::UpdateIntersection(id));
+        let (_, phase, remaining) = self.state[&id].current_phase(signal, now);
+        let time_into_phase = phase.duration - remaining;
+        if time_into_phase < phase.leading_ped_interval {
+            // Vehicles held back for pedestrians need to be woken up.
+            scheduler.push(
+                now + (phase.leading_ped_interval - time_into_phase),
+                Command::UpdateIntersection(id),
+            );
+        } else {
+            scheduler.push(now + remaining, Command::UpdateIntersection(id));
+        }
+    }
+
+    // Accounts for skipped push-button phases, so this may differ from the signal's fixed cycle.
+    pub fn current_signal_phase<'a>(
+        &self,
+        id: IntersectionID,
+        now: Time,
+        map: &'a Map,
+    ) -> (usize, &'a Phase, Duration) {
+        self.state[&id].current_phase(map.get_traffic_signal(id), now)
     }
 
     // For cars: The head car calls this when they're at the end of the lane WaitingToAdvance. If
@@ -200,8 +291,13 @@ impl IntersectionSimState {
     ) -> bool {
         //let debug = turn.parent == IntersectionID(64);
         let req = Request { agent, turn };
+        let closed = self.is_turn_closed(turn);
         let state = self.state.get_mut(&turn.parent).unwrap();
         state.waiting.entry(req.clone()).or_insert(now);
+        // Keep waiting; reopen wakes everybody up.
+        if closed {
+            return false;
+        }
 
         let allowed = if self.use_freeform_policy_everywhere {
             state.freeform_policy(&req, map)
@@ -238,6 +334,10 @@ impl IntersectionSimState {
             self.events
                 .push(Event::IntersectionDelayMeasured(turn.parent, delay));
         }
+        let freeform = self.use_freeform_policy_everywhere;
+        for near_miss in state.find_near_misses(&req, now, map, freeform) {
+            self.events.push(Event::NearMiss(turn.parent, agent, near_miss));
+        }
         state.accepted.insert(req);
         /*if debug {
             println!("{}: {} going!", now, agent)
@@ -270,6 +370,105 @@ impl IntersectionSimState {
 }
 
 impl State {
+    fn current_phase<'a>(
+        &self,
+        signal: &'a ControlTrafficSignal,
+        now: Time,
+    ) -> (usize, &'a Phase, Duration) {
+        signal.current_phase_and_remaining_time(now + self.skipped)
+    }
+
+    // Skip push-button phases if nobody's waiting for their crosswalks. Validation guarantees at
+    // least one phase isn't push-button.
+    fn skip_push_button_phases(&mut self, signal: &ControlTrafficSignal, now: Time) {
+        loop {
+            let (_, phase, remaining) = self.current_phase(signal, now);
+            if !phase.push_button
+                || self.waiting.keys().any(|req| {
+                    phase.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected
+                })
+            {
+                break;
+            }
+            self.skipped += remaining;
+        }
+    }
+
+    // Only vehicles starting a turn can cause near misses. Pedestrians get the benefit of the
+    // doubt.
+    fn find_near_misses(
+        &mut self,
+        req: &Request,
+        now: Time,
+        map: &Map,
+        freeform: bool,
+    ) -> Vec<NearMiss> {
+        while let Some((_, t)) = self.recently_finished.front() {
+            if now - *t < MIN_SAFE_GAP_TO_PEDESTRIAN {
+                break;
+            }
+            self.recently_finished.pop_front();
+        }
+
+        let vehicle_type = match req.agent {
+            AgentID::Car(c) => c.1,
+            AgentID::Pedestrian(_) => {
+                return Vec::new();
+            }
+        };
+        let turn = map.get_t(req.turn);
+        let mut results = Vec::new();
+
+        if vehicle_type == VehicleType::Bike
+            && map.get_l(req.turn.dst).lane_type == LaneType::Driving
+            && Traversable::Lane(req.turn.dst).speed_limit_at(map, now) >= FAST_TRAFFIC
+        {
+            results.push(NearMiss::BikeInFastTraffic);
+        }
+
+        // Everything is unprotected with the freeform policy.
+        let priority = if freeform {
+            TurnPriority::Yield
+        } else if let Some(ref signal) = map.maybe_get_traffic_signal(self.id) {
+            let (_, phase, remaining_phase_time) = self.current_phase(signal, now);
+            phase.get_priority_of_turn_at(req.turn, signal, phase.duration - remaining_phase_time)
+        } else if let Some(ref sign) = map.maybe_get_stop_sign(self.id) {
+            sign.get_priority(req.turn, map)
+        } else {
+            TurnPriority::Priority
+        };
+        if priority != TurnPriority::Yield {
+            return results;
+        }
+
+        // Just report the closest call of each kind.
+        let mut gap_to_ped: Option<Duration> = None;
+        let mut gap_to_vehicle: Option<Duration> = None;
+        for (other, t) in &self.recently_finished {
+            if other.agent == req.agent || !map.get_t(other.turn).conflicts_with(turn) {
+                continue;
+            }
+            let gap = now - *t;
+            match other.agent {
+                AgentID::Pedestrian(_) => {
+                    gap_to_ped = Some(gap_to_ped.map(|g| g.min(gap)).unwrap_or(gap));
+                }
+                AgentID::Car(_) => {
+                    if gap < MIN_SAFE_GAP_TO_VEHICLE {
+                        gap_to_vehicle = Some(gap_to_vehicle.map(|g| g.min(gap)).unwrap_or(gap));
+                    }
+                }
+            }
+        }
+        if let Some(gap) = gap_to_ped {
+            results.push(NearMiss::YieldedLateToPedestrian(gap));
+        }
+        if let Some(gap) = gap_to_vehicle {
+            results.push(NearMiss::ShortGapAccepted(gap));
+        }
+        results
+    }
+
     fn any_accepted_conflict_with(&self, t: TurnID, map: &Map) -> bool {
         let turn = map.get_t(t);
         self.accepted
@@ -347,10 +546,11 @@ impl State {
             return true;
         }
 
-        let (_, phase, remaining_phase_time) = signal.current_phase_and_remaining_time(now);
+        let (_, phase, remaining_phase_time) = self.current_phase(signal, now);
 
-        // Can't go at all this phase.
-        let our_priority = phase.get_priority_of_turn(req.turn, signal);
+        // Can't go at all this phase, or pedestrians have a head start.
+        let our_priority =
+            phase.get_priority_of_turn_at(req.turn, signal, phase.duration - remaining_phase_time);
         if our_priority == TurnPriority::Banned {
             return false;
         }
@@ -398,6 +598,14 @@ impl State {
     }
 }
 
+fn decrement<K: Ord>(counts: &mut BTreeMap<K, usize>, key: K) {
+    let cnt = counts.get_mut(&key).unwrap();
+    *cnt -= 1;
+    if *cnt == 0 {
+        counts.remove(&key);
+    }
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
 struct Request {
     agent: AgentID,
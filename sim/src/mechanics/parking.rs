@@ -38,6 +38,14 @@ pub struct ParkingSimState {
     )]
     driving_to_parking_lanes: MultiMap<LaneID, LaneID>,
 
+    // Loading zones work like on-street parking, but only trucks making deliveries use them.
+    loading_zones: BTreeMap<LaneID, ParkingLane>,
+    #[serde(
+        serialize_with = "serialize_multimap",
+        deserialize_with = "deserialize_multimap"
+    )]
+    driving_to_loading_zones: MultiMap<LaneID, LaneID>,
+
     // Off-street specific
     num_spots_per_offstreet: BTreeMap<BuildingID, usize>,
     #[serde(
@@ -60,13 +68,20 @@ impl ParkingSimState {
 
             onstreet_lanes: BTreeMap::new(),
             driving_to_parking_lanes: MultiMap::new(),
+            loading_zones: BTreeMap::new(),
+            driving_to_loading_zones: MultiMap::new(),
             num_spots_per_offstreet: BTreeMap::new(),
             driving_to_offstreet: MultiMap::new(),
         };
         for l in map.all_lanes() {
             if let Some(lane) = ParkingLane::new(l, map, timer) {
-                sim.driving_to_parking_lanes.insert(lane.driving_lane, l.id);
-                sim.onstreet_lanes.insert(lane.parking_lane, lane);
+                if l.is_loading_zone() {
+                    sim.driving_to_loading_zones.insert(lane.driving_lane, l.id);
+                    sim.loading_zones.insert(lane.parking_lane, lane);
+                } else {
+                    sim.driving_to_parking_lanes.insert(lane.driving_lane, l.id);
+                    sim.onstreet_lanes.insert(lane.parking_lane, lane);
+                }
             }
         }
         for b in map.all_buildings() {
@@ -151,7 +166,11 @@ impl ParkingSimState {
 
     pub fn get_draw_cars(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput> {
         let mut cars = Vec::new();
-        if let Some(ref lane) = self.onstreet_lanes.get(&id) {
+        if let Some(ref lane) = self
+            .onstreet_lanes
+            .get(&id)
+            .or_else(|| self.loading_zones.get(&id))
+        {
             for spot in lane.spots() {
                 if let Some(car) = self.occupants.get(&spot) {
                     cars.push(self.get_draw_car(*car, map).unwrap());
@@ -165,7 +184,7 @@ impl ParkingSimState {
         let p = self.parked_cars.get(&id)?;
         match p.spot {
             ParkingSpot::Onstreet(lane, idx) => {
-                let front_dist = self.onstreet_lanes[&lane].dist_along_for_car(idx, &p.vehicle);
+                let front_dist = self.get_lane(lane).dist_along_for_car(idx, &p.vehicle);
                 Some(DrawCarInput {
                     id: p.vehicle.id,
                     waiting_for_turn: None,
@@ -269,10 +288,43 @@ impl ParkingSimState {
         Some((spot, self.spot_to_driving_pos(spot, vehicle, map)))
     }
 
+    // The free loading zone spot closest to the target, within max_dist of it. The truck must be
+    // able to reach it from the current position.
+    pub fn get_free_loading_spot(
+        &self,
+        current: Position,
+        target: Position,
+        max_dist: Distance,
+        vehicle: &Vehicle,
+        map: &Map,
+    ) -> Option<(ParkingSpot, Position)> {
+        let mut best: Option<(ParkingSpot, Position, Distance)> = None;
+        for l in self.driving_to_loading_zones.get(current.lane()) {
+            for spot in self.loading_zones[l].spots() {
+                if !self.is_free(spot) {
+                    continue;
+                }
+                let pos = self.spot_to_driving_pos(spot, vehicle, map);
+                if pos.dist_along() < current.dist_along() {
+                    continue;
+                }
+                let dist = if pos.dist_along() > target.dist_along() {
+                    pos.dist_along() - target.dist_along()
+                } else {
+                    target.dist_along() - pos.dist_along()
+                };
+                if dist <= max_dist && best.map(|(_, _, d)| dist < d).unwrap_or(true) {
+                    best = Some((spot, pos, dist));
+                }
+            }
+        }
+        best.map(|(spot, pos, _)| (spot, pos))
+    }
+
     pub fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position {
         match spot {
             ParkingSpot::Onstreet(l, idx) => {
-                let lane = &self.onstreet_lanes[&l];
+                let lane = self.get_lane(l);
                 Position::new(l, lane.dist_along_for_car(idx, vehicle)).equiv_pos(
                     lane.driving_lane,
                     vehicle.length,
@@ -286,18 +338,24 @@ impl ParkingSimState {
     pub fn spot_to_sidewalk_pos(&self, spot: ParkingSpot, map: &Map) -> Position {
         match spot {
             ParkingSpot::Onstreet(l, idx) => {
-                let lane = &self.onstreet_lanes[&l];
+                let lane = self.get_lane(l);
                 // Always centered in the entire parking spot
-                Position::new(
-                    l,
-                    lane.spot_dist_along[idx] - (map_model::PARKING_SPOT_LENGTH / 2.0),
+                Position::new(l, lane.spot_dist_along[idx] - (lane.spot_length / 2.0)).equiv_pos(
+                    lane.sidewalk,
+                    Distance::ZERO,
+                    map,
                 )
-                .equiv_pos(lane.sidewalk, Distance::ZERO, map)
             }
             ParkingSpot::Offstreet(b, _) => map.get_b(b).front_path.sidewalk,
         }
     }
 
+    fn get_lane(&self, l: LaneID) -> &ParkingLane {
+        self.onstreet_lanes
+            .get(&l)
+            .unwrap_or_else(|| &self.loading_zones[&l])
+    }
+
     pub fn tooltip_lines(&self, id: CarID) -> Option<Vec<String>> {
         let c = self.parked_cars.get(&id)?;
         Some(vec![format!(
@@ -360,21 +418,26 @@ struct ParkingLane {
     parking_lane: LaneID,
     driving_lane: LaneID,
     sidewalk: LaneID,
+    spot_length: Distance,
     // The front of the parking spot (farthest along the lane)
     spot_dist_along: Vec<Distance>,
 }
 
 impl ParkingLane {
     fn new(lane: &Lane, map: &Map, timer: &mut Timer) -> Option<ParkingLane> {
-        if lane.lane_type != LaneType::Parking {
-            return None;
-        }
+        let (spot_length, num_spots) = match lane.lane_type {
+            LaneType::Parking => (map_model::PARKING_SPOT_LENGTH, lane.number_parking_spots()),
+            LaneType::LoadingZone => (map_model::LOADING_SPOT_LENGTH, lane.number_loading_spots()),
+            _ => {
+                return None;
+            }
+        };
 
         let driving_lane = if let Some(l) = map.get_parent(lane.id).parking_to_driving(lane.id) {
             l
         } else {
             // Serious enough to blow up loudly.
-            panic!("Parking lane {} has no driving lane!", lane.id);
+            panic!("{:?} lane {} has no driving lane!", lane.lane_type, lane.id);
         };
         if map.get_l(driving_lane).parking_blackhole.is_some() {
             return None;
@@ -382,7 +445,10 @@ impl ParkingLane {
         let sidewalk = if let Ok(l) = map.find_closest_lane(lane.id, vec![LaneType::Sidewalk]) {
             l
         } else {
-            timer.warn(format!("Parking lane {} has no sidewalk!", lane.id));
+            timer.warn(format!(
+                "{:?} lane {} has no sidewalk!",
+                lane.lane_type, lane.id
+            ));
             return None;
         };
 
@@ -390,15 +456,16 @@ impl ParkingLane {
             parking_lane: lane.id,
             driving_lane,
             sidewalk,
-            spot_dist_along: (0..lane.number_parking_spots())
-                .map(|idx| map_model::PARKING_SPOT_LENGTH * (2.0 + idx as f64))
+            spot_length,
+            spot_dist_along: (0..num_spots)
+                .map(|idx| spot_length * (2.0 + idx as f64))
                 .collect(),
         })
     }
 
     fn dist_along_for_car(&self, spot_idx: usize, vehicle: &Vehicle) -> Distance {
         // Find the offset to center this particular car in the parking spot
-        self.spot_dist_along[spot_idx] - (map_model::PARKING_SPOT_LENGTH - vehicle.length) / 2.0
+        self.spot_dist_along[spot_idx] - (self.spot_length - vehicle.length) / 2.0
     }
 
     fn spots(&self) -> Vec<ParkingSpot> {
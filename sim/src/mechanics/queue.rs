@@ -115,6 +115,7 @@ impl Queue {
                 CarState::Unparking(front, _, _) => front,
                 CarState::Parking(front, _, _) => front,
                 CarState::Idling(front, _) => front,
+                CarState::Yielding(front, _) => front,
             };
 
             result.push((*id, front));
@@ -241,6 +242,9 @@ fn dump_cars(
             CarState::Idling(_, ref time_int) => {
                 println!("  Idling during {} .. {}", time_int.start, time_int.end);
             }
+            CarState::Yielding(_, ref time_int) => {
+                println!("  Yielding during {} .. {}", time_int.start, time_int.end);
+            }
         }
     }
     println!();
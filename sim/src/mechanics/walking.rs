@@ -1,17 +1,32 @@
 use crate::{
-    AgentID, AgentMetadata, Command, CreatePedestrian, DistanceInterval, DrawPedCrowdInput,
-    DrawPedestrianInput, Event, IntersectionSimState, ParkingSimState, ParkingSpot,
-    PedCrowdLocation, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval,
-    TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
+    diff_maps, AgentID, AgentMetadata, Command, CreatePedestrian, DistanceInterval,
+    DrawPedCrowdInput, DrawPedestrianInput, DrivingSimState, Event, IntersectionSimState,
+    ParkingSimState, ParkingSpot, PedCrowdLocation, PedestrianID, Scheduler, SidewalkPOI,
+    SidewalkSpot, TimeInterval, TransitSimState, TripID, TripManager, TripPositions, UnzoomedAgent,
 };
 use abstutil::{deserialize_multimap, serialize_multimap, MultiMap};
 use geom::{Distance, Duration, Line, PolyLine, Speed, Time};
-use map_model::{BuildingID, BusRouteID, Map, Path, PathStep, Traversable, LANE_THICKNESS};
+use map_model::{
+    BuildingID, BusRouteID, LaneID, Map, Path, PathStep, Traversable, TurnType, LANE_THICKNESS,
+};
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
+// All densities are pedestrians per square meter of sidewalk or crosswalk.
+// Nobody can move at all past this point.
+const JAM_DENSITY: f64 = 5.4;
+// Even in a packed crowd, people shuffle forward a little.
+const MIN_CROWD_SPEED_FACTOR: f64 = 0.1;
+// Past this, people wait on the sidewalk for the crosswalk to clear.
+const MAX_CROSSWALK_DENSITY: f64 = 2.0;
+// Nothing wakes up a pedestrian waiting for a crowded crosswalk, so they check again this often.
+const CROWDED_CROSSWALK_RETRY: Duration = Duration::const_seconds(1.0);
+// About 25 mph. Nobody crosses mid-block on anything faster.
+const MAX_JAYWALKING_SPEED_LIMIT: Speed = Speed::const_meters_per_second(11.2);
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct WalkingSimState {
@@ -23,14 +38,19 @@ pub struct WalkingSimState {
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
     events: Vec<Event>,
+
+    jaywalking_probability: f64,
+    crowding: bool,
 }
 
 impl WalkingSimState {
-    pub fn new() -> WalkingSimState {
+    pub fn new(jaywalking_probability: f64, crowding: bool) -> WalkingSimState {
         WalkingSimState {
             peds: BTreeMap::new(),
             peds_per_traversable: MultiMap::new(),
             events: Vec::new(),
+            jaywalking_probability,
+            crowding,
         }
     }
 
@@ -40,6 +60,7 @@ impl WalkingSimState {
         params: CreatePedestrian,
         map: &Map,
         scheduler: &mut Scheduler,
+        rng: &mut XorShiftRng,
     ) {
         let start_lane = params.start.sidewalk_pos.lane();
         assert_eq!(params.path.current_step().as_lane(), start_lane);
@@ -64,7 +85,15 @@ impl WalkingSimState {
             path: params.path,
             goal: params.goal,
             trip: params.trip,
+            jaywalker: self.jaywalking_probability > 0.0
+                && rng.gen_bool(self.jaywalking_probability.min(1.0)),
         };
+        let start = Traversable::Lane(params.start.sidewalk_pos.lane());
+        self.peds_per_traversable.insert(start, params.id);
+        let density = current_density(self.crowding, &self.peds_per_traversable, start, map);
+        if self.crowding {
+            self.events.push(Event::PedDensityMeasured(start, density));
+        }
         ped.state = match params.start.connection {
             SidewalkPOI::Building(b) | SidewalkPOI::ParkingSpot(ParkingSpot::Offstreet(b, _)) => {
                 PedState::LeavingBuilding(
@@ -77,15 +106,11 @@ impl WalkingSimState {
                 Line::new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
                 TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
             ),
-            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map),
+            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, density, map),
         };
 
         scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
         self.peds.insert(ped.id, ped);
-        self.peds_per_traversable.insert(
-            Traversable::Lane(params.start.sidewalk_pos.lane()),
-            params.id,
-        );
     }
 
     pub fn get_draw_ped(
@@ -110,6 +135,7 @@ impl WalkingSimState {
         now: Time,
         map: &Map,
         intersections: &mut IntersectionSimState,
+        driving: &mut DrivingSimState,
         parking: &ParkingSimState,
         scheduler: &mut Scheduler,
         trips: &mut TripManager,
@@ -185,6 +211,8 @@ impl WalkingSimState {
                         now,
                         map,
                         intersections,
+                        driving,
+                        self.crowding,
                         &mut self.peds_per_traversable,
                         &mut self.events,
                         scheduler,
@@ -202,6 +230,8 @@ impl WalkingSimState {
                     now,
                     map,
                     intersections,
+                    driving,
+                    self.crowding,
                     &mut self.peds_per_traversable,
                     &mut self.events,
                     scheduler,
@@ -211,8 +241,20 @@ impl WalkingSimState {
                 }
             }
             PedState::LeavingBuilding(b, _) => {
-                ped.state =
-                    ped.crossing_state(map.get_b(b).front_path.sidewalk.dist_along(), now, map);
+                let density = current_density(
+                    self.crowding,
+                    &self.peds_per_traversable,
+                    ped.path.current_step().as_traversable(),
+                    map,
+                );
+                ped.state = ped.start_on_sidewalk(
+                    map.get_b(b).front_path.sidewalk.dist_along(),
+                    now,
+                    density,
+                    driving,
+                    scheduler,
+                    map,
+                );
                 scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
             PedState::EnteringBuilding(bldg, _) => {
@@ -228,7 +270,43 @@ impl WalkingSimState {
                 self.peds.remove(&id);
             }
             PedState::FinishingBiking(ref spot, _, _) => {
-                ped.state = ped.crossing_state(spot.sidewalk_pos.dist_along(), now, map);
+                let density = current_density(
+                    self.crowding,
+                    &self.peds_per_traversable,
+                    ped.path.current_step().as_traversable(),
+                    map,
+                );
+                ped.state = ped.start_on_sidewalk(
+                    spot.sidewalk_pos.dist_along(),
+                    now,
+                    density,
+                    driving,
+                    scheduler,
+                    map,
+                );
+                scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
+            }
+            PedState::Jaywalking(_, landing, _, _) => {
+                // Skip the crosswalk and land partway along the far sidewalk.
+                self.peds_per_traversable
+                    .remove(ped.path.current_step().as_traversable(), ped.id);
+                ped.path.shift(map);
+                ped.path.shift(map);
+                let on = ped.path.current_step().as_traversable();
+                self.peds_per_traversable.insert(on, ped.id);
+                let density = current_density(self.crowding, &self.peds_per_traversable, on, map);
+                ped.state = ped.crossing_state(landing, now, density, map);
+                self.events.push(Event::PedCrossedMidBlock(
+                    ped.id,
+                    map.get_l(on.as_lane()).parent,
+                ));
+                self.events.push(Event::AgentEntersTraversable(
+                    AgentID::Pedestrian(ped.id),
+                    on,
+                ));
+                if self.crowding {
+                    self.events.push(Event::PedDensityMeasured(on, density));
+                }
                 scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
             PedState::WaitingForBus(_) => unreachable!(),
@@ -331,6 +409,8 @@ impl WalkingSimState {
         let mut forwards: Vec<(PedestrianID, Distance)> = Vec::new();
         let mut backwards: Vec<(PedestrianID, Distance)> = Vec::new();
         let mut front_path: MultiMap<BuildingID, (PedestrianID, Distance)> = MultiMap::new();
+        // Out in the road, so never part of a sidewalk crowd
+        let mut jaywalkers: Vec<PedestrianID> = Vec::new();
 
         for id in self.peds_per_traversable.get(on) {
             let ped = &self.peds[id];
@@ -365,11 +445,17 @@ impl WalkingSimState {
                     // The backwards half of the sidewalk is closer to the road.
                     backwards.push((*id, dist));
                 }
+                PedState::Jaywalking(_, _, _, _) => {
+                    jaywalkers.push(*id);
+                }
             }
         }
 
         let mut crowds: Vec<DrawPedCrowdInput> = Vec::new();
-        let mut loners: Vec<DrawPedestrianInput> = Vec::new();
+        let mut loners: Vec<DrawPedestrianInput> = jaywalkers
+            .into_iter()
+            .map(|id| self.peds[&id].get_draw_ped(now, map))
+            .collect();
 
         // For each group, sort by distance along. Attempt to bundle into intervals.
         for (mut group, location, on_len) in vec![
@@ -415,6 +501,10 @@ impl WalkingSimState {
         (loners, crowds)
     }
 
+    pub fn diff_peds(&self, other: &WalkingSimState) -> Vec<(PedestrianID, String)> {
+        diff_maps(&self.peds, &other.peds, |ped| format!("{:?}", ped.state))
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::replace(&mut self.events, Vec::new())
     }
@@ -432,10 +522,20 @@ struct Pedestrian {
     path: Path,
     goal: SidewalkSpot,
     trip: TripID,
+    // Decided once at spawn time. Jaywalkers cross mid-block whenever the road allows it.
+    jaywalker: bool,
 }
 
 impl Pedestrian {
-    fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> PedState {
+    // The speed is fixed for the whole step, based on how crowded it is when the pedestrian
+    // starts.
+    fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        density: f64,
+        map: &Map,
+    ) -> PedState {
         let end_dist = if self.path.is_last_step() {
             self.goal.sidewalk_pos.dist_along()
         } else {
@@ -447,10 +547,108 @@ impl Pedestrian {
             }
         };
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / self.speed);
+        let speed = crowded_speed(self.speed, density);
+        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
         PedState::Crossing(dist_int, time_int)
     }
 
+    // Usually the same as crossing_state, but sometimes people don't bother walking to the corner.
+    fn start_on_sidewalk(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        density: f64,
+        driving: &mut DrivingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> PedState {
+        if let Some(state) = self.maybe_jaywalk(start_dist, start_time, driving, scheduler, map) {
+            return state;
+        }
+        self.crossing_state(start_dist, start_time, density, map)
+    }
+
+    // If the path walks down this sidewalk only to take a crosswalk to the other side of the same
+    // road, cross right here instead, as long as traffic leaves a gap.
+    fn maybe_jaywalk(
+        &self,
+        start_dist: Distance,
+        now: Time,
+        driving: &mut DrivingSimState,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> Option<PedState> {
+        if !self.jaywalker || self.path.get_steps().len() < 3 {
+            return None;
+        }
+        let from = match self.path.current_step() {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => map.get_l(l),
+            PathStep::Turn(_) => {
+                return None;
+            }
+        };
+        let turn = map.get_t(self.path.next_step().as_turn());
+        let to = map.get_l(turn.id.dst);
+        if turn.turn_type != TurnType::Crosswalk || from.parent != to.parent {
+            return None;
+        }
+        let road = map.get_r(from.parent);
+        if road.speed_limit_forwards.normal > MAX_JAYWALKING_SPEED_LIMIT
+            || road.speed_limit_backwards.normal > MAX_JAYWALKING_SPEED_LIMIT
+        {
+            return None;
+        }
+        // How far along the road are we, measured from its src_i?
+        let pct = start_dist / from.length();
+        let pct_fwds = if from.src_i == road.src_i {
+            pct
+        } else {
+            1.0 - pct
+        };
+        let dist_on = |l: LaneID| {
+            let lane = map.get_l(l);
+            if lane.src_i == road.src_i {
+                pct_fwds * lane.length()
+            } else {
+                (1.0 - pct_fwds) * lane.length()
+            }
+        };
+
+        let landing = dist_on(to.id);
+        // Don't land past the goal and have to walk back.
+        if self.path.get_steps().len() == 3 {
+            let goal = self.goal.sidewalk_pos.dist_along();
+            let overshoots = match self.path.last_step() {
+                PathStep::Lane(_) => landing > goal,
+                PathStep::ContraflowLane(_) => landing < goal,
+                PathStep::Turn(_) => unreachable!(),
+            };
+            if overshoots {
+                return None;
+            }
+        }
+
+        let line = Line::maybe_new(from.dist_along(start_dist).0, to.dist_along(landing).0)?;
+        let until = now + line.length() / self.speed;
+        // Cars entering the road after somebody steps out aren't stopped; the gap only considers
+        // who's already on it.
+        let crossing = road
+            .all_lanes()
+            .into_iter()
+            .filter(|l| map.get_l(*l).lane_type.is_for_moving_vehicles())
+            .map(|l| (l, dist_on(l)))
+            .collect();
+        if !driving.yield_to_pedestrian(self.id, crossing, until, now, scheduler) {
+            return None;
+        }
+        Some(PedState::Jaywalking(
+            start_dist,
+            landing,
+            line,
+            TimeInterval::new(now, until),
+        ))
+    }
+
     fn get_dist_along(&self, now: Time, map: &Map) -> Distance {
         match self.state {
             PedState::Crossing(ref dist_int, ref time_int) => dist_int.lerp(time_int.percent(now)),
@@ -460,6 +658,7 @@ impl Pedestrian {
             PedState::StartingToBike(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
             PedState::FinishingBiking(ref spot, _, _) => spot.sidewalk_pos.dist_along(),
             PedState::WaitingForBus(_) => self.goal.sidewalk_pos.dist_along(),
+            PedState::Jaywalking(from, _, _, _) => from,
         }
     }
 
@@ -520,6 +719,9 @@ impl Pedestrian {
             PedState::FinishingBiking(_, ref line, ref time_int) => {
                 (line.percent_along(time_int.percent(now)), line.angle())
             }
+            PedState::Jaywalking(_, _, ref line, ref time_int) => {
+                (line.percent_along(time_int.percent(now)), line.angle())
+            }
             PedState::WaitingForBus(_) => {
                 let (pt, angle) = self.goal.sidewalk_pos.pt_and_angle(map);
                 // Face the road
@@ -561,11 +763,22 @@ impl Pedestrian {
         now: Time,
         map: &Map,
         intersections: &mut IntersectionSimState,
+        driving: &mut DrivingSimState,
+        crowding: bool,
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
         events: &mut Vec<Event>,
         scheduler: &mut Scheduler,
     ) -> bool {
         if let PathStep::Turn(t) = self.path.next_step() {
+            if crowding && map.get_t(t).turn_type == TurnType::Crosswalk {
+                let on = Traversable::Turn(t);
+                let num_peds = peds_per_traversable.get(on).len();
+                // Somebody can always cross an empty crosswalk, no matter how narrow.
+                if num_peds > 0 && ped_density(num_peds + 1, on, map) > MAX_CROSSWALK_DENSITY {
+                    scheduler.update(now + CROWDED_CROSSWALK_RETRY, Command::UpdatePed(self.id));
+                    return false;
+                }
+            }
             if !intersections.maybe_start_turn(
                 AgentID::Pedestrian(self.id),
                 t,
@@ -586,12 +799,17 @@ impl Pedestrian {
             PathStep::ContraflowLane(l) => map.get_l(l).length(),
             PathStep::Turn(_) => Distance::ZERO,
         };
-        self.state = self.crossing_state(start_dist, now, map);
-        peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
+        let on = self.path.current_step().as_traversable();
+        peds_per_traversable.insert(on, self.id);
+        let density = current_density(crowding, peds_per_traversable, on, map);
+        self.state = self.start_on_sidewalk(start_dist, now, density, driving, scheduler, map);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
-            self.path.current_step().as_traversable(),
+            on,
         ));
+        if crowding {
+            events.push(Event::PedDensityMeasured(on, density));
+        }
         true
     }
 }
@@ -605,6 +823,9 @@ enum PedState {
     EnteringBuilding(BuildingID, TimeInterval),
     StartingToBike(SidewalkSpot, Line, TimeInterval),
     FinishingBiking(SidewalkSpot, Line, TimeInterval),
+    // Crossing the road mid-block, from a distance along the current sidewalk to a distance along
+    // the sidewalk after the next crosswalk
+    Jaywalking(Distance, Distance, Line, TimeInterval),
     WaitingForBus(BusRouteID),
 }
 
@@ -617,11 +838,45 @@ impl PedState {
             PedState::EnteringBuilding(_, ref time_int) => time_int.end,
             PedState::StartingToBike(_, _, ref time_int) => time_int.end,
             PedState::FinishingBiking(_, _, ref time_int) => time_int.end,
+            PedState::Jaywalking(_, _, _, ref time_int) => time_int.end,
             PedState::WaitingForBus(_) => unreachable!(),
         }
     }
 }
 
+// Without crowding, everybody walks at their free speed.
+fn current_density(
+    crowding: bool,
+    peds_per_traversable: &MultiMap<Traversable, PedestrianID>,
+    on: Traversable,
+    map: &Map,
+) -> f64 {
+    if !crowding {
+        return 0.0;
+    }
+    ped_density(peds_per_traversable.get(on).len(), on, map)
+}
+
+// Crosswalks are as wide as the sidewalk they start from.
+fn ped_density(num_peds: usize, on: Traversable, map: &Map) -> f64 {
+    let width = match on {
+        Traversable::Lane(l) => map.get_l(l).width,
+        Traversable::Turn(t) => map.get_l(t.src).width,
+    };
+    // Tiny slivers of sidewalk near intersections shouldn't look infinitely crowded.
+    let area = (on.length(map).inner_meters() * width.inner_meters()).max(1.0);
+    (num_peds as f64) / area
+}
+
+// Weidmann's fundamental diagram for pedestrians
+fn crowded_speed(free_speed: Speed, density: f64) -> Speed {
+    if density <= 0.0 {
+        return free_speed;
+    }
+    let factor = 1.0 - (-1.913 * (1.0 / density - 1.0 / JAM_DENSITY)).exp();
+    free_speed * factor.max(MIN_CROWD_SPEED_FACTOR)
+}
+
 // The crowds returned here may have low/high values extending up to radius past the real geometry.
 fn find_crowds(
     input: Vec<(PedestrianID, Distance)>,
@@ -0,0 +1,264 @@
+use crate::{Analytics, TripMode};
+use geom::{Duration, DurationHistogram, Statistic, Time};
+use map_model::IntersectionID;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Two-sided 95% critical values of Student's t distribution, indexed by degrees of freedom - 1.
+// Past the end of the table, the normal approximation is close enough.
+const T_CRITICAL_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+const Z_CRITICAL_95: f64 = 1.96;
+
+fn trip_time_stats() -> Vec<Statistic> {
+    vec![
+        Statistic::Mean,
+        Statistic::P50,
+        Statistic::P90,
+        Statistic::P99,
+    ]
+}
+
+// The results of one run that get compared across RNG seeds.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunSummary {
+    pub rng_seed: u8,
+    pub finished_at: Time,
+    pub finished_trips: usize,
+    pub aborted_trips: usize,
+    pub trip_times: BTreeMap<Statistic, Duration>,
+    // Modes without any finished trips are missing.
+    pub trip_times_per_mode: BTreeMap<TripMode, BTreeMap<Statistic, Duration>>,
+    // Mean delay per intersection. Summaries count intersections missing here as no delay.
+    pub intersection_delays: BTreeMap<IntersectionID, Duration>,
+}
+
+impl RunSummary {
+    pub fn new(rng_seed: u8, analytics: &Analytics, now: Time) -> RunSummary {
+        let (all, aborted_trips, per_mode) = analytics.all_finished_trips(now);
+        let select = |distrib: &DurationHistogram| {
+            trip_time_stats()
+                .into_iter()
+                .map(|stat| (stat, distrib.select(stat)))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut intersection_delays = BTreeMap::new();
+        for i in analytics.intersection_delays.keys() {
+            let delays = analytics.intersection_delays(*i, Time::START_OF_DAY, now);
+            intersection_delays.insert(
+                *i,
+                if delays.count() > 0 {
+                    delays.select(Statistic::Mean)
+                } else {
+                    Duration::ZERO
+                },
+            );
+        }
+
+        RunSummary {
+            rng_seed,
+            finished_at: now,
+            finished_trips: all.count(),
+            aborted_trips,
+            trip_times: if all.count() > 0 {
+                select(&all)
+            } else {
+                BTreeMap::new()
+            },
+            trip_times_per_mode: per_mode
+                .into_iter()
+                .filter(|(_, distrib)| distrib.count() > 0)
+                .map(|(mode, distrib)| (mode, select(&distrib)))
+                .collect(),
+            intersection_delays,
+        }
+    }
+}
+
+// A sample mean and its 95% confidence interval.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub ci_low: f64,
+    pub ci_high: f64,
+    pub samples: usize,
+}
+
+impl Estimate {
+    // With fewer than 2 samples, there's no spread to measure, so the interval is just the mean.
+    pub fn new(samples: &[f64]) -> Estimate {
+        assert!(!samples.is_empty());
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / (n as f64);
+        if n < 2 {
+            return Estimate {
+                mean,
+                ci_low: mean,
+                ci_high: mean,
+                samples: n,
+            };
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / ((n - 1) as f64);
+        let critical = T_CRITICAL_95.get(n - 2).cloned().unwrap_or(Z_CRITICAL_95);
+        let half_width = critical * (variance / (n as f64)).sqrt();
+        Estimate {
+            mean,
+            ci_low: mean - half_width,
+            ci_high: mean + half_width,
+            samples: n,
+        }
+    }
+
+    // Does the interval exclude the other's? If so, the difference is unlikely to be noise.
+    pub fn differs_from(&self, other: &Estimate) -> bool {
+        self.ci_high < other.ci_low || other.ci_high < self.ci_low
+    }
+
+    fn describe_duration(&self) -> String {
+        format!(
+            "{} ({} - {})",
+            Duration::seconds(self.mean),
+            // Times can't be negative, even if the interval is wide
+            Duration::seconds(self.ci_low.max(0.0)),
+            Duration::seconds(self.ci_high)
+        )
+    }
+}
+
+// Aggregates the same scenario run under different seeds. All trip times and delays are in
+// seconds.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct MonteCarloSummary {
+    pub runs: Vec<RunSummary>,
+    pub finished_trips: Estimate,
+    pub aborted_trips: Estimate,
+    pub trip_times: BTreeMap<Statistic, Estimate>,
+    // Only from the runs where the mode had any trips; see Estimate::samples.
+    pub trip_times_per_mode: BTreeMap<TripMode, BTreeMap<Statistic, Estimate>>,
+    pub intersection_delays: BTreeMap<IntersectionID, Estimate>,
+}
+
+impl MonteCarloSummary {
+    pub fn new(runs: Vec<RunSummary>) -> MonteCarloSummary {
+        assert!(!runs.is_empty());
+
+        let mut trip_times: BTreeMap<Statistic, Vec<f64>> = BTreeMap::new();
+        let mut per_mode: BTreeMap<TripMode, BTreeMap<Statistic, Vec<f64>>> = BTreeMap::new();
+        let mut delays: BTreeMap<IntersectionID, Vec<f64>> = BTreeMap::new();
+        for run in &runs {
+            for (stat, dt) in &run.trip_times {
+                trip_times
+                    .entry(*stat)
+                    .or_insert_with(Vec::new)
+                    .push(dt.inner_seconds());
+            }
+            for (mode, stats) in &run.trip_times_per_mode {
+                for (stat, dt) in stats {
+                    per_mode
+                        .entry(*mode)
+                        .or_insert_with(BTreeMap::new)
+                        .entry(*stat)
+                        .or_insert_with(Vec::new)
+                        .push(dt.inner_seconds());
+                }
+            }
+        }
+        // Runs where nothing waited at an intersection count as no delay there. Skipping them
+        // would only average the runs that had delays.
+        let intersections: BTreeSet<IntersectionID> = runs
+            .iter()
+            .flat_map(|r| r.intersection_delays.keys().cloned())
+            .collect();
+        for i in intersections {
+            delays.insert(
+                i,
+                runs.iter()
+                    .map(|r| {
+                        r.intersection_delays
+                            .get(&i)
+                            .map(|dt| dt.inner_seconds())
+                            .unwrap_or(0.0)
+                    })
+                    .collect(),
+            );
+        }
+
+        let finished: Vec<f64> = runs.iter().map(|r| r.finished_trips as f64).collect();
+        let aborted: Vec<f64> = runs.iter().map(|r| r.aborted_trips as f64).collect();
+        MonteCarloSummary {
+            finished_trips: Estimate::new(&finished),
+            aborted_trips: Estimate::new(&aborted),
+            trip_times: trip_times
+                .into_iter()
+                .map(|(stat, samples)| (stat, Estimate::new(&samples)))
+                .collect(),
+            trip_times_per_mode: per_mode
+                .into_iter()
+                .map(|(mode, stats)| {
+                    (
+                        mode,
+                        stats
+                            .into_iter()
+                            .map(|(stat, samples)| (stat, Estimate::new(&samples)))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            intersection_delays: delays
+                .into_iter()
+                .map(|(i, samples)| (i, Estimate::new(&samples)))
+                .collect(),
+            runs,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut lines = vec![format!(
+            "{} runs, 95% confidence intervals in parentheses",
+            self.runs.len()
+        )];
+        lines.push(format!(
+            "Finished trips: {:.1} ({:.1} - {:.1})",
+            self.finished_trips.mean, self.finished_trips.ci_low, self.finished_trips.ci_high
+        ));
+        lines.push(format!(
+            "Aborted trips: {:.1} ({:.1} - {:.1})",
+            self.aborted_trips.mean, self.aborted_trips.ci_low, self.aborted_trips.ci_high
+        ));
+        for (stat, est) in &self.trip_times {
+            lines.push(format!(
+                "All trips, {} time: {}",
+                stat,
+                est.describe_duration()
+            ));
+        }
+        for (mode, stats) in &self.trip_times_per_mode {
+            for (stat, est) in stats {
+                lines.push(format!(
+                    "{} trips, {} time: {}, over {} runs",
+                    mode,
+                    stat,
+                    est.describe_duration(),
+                    est.samples
+                ));
+            }
+        }
+
+        let mut worst: Vec<(&IntersectionID, &Estimate)> =
+            self.intersection_delays.iter().collect();
+        worst.sort_by(|a, b| b.1.mean.partial_cmp(&a.1.mean).unwrap());
+        for (i, est) in worst.into_iter().take(10) {
+            lines.push(format!(
+                "Mean delay at {}: {}, over {} runs",
+                i,
+                est.describe_duration(),
+                est.samples
+            ));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{CarID, Event, PedestrianID, Router, Scheduler, TripID, TripManager, MAX_CAR_LENGTH};
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+use map_model::{LaneID, LaneType, Map, Path, PathConstraints, PathRequest, PathStep, Position};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// Vehicles stop at the curb this long to let somebody in or out, blocking the lane.
+const PICKUP_TIME: Duration = Duration::const_seconds(30.0);
+const DROPOFF_TIME: Duration = Duration::const_seconds(15.0);
+// When an idle vehicle can't find anywhere to cruise, wait this long before trying again.
+const IDLE_RETRY: Duration = Duration::const_seconds(60.0);
+// Idle vehicles wander this many turns before checking in again.
+const CRUISE_TURNS: usize = 5;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RideHailRequest {
+    pub ped: PedestrianID,
+    pub trip: TripID,
+    pub requested_at: Time,
+    // Positions along driving lanes
+    pub pickup: Position,
+    pub dropoff: Position,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+struct RideHailVehicle {
+    car: CarID,
+    state: VehicleState,
+    // Where the vehicle will be when it next checks in
+    next_stop: Position,
+    // How far the current route is, for deadheading stats
+    route_dist: Distance,
+    // Just used to vary where idle vehicles cruise
+    num_hops: usize,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+enum VehicleState {
+    Cruising,
+    // Couldn't be rerouted right away, so finishing the current cruise, then heading to the pickup
+    Assigned(RideHailRequest),
+    DrivingToPickup(RideHailRequest),
+    DrivingToDropoff(RideHailRequest),
+}
+
+// A fleet of vehicles without owners, serving requests from any trip. Like TransitSimState,
+// this manages the vehicles' transitions and hands out new routes when they reach each stop.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct RideHailSimState {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    vehicles: BTreeMap<CarID, RideHailVehicle>,
+    // Oldest first
+    unassigned: VecDeque<RideHailRequest>,
+
+    events: Vec<Event>,
+}
+
+impl RideHailSimState {
+    pub fn new() -> RideHailSimState {
+        RideHailSimState {
+            vehicles: BTreeMap::new(),
+            unassigned: VecDeque::new(),
+            events: Vec::new(),
+        }
+    }
+
+    // Returns the first route for a new vehicle, which just cruises around.
+    pub fn vehicle_created(&mut self, car: CarID, start: Position, map: &Map) -> Option<Router> {
+        let mut vehicle = RideHailVehicle {
+            car,
+            state: VehicleState::Cruising,
+            next_stop: start,
+            route_dist: Distance::ZERO,
+            num_hops: 0,
+        };
+        let router = vehicle.cruise(start, map)?;
+        self.vehicles.insert(car, vehicle);
+        Some(router)
+    }
+
+    pub fn vehicle_failed_start(&mut self, car: CarID) {
+        self.vehicles.remove(&car);
+    }
+
+    // Assign the request to the closest idle vehicle, or wait for one to free up. Returns the
+    // assigned vehicle, which the caller should try to reroute right away.
+    pub fn request_ride(&mut self, req: RideHailRequest, map: &Map) -> Option<CarID> {
+        let pickup_pt = req.pickup.pt(map);
+        if let Some(vehicle) = self
+            .vehicles
+            .values_mut()
+            .filter(|v| v.state == VehicleState::Cruising)
+            .min_by_key(|v| v.next_stop.pt(map).dist_to(pickup_pt))
+        {
+            vehicle.state = VehicleState::Assigned(req);
+            Some(vehicle.car)
+        } else {
+            self.unassigned.push_back(req);
+            None
+        }
+    }
+
+    // An assigned vehicle in the middle of its cruise heads to the pickup from the lane it's on,
+    // instead of finishing the cruise first. If this returns None, the vehicle stays assigned and
+    // heads over when the cruise ends.
+    pub fn reroute_assigned(
+        &mut self,
+        car: CarID,
+        current: &Path,
+        end_dist: Distance,
+        map: &Map,
+    ) -> Option<Router> {
+        let vehicle = self.vehicles.get_mut(&car)?;
+        let req = match vehicle.state {
+            VehicleState::Assigned(ref req) => req.clone(),
+            _ => {
+                return None;
+            }
+        };
+        let lane = match current.current_step() {
+            PathStep::Lane(l) => l,
+            _ => {
+                return None;
+            }
+        };
+        // The vehicle's exact position along the lane isn't known here, so don't try when the
+        // pickup might be behind it.
+        if current.is_last_step() || req.pickup.lane() == lane {
+            return None;
+        }
+        let start = Position::new(lane, Distance::ZERO);
+        let old_end = Position::new(current.last_step().as_lane(), end_dist);
+        // Keep counting the part of the cruise already driven
+        let driven =
+            (vehicle.route_dist - path_length(current, start, old_end, map)).max(Distance::ZERO);
+        let router = vehicle.drive_to(start, req.pickup, map)?;
+        vehicle.route_dist += driven;
+        vehicle.state = VehicleState::DrivingToPickup(req);
+        Some(router)
+    }
+
+    // Called when a vehicle reaches the end of its route. Returns the next route, if there is
+    // one, and how long to stay stopped before starting it.
+    pub fn vehicle_arrived(
+        &mut self,
+        now: Time,
+        car: CarID,
+        pos: Position,
+        trips: &mut TripManager,
+        scheduler: &mut Scheduler,
+        map: &Map,
+    ) -> (Option<Router>, Duration) {
+        let mut vehicle = self.vehicles.remove(&car).unwrap();
+        let had_passenger = match vehicle.state {
+            VehicleState::DrivingToDropoff(_) => true,
+            _ => false,
+        };
+        if vehicle.route_dist > Distance::ZERO {
+            self.events.push(Event::RideHailVehicleMoved(
+                car,
+                vehicle.route_dist,
+                had_passenger,
+            ));
+        }
+        vehicle.next_stop = pos;
+        vehicle.route_dist = Distance::ZERO;
+
+        let state = std::mem::replace(&mut vehicle.state, VehicleState::Cruising);
+        let (router, dwell) = match state {
+            VehicleState::Cruising => (self.find_work(&mut vehicle, pos, trips, map), IDLE_RETRY),
+            VehicleState::Assigned(req) => {
+                if let Some(router) = vehicle.drive_to(pos, req.pickup, map) {
+                    vehicle.state = VehicleState::DrivingToPickup(req);
+                    (Some(router), Duration::ZERO)
+                } else {
+                    trips.abort_ride_hail(req.ped);
+                    (self.find_work(&mut vehicle, pos, trips, map), IDLE_RETRY)
+                }
+            }
+            VehicleState::DrivingToPickup(req) => {
+                if let Some(router) = vehicle.drive_to(pos, req.dropoff, map) {
+                    self.events
+                        .push(Event::RideHailPickup(car, req.ped, now - req.requested_at));
+                    trips.ped_picked_up(req.ped, car, pos, req.dropoff);
+                    vehicle.state = VehicleState::DrivingToDropoff(req);
+                    (Some(router), PICKUP_TIME)
+                } else {
+                    trips.abort_ride_hail(req.ped);
+                    (self.find_work(&mut vehicle, pos, trips, map), IDLE_RETRY)
+                }
+            }
+            VehicleState::DrivingToDropoff(req) => {
+                self.events.push(Event::RideHailDropoff(car, req.ped));
+                trips.ped_dropped_off(now, req.ped, map, scheduler);
+                (self.find_work(&mut vehicle, pos, trips, map), DROPOFF_TIME)
+            }
+        };
+        // Only wait around a while if there's nowhere to go.
+        let dwell = if router.is_some() && dwell == IDLE_RETRY {
+            Duration::ZERO
+        } else {
+            dwell
+        };
+        self.vehicles.insert(car, vehicle);
+        (router, dwell)
+    }
+
+    // Take the oldest waiting request, or go cruising.
+    fn find_work(
+        &mut self,
+        vehicle: &mut RideHailVehicle,
+        pos: Position,
+        trips: &mut TripManager,
+        map: &Map,
+    ) -> Option<Router> {
+        while let Some(req) = self.unassigned.pop_front() {
+            if let Some(router) = vehicle.drive_to(pos, req.pickup, map) {
+                vehicle.state = VehicleState::DrivingToPickup(req);
+                return Some(router);
+            }
+            trips.abort_ride_hail(req.ped);
+        }
+        vehicle.state = VehicleState::Cruising;
+        vehicle.cruise(pos, map)
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    pub fn get_passenger(&self, car: CarID) -> Option<PedestrianID> {
+        match self.vehicles.get(&car)?.state {
+            VehicleState::DrivingToDropoff(ref req) => Some(req.ped),
+            _ => None,
+        }
+    }
+}
+
+impl RideHailVehicle {
+    fn drive_to(&mut self, start: Position, end: Position, map: &Map) -> Option<Router> {
+        let path = route(start, end, map)?;
+        self.route_dist = path_length(&path, start, end, map);
+        self.next_stop = end;
+        Some(Router::ride_hail(path, end.dist_along()))
+    }
+
+    fn cruise(&mut self, start: Position, map: &Map) -> Option<Router> {
+        self.num_hops += 1;
+        // Mix the vehicle's ID and progress to spread vehicles out, without touching the sim's RNG.
+        let mut current = start.lane();
+        for i in 0..CRUISE_TURNS {
+            // Avoid dead-ends, so the vehicle doesn't get stuck
+            let choices: Vec<LaneID> = map
+                .get_turns_for(current, PathConstraints::Car)
+                .into_iter()
+                .map(|t| t.id.dst)
+                .filter(|l| !map.get_turns_for(*l, PathConstraints::Car).is_empty())
+                .collect();
+            if choices.is_empty() {
+                break;
+            }
+            current = choices[(self.car.0 + self.num_hops + i) % choices.len()];
+        }
+        let len = map.get_l(current).length();
+        if current == start.lane() || len <= MAX_CAR_LENGTH * 2.0 {
+            return None;
+        }
+        self.drive_to(start, Position::new(current, len / 2.0), map)
+    }
+}
+
+// Where a vehicle stops to serve somebody on this sidewalk
+pub fn curb_near(sidewalk_pos: Position, map: &Map) -> Option<Position> {
+    let lane = map
+        .find_closest_lane(sidewalk_pos.lane(), vec![LaneType::Driving])
+        .ok()?;
+    if map.get_l(lane).length() <= MAX_CAR_LENGTH {
+        return None;
+    }
+    let pos = sidewalk_pos.equiv_pos(lane, Distance::ZERO, map);
+    // Don't stop with the back of the vehicle sticking into the intersection
+    Some(Position::new(lane, pos.dist_along().max(MAX_CAR_LENGTH)))
+}
+
+fn route(start: Position, end: Position, map: &Map) -> Option<Path> {
+    if start.lane() != end.lane() || start.dist_along() <= end.dist_along() {
+        return map.pathfind(PathRequest {
+            start,
+            end,
+            constraints: PathConstraints::Car,
+        });
+    }
+
+    // The destination is behind us on the same lane, so go around the block.
+    let turn = map
+        .get_turns_for(start.lane(), PathConstraints::Car)
+        .into_iter()
+        .next()?
+        .id;
+    let mut path = map.pathfind(PathRequest {
+        start,
+        end: Position::new(start.lane(), map.get_l(start.lane()).length()),
+        constraints: PathConstraints::Car,
+    })?;
+    let rest = map.pathfind(PathRequest {
+        start: Position::new(turn.dst, Distance::ZERO),
+        end,
+        constraints: PathConstraints::Car,
+    })?;
+    path.add(PathStep::Turn(turn), map);
+    for step in rest.get_steps() {
+        path.add(*step, map);
+    }
+    Some(path)
+}
+
+// The car pathfinder's total_length is really a cost, so measure the real distance.
+fn path_length(path: &Path, start: Position, end: Position, map: &Map) -> Distance {
+    let mut dist = Distance::ZERO;
+    for step in path.get_steps() {
+        dist += step.as_traversable().length(map);
+    }
+    dist - start.dist_along() - (map.get_l(end.lane()).length() - end.dist_along())
+}
@@ -2,8 +2,8 @@ use crate::mechanics::Queue;
 use crate::{Event, ParkingSimState, ParkingSpot, SidewalkSpot, TripID, Vehicle};
 use geom::Distance;
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, TurnID,
+    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, PathStep, Position, Traversable,
+    TurnID,
 };
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, VecDeque};
@@ -22,9 +22,14 @@ pub enum ActionAtEnd {
     GotoLaneEnd,
     StopBiking(SidewalkSpot),
     BusAtStop,
+    DoubleParkForDelivery,
+    RideHailStop,
     AbortTrip,
 }
 
+// Trucks pull into a free loading zone this close to the delivery stop.
+const MAX_DIST_TO_LOADING_ZONE: Distance = Distance::const_meters(50.0);
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 enum Goal {
     // Spot and cached distance along the last driving lane
@@ -45,6 +50,17 @@ enum Goal {
     FollowBusRoute {
         end_dist: Distance,
     },
+    // Pull into a loading zone if one's free near the building, otherwise double-park at
+    // end_dist. The spot is cached like ParkNearBuilding.
+    MakeDelivery {
+        target: BuildingID,
+        end_dist: Distance,
+        spot: Option<(ParkingSpot, Distance)>,
+    },
+    // Ride-hail vehicles stop at the curb, then RideHailSimState decides what's next
+    RideHailStop {
+        end_dist: Distance,
+    },
 }
 
 impl Router {
@@ -80,6 +96,24 @@ impl Router {
         }
     }
 
+    pub fn make_delivery(path: Path, bldg: BuildingID, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::MakeDelivery {
+                target: bldg,
+                end_dist,
+                spot: None,
+            },
+        }
+    }
+
+    pub fn ride_hail(path: Path, end_dist: Distance) -> Router {
+        Router {
+            path,
+            goal: Goal::RideHailStop { end_dist },
+        }
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -112,6 +146,8 @@ impl Router {
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { end_dist } => end_dist,
             Goal::FollowBusRoute { end_dist } => end_dist,
+            Goal::RideHailStop { end_dist } => end_dist,
+            Goal::MakeDelivery { end_dist, spot, .. } => spot.map(|(_, d)| d).unwrap_or(end_dist),
         }
     }
 
@@ -197,7 +233,7 @@ impl Router {
                             Some(PathRequest {
                                 start: Position::new(current_lane, front),
                                 end: new_pos,
-                                constraints: PathConstraints::Car,
+                                constraints: vehicle.vehicle_type.to_constraints(),
                             }),
                             format!("parking on the current lane"),
                         ));
@@ -217,7 +253,7 @@ impl Router {
                                 Some(PathRequest {
                                     start: Position::new(current_lane, front),
                                     end: new_pos,
-                                    constraints: PathConstraints::Car,
+                                    constraints: vehicle.vehicle_type.to_constraints(),
                                 }),
                                 format!("parking somewhere else"),
                             ));
@@ -258,6 +294,52 @@ impl Router {
                     None
                 }
             }
+            Goal::RideHailStop { end_dist } => {
+                if end_dist == front {
+                    Some(ActionAtEnd::RideHailStop)
+                } else {
+                    None
+                }
+            }
+            Goal::MakeDelivery {
+                end_dist,
+                ref mut spot,
+                ..
+            } => {
+                let need_new_spot = match spot {
+                    Some((s, _)) => !parking.is_free(*s),
+                    None => true,
+                };
+                if need_new_spot {
+                    let current_lane = self.path.current_step().as_lane();
+                    let old_end_dist = spot.map(|(_, d)| d).unwrap_or(end_dist);
+                    *spot = parking
+                        .get_free_loading_spot(
+                            Position::new(current_lane, front),
+                            Position::new(current_lane, end_dist),
+                            MAX_DIST_TO_LOADING_ZONE,
+                            vehicle,
+                            map,
+                        )
+                        .map(|(s, pos)| (s, pos.dist_along()));
+                    let new_end_dist = spot.map(|(_, d)| d).unwrap_or(end_dist);
+                    if new_end_dist != old_end_dist && new_end_dist != front {
+                        return Some(ActionAtEnd::GotoLaneEnd);
+                    }
+                }
+
+                match spot {
+                    Some((s, d)) if *d == front => Some(ActionAtEnd::StartParking(*s)),
+                    Some(_) => None,
+                    None => {
+                        if end_dist == front {
+                            Some(ActionAtEnd::DoubleParkForDelivery)
+                        } else {
+                            None
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -330,6 +412,21 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
 
+    // Move to an adjacent lane in the middle of the road, then take this turn to the same lane as
+    // before.
+    pub fn change_lanes_midblock(&mut self, lane: LaneID, turn: TurnID, map: &Map) {
+        assert_eq!(self.path.next_step().as_turn().dst, turn.dst);
+        self.path.modify_current_step(PathStep::Lane(lane), map);
+        self.path.modify_step(1, PathStep::Turn(turn), map);
+    }
+
+    // Same destination, different way to get there
+    pub fn detour(&mut self, path: Path) {
+        assert_eq!(self.path.current_step(), path.current_step());
+        assert_eq!(self.path.last_step(), path.last_step());
+        self.path = path;
+    }
+
     pub fn replace_path_for_serialization(&mut self, path: Path) -> Path {
         std::mem::replace(&mut self.path, path)
     }
@@ -374,7 +471,7 @@ fn path_to_free_parking_spot(
                 }
             }
         }
-        for turn in map.get_turns_for(current, PathConstraints::Car) {
+        for turn in map.get_turns_for(current, vehicle.vehicle_type.to_constraints()) {
             if !backrefs.contains_key(&turn.id.dst) {
                 backrefs.insert(turn.id.dst, turn.id);
                 queue.push_back(turn.id.dst);
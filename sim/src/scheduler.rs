@@ -1,4 +1,6 @@
-use crate::{AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID};
+use crate::{
+    diff_maps, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, RideHailRequest,
+};
 use derivative::Derivative;
 use geom::{Duration, DurationHistogram, Time};
 use map_model::{IntersectionID, Path, PathRequest};
@@ -11,11 +13,15 @@ pub enum Command {
     // If true, retry when there's no room to spawn somewhere
     SpawnCar(CreateCar, bool),
     SpawnPed(CreatePedestrian),
+    RequestRideHail(RideHailRequest),
     UpdateCar(CarID),
     // Distinguish this from UpdateCar to avoid confusing things
     UpdateLaggyHead(CarID),
     UpdatePed(PedestrianID),
     UpdateIntersection(IntersectionID),
+    // Indices into Sim's incidents
+    StartIncident(usize),
+    EndIncident(usize),
     Savestate(Duration),
 }
 
@@ -31,10 +37,13 @@ impl Command {
         match self {
             Command::SpawnCar(ref create, _) => CommandType::Car(create.vehicle.id),
             Command::SpawnPed(ref create) => CommandType::Ped(create.id),
+            Command::RequestRideHail(ref req) => CommandType::Ped(req.ped),
             Command::UpdateCar(id) => CommandType::Car(*id),
             Command::UpdateLaggyHead(id) => CommandType::CarLaggyHead(*id),
             Command::UpdatePed(id) => CommandType::Ped(*id),
             Command::UpdateIntersection(id) => CommandType::Intersection(*id),
+            Command::StartIncident(id) => CommandType::StartIncident(*id),
+            Command::EndIncident(id) => CommandType::EndIncident(*id),
             Command::Savestate(_) => CommandType::Savestate,
         }
     }
@@ -48,6 +57,8 @@ pub enum CommandType {
     CarLaggyHead(CarID),
     Ped(PedestrianID),
     Intersection(IntersectionID),
+    StartIncident(usize),
+    EndIncident(usize),
     Savestate,
 }
 
@@ -181,6 +192,24 @@ impl Scheduler {
         }
     }
 
+    pub fn diff(&self, other: &Scheduler) -> Vec<String> {
+        diff_maps(
+            &self.queued_commands,
+            &other.queued_commands,
+            |(cmd, time)| match cmd {
+                // The full paths are too much to print
+                Command::SpawnCar(ref create_car, _) => {
+                    format!("spawn {} at {}", create_car.vehicle.id, time)
+                }
+                Command::SpawnPed(ref create_ped) => format!("spawn {} at {}", create_ped.id, time),
+                _ => format!("{:?} at {}", cmd, time),
+            },
+        )
+        .into_iter()
+        .map(|(cmd_type, msg)| format!("{:?}: {}", cmd_type, msg))
+        .collect()
+    }
+
     pub fn describe_stats(&self) -> String {
         format!("delta times for events: {}", self.delta_times.describe())
     }
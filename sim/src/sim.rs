@@ -1,18 +1,22 @@
 use crate::{
     AgentID, AgentMetadata, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingGoal, DrivingSimState, Event, GetDrawAgents, IntersectionSimState,
-    ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Router, Scheduler, SidewalkPOI,
-    SidewalkSpot, TransitSimState, TripCount, TripEnd, TripID, TripLeg, TripManager, TripMode,
-    TripPositions, TripResult, TripSpawner, TripSpec, TripStart, UnzoomedAgent, VehicleSpec,
-    VehicleType, WalkingSimState, BUS_LENGTH,
+    DrawPedestrianInput, DrivingGoal, DrivingSimState, EmissionFactors, Event, GetDrawAgents,
+    Incident, IntersectionSimState, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID,
+    RideHailSimState, Router, Scenario, Scheduler, SidewalkPOI, SidewalkSpot, SimDiff,
+    TransitSimState, TripCount, TripEnd, TripID, TripLeg, TripManager, TripMode, TripPositions,
+    TripResult, TripSpawner, TripSpec, TripStart, UnzoomedAgent, VehicleSpec, VehicleType,
+    WalkingSimState, BUS_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
 use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
     BuildingID, BusRoute, BusRouteID, IntersectionID, LaneID, Map, Path, PathConstraints,
-    PathRequest, PathStep, Traversable,
+    PathRequest, PathStep, Phase, Position, Traversable,
 };
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
 use std::panic;
@@ -29,12 +33,18 @@ pub struct Sim {
     walking: WalkingSimState,
     intersections: IntersectionSimState,
     transit: TransitSimState,
+    ride_hail: RideHailSimState,
+    incidents: Vec<Incident>,
     trips: TripManager,
     spawner: TripSpawner,
     scheduler: Scheduler,
     time: Time,
     car_id_counter: usize,
     ped_id_counter: usize,
+    // For choices made while the simulation runs, like who jaywalks. SimFlags seeds this from
+    // --rng_seed.
+    #[derivative(PartialEq = "ignore")]
+    rng: XorShiftRng,
 
     // TODO Reconsider these
     pub(crate) map_name: String,
@@ -64,6 +74,16 @@ pub struct SimOptions {
     pub use_freeform_policy_everywhere: bool,
     pub disable_block_the_box: bool,
     pub recalc_lanechanging: bool,
+    // Make vehicles speed up and brake using per-vehicle limits, instead of instantly changing
+    // speed.
+    pub kinematics: bool,
+    pub emission_factors: EmissionFactors,
+    // How likely pedestrians are to cross low-speed roads in the middle of the block, instead of
+    // walking to the corner and back. 0 means never.
+    pub jaywalking_probability: f64,
+    // Slow pedestrians down on crowded sidewalks, and make them wait for crowded crosswalks to
+    // clear.
+    pub ped_crowding: bool,
 }
 
 impl SimOptions {
@@ -74,6 +94,10 @@ impl SimOptions {
             use_freeform_policy_everywhere: false,
             disable_block_the_box: false,
             recalc_lanechanging: true,
+            kinematics: false,
+            emission_factors: EmissionFactors::default_factors(),
+            jaywalking_probability: 0.0,
+            ped_crowding: false,
         }
     }
 }
@@ -86,9 +110,14 @@ impl Sim {
             scheduler.push(Time::START_OF_DAY + d, Command::Savestate(d));
         }
         Sim {
-            driving: DrivingSimState::new(map, opts.recalc_lanechanging),
+            driving: DrivingSimState::new(
+                map,
+                opts.recalc_lanechanging,
+                opts.kinematics,
+                opts.emission_factors,
+            ),
             parking: ParkingSimState::new(map, timer),
-            walking: WalkingSimState::new(),
+            walking: WalkingSimState::new(opts.jaywalking_probability, opts.ped_crowding),
             intersections: IntersectionSimState::new(
                 map,
                 &mut scheduler,
@@ -96,12 +125,15 @@ impl Sim {
                 opts.disable_block_the_box,
             ),
             transit: TransitSimState::new(),
+            ride_hail: RideHailSimState::new(),
+            incidents: Vec::new(),
             trips: TripManager::new(),
             spawner: TripSpawner::new(),
             scheduler,
             time: Time::START_OF_DAY,
             car_id_counter: 0,
             ped_id_counter: 0,
+            rng: XorShiftRng::from_seed([42; 16]),
 
             map_name: map.get_name().to_string(),
             // TODO
@@ -114,6 +146,10 @@ impl Sim {
         }
     }
 
+    pub fn set_rng(&mut self, rng: XorShiftRng) {
+        self.rng = rng;
+    }
+
     pub fn schedule_trip(
         &mut self,
         start_time: Time,
@@ -125,6 +161,11 @@ impl Sim {
                 ref vehicle_spec,
                 ref goal,
                 ..
+            }
+            | TripSpec::DeliveryTour {
+                ref vehicle_spec,
+                ref goal,
+                ..
             } => {
                 let car = CarID(self.car_id_counter, vehicle_spec.vehicle_type);
                 self.car_id_counter += 1;
@@ -141,7 +182,8 @@ impl Sim {
             TripSpec::UsingParkedCar { .. }
             | TripSpec::MaybeUsingParkedCar { .. }
             | TripSpec::JustWalking { .. }
-            | TripSpec::UsingTransit { .. } => {
+            | TripSpec::UsingTransit { .. }
+            | TripSpec::UsingRideHail { .. } => {
                 let id = PedestrianID(self.ped_id_counter);
                 self.ped_id_counter += 1;
                 (Some(id), None)
@@ -222,10 +264,14 @@ impl Sim {
 
             // For now, no desire for randomness. Caller can pass in list of specs if that ever
             // changes.
+            let (max_accel, max_decel) = VehicleType::Bus.accel_limits();
             let vehicle = VehicleSpec {
                 vehicle_type: VehicleType::Bus,
                 length: BUS_LENGTH,
                 max_speed: None,
+                max_accel,
+                max_decel,
+                electric: false,
             }
             .make(id, None);
 
@@ -293,6 +339,105 @@ impl Sim {
         results
     }
 
+    // Spawn ownerless vehicles at random buildings. They cruise around until somebody requests a
+    // ride.
+    pub fn seed_ride_hail_fleet(
+        &mut self,
+        num_vehicles: usize,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) -> Vec<CarID> {
+        let mut results: Vec<CarID> = Vec::new();
+        let bldgs: Vec<BuildingID> = map
+            .all_buildings()
+            .iter()
+            .filter(|b| Position::bldg_via_driving(b.id, map).is_some())
+            .map(|b| b.id)
+            .collect();
+        if bldgs.is_empty() {
+            timer.warn("No buildings to start a ride-hail fleet from".to_string());
+            return results;
+        }
+
+        for _ in 0..num_vehicles {
+            let b = *bldgs.choose(rng).unwrap();
+            let vehicle_spec = Scenario::rand_car(rng);
+            let start_pos =
+                match TripSpec::spawn_car_at(Position::bldg_via_driving(b, map).unwrap(), map) {
+                    Some(pos) => pos,
+                    None => {
+                        continue;
+                    }
+                };
+
+            let id = CarID(self.car_id_counter, VehicleType::Car);
+            self.car_id_counter += 1;
+            let trip = self.trips.new_trip(
+                self.time,
+                TripStart::Bldg(b),
+                vec![TripLeg::ServeRideHail(id)],
+            );
+            let router = match self.ride_hail.vehicle_created(id, start_pos, map) {
+                Some(r) => r,
+                None => {
+                    self.trips.abort_trip_failed_start(trip);
+                    continue;
+                }
+            };
+            let req = PathRequest {
+                start: start_pos,
+                end: Position::new(
+                    router.get_path().last_step().as_lane(),
+                    router.get_end_dist(),
+                ),
+                constraints: PathConstraints::Car,
+            };
+            let vehicle = vehicle_spec.make(id, None);
+
+            if self.driving.start_car_on_lane(
+                self.time,
+                CreateCar {
+                    start_dist: start_pos.dist_along(),
+                    vehicle,
+                    req,
+                    router,
+                    maybe_parked_car: None,
+                    trip,
+                },
+                map,
+                &self.intersections,
+                &self.parking,
+                &mut self.scheduler,
+            ) {
+                self.trips.agent_starting_trip_leg(AgentID::Car(id), trip);
+                results.push(id);
+            } else {
+                self.ride_hail.vehicle_failed_start(id);
+                self.trips.abort_trip_failed_start(trip);
+            }
+        }
+        if results.len() < num_vehicles {
+            timer.warn(format!(
+                "Only seeded {} of {} ride-hail vehicles",
+                results.len(),
+                num_vehicles
+            ));
+        }
+        results
+    }
+
+    pub fn schedule_incident(&mut self, incident: Incident) {
+        assert!(incident.start < incident.end);
+        let id = self.incidents.len();
+        // Something that already started just starts now.
+        self.scheduler
+            .push(incident.start.max(self.time), Command::StartIncident(id));
+        self.scheduler
+            .push(incident.end.max(self.time), Command::EndIncident(id));
+        self.incidents.push(incident);
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.run_name = name;
     }
@@ -512,6 +657,7 @@ impl Sim {
                                     create_ped,
                                     map,
                                     &mut self.scheduler,
+                                    &mut self.rng,
                                 );
                             }
                         }
@@ -519,6 +665,21 @@ impl Sim {
                         self.trips.abort_trip_failed_start(create_ped.trip);
                     }
                 }
+                Command::RequestRideHail(req) => {
+                    self.trips
+                        .agent_starting_trip_leg(AgentID::Pedestrian(req.ped), req.trip);
+                    self.trips.ped_requested_ride_hail(req.ped);
+                    if let Some(car) = self.ride_hail.request_ride(req, map) {
+                        self.driving.reroute_ride_hail(
+                            car,
+                            self.time,
+                            &mut self.ride_hail,
+                            &mut self.intersections,
+                            &mut self.scheduler,
+                            map,
+                        );
+                    }
+                }
                 Command::UpdateCar(car) => {
                     self.driving.update_car(
                         car,
@@ -529,6 +690,7 @@ impl Sim {
                         &mut self.trips,
                         &mut self.scheduler,
                         &mut self.transit,
+                        &mut self.ride_hail,
                         &mut self.walking,
                     );
                 }
@@ -547,6 +709,7 @@ impl Sim {
                         self.time,
                         map,
                         &mut self.intersections,
+                        &mut self.driving,
                         &self.parking,
                         &mut self.scheduler,
                         &mut self.trips,
@@ -557,6 +720,23 @@ impl Sim {
                     self.intersections
                         .update_intersection(self.time, i, map, &mut self.scheduler);
                 }
+                Command::StartIncident(id) => {
+                    self.intersections.close(&self.incidents[id].blockage);
+                    self.driving.detour_around_closures(
+                        self.time,
+                        &mut self.intersections,
+                        &mut self.scheduler,
+                        map,
+                    );
+                }
+                Command::EndIncident(id) => {
+                    self.intersections.reopen(
+                        &self.incidents[id].blockage,
+                        self.time,
+                        &mut self.scheduler,
+                        map,
+                    );
+                }
                 Command::Savestate(frequency) => {
                     self.scheduler
                         .push(self.time + frequency, Command::Savestate(frequency));
@@ -568,6 +748,7 @@ impl Sim {
             // Record events at precisely the time they occur.
             events.extend(self.trips.collect_events());
             events.extend(self.transit.collect_events());
+            events.extend(self.ride_hail.collect_events());
             events.extend(self.driving.collect_events());
             events.extend(self.walking.collect_events());
             events.extend(self.intersections.collect_events());
@@ -727,6 +908,56 @@ impl Sim {
         )
     }
 
+    // Sims that should match sometimes don't, and PartialEq doesn't say where.
+    pub fn diff(&self, other: &Sim) -> SimDiff {
+        let mut diff = SimDiff {
+            times: if self.time != other.time {
+                Some((self.time, other.time))
+            } else {
+                None
+            },
+            cars: self.driving.diff_cars(&other.driving),
+            peds: self.walking.diff_peds(&other.walking),
+            queues: self.driving.diff_queues(&other.driving),
+            scheduler: self.scheduler.diff(&other.scheduler),
+            other: Vec::new(),
+        };
+        // Details for these are covered above, unless something else inside them differs.
+        if self.driving != other.driving && diff.cars.is_empty() && diff.queues.is_empty() {
+            diff.other.push("driving".to_string());
+        }
+        if self.walking != other.walking && diff.peds.is_empty() {
+            diff.other.push("walking".to_string());
+        }
+        if self.scheduler != other.scheduler && diff.scheduler.is_empty() {
+            diff.other.push("scheduler".to_string());
+        }
+        for (name, same) in vec![
+            ("parking", self.parking == other.parking),
+            ("intersections", self.intersections == other.intersections),
+            ("transit", self.transit == other.transit),
+            ("ride_hail", self.ride_hail == other.ride_hail),
+            ("incidents", self.incidents == other.incidents),
+            ("trips", self.trips == other.trips),
+            ("spawner", self.spawner == other.spawner),
+            (
+                "car_id_counter",
+                self.car_id_counter == other.car_id_counter,
+            ),
+            (
+                "ped_id_counter",
+                self.ped_id_counter == other.ped_id_counter,
+            ),
+            ("map_name", self.map_name == other.map_name),
+            ("edits_name", self.edits_name == other.edits_name),
+        ] {
+            if !same {
+                diff.other.push(name.to_string());
+            }
+        }
+        diff
+    }
+
     pub fn save(&mut self) -> String {
         let restore = self.scheduler.before_savestate();
 
@@ -752,6 +983,10 @@ impl Sim {
                 "- transit: {} bytes",
                 abstutil::prettyprint_usize(abstutil::serialized_size_bytes(&self.transit))
             );
+            println!(
+                "- ride_hail: {} bytes",
+                abstutil::prettyprint_usize(abstutil::serialized_size_bytes(&self.ride_hail))
+            );
             println!(
                 "- trips: {} bytes",
                 abstutil::prettyprint_usize(abstutil::serialized_size_bytes(&self.trips))
@@ -909,17 +1144,24 @@ impl Sim {
     }
 
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
-        for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
+        for vt in &[
+            VehicleType::Car,
+            VehicleType::Bike,
+            VehicleType::Bus,
+            VehicleType::Truck,
+        ] {
             let id = CarID(idx, *vt);
             if self.driving.tooltip_lines(id, self.time).is_some() {
                 return Some(id);
             }
         }
 
-        let id = CarID(idx, VehicleType::Car);
-        // Only cars can be parked.
-        if self.parking.tooltip_lines(id).is_some() {
-            return Some(id);
+        // Only cars and trucks can be parked.
+        for vt in &[VehicleType::Car, VehicleType::Truck] {
+            let id = CarID(idx, *vt);
+            if self.parking.tooltip_lines(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -992,6 +1234,14 @@ impl Sim {
         self.intersections.get_accepted_agents(id)
     }
 
+    pub fn current_signal_phase<'a>(
+        &self,
+        id: IntersectionID,
+        map: &'a Map,
+    ) -> (usize, &'a Phase, Duration) {
+        self.intersections.current_signal_phase(id, self.time, map)
+    }
+
     pub fn location_of_buses(&self, route: BusRouteID, map: &Map) -> Vec<(CarID, Pt2D)> {
         let mut results = Vec::new();
         for car in self.transit.buses_for_route(route) {
@@ -1019,7 +1269,7 @@ impl Sim {
             .find_blockage_front(car, map, &self.intersections)
     }
 
-    pub fn trip_spec_to_path_req(&self, spec: &TripSpec, map: &Map) -> PathRequest {
+    pub fn trip_spec_to_path_req(&self, spec: &TripSpec, map: &Map) -> Option<PathRequest> {
         spec.get_pathfinding_request(map, &self.parking)
     }
 }
@@ -1,10 +1,10 @@
 use crate::{
     AgentID, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event, ParkingSimState,
-    ParkingSpot, PedestrianID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
-    Vehicle, VehicleType, WalkingSimState,
+    ParkingSpot, PedestrianID, Router, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState,
+    TripID, Vehicle, VehicleType, WalkingSimState,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
-use geom::{Speed, Time};
+use geom::{Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
 };
@@ -50,7 +50,7 @@ impl TripManager {
                         mode = TripMode::Drive;
                     }
                 }
-                TripLeg::Drive(_, _) => {
+                TripLeg::Drive(_, _) | TripLeg::Deliver(_, _, _) => {
                     mode = TripMode::Drive;
                 }
                 TripLeg::RideBus(_, _, _) => {
@@ -60,6 +60,12 @@ impl TripManager {
                     // Confusing, because Transit usually means riding transit
                     mode = TripMode::Transit;
                 }
+                TripLeg::RideHail(_, _) => {
+                    mode = TripMode::RideHail;
+                }
+                TripLeg::ServeRideHail(_) => {
+                    mode = TripMode::Drive;
+                }
             }
         }
         let end = match legs.last() {
@@ -77,6 +83,7 @@ impl TripManager {
                 DrivingGoal::Border(i, _) => TripEnd::Border(*i),
             },
             Some(TripLeg::ServeBusRoute(_, route)) => TripEnd::ServeBusRoute(*route),
+            Some(TripLeg::ServeRideHail(_)) => TripEnd::ServeRideHail,
             _ => unreachable!(),
         };
         let trip = Trip {
@@ -89,7 +96,7 @@ impl TripManager {
             start,
             end,
         };
-        if !trip.is_bus_trip() {
+        if !trip.never_finishes() {
             self.unfinished_trips += 1;
         }
         self.trips.push(trip);
@@ -119,7 +126,7 @@ impl TripManager {
         car: CarID,
         spot: ParkingSpot,
         map: &Map,
-        parking: &ParkingSimState,
+        parking: &mut ParkingSimState,
         scheduler: &mut Scheduler,
     ) {
         self.events.push(Event::CarReachedParkingSpot(car, spot));
@@ -127,6 +134,34 @@ impl TripManager {
 
         match trip.legs.pop_front() {
             Some(TripLeg::Drive(vehicle, DrivingGoal::ParkNear(_))) => assert_eq!(car, vehicle.id),
+            Some(TripLeg::Deliver(vehicle, b, dwell)) => {
+                // The truck pulled into a loading zone. Unpark after the delivery.
+                assert_eq!(car, vehicle.id);
+                self.events.push(Event::DeliveryMade(car, b));
+                let parked_car = parking.get_car_at_spot(spot).unwrap().clone();
+                let start = parking.spot_to_driving_pos(spot, &parked_car.vehicle, map);
+                if let Some((router, req)) = trip.route_next_driving_leg(now, start, map) {
+                    scheduler.push(
+                        now + dwell,
+                        Command::SpawnCar(
+                            CreateCar::for_parked_car(
+                                parked_car,
+                                router,
+                                req,
+                                start.dist_along(),
+                                trip.id,
+                            ),
+                            true,
+                        ),
+                    );
+                } else {
+                    parking.remove_parked_car(parked_car);
+                    self.unfinished_trips -= 1;
+                    trip.aborted = true;
+                    self.events.push(Event::TripAborted(trip.id));
+                }
+                return;
+            }
             _ => unreachable!(),
         };
 
@@ -224,6 +259,43 @@ impl TripManager {
         );
     }
 
+    // The truck stopped in the driving lane at pos to make a delivery, since no loading zone was
+    // free. Returns the route onwards and how long to stay double-parked first, or None if the
+    // trip was aborted.
+    pub fn truck_double_parked(
+        &mut self,
+        now: Time,
+        car: CarID,
+        pos: Position,
+        map: &Map,
+    ) -> Option<(Router, Duration)> {
+        let trip = &mut self.trips[self.active_trip_mode[&AgentID::Car(car)].0];
+        let (b, dwell) = match trip.legs.pop_front() {
+            Some(TripLeg::Deliver(vehicle, b, dwell)) => {
+                assert_eq!(car, vehicle.id);
+                (b, dwell)
+            }
+            _ => unreachable!(),
+        };
+        self.events.push(Event::DeliveryMade(car, b));
+        self.events.push(Event::TruckDoubleParked(car, pos.lane()));
+
+        if let Some((router, req)) = trip.route_next_driving_leg(now, pos, map) {
+            self.events.push(Event::TripPhaseStarting(
+                trip.id,
+                Some(req),
+                format!("{} leaving {}", car, b),
+            ));
+            Some((router, dwell))
+        } else {
+            self.active_trip_mode.remove(&AgentID::Car(car));
+            self.unfinished_trips -= 1;
+            trip.aborted = true;
+            self.events.push(Event::TripAborted(trip.id));
+            None
+        }
+    }
+
     pub fn ped_ready_to_bike(
         &mut self,
         now: Time,
@@ -391,6 +463,69 @@ impl TripManager {
         }
     }
 
+    pub fn ped_requested_ride_hail(&mut self, ped: PedestrianID) {
+        let trip = &self.trips[self.active_trip_mode[&AgentID::Pedestrian(ped)].0];
+        self.events.push(Event::TripPhaseStarting(
+            trip.id,
+            None,
+            format!("{} waiting for a ride-hail vehicle", ped),
+        ));
+    }
+
+    pub fn ped_picked_up(&mut self, ped: PedestrianID, car: CarID, start: Position, end: Position) {
+        let trip = &self.trips[self.active_trip_mode[&AgentID::Pedestrian(ped)].0];
+        match trip.legs[0] {
+            TripLeg::RideHail(p, _) => assert_eq!(p, ped),
+            _ => unreachable!(),
+        }
+        self.events.push(Event::TripPhaseStarting(
+            trip.id,
+            Some(PathRequest {
+                start,
+                end,
+                constraints: PathConstraints::Car,
+            }),
+            format!("{} riding in {}", ped, car),
+        ));
+    }
+
+    pub fn ped_dropped_off(
+        &mut self,
+        now: Time,
+        ped: PedestrianID,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let trip = &mut self.trips[self
+            .active_trip_mode
+            .remove(&AgentID::Pedestrian(ped))
+            .unwrap()
+            .0];
+        let start = match trip.legs.pop_front().unwrap() {
+            TripLeg::RideHail(_, spot) => spot,
+            _ => unreachable!(),
+        };
+
+        if !trip.spawn_ped(now, start, map, scheduler) {
+            self.unfinished_trips -= 1;
+        }
+    }
+
+    // No ride-hail vehicle could reach the passenger.
+    pub fn abort_ride_hail(&mut self, ped: PedestrianID) {
+        let trip = self
+            .active_trip_mode
+            .remove(&AgentID::Pedestrian(ped))
+            .unwrap();
+        println!(
+            "Aborting {} because no ride-hail vehicle could reach {}",
+            trip, ped
+        );
+        self.trips[trip.0].aborted = true;
+        self.unfinished_trips -= 1;
+        self.events.push(Event::TripAborted(trip));
+    }
+
     pub fn ped_reached_border(
         &mut self,
         now: Time,
@@ -436,7 +571,7 @@ impl TripManager {
 
     pub fn abort_trip_failed_start(&mut self, id: TripID) {
         self.trips[id.0].aborted = true;
-        if !self.trips[id.0].is_bus_trip() {
+        if !self.trips[id.0].never_finishes() {
             self.unfinished_trips -= 1;
         }
         self.events.push(Event::TripAborted(id));
@@ -444,7 +579,7 @@ impl TripManager {
 
     pub fn abort_trip_impossible_parking(&mut self, car: CarID) {
         let trip = self.active_trip_mode.remove(&AgentID::Car(car)).unwrap();
-        assert!(!self.trips[trip.0].is_bus_trip());
+        assert!(!self.trips[trip.0].never_finishes());
         self.trips[trip.0].aborted = true;
         self.unfinished_trips -= 1;
         self.events.push(Event::TripAborted(trip));
@@ -470,10 +605,14 @@ impl TripManager {
 
         match &trip.legs[0] {
             TripLeg::Walk(id, _, _) => TripResult::Ok(AgentID::Pedestrian(*id)),
-            TripLeg::Drive(vehicle, _) => TripResult::Ok(AgentID::Car(vehicle.id)),
+            TripLeg::Drive(vehicle, _) | TripLeg::Deliver(vehicle, _, _) => {
+                TripResult::Ok(AgentID::Car(vehicle.id))
+            }
             // TODO Should be the bus, but apparently transit sim tracks differently?
             TripLeg::RideBus(ped, _, _) => TripResult::Ok(AgentID::Pedestrian(*ped)),
             TripLeg::ServeBusRoute(id, _) => TripResult::Ok(AgentID::Car(*id)),
+            TripLeg::RideHail(ped, _) => TripResult::Ok(AgentID::Pedestrian(*ped)),
+            TripLeg::ServeRideHail(id) => TripResult::Ok(AgentID::Car(*id)),
         }
     }
 
@@ -594,7 +733,9 @@ impl Trip {
             },
             // No need to look up the contents of a SidewalkPOI::ParkingSpot. If a trip uses a
             // specific parked car, then there'll be a TripLeg::Drive with it already.
-            TripLeg::Drive(ref vehicle, _) => vehicle.id == id,
+            TripLeg::Drive(ref vehicle, _) | TripLeg::Deliver(ref vehicle, _, _) => {
+                vehicle.id == id
+            }
             _ => false,
         })
     }
@@ -607,6 +748,16 @@ impl Trip {
             }
     }
 
+    // Buses and ride-hail vehicles serve other trips forever.
+    fn never_finishes(&self) -> bool {
+        self.is_bus_trip()
+            || (self.legs.len() == 1
+                && match self.legs[0] {
+                    TripLeg::ServeRideHail(_) => true,
+                    _ => false,
+                })
+    }
+
     // Returns true if this succeeds. If not, trip aborted.
     fn spawn_ped(
         &self,
@@ -650,6 +801,46 @@ impl Trip {
         true
     }
 
+    // For trucks partway through a delivery tour. The next leg is either another delivery or
+    // the final drive.
+    fn route_next_driving_leg(
+        &self,
+        now: Time,
+        start: Position,
+        map: &Map,
+    ) -> Option<(Router, PathRequest)> {
+        let (vehicle_type, end) = match self.legs[0] {
+            TripLeg::Deliver(ref vehicle, b, _) => {
+                (vehicle.vehicle_type, Position::bldg_via_driving(b, map)?)
+            }
+            TripLeg::Drive(ref vehicle, ref goal) => (
+                vehicle.vehicle_type,
+                goal.goal_pos(vehicle.vehicle_type.to_constraints(), map),
+            ),
+            _ => unreachable!(),
+        };
+        let req = PathRequest {
+            start,
+            end,
+            constraints: vehicle_type.to_constraints(),
+        };
+        let path = if let Some(p) = map.pathfind(req.clone()) {
+            p
+        } else {
+            println!(
+                "Aborting {} at {} because no path for the next delivery! {} to {}",
+                self.id, now, start, end
+            );
+            return None;
+        };
+        let router = match self.legs[0] {
+            TripLeg::Deliver(_, b, _) => Router::make_delivery(path, b, end.dist_along()),
+            TripLeg::Drive(_, ref goal) => goal.make_router(path, map, vehicle_type),
+            _ => unreachable!(),
+        };
+        Some((router, req))
+    }
+
     fn assert_walking_leg(&mut self, ped: PedestrianID, goal: SidewalkSpot) {
         match self.legs.pop_front() {
             Some(TripLeg::Walk(p, _, spot)) => {
@@ -667,8 +858,13 @@ impl Trip {
 pub enum TripLeg {
     Walk(PedestrianID, Speed, SidewalkSpot),
     Drive(Vehicle, DrivingGoal),
+    // Drive to a building, then stay there for the delivery
+    Deliver(Vehicle, BuildingID, Duration),
     RideBus(PedestrianID, BusRouteID, BusStopID),
     ServeBusRoute(CarID, BusRouteID),
+    // Ride as a passenger, getting out at this spot
+    RideHail(PedestrianID, SidewalkSpot),
+    ServeRideHail(CarID),
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
@@ -677,6 +873,7 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    RideHail,
 }
 
 impl TripMode {
@@ -686,6 +883,7 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::RideHail,
         ]
     }
 
@@ -693,7 +891,7 @@ impl TripMode {
         match id {
             AgentID::Pedestrian(_) => TripMode::Walk,
             AgentID::Car(id) => match id.1 {
-                VehicleType::Car => TripMode::Drive,
+                VehicleType::Car | VehicleType::Truck => TripMode::Drive,
                 VehicleType::Bike => TripMode::Bike,
                 VehicleType::Bus => TripMode::Transit,
             },
@@ -708,6 +906,7 @@ impl std::fmt::Display for TripMode {
             TripMode::Bike => write!(f, "bike"),
             TripMode::Transit => write!(f, "transit"),
             TripMode::Drive => write!(f, "drive"),
+            TripMode::RideHail => write!(f, "ride-hail"),
         }
     }
 }
@@ -726,6 +925,7 @@ pub enum TripEnd {
     Border(IntersectionID),
     // No end!
     ServeBusRoute(BusRouteID),
+    ServeRideHail,
 }
 
 pub enum TripResult<T> {
@@ -1,12 +1,165 @@
 use crate::runner::TestRunner;
+use geom::{Distance, Duration, GPSBounds, LonLat, Speed, Time};
+use kml::AttributeMapping;
+use map_model::{
+    osm, parse_conditional_maxspeed, parse_maxspeed, DirectedRoadID, EditCmd, LaneType,
+    LevelOfTrafficStress, PathConstraints, PathRequest, PathStep, PermanentMapEdits, Position,
+    RoadSpec, TravelMode,
+};
+use std::collections::{BTreeMap, HashSet};
 
 pub fn run(t: &mut TestRunner) {
+    t.run_fast("maxspeed_parsing", |_| {
+        assert_eq!(parse_maxspeed("25 mph"), Some(Speed::miles_per_hour(25.0)));
+        assert_eq!(parse_maxspeed("36"), Some(Speed::meters_per_second(10.0)));
+        assert_eq!(
+            parse_maxspeed("36 km/h"),
+            Some(Speed::meters_per_second(10.0))
+        );
+        assert_eq!(parse_maxspeed("signals"), None);
+
+        let school_zone = parse_conditional_maxspeed("20 mph @ (Mo-Fr 07:00-09:00, 14:00-16:00)");
+        assert_eq!(school_zone.len(), 2);
+        assert_eq!(school_zone[0].0, Time::parse("07:00:00").unwrap());
+        assert_eq!(school_zone[1].1, Time::parse("16:00:00").unwrap());
+        assert_eq!(school_zone[1].2, Speed::miles_per_hour(20.0));
+        assert!(parse_conditional_maxspeed("30 @ wet").is_empty());
+    });
+
+    t.run_fast("road_spec_widths", |_| {
+        let spec = RoadSpec::parse("db1.5s2/ds".to_string()).unwrap();
+        assert_eq!(
+            spec.fwd[0],
+            (LaneType::Driving, LaneType::Driving.default_width())
+        );
+        assert_eq!(spec.fwd[1], (LaneType::Biking, Distance::meters(1.5)));
+        assert_eq!(spec.fwd[2], (LaneType::Sidewalk, Distance::meters(2.0)));
+        assert_eq!(spec.back.len(), 2);
+        assert_eq!(spec.to_string(), "db1.5s2/ds");
+        assert!(RoadSpec::parse("dx/d".to_string()).is_none());
+    });
+
+    t.run_fast("wkt_in_csv", |_| {
+        let path = "wkt_in_csv.csv".to_string();
+        std::fs::write(
+            &path,
+            "name,WKT\n\
+             pt,POINT (-122.3 47.6)\n\
+             line,\"LINESTRING Z (-122.3 47.6 1, -122.31 47.61 2)\"\n\
+             multi,\"MULTIPOINT ((-122.3 47.6), (-122.31 47.61))\"\n\
+             holes,\"POLYGON ((-122.3 47.6, -122.31 47.6, -122.3 47.61), (0 0, 1 1, 1 0))\"\n\
+             parts,\"MULTIPOLYGON (((-122.3 47.6, -122.31 47.6, -122.3 47.61)), ((-122.32 47.6, \
+             -122.33 47.6, -122.32 47.61)))\"\n\
+             far_away,POINT (0 0)\n\
+             garbage,CIRCLE (1 2)\n",
+        )
+        .unwrap();
+        let shapes = kml::load_any(
+            &path,
+            &GPSBounds::seattle_bounds(),
+            &AttributeMapping::default(),
+            &mut abstutil::Timer::throwaway(),
+        )
+        .unwrap()
+        .shapes;
+        std::fs::remove_file(&path).unwrap();
+
+        let names: Vec<&str> = shapes
+            .iter()
+            .map(|s| s.attributes["name"].as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["pt", "line", "multi", "multi", "holes", "parts", "parts"]
+        );
+        assert_eq!(shapes[0].points, vec![LonLat::new(-122.3, 47.6)]);
+        assert_eq!(shapes[1].points[1], LonLat::new(-122.31, 47.61));
+        // Only the outer ring of a polygon is kept.
+        assert_eq!(shapes[4].points.len(), 3);
+        assert!(!shapes[0].attributes.contains_key("WKT"));
+    });
+
+    t.run_fast("geojson_shapes", |_| {
+        let path = "geojson_shapes.geojson".to_string();
+        std::fs::write(
+            &path,
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "properties": {"kind": "a", "width": 2, "note": null},
+                 "geometry": {"type": "LineString",
+                              "coordinates": [[-122.3, 47.6], [-122.31, 47.61]]}},
+                {"type": "Feature", "properties": {}, "geometry": null}
+            ]}"#,
+        )
+        .unwrap();
+        let shapes = kml::load_any(
+            &path,
+            &GPSBounds::seattle_bounds(),
+            &AttributeMapping::default(),
+            &mut abstutil::Timer::throwaway(),
+        )
+        .unwrap()
+        .shapes;
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].attributes["width"], "2");
+        assert!(!shapes[0].attributes.contains_key("note"));
+
+        // A bare geometry is an error, not a panic.
+        std::fs::write(&path, r#"{"type": "Point", "coordinates": [-122.3, 47.6]}"#).unwrap();
+        assert!(kml::load_any(
+            &path,
+            &GPSBounds::seattle_bounds(),
+            &AttributeMapping::default(),
+            &mut abstutil::Timer::throwaway(),
+        )
+        .is_err());
+        std::fs::remove_file(&path).unwrap();
+    });
+
+    t.run_fast("attribute_mapping", |_| {
+        let shape = |attribs: Vec<(&str, &str)>| kml::ExtraShape {
+            points: vec![LonLat::new(-122.3, 47.6)],
+            attributes: attribs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let mut mapping = AttributeMapping::default();
+        mapping
+            .rename_keys
+            .insert("TYPE".to_string(), "kind".to_string());
+        let mut values = BTreeMap::new();
+        values.insert("PAID".to_string(), "paid".to_string());
+        mapping.rename_values.insert("kind".to_string(), values);
+        mapping
+            .keep_if
+            .insert("kind".to_string(), vec!["paid".to_string()]);
+
+        let result = mapping.apply(kml::ExtraShapes {
+            shapes: vec![
+                shape(vec![("TYPE", "PAID"), ("other", "x")]),
+                shape(vec![("TYPE", "FREE")]),
+                shape(vec![("other", "y")]),
+            ],
+        });
+        assert_eq!(result.shapes.len(), 1);
+        assert_eq!(result.shapes[0].attributes["kind"], "paid");
+        assert_eq!(result.shapes[0].attributes["other"], "x");
+
+        // The identity mapping keeps everything untouched.
+        let result = AttributeMapping::default().apply(kml::ExtraShapes {
+            shapes: vec![shape(vec![("TYPE", "FREE")])],
+        });
+        assert_eq!(result.shapes[0].attributes["TYPE"], "FREE");
+    });
+
     t.run_slow("convert_osm_twice", |_| {
         let flags = convert_osm::Flags {
             osm: "../data/input/osm/montlake.osm".to_string(),
             parking_shapes: Some("../data/input/blockface.bin".to_string()),
+            parking_shapes_mapping: None,
             offstreet_parking: Some("../data/input/offstreet_parking.kml".to_string()),
             sidewalks: Some("../data/input/sidewalks.bin".to_string()),
+            sidewalks_mapping: None,
             gtfs: Some("../data/input/google_transit_2018_18_08".to_string()),
             neighborhoods: Some("../data/input/neighborhoods.geojson".to_string()),
             clip: Some(abstutil::path_polygon("montlake")),
@@ -44,6 +197,245 @@ pub fn run(t: &mut TestRunner) {
         }
     });
 
+    t.run_slow("pathfind_alternatives", |_| {
+        let map = map_model::Map::new(
+            abstutil::path_raw_map("montlake"),
+            true,
+            &mut abstutil::Timer::throwaway(),
+        );
+        // Stick to the well-connected part of the map, so a path definitely exists
+        let (connected, _) = map_model::connectivity::find_scc(&map, PathConstraints::Car);
+        let req = PathRequest {
+            start: Position::new(*connected.iter().min().unwrap(), Distance::ZERO),
+            end: Position::new(*connected.iter().max().unwrap(), Distance::ZERO),
+            constraints: PathConstraints::Car,
+        };
+        let alts = map.pathfind_alternatives(req.clone(), 3);
+        if alts.len() < 2 {
+            panic!(
+                "Only found {} route between two far-apart lanes",
+                alts.len()
+            );
+        }
+        let mut seen = HashSet::new();
+        for alt in &alts {
+            // Every alternative is a different route between the same two places
+            assert!(seen.insert(alt.path.get_steps().clone()));
+            assert_eq!(alt.path.current_step(), PathStep::Lane(req.start.lane()));
+            assert_eq!(alt.path.last_step(), PathStep::Lane(req.end.lane()));
+            assert!(alt.distance > Distance::ZERO);
+            assert!(alt.pct_bike_lanes >= 0.0 && alt.pct_bike_lanes <= 1.0);
+        }
+        // Cheapest first
+        for pair in alts.windows(2) {
+            assert!(pair[0].cost <= pair[1].cost);
+        }
+    });
+
+    t.run_slow("bike_lts", |_| {
+        let map = map_model::Map::new(
+            abstutil::path_raw_map("montlake"),
+            true,
+            &mut abstutil::Timer::throwaway(),
+        );
+        let is_motor_lane = |lt: &LaneType| *lt == LaneType::Driving || *lt == LaneType::Bus;
+        let mut num_quiet = 0;
+        for l in map.all_lanes() {
+            let r = map.get_r(l.parent);
+            let (fwds, _) = r.dir_and_offset(l.id);
+            let speed = r.get_speed_limit(fwds).normal;
+            let this_side = if fwds {
+                &r.children_forwards
+            } else {
+                &r.children_backwards
+            };
+            let motor_lanes_this_side =
+                this_side.iter().filter(|(_, lt)| is_motor_lane(lt)).count();
+            let motor_lanes = r
+                .children_forwards
+                .iter()
+                .chain(r.children_backwards.iter())
+                .filter(|(_, lt)| is_motor_lane(lt))
+                .count();
+            let residential = match r.osm_tags.get(osm::HIGHWAY).map(|x| x.as_str()) {
+                Some("residential") | Some("living_street") | Some("service") => true,
+                _ => false,
+            };
+
+            match l.lane_type {
+                LaneType::Sidewalk
+                | LaneType::Parking
+                | LaneType::SharedLeftTurn
+                | LaneType::Construction
+                | LaneType::LoadingZone => {
+                    assert_eq!(l.bike_lts, None);
+                }
+                LaneType::Driving | LaneType::Bus => {
+                    if residential && motor_lanes <= 3 && speed <= Speed::miles_per_hour(25.0) {
+                        assert_eq!(l.bike_lts, Some(LevelOfTrafficStress::LTS1));
+                        num_quiet += 1;
+                    } else if speed > Speed::miles_per_hour(30.0) {
+                        assert_eq!(l.bike_lts, Some(LevelOfTrafficStress::LTS4));
+                    } else {
+                        assert!(l.bike_lts >= Some(LevelOfTrafficStress::LTS2));
+                    }
+                }
+                LaneType::Biking => {
+                    if motor_lanes_this_side <= 1 && speed <= Speed::miles_per_hour(25.0) {
+                        assert_eq!(l.bike_lts, Some(LevelOfTrafficStress::LTS1));
+                    } else if motor_lanes_this_side >= 2 {
+                        assert!(l.bike_lts >= Some(LevelOfTrafficStress::LTS3));
+                    }
+                }
+            }
+        }
+        // Montlake is mostly quiet residential streets.
+        assert!(num_quiet > 0);
+        assert_eq!(
+            LevelOfTrafficStress::parse("2"),
+            Ok(LevelOfTrafficStress::LTS2)
+        );
+        assert!(LevelOfTrafficStress::parse("5").is_err());
+    });
+
+    t.run_slow("isochrones", |_| {
+        let map = map_model::Map::new(
+            abstutil::path_raw_map("montlake"),
+            true,
+            &mut abstutil::Timer::throwaway(),
+        );
+        let start = map.all_buildings()[0].id;
+        for mode in vec![TravelMode::Walk, TravelMode::Transit, TravelMode::Bike] {
+            let small = map.isochrone(start, mode, Duration::minutes(5));
+            let big = map.isochrone(start, mode, Duration::minutes(15));
+            assert!(small.times.contains_key(&start));
+            let (only_small, _) = small.compare(&big);
+            assert!(only_small.is_empty());
+            for (b, t) in &small.times {
+                assert_eq!(big.times[b], *t);
+            }
+        }
+    });
+
+    t.run_slow("permanent_edits_roundtrip", |_| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let lane = map
+            .all_lanes()
+            .iter()
+            .find(|l| l.lane_type == LaneType::Driving)
+            .unwrap()
+            .id;
+        let dr = DirectedRoadID {
+            id: map.get_l(lane).parent,
+            forwards: true,
+        };
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeLaneType {
+            id: lane,
+            lt: LaneType::Bus,
+            orig_lt: LaneType::Driving,
+        });
+        edits.commands.push(EditCmd::ChangeSpeedLimit {
+            id: dr,
+            new: Speed::miles_per_hour(20.0),
+            old: map.get_r(dr.id).get_speed_limit(true).normal,
+        });
+        map.apply_edits(edits, &mut timer);
+
+        let copy = PermanentMapEdits::new(&mut map, &mut timer).migrate(&mut map, &mut timer);
+        assert_eq!(
+            format!("{:?}", copy.commands),
+            format!("{:?}", map.get_edits().commands)
+        );
+    });
+
+    t.run_slow("restripe_roundtrip", |_| {
+        let mut timer = abstutil::Timer::throwaway();
+        let mut map = map_model::Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+        let r = map
+            .all_roads()
+            .iter()
+            .find(|r| {
+                r.children_forwards.len() == 2
+                    && r.children_forwards[0].1 == LaneType::Driving
+                    && r.all_bus_stops(&map).is_empty()
+            })
+            .unwrap()
+            .id;
+        let (i1, i2) = (map.get_r(r).src_i, map.get_r(r).dst_i);
+        let orig_children = map.get_r(r).children_forwards.clone();
+        let orig_polygons = vec![
+            map.get_i(i1).polygon.points().clone(),
+            map.get_i(i2).polygon.points().clone(),
+        ];
+        let orig_turns = map.all_turns().len();
+
+        // Widen the road with another driving lane
+        let new_lane = map.next_lane_id();
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::AddLane {
+            id: new_lane,
+            r,
+            forwards: true,
+            idx: 1,
+            lt: LaneType::Driving,
+            width: Distance::meters(3.0),
+        });
+        map.apply_edits(edits.clone(), &mut timer);
+        assert_eq!(map.get_r(r).children_forwards[1].0, new_lane);
+        assert_eq!(map.get_l(new_lane).width, Distance::meters(3.0));
+        assert!(!map.get_turns_from_lane(new_lane).is_empty());
+        // The intersections have to make room for it
+        assert_ne!(map.get_i(i1).polygon.points(), &orig_polygons[0]);
+
+        // The new ID has to be the next free one
+        assert!(EditCmd::AddLane {
+            id: map_model::LaneID(new_lane.0 + 10),
+            r,
+            forwards: true,
+            idx: 0,
+            lt: LaneType::Driving,
+            width: Distance::meters(3.0),
+        }
+        .check_ids(&map)
+        .is_err());
+
+        edits.commands.push(EditCmd::ChangeLaneWidth {
+            id: new_lane,
+            width: Distance::meters(4.0),
+            orig_width: Distance::meters(3.0),
+        });
+        edits
+            .commands
+            .push(EditCmd::delete_lane(orig_children[1].0, &map));
+        map.apply_edits(edits, &mut timer);
+        assert!(map.is_lane_deleted(orig_children[1].0));
+        assert!(!map.get_r(r).all_lanes().contains(&orig_children[1].0));
+        assert!(map.get_turns_from_lane(orig_children[1].0).is_empty());
+        assert!(map
+            .all_turns()
+            .keys()
+            .all(|t| t.src != orig_children[1].0 && t.dst != orig_children[1].0));
+
+        // Undoing everything should get back the original road
+        map.apply_edits(
+            map_model::MapEdits::new(map.get_name().to_string()),
+            &mut timer,
+        );
+        assert_eq!(map.get_r(r).children_forwards, orig_children);
+        assert!(!map.is_lane_deleted(orig_children[1].0));
+        assert!(map.is_lane_deleted(new_lane));
+        assert_eq!(map.all_turns().len(), orig_turns);
+        for (i, orig) in vec![i1, i2].into_iter().zip(orig_polygons) {
+            let pts = map.get_i(i).polygon.points();
+            assert_eq!(pts.len(), orig.len());
+            for (pt1, pt2) in pts.iter().zip(orig.iter()) {
+                assert!(pt1.approx_eq(*pt2, Distance::meters(0.1)));
+            }
+        }
+    });
+
     t.run_slow("bigger_map_loads", |_| {
         map_model::Map::new(
             abstutil::path_raw_map("23rd"),
@@ -1,7 +1,11 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::Duration;
-use sim::{Scenario, SimFlags};
+use geom::{Duration, Time};
+use map_model::IntersectionID;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{Estimate, MonteCarloSummary, RunSummary, Scenario, Sim, SimFlags, SimOptions};
+use std::collections::BTreeMap;
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("small_spawn_completes", |h| {
@@ -12,4 +16,71 @@ pub fn run(t: &mut TestRunner) {
         h.setup_done(&mut sim);
         sim.just_run_until_done(&map, Some(Duration::minutes(70)));
     });
+
+    t.run_fast("confidence_interval", |_| {
+        let est = Estimate::new(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(est.mean, 3.0);
+        assert_eq!(est.samples, 5);
+        // t = 2.776 for 4 degrees of freedom, standard error is sqrt(2.5 / 5)
+        assert!((est.ci_high - est.mean - 2.776 * 0.5_f64.sqrt()).abs() < 1e-9);
+        assert!((est.mean - est.ci_low - 2.776 * 0.5_f64.sqrt()).abs() < 1e-9);
+
+        let single = Estimate::new(&[7.0]);
+        assert_eq!((single.ci_low, single.ci_high), (7.0, 7.0));
+        assert!(!est.differs_from(&Estimate::new(&[3.5, 4.0, 4.5])));
+        assert!(est.differs_from(&Estimate::new(&[10.0, 10.5, 11.0])));
+    });
+
+    t.run_fast("monte_carlo_missing_delays", |_| {
+        // Only one of the runs had any delay at the intersection.
+        let run = |seed: u8, delay: Option<f64>| RunSummary {
+            rng_seed: seed,
+            finished_at: Time::START_OF_DAY + Duration::hours(1),
+            finished_trips: 10,
+            aborted_trips: 0,
+            trip_times: BTreeMap::new(),
+            trip_times_per_mode: BTreeMap::new(),
+            intersection_delays: delay
+                .map(|dt| (IntersectionID(0), Duration::seconds(dt)))
+                .into_iter()
+                .collect(),
+        };
+        let summary =
+            MonteCarloSummary::new(vec![run(1, Some(30.0)), run(2, None), run(3, Some(0.0))]);
+        let est = &summary.intersection_delays[&IntersectionID(0)];
+        assert_eq!(est.samples, 3);
+        assert_eq!(est.mean, 10.0);
+    });
+
+    t.run_slow("monte_carlo_summary", |_| {
+        let (map, _, _) = SimFlags::for_test("monte_carlo_summary").load(&mut Timer::throwaway());
+        let scenario = Scenario::small_run(&map);
+        let mut runs = Vec::new();
+        for seed in 1..=3 {
+            let mut sim = Sim::new(
+                &map,
+                SimOptions::new("monte_carlo_summary"),
+                &mut Timer::throwaway(),
+            );
+            let mut rng = XorShiftRng::from_seed([seed; 16]);
+            scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+            sim.step(&map, Duration::minutes(10));
+            runs.push(RunSummary::new(seed, sim.get_analytics(), sim.time()));
+        }
+
+        let summary = MonteCarloSummary::new(runs);
+        assert_eq!(summary.runs.len(), 3);
+        assert_eq!(summary.finished_trips.samples, 3);
+        for est in summary.trip_times.values() {
+            assert!(est.ci_low <= est.mean && est.mean <= est.ci_high);
+        }
+        for est in summary.intersection_delays.values() {
+            assert_eq!(est.samples, 3);
+        }
+        let path = "monte_carlo_summary.json".to_string();
+        abstutil::write_json(path.clone(), &summary);
+        let loaded: MonteCarloSummary = abstutil::read_json(path.clone(), &mut Timer::throwaway());
+        assert!(loaded == summary);
+        std::fs::remove_file(path).unwrap();
+    });
 }
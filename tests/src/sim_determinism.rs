@@ -42,9 +42,10 @@ pub fn run(t: &mut TestRunner) {
             if sim1 != sim2 {
                 // TODO need to sort dicts in json output to compare
                 panic!(
-                    "sim state differs between {} and {}",
+                    "sim state differs between {} and {}:\n{}",
                     sim1.save(),
-                    sim2.save()
+                    sim2.save(),
+                    sim1.diff(&sim2).describe().join("\n")
                 );
             }
             sim1.step(&map, dt);
@@ -79,9 +80,10 @@ pub fn run(t: &mut TestRunner) {
 
         if sim1 != sim2 {
             panic!(
-                "sim state differs between {} and {}",
+                "sim state differs between {} and {}:\n{}",
                 sim1.save(),
-                sim2.save()
+                sim2.save(),
+                sim1.diff(&sim2).describe().join("\n")
             );
         }
 
@@ -89,6 +91,10 @@ pub fn run(t: &mut TestRunner) {
 
         sim1.step(&map, Duration::seconds(30.0));
 
+        let diff = sim1.diff(&sim2);
+        assert_eq!(diff.times, Some((sim1.time(), sim2.time())));
+        assert!(!diff.cars.is_empty() || !diff.peds.is_empty());
+        assert!(sim2.diff(&sim2).is_empty());
         if sim1 == sim2 {
             panic!(
                 "sim state unexpectly the same -- {} and {}",
@@ -102,9 +108,10 @@ pub fn run(t: &mut TestRunner) {
         sim3.set_name("with_savestating_3".to_string());
         if sim3 != sim2 {
             panic!(
-                "sim state differs between {} and {}",
+                "sim state differs between {} and {}:\n{}",
                 sim3.save(),
-                sim2.save()
+                sim2.save(),
+                sim3.diff(&sim2).describe().join("\n")
             );
         }
 
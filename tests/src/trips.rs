@@ -1,8 +1,18 @@
 use crate::runner::TestRunner;
 use abstutil::Timer;
-use geom::{Duration, Time};
-use map_model::{BuildingID, IntersectionID};
-use sim::{DrivingGoal, Event, Scenario, SidewalkSpot, SimFlags, TripSpec};
+use geom::{Distance, Duration, LonLat, Time};
+use map_model::raw::{OriginalBuilding, RawMap};
+use map_model::{
+    Building, BuildingID, EditCmd, IntersectionID, Map, PathConstraints, PathRequest, PathStep,
+    Phase, Position, Traversable, TurnID, TurnPriority, TurnType,
+};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use sim::{
+    AgentID, Blockage, DrivingGoal, Event, Incident, NearMiss, PermanentScenario, SavedScenario,
+    Scenario, SidewalkPOI, SidewalkSpot, Sim, SimFlags, SpawnTrip, TripMode, TripSpec, VehicleType,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub fn run(t: &mut TestRunner) {
     t.run_slow("bike_from_border", |h| {
@@ -37,4 +47,1167 @@ pub fn run(t: &mut TestRunner) {
         );
         sim.just_run_until_done(&map, Some(Duration::minutes(1)));
     });
+    t.run_slow("delivery_tour", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("delivery_tour").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let border = map.get_i(IntersectionID(186));
+        let goal_bldg = BuildingID(319);
+        let start_lane = border.get_outgoing_lanes(&map, PathConstraints::Truck)[0];
+        let (_, truck) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::DeliveryTour {
+                start_pos: TripSpec::spawn_truck_at(
+                    Position::new(start_lane, Distance::ZERO),
+                    &map,
+                )
+                .unwrap(),
+                vehicle_spec: Scenario::rand_truck(&mut rng),
+                stops: vec![(goal_bldg, Duration::minutes(2))],
+                goal: DrivingGoal::end_at_border(
+                    border.some_incoming_road(&map),
+                    PathConstraints::Truck,
+                    &map,
+                )
+                .unwrap(),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::DeliveryMade(truck.unwrap(), goal_bldg)],
+            Duration::minutes(7),
+        );
+        sim.just_run_until_done(&map, Some(Duration::minutes(5)));
+    });
+    t.run_slow("lane_change_around_idling_truck", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("lane_change_around_idling_truck").load(&mut Timer::throwaway());
+        // A truck double-parked for a delivery idles in the lane, just like a bus at a stop. Find
+        // a building served by a lane with a sibling next to it, without any parking nearby for
+        // the truck to use instead, and leaving room for somebody to queue behind the truck.
+        let (bldg, lane) = map
+            .all_buildings()
+            .iter()
+            .find_map(|b| {
+                let pos = Position::bldg_via_driving(b.id, &map)?;
+                let road = map.get_parent(pos.lane());
+                if !road.adjacent_lanes(pos.lane()).is_empty()
+                    && !road
+                        .all_lanes()
+                        .into_iter()
+                        .any(|l| map.get_l(l).is_parking())
+                    && pos.dist_along() > Distance::meters(50.0)
+                {
+                    Some((b.id, pos.lane()))
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        // Both vehicles leave the lane through the same border.
+        let (border, goal) = map
+            .all_outgoing_borders()
+            .into_iter()
+            .find_map(|i| {
+                let goal = DrivingGoal::end_at_border(
+                    i.some_incoming_road(&map),
+                    PathConstraints::Car,
+                    &map,
+                )?;
+                let end = goal.goal_pos(PathConstraints::Car, &map);
+                if end.lane() == lane {
+                    return None;
+                }
+                map.pathfind(PathRequest {
+                    start: Position::new(lane, Distance::ZERO),
+                    end,
+                    constraints: PathConstraints::Car,
+                })?;
+                Some((i.id, goal))
+            })
+            .unwrap();
+
+        sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::DeliveryTour {
+                start_pos: TripSpec::spawn_truck_at(Position::new(lane, Distance::ZERO), &map)
+                    .unwrap(),
+                vehicle_spec: Scenario::rand_truck(&mut rng),
+                stops: vec![(bldg, Duration::minutes(10))],
+                goal: goal.clone(),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        let (_, car) = sim.schedule_trip(
+            Time::START_OF_DAY + Duration::seconds(10.0),
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(lane, Distance::ZERO), &map)
+                    .unwrap(),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                goal,
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        // The car gets out long before the truck finishes the delivery, so it must have gone
+        // around.
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::CarOrBikeReachedBorder(car.unwrap(), border)],
+            Duration::minutes(5),
+        );
+        let road = map.get_l(lane).parent;
+        assert!(sim
+            .get_analytics()
+            .lane_changes
+            .iter()
+            .any(|(_, r)| *r == road));
+        sim.just_run_until_done(&map, Some(Duration::minutes(15)));
+    });
+    t.run_slow("kinematic_car", |h| {
+        let mut flags = SimFlags::for_test("kinematic_car");
+        flags.opts.kinematics = true;
+        let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let border = map.get_i(IntersectionID(186));
+        let start_lane = border.get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let (_, car) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map)
+                    .unwrap(),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                goal: DrivingGoal::end_at_border(
+                    border.some_incoming_road(&map),
+                    PathConstraints::Car,
+                    &map,
+                )
+                .unwrap(),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::CarOrBikeReachedBorder(car.unwrap(), border.id)],
+            Duration::minutes(7),
+        );
+        sim.just_run_until_done(&map, Some(Duration::minutes(1)));
+    });
+    t.run_slow("kinematic_crossing_times", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("kinematic_crossing_times").load(&mut Timer::throwaway());
+        let (accel, decel) = VehicleType::Car.accel_limits();
+
+        // How long one car alone on the map takes to drive from start to goal.
+        let drive = |kinematics: bool, start: Position, goal: DrivingGoal| -> f64 {
+            let mut flags = SimFlags::for_test("kinematic_crossing_times");
+            flags.opts.kinematics = kinematics;
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            sim.schedule_trip(
+                Time::START_OF_DAY,
+                TripSpec::CarAppearing {
+                    start_pos: start,
+                    vehicle_spec: Scenario::rand_car(&mut rng),
+                    goal,
+                    ped_speed: Scenario::rand_ped_speed(&mut rng),
+                },
+                &map,
+            );
+            sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+            sim.just_run_until_done(&map, Some(Duration::minutes(10)));
+            let finished = &sim.get_analytics().finished_trips;
+            assert_eq!(finished.len(), 1);
+            assert_eq!(finished[0].2, Some(TripMode::Drive));
+            finished[0].3.inner_seconds()
+        };
+        // Textbook kinematics: time to cover d meters with cruising speed v, starting at v0 and
+        // maybe stopping at the end, plus the final speed.
+        let ramp = |d: f64, v: f64, v0: f64, to_rest: bool| -> (f64, f64) {
+            let accel_dist = (v * v - v0 * v0) / (2.0 * accel);
+            let decel_dist = if to_rest { v * v / (2.0 * decel) } else { 0.0 };
+            if accel_dist + decel_dist <= d {
+                let t = (v - v0) / accel + (d - accel_dist - decel_dist) / v;
+                return if to_rest {
+                    (t + v / decel, 0.0)
+                } else {
+                    (t, v)
+                };
+            }
+            if !to_rest {
+                let v1 = (v0 * v0 + 2.0 * accel * d).sqrt();
+                return ((v1 - v0) / accel, v1);
+            }
+            let peak = ((2.0 * accel * decel * d + decel * v0 * v0) / (accel + decel)).sqrt();
+            ((peak - v0) / accel + peak / decel, 0.0)
+        };
+        let speed = |on: Traversable| {
+            on.speed_limit_at(&map, Time::START_OF_DAY)
+                .inner_meters_per_second()
+        };
+        let check = |actual: f64, expected: f64| {
+            if (actual - expected).abs() > 0.1 {
+                panic!("Trip took {}s, but expected {}s", actual, expected);
+            }
+        };
+
+        // One lane straight into a border: speed up from rest, cruise, and brake to a stop.
+        let lane = map
+            .all_outgoing_borders()
+            .into_iter()
+            .flat_map(|i| i.incoming_lanes.clone())
+            .filter(|l| map.get_l(*l).is_driving())
+            .max_by_key(|l| map.get_l(*l).length())
+            .unwrap();
+        let start = TripSpec::spawn_car_at(Position::new(lane, Distance::ZERO), &map).unwrap();
+        let goal = DrivingGoal::Border(map.get_l(lane).dst_i, lane);
+        let d = (map.get_l(lane).length() - start.dist_along()).inner_meters();
+        let v = speed(Traversable::Lane(lane));
+        check(drive(false, start, goal.clone()), d / v);
+        check(drive(true, start, goal), ramp(d, v, 0.0, true).0);
+
+        // Through a stop sign and into a border. If the car doesn't have to stop, it carries its
+        // speed through the turn; otherwise it brakes before the intersection, waits, and starts
+        // over.
+        for must_stop in vec![false, true] {
+            let (turn, start, goal) = map
+                .all_turns()
+                .values()
+                .filter_map(|turn| {
+                    let t = turn.id;
+                    let sign = map.maybe_get_stop_sign(t.parent)?;
+                    let wanted = if must_stop {
+                        TurnPriority::Yield
+                    } else {
+                        TurnPriority::Protected
+                    };
+                    if !map.get_l(t.src).is_driving()
+                        || !map.get_l(t.dst).is_driving()
+                        || sign.get_priority(t, &map) != wanted
+                        || !map.get_i(map.get_l(t.dst).dst_i).is_border()
+                    {
+                        return None;
+                    }
+                    let start = TripSpec::spawn_car_at(Position::new(t.src, Distance::ZERO), &map)?;
+                    let goal = DrivingGoal::Border(map.get_l(t.dst).dst_i, t.dst);
+                    let path = map.pathfind(PathRequest {
+                        start,
+                        end: goal.goal_pos(PathConstraints::Car, &map),
+                        constraints: PathConstraints::Car,
+                    })?;
+                    if path.get_steps().iter().cloned().collect::<Vec<_>>()
+                        != vec![
+                            PathStep::Lane(t.src),
+                            PathStep::Turn(t),
+                            PathStep::Lane(t.dst),
+                        ]
+                    {
+                        return None;
+                    }
+                    Some((t, start, goal))
+                })
+                .next()
+                .unwrap();
+
+            let steps = vec![
+                (
+                    (map.get_l(turn.src).length() - start.dist_along()).inner_meters(),
+                    speed(Traversable::Lane(turn.src)),
+                ),
+                (
+                    Traversable::Turn(turn).length(&map).inner_meters(),
+                    speed(Traversable::Turn(turn)),
+                ),
+                (
+                    map.get_l(turn.dst).length().inner_meters(),
+                    speed(Traversable::Lane(turn.dst)),
+                ),
+            ];
+            // Stop signs make cars wait half a second before going.
+            let wait = if must_stop { 0.5 } else { 0.0 };
+            check(
+                drive(false, start, goal.clone()),
+                steps.iter().map(|(d, v)| d / v).sum::<f64>() + wait,
+            );
+
+            let (t1, v1) = ramp(steps[0].0, steps[0].1, 0.0, must_stop);
+            let (t2, v2) = ramp(steps[1].0, steps[1].1, v1, false);
+            let (t3, _) = ramp(steps[2].0, steps[2].1, v2, true);
+            check(drive(true, start, goal), t1 + wait + t2 + t3);
+        }
+    });
+    t.run_slow("ped_crowding", |_| {
+        // The same crowd walks down the same sidewalk, with and without crowding.
+        let walk = |crowding: bool| {
+            let mut flags = SimFlags::for_test("ped_crowding");
+            flags.opts.ped_crowding = crowding;
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            let sidewalk = map
+                .all_lanes()
+                .iter()
+                .max_by_key(|l| l.building_paths.len())
+                .unwrap();
+            let start = SidewalkSpot::building(sidewalk.building_paths[0], &map);
+            let goal = SidewalkSpot::building(*sidewalk.building_paths.last().unwrap(), &map);
+            for _ in 0..100 {
+                sim.schedule_trip(
+                    Time::START_OF_DAY,
+                    TripSpec::JustWalking {
+                        start: start.clone(),
+                        goal: goal.clone(),
+                        ped_speed: Scenario::rand_ped_speed(&mut rng),
+                    },
+                    &map,
+                );
+            }
+            sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+            sim.just_run_until_done(&map, Some(Duration::minutes(30)));
+            let total: Duration = sim
+                .get_analytics()
+                .finished_trips
+                .iter()
+                .fold(Duration::ZERO, |sum, (_, _, _, dt)| sum + *dt);
+            (total, sim.get_analytics().ped_density.clone())
+        };
+
+        let (free_time, free_density) = walk(false);
+        let (crowded_time, crowded_density) = walk(true);
+        if !free_density.is_empty() {
+            panic!("Measured crowding even though it's off");
+        }
+        if crowded_density.is_empty() {
+            panic!("Didn't measure any crowding");
+        }
+        if crowded_time <= free_time {
+            panic!(
+                "Walking in a crowd took {}, but walking freely took {}",
+                crowded_time, free_time
+            );
+        }
+        // Only the peak per minute is kept
+        for samples in crowded_density.values() {
+            for pair in samples.windows(2) {
+                assert!(pair[1].0 - pair[0].0 >= Duration::minutes(1));
+            }
+        }
+    });
+    t.run_slow("jaywalking", |_| {
+        let run = |probability: f64, kinematics: bool| {
+            let mut flags = SimFlags::for_test("jaywalking");
+            flags.opts.jaywalking_probability = probability;
+            flags.opts.kinematics = kinematics;
+            let (map, mut sim, mut rng) = flags.load(&mut Timer::throwaway());
+            Scenario::small_run(&map).instantiate(
+                &mut sim,
+                &map,
+                &mut rng,
+                &mut Timer::throwaway(),
+            );
+            sim.just_run_until_done(&map, Some(Duration::minutes(70)));
+            let analytics = sim.get_analytics();
+            (
+                analytics.mid_block_crossings.clone(),
+                analytics.yields_to_peds.clone(),
+            )
+        };
+
+        let (crossings, yields) = run(0.0, false);
+        if !crossings.is_empty() || !yields.is_empty() {
+            panic!(
+                "Nobody should jaywalk, but {} did and {} cars yielded",
+                crossings.len(),
+                yields.len()
+            );
+        }
+
+        // Everybody crosses mid-block when the road allows it, and some drivers already on the
+        // road have to stop for them.
+        let (crossings, yields) = run(1.0, false);
+        if crossings.is_empty() {
+            panic!("Nobody crossed mid-block");
+        }
+        if yields.is_empty() {
+            panic!("No cars yielded to the {} jaywalkers", crossings.len());
+        }
+
+        // With kinematics, drivers need room to brake, but some still stop.
+        let (crossings, yields) = run(1.0, true);
+        if yields.is_empty() {
+            panic!(
+                "No cars braked for the {} jaywalkers with kinematics",
+                crossings.len()
+            );
+        }
+
+        // Who jaywalks only depends on --rng_seed.
+        let (crossings1, yields1) = run(0.5, false);
+        let (crossings2, yields2) = run(0.5, false);
+        if crossings1.is_empty() {
+            panic!("Nobody crossed mid-block, even though half of people want to");
+        }
+        assert_eq!(crossings1, crossings2);
+        assert_eq!(yields1, yields2);
+    });
+    t.run_slow("signal_phases", |_| {
+        let flags = SimFlags::for_test("signal_phases");
+        let (mut map, _, _) = flags.load(&mut Timer::throwaway());
+
+        // Find a phase where vehicles conflict with a protected crosswalk.
+        let mut found = None;
+        for i in map.all_intersections() {
+            if !i.is_traffic_signal() {
+                continue;
+            }
+            let signal = map.get_traffic_signal(i.id);
+            for (idx, phase) in signal.phases.iter().enumerate() {
+                let crosswalks: Vec<_> = phase
+                    .protected_groups
+                    .iter()
+                    .map(|g| &signal.turn_groups[g])
+                    .filter(|g| g.turn_type == TurnType::Crosswalk)
+                    .collect();
+                let vehicles = phase
+                    .protected_groups
+                    .iter()
+                    .chain(phase.yield_groups.iter())
+                    .map(|g| &signal.turn_groups[g])
+                    .find(|g| {
+                        g.turn_type != TurnType::Crosswalk
+                            && crosswalks.iter().any(|c| c.conflicts_with(g))
+                    });
+                if let Some(g) = vehicles {
+                    found = Some((i.id, idx, g.members[0]));
+                    break;
+                }
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+        let (i, idx, vehicle_turn) = found.unwrap();
+
+        // Give pedestrians a head start in that phase, and add a push-button phase at the start of
+        // the cycle.
+        let mut signal = map.get_traffic_signal(i).clone();
+        signal.phases[idx].leading_ped_interval = Duration::seconds(5.0);
+        let mut walk = Phase::new();
+        walk.push_button = true;
+        walk.protected_groups = signal
+            .turn_groups
+            .values()
+            .filter(|g| g.turn_type == TurnType::Crosswalk)
+            .map(|g| g.id)
+            .collect();
+        signal.phases.insert(0, walk);
+        let signal = signal.validate().unwrap();
+
+        let phase = &signal.phases[idx + 1];
+        let normal = phase.get_priority_of_turn(vehicle_turn, &signal);
+        assert_ne!(normal, TurnPriority::Banned);
+        assert_eq!(
+            phase.get_priority_of_turn_at(vehicle_turn, &signal, Duration::seconds(1.0)),
+            TurnPriority::Banned
+        );
+        assert_eq!(
+            phase.get_priority_of_turn_at(vehicle_turn, &signal, Duration::seconds(5.0)),
+            normal
+        );
+
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(EditCmd::ChangeTrafficSignal(signal));
+        map.apply_edits(edits, &mut Timer::throwaway());
+
+        // Nobody's waiting to cross, so the push-button phase is skipped from the very start.
+        let sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+        let (current, _, _) = sim.current_signal_phase(i, &map);
+        assert_ne!(current, 0);
+    });
+    t.run_slow("near_misses", |_| {
+        let mut flags = SimFlags::for_test("near_misses");
+        // Every turn yields, so conflicting turns can go one right after the other anywhere.
+        flags.opts.use_freeform_policy_everywhere = true;
+        let (map, _, _) = flags.load(&mut Timer::throwaway());
+        let mut rng = XorShiftRng::seed_from_u64(42);
+
+        // Where a car can appear, drive straight into some turn, and eventually leave the map,
+        // plus how long it takes to reach the turn.
+        let approach = |t: TurnID| -> Option<(Position, DrivingGoal, Duration)> {
+            if !map.get_l(t.src).is_driving() || !map.get_l(t.dst).is_driving() {
+                return None;
+            }
+            let start = TripSpec::spawn_car_at(Position::new(t.src, Distance::ZERO), &map)?;
+            let goal = map
+                .all_outgoing_borders()
+                .into_iter()
+                .filter_map(|i| {
+                    DrivingGoal::end_at_border(
+                        i.some_incoming_road(&map),
+                        PathConstraints::Car,
+                        &map,
+                    )
+                })
+                .find(|goal| {
+                    map.pathfind(PathRequest {
+                        start,
+                        end: goal.goal_pos(PathConstraints::Car, &map),
+                        constraints: PathConstraints::Car,
+                    })
+                    .map(|path| {
+                        let steps = path.get_steps();
+                        steps.len() > 2
+                            && steps[0] == PathStep::Lane(t.src)
+                            && steps[1] == PathStep::Turn(t)
+                    })
+                    .unwrap_or(false)
+                })?;
+            let dt = (map.get_l(t.src).length() - start.dist_along())
+                / Traversable::Lane(t.src).speed_limit_at(&map, Time::START_OF_DAY);
+            Some((start, goal, dt))
+        };
+        // Start two trips so that they reach their turns at the same time, no matter how long
+        // each one takes to get there.
+        let sync = |dt1: Duration, dt2: Duration| {
+            let latest = if dt1 > dt2 { dt1 } else { dt2 };
+            (
+                Time::START_OF_DAY + (latest - dt1),
+                Time::START_OF_DAY + (latest - dt2),
+            )
+        };
+        let car_trip =
+            |start: Position, goal: DrivingGoal, rng: &mut XorShiftRng| TripSpec::CarAppearing {
+                start_pos: start,
+                vehicle_spec: Scenario::rand_car(rng),
+                goal,
+                ped_speed: Scenario::rand_ped_speed(rng),
+            };
+
+        // A pedestrian walks across a crosswalk, and a car turning across it shows up halfway
+        // through. It has to wait, then goes the moment the pedestrian is out of the way.
+        {
+            let ped_speed = Scenario::rand_ped_speed(&mut rng);
+            let (crosswalk, ped_start, ped_goal, turn, car_start, car_goal, car_dt) = map
+                .all_turns()
+                .values()
+                .filter(|c| c.turn_type == TurnType::Crosswalk)
+                .find_map(|c| {
+                    let i = c.id.parent;
+                    let sidewalk = map.get_l(c.id.src);
+                    let ped_start = SidewalkSpot::suddenly_appear(
+                        sidewalk.id,
+                        if sidewalk.dst_i == i {
+                            sidewalk.length() - Distance::meters(1.0)
+                        } else {
+                            Distance::meters(1.0)
+                        },
+                        &map,
+                    );
+                    let ped_goal =
+                        SidewalkSpot::building(*map.get_l(c.id.dst).building_paths.get(0)?, &map);
+                    let path = map.pathfind(PathRequest {
+                        start: ped_start.sidewalk_pos,
+                        end: ped_goal.sidewalk_pos,
+                        constraints: PathConstraints::Pedestrian,
+                    })?;
+                    if path.get_steps().len() != 3 || path.get_steps()[1] != PathStep::Turn(c.id) {
+                        return None;
+                    }
+                    let (turn, (car_start, car_goal, car_dt)) = map
+                        .get_i(i)
+                        .turns
+                        .iter()
+                        .filter(|t| map.get_t(**t).conflicts_with(c))
+                        .find_map(|t| Some((*t, approach(*t)?)))?;
+                    Some((c.id, ped_start, ped_goal, turn, car_start, car_goal, car_dt))
+                })
+                .unwrap();
+
+            let ped_dt = Distance::meters(1.0) / ped_speed
+                + Traversable::Turn(crosswalk).length(&map) / ped_speed / 2.0;
+            let (ped_time, car_time) = sync(ped_dt, car_dt);
+            let mut sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+            sim.schedule_trip(
+                ped_time,
+                TripSpec::JustWalking {
+                    start: ped_start,
+                    goal: ped_goal,
+                    ped_speed,
+                },
+                &map,
+            );
+            let (_, car) =
+                sim.schedule_trip(car_time, car_trip(car_start, car_goal, &mut rng), &map);
+            sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+            sim.just_run_until_done(&map, Some(Duration::minutes(10)));
+
+            let agent = AgentID::Car(car.unwrap());
+            let found = sim
+                .get_analytics()
+                .near_misses
+                .iter()
+                .any(|(_, i, a, miss)| {
+                    *i == turn.parent
+                        && *a == agent
+                        && match miss {
+                            NearMiss::YieldedLateToPedestrian(gap) => *gap < Duration::seconds(3.0),
+                            _ => false,
+                        }
+                });
+            if !found {
+                panic!(
+                    "{} turned right after someone finished {}, but no near miss: {:?}",
+                    agent,
+                    crosswalk,
+                    sim.get_analytics().near_misses
+                );
+            }
+        }
+
+        // Two cars reach conflicting turns at once. Whoever goes second accepts a short gap.
+        {
+            let (t1, (start1, goal1, dt1), t2, (start2, goal2, dt2)) = map
+                .all_intersections()
+                .iter()
+                .find_map(|i| {
+                    let vehicle_turns: Vec<TurnID> = i
+                        .turns
+                        .iter()
+                        .filter(|t| map.get_t(**t).turn_type != TurnType::Crosswalk)
+                        .cloned()
+                        .collect();
+                    for t1 in &vehicle_turns {
+                        for t2 in &vehicle_turns {
+                            if t1.src == t2.src
+                                || t1.dst == t2.dst
+                                || !map.get_t(*t1).conflicts_with(map.get_t(*t2))
+                            {
+                                continue;
+                            }
+                            if let (Some(a), Some(b)) = (approach(*t1), approach(*t2)) {
+                                return Some((*t1, a, *t2, b));
+                            }
+                        }
+                    }
+                    None
+                })
+                .unwrap();
+
+            let (time1, time2) = sync(dt1, dt2);
+            let mut sim = Sim::new(&map, flags.opts.clone(), &mut Timer::throwaway());
+            let (_, car1) = sim.schedule_trip(time1, car_trip(start1, goal1, &mut rng), &map);
+            let (_, car2) = sim.schedule_trip(time2, car_trip(start2, goal2, &mut rng), &map);
+            sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+            sim.just_run_until_done(&map, Some(Duration::minutes(10)));
+
+            let cars = vec![AgentID::Car(car1.unwrap()), AgentID::Car(car2.unwrap())];
+            let found = sim
+                .get_analytics()
+                .near_misses
+                .iter()
+                .any(|(_, i, a, miss)| {
+                    *i == t1.parent
+                        && cars.contains(a)
+                        && match miss {
+                            NearMiss::ShortGapAccepted(gap) => *gap < Duration::seconds(1.5),
+                            _ => false,
+                        }
+                });
+            if !found {
+                panic!(
+                    "Cars doing {} and {} met, but no near miss: {:?}",
+                    t1,
+                    t2,
+                    sim.get_analytics().near_misses
+                );
+            }
+        }
+    });
+    t.run_slow("od_matrix", |_| {
+        let (map, _, mut rng) = SimFlags::for_test("od_matrix").load(&mut Timer::throwaway());
+        // One zone around all of the buildings, and another one off the map
+        let pts: Vec<LonLat> = map
+            .all_buildings()
+            .iter()
+            .map(|b| b.polygon.center().to_gps(map.get_gps_bounds()).unwrap())
+            .collect();
+        let min_lon = pts
+            .iter()
+            .map(|pt| pt.longitude)
+            .fold(std::f64::MAX, f64::min)
+            - 0.001;
+        let max_lon = pts
+            .iter()
+            .map(|pt| pt.longitude)
+            .fold(std::f64::MIN, f64::max)
+            + 0.001;
+        let min_lat = pts
+            .iter()
+            .map(|pt| pt.latitude)
+            .fold(std::f64::MAX, f64::min)
+            - 0.001;
+        let max_lat = pts
+            .iter()
+            .map(|pt| pt.latitude)
+            .fold(std::f64::MIN, f64::max)
+            + 0.001;
+        let square = |dx: f64| {
+            vec![
+                (min_lon, min_lat),
+                (max_lon, min_lat),
+                (max_lon, max_lat),
+                (min_lon, max_lat),
+                (min_lon, min_lat),
+            ]
+            .into_iter()
+            .map(|(lon, lat)| format!("[{}, {}]", lon + dx, lat))
+            .collect::<Vec<_>>()
+            .join(", ")
+        };
+        let zones_path = "od_matrix_zones.geojson".to_string();
+        std::fs::write(
+            &zones_path,
+            format!(
+                r#"{{"type": "FeatureCollection", "features": [
+                    {{"type": "Feature", "properties": {{"id": "here"}},
+                     "geometry": {{"type": "Polygon", "coordinates": [[{}]]}}}},
+                    {{"type": "Feature", "properties": {{"id": "away"}},
+                     "geometry": {{"type": "Polygon", "coordinates": [[{}]]}}}}
+                ]}}"#,
+                square(0.0),
+                square(1.0)
+            ),
+        )
+        .unwrap();
+        let matrix_path = "od_matrix.csv".to_string();
+        let import = |rows: &str, rng: &mut XorShiftRng| {
+            std::fs::write(
+                &matrix_path,
+                format!(
+                    "origin,destination,mode,start_time,end_time,trips\n{}",
+                    rows
+                ),
+            )
+            .unwrap();
+            popdat::import_od_matrix(
+                &zones_path,
+                "id",
+                &matrix_path,
+                popdat::BuildingWeight::Uniform,
+                &map,
+                rng,
+                &mut Timer::throwaway(),
+            )
+        };
+
+        // Pass-through trips are skipped, and a few others might not have a path.
+        let trips = import(
+            "here,here,walk,7:00:00.0,8:00:00.0,5\n\
+             here,away,drive,7:00:00.0,8:00:00.0,3\n\
+             away,away,drive,7:00:00.0,8:00:00.0,4\n",
+            &mut rng,
+        )
+        .unwrap();
+        assert!(trips.len() <= 8);
+        let walking = trips
+            .iter()
+            .filter(|trip| match trip {
+                SpawnTrip::JustWalking(_, _, _) => true,
+                _ => false,
+            })
+            .count();
+        let leaving = trips
+            .iter()
+            .filter(|trip| match trip {
+                SpawnTrip::MaybeUsingParkedCar(_, _, DrivingGoal::Border(_, _)) => true,
+                _ => false,
+            })
+            .count();
+        if walking == 0 || leaving == 0 || walking > 5 || leaving > 3 {
+            panic!(
+                "Expected up to 5 walking trips and 3 driving off the map, got {} and {}",
+                walking, leaving
+            );
+        }
+
+        for bad in vec![
+            "here,here,walk,7:00:00.0,8:00:00.0,-1",
+            "here,here,walk,7:00:00.0,8:00:00.0,NaN",
+            "here,here,walk,7:00:00.0,8:00:00.0,inf",
+            "here,nowhere,walk,7:00:00.0,8:00:00.0,1",
+            "here,here,fly,7:00:00.0,8:00:00.0,1",
+            "here,here,walk,8:00:00.0,7:00:00.0,1",
+        ] {
+            if import(bad, &mut rng).is_ok() {
+                panic!("Imported a bad OD matrix row: {}", bad);
+            }
+        }
+        std::fs::remove_file(&zones_path).unwrap();
+        std::fs::remove_file(&matrix_path).unwrap();
+    });
+    t.run_slow("car_emissions", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("car_emissions").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let border = map.get_i(IntersectionID(186));
+        let start_lane = border.get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let mut vehicle_spec = Scenario::rand_car(&mut rng);
+        vehicle_spec.electric = false;
+        let (_, car) = sim.schedule_trip(
+            Time::START_OF_DAY,
+            TripSpec::CarAppearing {
+                start_pos: TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map)
+                    .unwrap(),
+                vehicle_spec,
+                goal: DrivingGoal::end_at_border(
+                    border.some_incoming_road(&map),
+                    PathConstraints::Car,
+                    &map,
+                )
+                .unwrap(),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::CarOrBikeReachedBorder(car.unwrap(), border.id)],
+            Duration::minutes(7),
+        );
+        let per_trip = sim.get_analytics().emissions_per_trip(sim.time());
+        if per_trip.len() != 1 || per_trip.values().any(|e| e.co2 <= 0.0 || e.energy <= 0.0) {
+            panic!(
+                "Expected emissions for exactly one trip, got {:?}",
+                per_trip
+            );
+        }
+        if sim
+            .get_analytics()
+            .emissions_per_road(sim.time(), &map)
+            .is_empty()
+        {
+            panic!("No emissions recorded per road");
+        }
+    });
+    t.run_slow("ride_hail", |h| {
+        let (map, mut sim, mut rng) = SimFlags::for_test("ride_hail").load(&mut Timer::throwaway());
+        sim.seed_ride_hail_fleet(3, &map, &mut rng, &mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let goal_bldg = BuildingID(319);
+        let (ped, _) = sim.schedule_trip(
+            Time::START_OF_DAY + Duration::seconds(30.0),
+            TripSpec::UsingRideHail {
+                start: BuildingID(0),
+                goal: SidewalkSpot::building(goal_bldg, &map),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::PedReachedBuilding(ped.unwrap(), goal_bldg)],
+            Duration::minutes(20),
+        );
+        // The fleet keeps cruising forever, but doesn't count as unfinished trips.
+        sim.just_run_until_done(&map, Some(Duration::minutes(1)));
+    });
+    t.run_slow("ride_hail_scenario", |_| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("ride_hail_scenario").load(&mut Timer::throwaway());
+        let mut scenario = Scenario::empty(&map, "ride_hail_scenario");
+        scenario.ride_hail_fleet = 3;
+        // TODO Hardcoding IDs is fragile
+        scenario.individ_trips = vec![SpawnTrip::UsingRideHail(
+            Time::START_OF_DAY + Duration::seconds(30.0),
+            BuildingID(0),
+            SidewalkSpot::building(BuildingID(319), &map),
+        )];
+
+        // The fleet and trip survive saving and exporting.
+        let (copy, problems) = PermanentScenario::new(&scenario, &map).try_migrate(&map);
+        assert!(problems.is_empty());
+        assert_eq!(format!("{:?}", copy), format!("{:?}", scenario));
+        let copy = scenario
+            .to_external(&map, &mut Timer::throwaway())
+            .import(&map, &mut Timer::throwaway());
+        assert_eq!(copy.ride_hail_fleet, 3);
+        assert_eq!(
+            format!("{:?}", copy.individ_trips),
+            format!("{:?}", scenario.individ_trips)
+        );
+
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut Timer::throwaway());
+        sim.just_run_until_done(&map, Some(Duration::minutes(20)));
+        let rides = sim
+            .get_analytics()
+            .finished_trips
+            .iter()
+            .filter(|(_, _, mode, _)| *mode == Some(TripMode::RideHail))
+            .count();
+        assert_eq!(rides, 1);
+    });
+    t.run_slow("incident_detour", |h| {
+        let (map, mut sim, mut rng) =
+            SimFlags::for_test("incident_detour").load(&mut Timer::throwaway());
+        // TODO Hardcoding IDs is fragile
+        let border = map.get_i(IntersectionID(186));
+        let goal_bldg = BuildingID(319);
+        let start_lane = border.get_outgoing_lanes(&map, PathConstraints::Car)[0];
+        let start_pos =
+            TripSpec::spawn_car_at(Position::new(start_lane, Distance::ZERO), &map).unwrap();
+
+        // Close an intersection in the middle of the usual route, for longer than the trip takes.
+        let path = map
+            .pathfind(PathRequest {
+                start: start_pos,
+                end: Position::new(
+                    map.find_driving_lane_near_building(goal_bldg),
+                    Distance::ZERO,
+                ),
+                constraints: PathConstraints::Car,
+            })
+            .unwrap();
+        let turns: Vec<TurnID> = path
+            .get_steps()
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Turn(t) => Some(*t),
+                _ => None,
+            })
+            .collect();
+        sim.schedule_incident(Incident {
+            start: Time::START_OF_DAY,
+            end: Time::START_OF_DAY + Duration::minutes(30),
+            blockage: Blockage::Intersection(turns[turns.len() / 2].parent),
+        });
+
+        let (ped, _) = sim.schedule_trip(
+            Time::START_OF_DAY + Duration::seconds(10.0),
+            TripSpec::CarAppearing {
+                start_pos,
+                goal: DrivingGoal::ParkNear(goal_bldg),
+                vehicle_spec: Scenario::rand_car(&mut rng),
+                ped_speed: Scenario::rand_ped_speed(&mut rng),
+            },
+            &map,
+        );
+        sim.spawn_all_trips(&map, &mut Timer::throwaway(), false);
+        h.setup_done(&mut sim);
+
+        sim.run_until_expectations_met(
+            &map,
+            vec![Event::PedReachedBuilding(ped.unwrap(), goal_bldg)],
+            Duration::minutes(15),
+        );
+        sim.just_run_until_done(&map, Some(Duration::minutes(1)));
+    });
+    t.run_slow("external_scenario_roundtrip", |_| {
+        let (map, _, _) =
+            SimFlags::for_test("external_scenario_roundtrip").load(&mut Timer::throwaway());
+        let (b1, b2) = (BuildingID(0), BuildingID(319));
+        let depart = Time::START_OF_DAY + Duration::seconds(90.0);
+        let mut parked_cars = BTreeMap::new();
+        parked_cars.insert(b1, 2);
+        let scenario = Scenario {
+            scenario_name: "roundtrip".to_string(),
+            map_name: map.get_name().to_string(),
+            seed_buses: false,
+            seed_parked_cars: Vec::new(),
+            spawn_over_time: Vec::new(),
+            border_spawn_over_time: Vec::new(),
+            individ_trips: vec![
+                SpawnTrip::JustWalking(
+                    depart,
+                    SidewalkSpot::building(b1, &map),
+                    SidewalkSpot::building(b2, &map),
+                ),
+                SpawnTrip::MaybeUsingParkedCar(depart, b2, DrivingGoal::ParkNear(b1)),
+            ],
+            individ_parked_cars: parked_cars,
+            ride_hail_fleet: 0,
+            incidents: Vec::new(),
+        };
+
+        let copy = scenario
+            .to_external(&map, &mut Timer::throwaway())
+            .import(&map, &mut Timer::throwaway());
+        assert_eq!(copy.individ_parked_cars, scenario.individ_parked_cars);
+        assert_eq!(copy.individ_trips.len(), 2);
+        match (&copy.individ_trips[0], &copy.individ_trips[1]) {
+            (
+                SpawnTrip::JustWalking(t1, start, goal),
+                SpawnTrip::MaybeUsingParkedCar(t2, bldg, DrivingGoal::ParkNear(goal_bldg)),
+            ) => {
+                assert_eq!(*t1, depart);
+                assert_eq!(*t2, depart);
+                assert_eq!(
+                    start.sidewalk_pos,
+                    SidewalkSpot::building(b1, &map).sidewalk_pos
+                );
+                assert_eq!(
+                    goal.sidewalk_pos,
+                    SidewalkSpot::building(b2, &map).sidewalk_pos
+                );
+                assert_eq!(*bldg, b2);
+                assert_eq!(*goal_bldg, b1);
+            }
+            x => panic!("Trips changed after a round-trip: {:?}", x),
+        }
+    });
+    t.run_slow("permanent_scenario_migration", |_| {
+        let mut timer = Timer::throwaway();
+        let map = Map::new(abstutil::path_raw_map("montlake"), true, &mut timer);
+
+        // Rebuild the same map without its first few buildings, shifting all the building IDs.
+        let mut raw: RawMap = abstutil::read_binary(abstutil::path_raw_map("montlake"), &mut timer);
+        raw.apply_all_fixes(&mut timer);
+        let removed: Vec<OriginalBuilding> = raw.buildings.keys().take(50).cloned().collect();
+        for id in &removed {
+            raw.buildings.remove(id);
+        }
+        let path = std::env::temp_dir()
+            .join("montlake_fewer_buildings.json")
+            .to_str()
+            .unwrap()
+            .to_string();
+        abstutil::write_json(path.clone(), &raw);
+        let rebuilt = Map::new(path, false, &mut timer);
+
+        let osm_id = |m: &Map, b: BuildingID| m.get_b(b).osm_way_id;
+        let bldg = |spot: &SidewalkSpot| match spot.connection {
+            SidewalkPOI::Building(b) => b,
+            ref x => panic!("Walking trip doesn't start or end at a building: {:?}", x),
+        };
+        let (kept, gone): (Vec<&Building>, Vec<&Building>) =
+            map.all_buildings().iter().partition(|b| {
+                !removed.contains(&OriginalBuilding {
+                    osm_way_id: b.osm_way_id,
+                })
+            });
+        let (b1, b2, b3) = (kept[0].id, kept.last().unwrap().id, gone[0].id);
+        // Make sure the IDs really did change
+        assert!(rebuilt
+            .all_buildings()
+            .iter()
+            .any(|b| b.osm_way_id == osm_id(&map, b1) && b.id != b1));
+
+        let depart = Time::START_OF_DAY + Duration::seconds(90.0);
+        let mut scenario = Scenario::empty(&map, "permanent_scenario_migration");
+        scenario.individ_trips = vec![
+            SpawnTrip::JustWalking(
+                depart,
+                SidewalkSpot::building(b1, &map),
+                SidewalkSpot::building(b2, &map),
+            ),
+            SpawnTrip::MaybeUsingParkedCar(depart, b2, DrivingGoal::ParkNear(b1)),
+            SpawnTrip::JustWalking(
+                depart,
+                SidewalkSpot::building(b3, &map),
+                SidewalkSpot::building(b1, &map),
+            ),
+        ];
+        scenario.individ_parked_cars.insert(b1, 2);
+        scenario.individ_parked_cars.insert(b3, 1);
+
+        // Against the same map, nothing should change.
+        let (copy, problems) = PermanentScenario::new(&scenario, &map).try_migrate(&map);
+        assert_eq!(format!("{:?}", copy), format!("{:?}", scenario));
+        assert!(problems.is_empty());
+
+        // Against the rebuilt map, everything refers to the same OSM buildings, and anything
+        // touching a removed building is dropped and reported.
+        let (migrated, problems) = PermanentScenario::new(&scenario, &map).try_migrate(&rebuilt);
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert_eq!(migrated.individ_trips.len(), 2);
+        match &migrated.individ_trips[0] {
+            SpawnTrip::JustWalking(_, start, goal) => {
+                assert_eq!(osm_id(&rebuilt, bldg(start)), osm_id(&map, b1));
+                assert_eq!(osm_id(&rebuilt, bldg(goal)), osm_id(&map, b2));
+            }
+            x => panic!("Wrong trip after migrating: {:?}", x),
+        }
+        match &migrated.individ_trips[1] {
+            SpawnTrip::MaybeUsingParkedCar(_, start, DrivingGoal::ParkNear(goal)) => {
+                assert_eq!(osm_id(&rebuilt, *start), osm_id(&map, b2));
+                assert_eq!(osm_id(&rebuilt, *goal), osm_id(&map, b1));
+            }
+            x => panic!("Wrong trip after migrating: {:?}", x),
+        }
+        let parked: Vec<(i64, usize)> = migrated
+            .individ_parked_cars
+            .iter()
+            .map(|(b, cnt)| (osm_id(&rebuilt, *b), *cnt))
+            .collect();
+        assert_eq!(parked, vec![(osm_id(&map, b1), 2)]);
+    });
+    t.run_slow("scenario_transforms", |_| {
+        let (map, _, _) = SimFlags::for_test("scenario_transforms").load(&mut Timer::throwaway());
+        let mut scenario = Scenario::small_run(&map);
+        scenario.individ_trips = (0..100)
+            .map(|idx| {
+                SpawnTrip::MaybeUsingParkedCar(
+                    Time::START_OF_DAY + Duration::minutes(idx),
+                    BuildingID(0),
+                    DrivingGoal::ParkNear(BuildingID(319)),
+                )
+            })
+            .collect();
+        let incident = |start: usize, end: usize| Incident {
+            start: Time::START_OF_DAY + Duration::minutes(start),
+            end: Time::START_OF_DAY + Duration::minutes(end),
+            blockage: Blockage::Intersection(IntersectionID(0)),
+        };
+        scenario.incidents = vec![incident(5, 15), incident(30, 40)];
+        let rng = || XorShiftRng::from_seed([42; 16]);
+
+        assert_eq!(scenario.scale(1.2, &mut rng()).individ_trips.len(), 120);
+        let half = scenario.scale(0.5, &mut rng());
+        assert_eq!(half.individ_trips.len(), 50);
+        assert_eq!(
+            format!("{:?}", half),
+            format!("{:?}", scenario.scale(0.5, &mut rng()))
+        );
+        assert!(scenario.sample(0.1, &mut rng()).individ_trips.len() < 100);
+
+        let window = scenario.filter_time_window(
+            Time::START_OF_DAY + Duration::minutes(10),
+            Time::START_OF_DAY + Duration::minutes(20),
+            &mut rng(),
+        );
+        assert_eq!(window.individ_trips.len(), 10);
+        assert_eq!(window.incidents, vec![incident(10, 15)]);
+        let walking: BTreeSet<TripMode> = vec![TripMode::Walk].into_iter().collect();
+        assert!(scenario.filter_modes(&walking).individ_trips.is_empty());
+
+        let shifted = scenario.shift_departures(Duration::ZERO - Duration::minutes(15));
+        assert_eq!(shifted.individ_trips[0].departure(), Time::START_OF_DAY);
+        assert_eq!(
+            shifted.individ_trips[99].departure(),
+            Time::START_OF_DAY + Duration::minutes(84)
+        );
+        assert_eq!(shifted.incidents, vec![incident(15, 25)]);
+
+        // Incidents that don't end after they start are dropped when loading.
+        let mut bad = Scenario::empty(&map, "bad_incidents");
+        bad.incidents = vec![incident(10, 5), incident(5, 5), incident(5, 10)];
+        let loaded = SavedScenario::Permanent(PermanentScenario::new(&bad, &map))
+            .into_scenario(&map, &mut Timer::throwaway());
+        assert_eq!(loaded.incidents, vec![incident(5, 10)]);
+    });
 }